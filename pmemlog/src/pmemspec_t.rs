@@ -146,6 +146,18 @@ verus! {
                 self.inv(),
                 self.constants() == old(self).constants(),
                 self@ == update_contents_to_reflect_write(old(self)@, addr as int, bytes@);
+
+        /// This is the model of some routine that waits until all
+        /// preceding writes are durable. In this model, the contents
+        /// reflect each write as soon as it's made, so flushing
+        /// doesn't change them.
+        fn flush(&mut self)
+            requires
+                old(self).inv()
+            ensures
+                self.inv(),
+                self.constants() == old(self).constants(),
+                self@ == old(self)@;
     }
 
     /// We model the persistent memory as getting flushed in chunks,
@@ -47,6 +47,35 @@ verus! {
                     Err(_) => true // TODO
                 };
 
+        // Returns whether `pmem` holds a durable store with ID
+        // `kvstore_id` previously created by `new`, i.e., whether it
+        // should be opened with `restore` rather than `new`. It only
+        // reads the store's metadata.
+        fn contains_store(pmem: &PM, kvstore_id: u128) -> (result: bool)
+            requires
+                pmem.inv(),
+        ;
+
+        // Opens a durable store previously created by `new` on
+        // `pmem`, e.g., after a crash. It doesn't build any volatile
+        // state; see `rebuild_volatile_index`.
+        fn restore(pmem: PM,
+            kvstore_id: u128,
+        ) -> (result: Result<Self, KvError<K, E>>)
+            ensures
+                match(result) {
+                    Ok(durable_store) => {
+                        &&& durable_store.valid()
+                        &&& durable_store@.valid()
+                        &&& durable_store@.contents.dom().finite()
+                    }
+                    Err(_) => true // TODO
+                };
+
+        // Implementations should allocate a slot of
+        // `item.serialized_size()` bytes, plus whatever length prefix
+        // their layout needs, so that items of different sizes can be
+        // stored.
         fn create(
             &mut self,
             item: I,
@@ -75,6 +104,131 @@ verus! {
                 })
         ;
 
+        // Returns the number of metadata slots in the durable store,
+        // whether or not they're in use. Slots are numbered from 0, and
+        // a slot's number is the offset of the entry it holds.
+        fn num_slots(&self) -> (result: u64)
+            requires
+                self.valid(),
+            ensures
+                forall |i: int| #[trigger] self@.contains_key(i) ==> 0 <= i < result
+        ;
+
+        // Reads the entry in slot `slot` directly from persistent
+        // memory, checking its CRC, without relying on any volatile
+        // state. This is meant for recovery and repair tools, e.g.,
+        // to rebuild a lost volatile index. Returns `Ok(None)` if the
+        // slot is free and `Err(KvError::CRCMismatch)` if it's
+        // corrupted.
+        fn read_raw_entry(
+            &self,
+            slot: u64
+        ) -> (result: Result<Option<(K, &I)>, KvError<K, E>>)
+            requires
+                self.valid(),
+            ensures
+                match result {
+                    Ok(Some((key, item))) => {
+                        match self@[slot as int] {
+                            Some(entry) => {
+                                &&& entry.key() == key
+                                &&& entry.item() == item
+                            },
+                            None => false
+                        }
+                    },
+                    Ok(None) => self@[slot as int].is_None(),
+                    Err(KvError::CRCMismatch) => true,
+                    Err(_) => false
+                }
+        ;
+
+        // Reads the list nodes of the entry in slot `slot` directly
+        // from persistent memory, like `read_raw_entry`. Returns, in
+        // list order, each node's physical offset and the number of
+        // live list entries it holds.
+        fn read_raw_list_nodes(
+            &self,
+            slot: u64
+        ) -> (result: Result<Vec<(u64, u64)>, KvError<K, E>>)
+            requires
+                self.valid(),
+                self@.contains_key(slot as int),
+            ensures
+                match result {
+                    Ok(nodes) => {
+                        let list = self@[slot as int].unwrap().list();
+                        forall |i: int| 0 <= i < nodes@.len() ==> {
+                            let (node_offset, _) = #[trigger] nodes@[i];
+                            list.node_offset_map.contains_key(node_offset as int)
+                        }
+                    },
+                    Err(KvError::CRCMismatch) => true,
+                    Err(_) => false
+                }
+        ;
+
+        // Returns the store's generation, a counter that
+        // implementations must persist and advance on every update to
+        // the durable state. An index checkpoint records the
+        // generation it was taken at, so it can tell whether the
+        // store has changed since.
+        fn generation(&self) -> (result: u64)
+            requires
+                self.valid(),
+        ;
+
+        // Returns the slots whose entry or list has changed since the
+        // store was at generation `generation`, so that an index
+        // checkpoint taken then can be brought up to date by re-reading
+        // just those slots. Returns `None` if the store can't tell,
+        // e.g., because it doesn't track changes that far back; the
+        // index then has to be rebuilt.
+        fn slots_changed_since(&self, generation: u64) -> (result: Option<Vec<u64>>)
+            requires
+                self.valid(),
+        ;
+
+        // Returns the store's allocation high-water mark, i.e., the
+        // number of bytes of metadata and list-node space up to the
+        // end of the furthest entry or node it has ever allocated.
+        // Space freed below the mark still counts, so this
+        // overestimates the bytes in use, but it's cheap to track.
+        fn allocation_high_water_mark(&self) -> (result: u64)
+            requires
+                self.valid(),
+        ;
+
+        // Durably stores `checkpoint` in the store's dedicated
+        // index-checkpoint region, replacing any earlier one. The
+        // checkpoint isn't part of the abstract state, so this
+        // doesn't change the view or the generation. Returns
+        // `Err(KvError::OutOfSpace)` if the region is too small.
+        fn write_index_checkpoint(
+            &mut self,
+            checkpoint: &[u8],
+            perm: Tracked<&TrustedKvPermission<PM, K, I, L, Self, E>>,
+        ) -> (result: Result<(), KvError<K, E>>)
+            requires
+                old(self).valid(),
+            ensures
+                self.valid(),
+                self@ == old(self)@,
+                match result {
+                    Ok(()) => true,
+                    Err(KvError::OutOfSpace) => true,
+                    Err(_) => false
+                }
+        ;
+
+        // Reads back the bytes most recently stored with
+        // `write_index_checkpoint`, or `None` if there aren't any.
+        // The bytes aren't checked in any way.
+        fn read_index_checkpoint(&self) -> (result: Result<Option<Vec<u8>>, KvError<K, E>>)
+            requires
+                self.valid(),
+        ;
+
         fn read_item(
             &self,
             offset: u64
@@ -142,6 +296,12 @@ verus! {
                 }
         ;
 
+        // Deletes the entry at `offset`: its metadata slot is marked
+        // free and all of its list nodes, however many there are, are
+        // freed. An implementation must make this a single
+        // CRC-protected update committed by flipping a CDB, so that
+        // `perm` permits every crash state, and recovery finds either
+        // the whole entry or none of it.
         fn delete(
             &mut self,
             offset: u64,
@@ -153,9 +313,14 @@ verus! {
                 self.valid(),
                 match result {
                     Ok(()) => {
-                        self@[offset as int].is_None()
+                        &&& old(self)@.contains_key(offset as int)
+                        &&& self@ == old(self)@.delete(offset as int).unwrap()
                     }
-                    Err(_) => true // TODO
+                    Err(KvError::KeyNotFound) => {
+                        &&& !old(self)@.contains_key(offset as int)
+                        &&& self@ == old(self)@
+                    }
+                    Err(_) => false // TODO
                 }
         ;
 
@@ -174,6 +339,11 @@ verus! {
                     Ok(()) => {
                         let old_record = old(self)@.contents[offset as int];
                         let new_record = self@.contents[offset as int];
+                        &&& old(self)@.contains_key(offset as int)
+                        &&& self@.contents == old(self)@.contents.insert(offset as int, new_record)
+                        &&& self@.index_to_key_map == old(self)@.index_to_key_map
+                        &&& new_record.key() == old_record.key()
+                        &&& new_record.item() == old_record.item()
                         &&& new_record.list().list == old_record.list().list.push(new_entry)
                     }
                     Err(_) => false // TODO
@@ -194,10 +364,16 @@ verus! {
                     Ok(node_phys_offset) => {
                         let old_record = old(self)@.contents[offset as int];
                         let new_record = self@.contents[offset as int];
+                        &&& old(self)@.contains_key(offset as int)
+                        &&& self@.contents == old(self)@.contents.insert(offset as int, new_record)
+                        &&& self@.index_to_key_map == old(self)@.index_to_key_map
+                        &&& new_record.key() == old_record.key()
+                        &&& new_record.item() == old_record.item()
                         &&& new_record.list().list == old_record.list().list.push(new_entry)
                         &&& new_record.list().node_offset_map ==
                                 old_record.list().node_offset_map.insert(node_phys_offset as int, old(self)@.len() as int)
                     }
+                    Err(KvError::OutOfSpace) => self@ == old(self)@,
                     Err(_) => false // TODO
                 }
         ;
@@ -299,6 +475,49 @@ verus! {
                 }
         ;
 
+        // Replaces the entry at logical index `idx` without writing to
+        // the live list node at `old_node_offset`. Instead, this
+        // allocates a new node, copies the old node's entries into it
+        // with the one at `idx` replaced by `new_entry`, and then swaps
+        // it into the list with a single CRC-protected update to the
+        // pointer that referred to the old node (the previous node's
+        // next pointer, or the item's list head). The old node is freed
+        // afterward. A crash before the pointer update leaves the old
+        // list intact and one after leaves the new one, and the list's
+        // length never changes. Returns the offset of the new node.
+        fn replace_list_entry_at_index(
+            &mut self,
+            item_offset: u64,
+            old_node_offset: u64,
+            idx: usize,
+            new_entry: L,
+            Tracked(perm): Tracked<&TrustedKvPermission<PM, K, I, L, Self, E>>,
+        ) -> (result: Result<u64, KvError<K, E>>)
+            requires
+                old(self).valid(),
+            ensures
+                self.valid(),
+                match result {
+                    Ok(new_node_offset) => {
+                        let old_record = old(self)@.contents[item_offset as int];
+                        let new_record = self@.contents[item_offset as int];
+                        let old_map = old_record.list().node_offset_map;
+                        &&& old(self)@.contains_key(item_offset as int)
+                        &&& self@.contents == old(self)@.contents.insert(item_offset as int, new_record)
+                        &&& self@.index_to_key_map == old(self)@.index_to_key_map
+                        &&& idx < old_record.list().len()
+                        &&& new_record.key() == old_record.key()
+                        &&& new_record.item() == old_record.item()
+                        &&& new_record.list().list == old_record.list().list.update(idx as int, new_entry)
+                        &&& new_record.list().node_offset_map ==
+                                old_map.remove(old_node_offset as int).insert(new_node_offset as int,
+                                                                              old_map[old_node_offset as int])
+                    }
+                    Err(KvError::OutOfSpace) => self@ == old(self)@,
+                    Err(_) => false // TODO
+                }
+        ;
+
         fn trim_list(
             &mut self,
             item_offset: u64,
@@ -353,3 +572,70 @@ verus! {
         ;
     }
 }
+
+// The `RawEntries` iterator walks every metadata slot of a durable
+// store via `read_raw_entry`, yielding each live entry's key, item,
+// and slot number. Free slots are skipped. Corrupted slots yield an
+// error, so a repair tool can report them; callers that only want
+// the intact entries can filter them out. Since it only uses the
+// durable store, it works even when the volatile index is lost.
+pub struct RawEntries<'a, D, PM, K, I, L, E>
+where
+    D: DurableKvStore<PM, K, I, L, E>,
+    PM: PersistentMemoryRegions,
+    K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+    I: Serializable + Item<K> + Sized + std::fmt::Debug,
+    L: Serializable + std::fmt::Debug,
+    E: std::fmt::Debug,
+{
+    durable_store: &'a D,
+    next_slot: u64,
+    num_slots: u64,
+    _phantom: std::marker::PhantomData<(PM, K, I, L, E)>,
+}
+
+impl<'a, D, PM, K, I, L, E> RawEntries<'a, D, PM, K, I, L, E>
+where
+    D: DurableKvStore<PM, K, I, L, E>,
+    PM: PersistentMemoryRegions,
+    K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+    I: Serializable + Item<K> + Sized + std::fmt::Debug,
+    L: Serializable + std::fmt::Debug,
+    E: std::fmt::Debug,
+{
+    pub fn new(durable_store: &'a D) -> Self
+    {
+        Self {
+            durable_store,
+            next_slot: 0,
+            num_slots: durable_store.num_slots(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, D, PM, K, I, L, E> Iterator for RawEntries<'a, D, PM, K, I, L, E>
+where
+    D: DurableKvStore<PM, K, I, L, E>,
+    PM: PersistentMemoryRegions,
+    K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+    I: Serializable + Item<K> + Sized + std::fmt::Debug,
+    L: Serializable + std::fmt::Debug,
+    E: std::fmt::Debug,
+{
+    type Item = Result<(K, &'a I, u64), KvError<K, E>>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        while self.next_slot < self.num_slots {
+            let slot = self.next_slot;
+            self.next_slot += 1;
+            match self.durable_store.read_raw_entry(slot) {
+                Ok(Some((key, item))) => return Some(Ok((key, item, slot))),
+                Ok(None) => {},
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
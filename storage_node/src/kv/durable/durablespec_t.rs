@@ -153,6 +153,23 @@ verus! {
             }
         }
 
+        // Deleting the entry at `offset` frees its metadata slot and
+        // every node of its list, so nothing of it remains in the view.
+        pub open spec fn delete(self, offset: int) -> Result<Self, KvError<K, E>>
+        {
+            if !self.contents.contains_key(offset) {
+                Err(KvError::KeyNotFound)
+            } else {
+                Ok(
+                    Self {
+                        contents: self.contents.remove(offset),
+                        index_to_key_map: self.index_to_key_map.remove(offset),
+                        _phantom: None
+                    }
+                )
+            }
+        }
+
         // Returns true if the keys in the durable store match the keys in the ghost index_to_key_map
         pub open spec fn valid(self) -> bool
         {
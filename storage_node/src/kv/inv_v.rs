@@ -89,4 +89,121 @@ verus! {
             }
         }
     }
+
+    /// This lemma proves that, given a durable state and a volatile state that match,
+    /// removing `key` from the volatile index and deleting the entry at its offset
+    /// from the durable store leaves states that still match.
+    pub proof fn lemma_volatile_matches_durable_after_delete<K, I, L, E>(
+        old_durable_state: DurableKvStoreView<K, I, L, E>,
+        old_volatile_state: VolatileKvIndexView<K>,
+        offset: int,
+        key: K,
+    )
+        where
+            K: Hash + Eq + std::fmt::Debug,
+            I: Item<K>,
+            E: std::fmt::Debug
+        requires
+            old_durable_state.matches_volatile_index(old_volatile_state),
+            old_volatile_state.contains_key(key),
+            old_volatile_state[key].unwrap().item_offset == offset,
+        ensures
+            old_durable_state.contains_key(offset),
+            ({
+                let new_durable_state = old_durable_state.delete(offset).unwrap();
+                let new_volatile_state = old_volatile_state.remove(key);
+                new_durable_state.matches_volatile_index(new_volatile_state)
+            })
+    {
+        assert(old_durable_state.index_to_key_map.contains_key(offset));
+        assert(old_durable_state.index_to_key_map[offset] == key);
+        let new_durable_state = old_durable_state.delete(offset).unwrap();
+        let new_volatile_state = old_volatile_state.remove(key);
+
+        assert(new_durable_state.contents.dom() =~= old_durable_state.contents.dom().remove(offset));
+        assert(new_volatile_state.contents.dom() =~= old_volatile_state.contents.dom().remove(key));
+
+        assert forall |k: K| #![auto] new_volatile_state.contains_key(k) implies {
+            let indexed_offset = new_volatile_state[k].unwrap().item_offset;
+            &&& new_durable_state.index_to_key_map.contains_key(indexed_offset)
+            &&& new_durable_state.index_to_key_map[indexed_offset] == k
+        } by {
+            assert(old_volatile_state.contains_key(k));
+            let indexed_offset = old_volatile_state[k].unwrap().item_offset;
+            assert(old_durable_state.index_to_key_map.contains_key(indexed_offset));
+            assert(old_durable_state.index_to_key_map[indexed_offset] == k);
+            // `k` isn't `key`, so its entry isn't the one deleted
+            assert(indexed_offset != offset);
+        }
+
+        assert forall |i: int| #![auto] new_durable_state.contains_key(i) implies {
+            &&& new_durable_state.index_to_key_map.contains_key(i)
+            &&& new_volatile_state.contains_key(new_durable_state.index_to_key_map[i])
+            &&& new_volatile_state[new_durable_state.index_to_key_map[i]].unwrap().item_offset == i
+        } by {
+            assert(old_durable_state.contains_key(i));
+            let k = old_durable_state.index_to_key_map[i];
+            assert(old_volatile_state.contains_key(k));
+            assert(old_volatile_state[k].unwrap().item_offset == i);
+            // `i` isn't `offset`, so the key stored there isn't `key`
+            assert(k != key);
+        }
+    }
+
+    /// This lemma proves that an update that leaves the durable store's offsets and
+    /// keys, and the volatile index's keys and item offsets, unchanged, e.g., one that
+    /// only changes a list and where its nodes are, keeps the two states matching.
+    pub proof fn lemma_volatile_matches_durable_after_list_update<K, I, L, E>(
+        old_durable_state: DurableKvStoreView<K, I, L, E>,
+        old_volatile_state: VolatileKvIndexView<K>,
+        new_durable_state: DurableKvStoreView<K, I, L, E>,
+        new_volatile_state: VolatileKvIndexView<K>,
+    )
+        where
+            K: Hash + Eq + std::fmt::Debug,
+            I: Item<K>,
+            E: std::fmt::Debug
+        requires
+            old_durable_state.matches_volatile_index(old_volatile_state),
+            new_durable_state.contents.dom() == old_durable_state.contents.dom(),
+            new_durable_state.index_to_key_map == old_durable_state.index_to_key_map,
+            forall |i: int| #[trigger] new_durable_state.contains_key(i) ==>
+                new_durable_state[i].unwrap().key() == old_durable_state[i].unwrap().key(),
+            new_volatile_state.contents.dom() == old_volatile_state.contents.dom(),
+            forall |k: K| #[trigger] new_volatile_state.contains_key(k) ==>
+                new_volatile_state[k].unwrap().item_offset == old_volatile_state[k].unwrap().item_offset,
+        ensures
+            new_durable_state.matches_volatile_index(new_volatile_state)
+    {
+        assert forall |i: int| #![auto] new_durable_state.contains_key(i) <==>
+            new_durable_state.index_to_key_map.contains_key(i) by {
+            assert(new_durable_state.contains_key(i) == old_durable_state.contains_key(i));
+        }
+        assert forall |i: int| #![auto] new_durable_state.index_to_key_map.contains_key(i) implies {
+            &&& new_durable_state[i] is Some
+            &&& new_durable_state[i].unwrap().key() == new_durable_state.index_to_key_map[i]
+        } by {
+            assert(old_durable_state.contains_key(i));
+            assert(new_durable_state.contains_key(i));
+        }
+
+        assert forall |k: K| #![auto] new_volatile_state.contains_key(k) implies {
+            let indexed_offset = new_volatile_state[k].unwrap().item_offset;
+            &&& new_durable_state.index_to_key_map.contains_key(indexed_offset)
+            &&& new_durable_state.index_to_key_map[indexed_offset] == k
+        } by {
+            assert(old_volatile_state.contains_key(k));
+        }
+
+        assert forall |i: int| #![auto] new_durable_state.contains_key(i) implies {
+            &&& new_durable_state.index_to_key_map.contains_key(i)
+            &&& new_volatile_state.contains_key(new_durable_state.index_to_key_map[i])
+            &&& new_volatile_state[new_durable_state.index_to_key_map[i]].unwrap().item_offset == i
+        } by {
+            assert(old_durable_state.contains_key(i));
+            let k = old_durable_state.index_to_key_map[i];
+            assert(old_volatile_state.contains_key(k));
+            assert(new_volatile_state.contains_key(k));
+        }
+    }
 }
@@ -47,21 +47,35 @@ where
     KeyNotFound,
     KeyAlreadyExists,
     InvalidKey{ key: K },
+    ItemKeyMismatch,
     IndexOutOfRange,
     RegionTooSmall { required: usize, actual: usize },
     OutOfSpace,
     InvalidPersistentMemoryRegionProvided, // TODO: reason
     SerializationError { error: E },
     DeserializationError { error: E },
+    InvalidImportData,
+    CRCMismatch,
 }
 
 pub trait Item<K> : Sized {
     spec fn spec_key(self) -> K;
 
+    // The number of bytes this particular item occupies when stored,
+    // which may vary between items, e.g., if they hold variable-length
+    // keys. Durable stores lay out items using this rather than a
+    // fixed per-type size.
+    spec fn spec_serialized_size(self) -> nat;
+
     fn key(&self) -> (out: K)
         ensures
             out == self.spec_key()
     ;
+
+    fn serialized_size(&self) -> (out: u64)
+        ensures
+            out == self.spec_serialized_size()
+    ;
 }
 
 // TODO: should the constructor take one PM region and break it up into the required sub-regions,
@@ -107,7 +121,8 @@ where
     }
 
     /// The `KvStore` constructor calls the constructors for the durable and
-    /// volatile components of the key-value store.
+    /// volatile components of the key-value store. If `pmem` already holds a
+    /// store with ID `kvstore_id`, e.g., after a crash, it reopens it instead.
     /// `list_node_size` is the number of list entries in each node (not the number
     /// of bytes used by each node)
     fn new(
@@ -160,7 +175,6 @@ where
     fn create(&mut self, key: &K, item: I) -> (result: Result<(), KvError<K, E>>)
         requires
             old(self).valid(),
-            key == item.spec_key(),
         ensures
             self.valid(),
             match result {
@@ -171,6 +185,10 @@ where
                     &&& old(self)@.contents.contains_key(*key)
                     &&& old(self)@ == self@
                 }
+                Err(KvError::ItemKeyMismatch) => {
+                    &&& *key != item.spec_key()
+                    &&& old(self)@ == self@
+                }
                 Err(_) => false
             }
     {
@@ -203,6 +221,30 @@ where
         self.untrusted_kv_impl.untrusted_read_item(key)
     }
 
+    // This is like `read_item`, but it returns an owned copy of the
+    // item. That way, the caller can read an item and then pass an
+    // updated copy to `update_item`.
+    fn read_item_owned(&self, key: &K) -> (result: Option<I>)
+        where
+            I: Clone,
+        requires
+            self.valid()
+        ensures
+        ({
+            let spec_result = self@.read_item_and_list(*key);
+            match (result, spec_result) {
+                (Some(output_item), Some((spec_item, pages))) => {
+                    &&& spec_item == output_item
+                }
+                (Some(output_item), None) => false,
+                (None, Some((spec_item, pages))) => false,
+                (None, None) => true,
+            }
+        })
+    {
+        self.untrusted_kv_impl.untrusted_read_item_owned(key)
+    }
+
     // fn read_item_and_list(&self, key: &K) -> (result: Option<(&I, Vec<&L>)>)
     //     requires
     //         self.valid(),
@@ -247,6 +289,61 @@ where
         self.untrusted_kv_impl.untrusted_read_list_entry_at_index(key, idx)
     }
 
+    // Returns at most `count` list entries starting at index `start_idx`,
+    // so that callers can page through a long list without reading all of it.
+    // `start_idx + count` mustn't overflow; to read to the end of the list
+    // from `start_idx`, pass `usize::MAX - start_idx` as `count`.
+    fn read_list_range(&self, key: &K, start_idx: usize, count: usize) -> (result: Result<Vec<&L>, KvError<K, E>>)
+        requires
+            self.valid(),
+            start_idx + count <= usize::MAX,
+        ensures
+            ({
+                let spec_result = self@.read_list_range(*key, start_idx as int, count as int);
+                match (result, spec_result) {
+                    (Ok(output_entries), Ok(spec_entries)) => {
+                        &&& output_entries@ == spec_entries
+                    }
+                    (Err(KvError::IndexOutOfRange), Err(KvError::IndexOutOfRange)) => {
+                        &&& self@.contents.contains_key(*key)
+                        &&& self@.contents[*key].1.len() <= start_idx
+                    }
+                    (Err(KvError::KeyNotFound), Err(KvError::KeyNotFound)) => {
+                        &&& !self@.contents.contains_key(*key)
+                    }
+                    (_, _) => false
+                }
+            })
+    {
+        self.untrusted_kv_impl.untrusted_read_list_range(key, start_idx, count)
+    }
+
+    // This returns an iterator over references to the entries of
+    // `key`'s list, or `None` if `key` isn't in the store. Unlike
+    // `read_list_range`, it doesn't collect the entries, and unlike
+    // the owned variants, it doesn't require `L: Clone`. See
+    // `ListIter` for what it yields.
+    pub fn read_list_iter<'a>(&'a self, key: &'a K) -> (result: Option<ListIter<'a, PM, K, I, L, D, V, E>>)
+        requires
+            self.valid()
+        ensures
+            match result {
+                Some(iter) => {
+                    &&& iter.valid()
+                    &&& self@.read_item_and_list(*key) is Some
+                    &&& iter.list() == self@.read_item_and_list(*key).unwrap().1
+                    &&& iter.index() == 0
+                    &&& !iter.done()
+                },
+                None => self@.read_item_and_list(*key) is None,
+            }
+    {
+        match self.read_item(key) {
+            Some(_) => Some(ListIter { kv: self, key, idx: 0, done: false }),
+            None => None,
+        }
+    }
+
     // fn read_list(&self, key: &K) -> (result: Option<&Vec<L>>)
     //     requires
     //         self.valid(),
@@ -333,7 +430,7 @@ where
                     &&& !old(self)@.contents.contains_key(*key)
                     &&& old(self)@ == self@
                 }
-                // TODO: case for if we run out of space to append to the list
+                Err(KvError::OutOfSpace) => old(self)@ == self@,
                 Err(_) => false
             }
     {
@@ -399,6 +496,46 @@ where
         }
     }
 
+    // Like `update_list_entry_at_index`, but it never writes to the
+    // live list node. It copies the node, changes the copy, and swaps
+    // the copy into the list in one atomic pointer update, so a torn
+    // write can't corrupt the entry being replaced. Returns
+    // `Err(KvError::IndexOutOfRange)` if the list has no entry `idx`,
+    // and `Err(KvError::OutOfSpace)` if there's no free node to copy
+    // into, changing nothing in either case. It can also return
+    // `Err(KvError::InternalError)`, changing nothing, if the volatile
+    // index has lost track of the node holding entry `idx`.
+    fn replace_list_entry_at_index(&mut self, key: &K, idx: usize, new_list_entry: L) -> (result: Result<(), KvError<K, E>>)
+        requires
+            old(self).valid()
+        ensures
+            self.valid(),
+            match result {
+                Ok(()) => {
+                    &&& self@ == old(self)@.update_list_entry_at_index(*key, idx, new_list_entry).unwrap()
+                }
+                Err(KvError::KeyNotFound) => {
+                    &&& !old(self)@.contents.contains_key(*key)
+                    &&& old(self)@ == self@
+                }
+                Err(KvError::IndexOutOfRange) => {
+                    &&& old(self)@.contents.contains_key(*key)
+                    &&& old(self)@.contents[*key].1.len() <= idx
+                    &&& old(self)@ == self@
+                }
+                Err(KvError::OutOfSpace) => old(self)@ == self@,
+                Err(KvError::InternalError) => old(self)@ == self@,
+                Err(_) => false
+            }
+    {
+        if self.untrusted_kv_impl.untrusted_contains_key(key) {
+            let tracked perm = TrustedKvPermission::new_two_possibilities(self.id, self@, self@.update_list_entry_at_index(*key, idx, new_list_entry).unwrap());
+            self.untrusted_kv_impl.untrusted_replace_list_entry_at_index(key, idx, new_list_entry, Tracked(&perm))
+        } else {
+            Err(KvError::KeyNotFound)
+        }
+    }
+
     fn update_entry_at_index_and_item(
         &mut self,
         key: &K,
@@ -494,6 +631,389 @@ where
     {
         self.untrusted_kv_impl.untrusted_get_keys()
     }
+
+    /// Returns the number of keys in the store. It only consults the
+    /// volatile index, so it's cheap enough to poll for monitoring.
+    fn key_count(&self) -> (result: usize)
+        requires
+            self.valid()
+        ensures
+            result == self@.num_keys()
+    {
+        self.untrusted_kv_impl.untrusted_key_count()
+    }
+
+    /// Returns an estimate of the bytes of persistent memory the store
+    /// uses. It's an overestimate, since space freed by deletes and
+    /// trims still counts until it's reused.
+    fn durable_bytes_used(&self) -> (result: u64)
+        requires
+            self.valid()
+    {
+        self.untrusted_kv_impl.untrusted_durable_bytes_used()
+    }
+
+    /// Saves a checkpoint of the volatile index to persistent memory, so
+    /// that the next `restore` can load it rather than rebuilding the
+    /// index by scanning the durable store. If the store changes after
+    /// the checkpoint is taken, only the slots that changed are re-read;
+    /// the index is rebuilt only if the store can't say which those are.
+    fn checkpoint_index(&mut self) -> (result: Result<(), KvError<K, E>>)
+        requires
+            old(self).valid()
+        ensures
+            self.valid(),
+            self@ == old(self)@,
+            match result {
+                Ok(()) => true,
+                Err(KvError::OutOfSpace) => true,
+                Err(_) => false
+            }
+    {
+        let tracked perm = TrustedKvPermission::new_one_possibility(self.id, self@);
+        self.untrusted_kv_impl.untrusted_checkpoint_index(Tracked(&perm))
+    }
+
+    /// Exports every entry in the store as a byte stream, in the format
+    /// described by `AbstractKvStoreState::spec_decode_export`, so that it
+    /// can be imported into another store with `import`. If any entry can't
+    /// be read, it returns the error rather than a partial snapshot.
+    fn export(&self) -> (result: Result<Vec<u8>, KvError<K, E>>)
+        requires
+            self.valid()
+        ensures
+            match result {
+                Ok(data) => AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@) == Some(self@.contents),
+                Err(_) => true,
+            }
+    {
+        self.untrusted_kv_impl.untrusted_export()
+    }
+
+    /// Imports a byte stream produced by `export` into this store, which
+    /// must be empty, by replaying a `create` for each key followed by an
+    /// `append_to_list` for each of its list entries. Each replayed operation
+    /// is crash-consistent on its own, but the import as a whole isn't, so a
+    /// crash partway through leaves a partially imported store. So does a
+    /// replayed operation that fails: the store is then still valid, but
+    /// only holds part of the data, as described by `is_partial_import_of`.
+    fn import(&mut self, data: &[u8]) -> (result: Result<(), KvError<K, E>>)
+        requires
+            old(self).valid(),
+            old(self)@.empty(),
+        ensures
+            self.valid(),
+            self@.id == old(self)@.id,
+            match result {
+                Ok(()) => Some(self@.contents) == AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@),
+                Err(KvError::InvalidImportData) => {
+                    &&& AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@) is None
+                    &&& self@ == old(self)@
+                }
+                Err(_) => {
+                    let decoded = AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@);
+                    &&& decoded is Some
+                    &&& self@.is_partial_import_of(decoded.unwrap())
+                }
+            }
+    {
+        // Parse everything before changing anything, so that malformed
+        // data leaves the store untouched.
+        let mut entries = UntrustedKvStoreImpl::<PM, K, I, L, D, V, E>::untrusted_parse_import(data)?;
+        let ghost decoded = AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@).unwrap();
+        let ghost all_entries = entries@;
+
+        // Entries are replayed from last to first, since popping them
+        // off the end of `entries` moves them out without copying.
+        // Entries `entries.len()` onward have been replayed.
+        while entries.len() > 0
+            invariant
+                self.valid(),
+                self@.id == old(self)@.id,
+                AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@) == Some(decoded),
+                entries@ == all_entries.subrange(0, entries@.len() as int),
+                forall |i: int| 0 <= i < all_entries.len() ==> {
+                    let (key, item, list) = #[trigger] all_entries[i];
+                    &&& decoded.contains_key(key)
+                    &&& decoded[key] == (item, list@)
+                },
+                forall |key: K| #[trigger] decoded.contains_key(key) ==>
+                    exists |i: int| 0 <= i < all_entries.len() && (#[trigger] all_entries[i]).0 == key,
+                forall |i: int, j: int| 0 <= i < j < all_entries.len() ==>
+                    (#[trigger] all_entries[i]).0 != (#[trigger] all_entries[j]).0,
+                self@.is_partial_import_of(decoded),
+                forall |i: int| entries@.len() <= i < all_entries.len() ==> {
+                    let key = (#[trigger] all_entries[i]).0;
+                    &&& self@.contents.contains_key(key)
+                    &&& self@.contents[key] == decoded[key]
+                },
+                forall |key: K| #[trigger] self@.contents.contains_key(key) ==>
+                    exists |i: int| entries@.len() <= i < all_entries.len() && (#[trigger] all_entries[i]).0 == key,
+        {
+            // The entry to replay has a different key from every
+            // entry replayed so far, so its key isn't in the store.
+            let ghost which = entries@.len() - 1;
+            assert(!self@.contents.contains_key(all_entries[which].0)) by {
+                if self@.contents.contains_key(all_entries[which].0) {
+                    let i = choose |i: int| entries@.len() <= i < all_entries.len() &&
+                        (#[trigger] all_entries[i]).0 == all_entries[which].0;
+                    assert(which < i);
+                }
+            }
+
+            let entry = entries.pop().unwrap();
+            assert(entries@ =~= all_entries.subrange(0, entries@.len() as int));
+            let (key, item, mut list) = entry;
+            let ghost full_list = list@;
+            let ghost item_view = item;
+            let ghost before = self@;
+            assert(decoded.contains_key(key) && decoded[key] == (item_view, full_list));
+
+            self.create(&key, item)?;
+            assert(full_list.subrange(0, 0) =~= Seq::<L>::empty());
+
+            // Reverse the list, so that popping its entries off the end
+            // replays them in order.
+            let mut reversed: Vec<L> = Vec::new();
+            while list.len() > 0
+                invariant
+                    list@ == full_list.subrange(0, list@.len() as int),
+                    reversed@.len() + list@.len() == full_list.len(),
+                    forall |i: int| 0 <= i < reversed@.len() ==>
+                        #[trigger] reversed@[i] == full_list[full_list.len() - 1 - i],
+            {
+                let list_entry = list.pop().unwrap();
+                assert(list@ =~= full_list.subrange(0, list@.len() as int));
+                reversed.push(list_entry);
+            }
+
+            while reversed.len() > 0
+                invariant
+                    self.valid(),
+                    self@.id == old(self)@.id,
+                    reversed@.len() <= full_list.len(),
+                    forall |i: int| 0 <= i < reversed@.len() ==>
+                        #[trigger] reversed@[i] == full_list[full_list.len() - 1 - i],
+                    self@.contents == before.contents.insert(
+                        key, (item_view, full_list.subrange(0, full_list.len() - reversed@.len()))),
+            {
+                let ghost appended = full_list.len() - reversed@.len();
+                let list_entry = reversed.pop().unwrap();
+                self.append_to_list(&key, list_entry)?;
+                assert(full_list.subrange(0, appended).push(full_list[appended]) =~=
+                       full_list.subrange(0, appended + 1));
+                assert(self@.contents =~= before.contents.insert(
+                    key, (item_view, full_list.subrange(0, appended + 1))));
+            }
+            assert(full_list.subrange(0, full_list.len() as int) =~= full_list);
+
+            assert forall |key2: K| #[trigger] self@.contents.contains_key(key2) implies {
+                let (item2, list2) = self@.contents[key2];
+                &&& decoded.contains_key(key2)
+                &&& item2 == decoded[key2].0
+                &&& list2.len() <= decoded[key2].1.len()
+                &&& list2 == decoded[key2].1.subrange(0, list2.len() as int)
+            } by {
+                if key2 != key {
+                    assert(before.contents.contains_key(key2));
+                }
+            }
+            assert forall |i: int| entries@.len() <= i < all_entries.len() implies {
+                let key2 = (#[trigger] all_entries[i]).0;
+                &&& self@.contents.contains_key(key2)
+                &&& self@.contents[key2] == decoded[key2]
+            } by {
+                if i != which {
+                    assert(all_entries[i].0 != key);
+                }
+            }
+            assert forall |key2: K| #[trigger] self@.contents.contains_key(key2) implies
+                exists |i: int| entries@.len() <= i < all_entries.len() && (#[trigger] all_entries[i]).0 == key2 by {
+                if key2 == key {
+                    assert(all_entries[which].0 == key2);
+                }
+            }
+        }
+
+        assert(self@.contents =~= decoded) by {
+            assert forall |key: K| #[trigger] decoded.contains_key(key) implies self@.contents.contains_key(key) by {
+                let i = choose |i: int| 0 <= i < all_entries.len() && (#[trigger] all_entries[i]).0 == key;
+                assert(self@.contents.contains_key(all_entries[i].0));
+            }
+        }
+        Ok(())
+    }
+}
+
+// `ListIter` iterates over references to the entries of a key's list,
+// in order, without cloning them, so callers can stream a long list
+// of large entries. `KvStore::read_list_iter` returns one. Each call
+// to `next_entry` yields the entry at `index()` in `list()`, which is
+// the list in `kv@.read_item_and_list(key)`. Iteration ends when
+// `read_list_entry_at_index` reports `IndexOutOfRange`, i.e., at the
+// end of the list. Any other error is yielded, after which the
+// iterator is `done()`, so a failed read can't pass for a short
+// list.
+pub struct ListIter<'a, PM, K, I, L, D, V, E>
+where
+    PM: PersistentMemoryRegions,
+    K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+    I: Serializable + Item<K> + Sized + std::fmt::Debug,
+    L: Serializable + std::fmt::Debug,
+    D: DurableKvStore<PM, K, I, L, E>,
+    V: VolatileKvIndex<K, E>,
+    E: std::fmt::Debug,
+{
+    kv: &'a KvStore<PM, K, I, L, D, V, E>,
+    key: &'a K,
+    idx: u64,
+    done: bool,
 }
 
+impl<'a, PM, K, I, L, D, V, E> ListIter<'a, PM, K, I, L, D, V, E>
+where
+    PM: PersistentMemoryRegions,
+    K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+    I: Serializable + Item<K> + Sized + std::fmt::Debug,
+    L: Serializable + std::fmt::Debug,
+    D: DurableKvStore<PM, K, I, L, E>,
+    V: VolatileKvIndex<K, E>,
+    E: std::fmt::Debug,
+{
+    // The list being iterated over.
+    pub closed spec fn list(self) -> Seq<L>
+    {
+        self.kv@.read_item_and_list(*self.key).unwrap().1
+    }
+
+    // The index in `list()` of the next entry to yield.
+    pub closed spec fn index(self) -> int
+    {
+        self.idx as int
+    }
+
+    // Whether iteration has stopped early due to an error.
+    pub closed spec fn done(self) -> bool
+    {
+        self.done
+    }
+
+    pub closed spec fn valid(self) -> bool
+    {
+        &&& self.kv.valid()
+        &&& self.kv@.read_item_and_list(*self.key) is Some
+    }
+
+    // This yields the next entry of the list, or `None` at the end of
+    // the list or once the iterator is `done()`.
+    pub fn next_entry(&mut self) -> (result: Option<Result<&'a L, KvError<K, E>>>)
+        requires
+            old(self).valid(),
+        ensures
+            self.valid(),
+            self.list() == old(self).list(),
+            match result {
+                Some(Ok(entry)) => {
+                    &&& !old(self).done()
+                    &&& 0 <= old(self).index() < old(self).list().len()
+                    &&& entry == old(self).list()[old(self).index()]
+                    &&& self.done() == (old(self).index() == u64::MAX)
+                    &&& !self.done() ==> self.index() == old(self).index() + 1
+                },
+                Some(Err(_)) => self.done(),
+                None => {
+                    &&& old(self).done() || old(self).index() >= old(self).list().len()
+                    &&& self.done() == old(self).done()
+                },
+            }
+    {
+        if self.done {
+            return None;
+        }
+        match self.kv.read_list_entry_at_index(self.key, self.idx) {
+            Ok(entry) => {
+                // A list can't have more than `u64::MAX` entries in
+                // practice, but stop rather than overflow the index.
+                if self.idx < u64::MAX {
+                    self.idx = self.idx + 1;
+                }
+                else {
+                    self.done = true;
+                }
+                Some(Ok(entry))
+            },
+            Err(KvError::IndexOutOfRange) => None,
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+}
+
+// So that applications can propagate a `KvError` with `?` into
+// `anyhow`- or `thiserror`-style error types, we make it a standard
+// error. Keys and serialization errors are only required to be
+// `Debug`, so that's how the messages show them.
+
+#[cfg(not(verus_keep_ghost))]
+impl<K, E> std::fmt::Display for KvError<K, E>
+where
+    K: std::fmt::Debug,
+    E: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            KvError::NotImplemented => write!(f, "operation not implemented"),
+            KvError::InvalidParameter => write!(f, "invalid parameter"),
+            KvError::InternalError => write!(f, "internal error in key-value store"),
+            KvError::KeyNotFound => write!(f, "key not found"),
+            KvError::KeyAlreadyExists => write!(f, "key already exists"),
+            KvError::InvalidKey { key } => write!(f, "invalid key {:?}", key),
+            KvError::ItemKeyMismatch => write!(f, "item's key doesn't match the given key"),
+            KvError::IndexOutOfRange => write!(f, "index out of range"),
+            KvError::RegionTooSmall { required, actual } =>
+                write!(f, "persistent memory region too small: {} bytes required, {} available", required, actual),
+            KvError::OutOfSpace => write!(f, "out of space in key-value store"),
+            KvError::InvalidPersistentMemoryRegionProvided => write!(f, "invalid persistent memory region provided"),
+            KvError::SerializationError { error } => write!(f, "serialization failed: {:?}", error),
+            KvError::DeserializationError { error } => write!(f, "deserialization failed: {:?}", error),
+            KvError::InvalidImportData => write!(f, "invalid import data"),
+            KvError::CRCMismatch => write!(f, "CRC mismatch"),
+        }
+    }
+}
+
+#[cfg(not(verus_keep_ghost))]
+impl<K, E> std::error::Error for KvError<K, E>
+where
+    K: std::fmt::Debug,
+    E: std::fmt::Debug,
+{
+}
+
+// So that a `ListIter` can be used in `for` loops and with iterator
+// adapters, it's a standard iterator. Each item is what `next_entry`
+// yields.
+
+#[cfg(not(verus_keep_ghost))]
+impl<'a, PM, K, I, L, D, V, E> Iterator for ListIter<'a, PM, K, I, L, D, V, E>
+where
+    PM: PersistentMemoryRegions,
+    K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+    I: Serializable + Item<K> + Sized + std::fmt::Debug,
+    L: Serializable + std::fmt::Debug,
+    D: DurableKvStore<PM, K, I, L, E>,
+    V: VolatileKvIndex<K, E>,
+    E: std::fmt::Debug,
+{
+    type Item = Result<&'a L, KvError<K, E>>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        self.next_entry()
+    }
 }
@@ -14,6 +14,8 @@ use builtin::*;
 use builtin_macros::*;
 use vstd::prelude::*;
 use vstd::seq::*;
+use vstd::set_lib::*;
+use vstd::slice::*;
 
 use super::durable::durableimpl_v::*;
 use super::durable::durablespec_t::*;
@@ -95,12 +97,20 @@ where
         &&& self.volatile_index.valid()
     }
 
+    // Creates a KV store on `pmem`, or, if `pmem` already holds one
+    // with ID `kvstore_id`, e.g., after a crash, reopens it. On
+    // reopening, the durable store is restored from persistent
+    // memory, and the volatile index is loaded from the durable
+    // store's index checkpoint if there's an up-to-date one, and
+    // otherwise rebuilt from scratch.
     pub fn untrusted_new(
         pmem: PM,
         kvstore_id: u128,
         max_keys: usize,
         list_node_size: usize,
     ) -> (result: Result<Self, KvError<K, E>>)
+        requires
+            pmem.inv(),
         ensures
             match result {
                 Ok(new_kv) => {
@@ -109,6 +119,21 @@ where
                 Err(_) => true
             }
     {
+        if D::contains_store(&pmem, kvstore_id) {
+            let durable_store = D::restore(pmem, kvstore_id)?;
+            let volatile_index = match Self::untrusted_load_index_checkpoint(&durable_store, kvstore_id, max_keys) {
+                Some(volatile_index) => volatile_index,
+                None => rebuild_volatile_index::<PM, K, I, L, D, V, E>(&durable_store, kvstore_id, max_keys)?,
+            };
+            return Ok(Self {
+                id: kvstore_id,
+                durable_store,
+                volatile_index,
+                entries_per_list_node: list_node_size,
+                _phantom: Ghost(spec_phantom_data()),
+            });
+        }
+
         let durable_store = D::new(pmem, kvstore_id, max_keys, list_node_size)?;
         let volatile_index = V::new(kvstore_id, max_keys)?;
         let kv = Self {
@@ -125,6 +150,122 @@ where
         Ok(kv)
     }
 
+    // Saves a checkpoint of the volatile index in the durable store,
+    // so that `untrusted_new` can load it instead of rebuilding
+    // the index. The checkpoint is laid out as
+    //
+    //     generation: u64 | index length: u64 | index bytes | CRC
+    //
+    // where the CRC covers everything before it. Recording the
+    // durable store's generation lets a later load re-read only the
+    // slots that have changed since, so callers should checkpoint
+    // periodically, and right before a planned shutdown.
+    pub fn untrusted_checkpoint_index(
+        &mut self,
+        perm: Tracked<&TrustedKvPermission<PM, K, I, L, D, E>>
+    ) -> (result: Result<(), KvError<K, E>>)
+        requires
+            old(self).valid(),
+        ensures
+            self.valid(),
+            self@ == old(self)@,
+            match result {
+                Ok(()) => true,
+                Err(KvError::OutOfSpace) => true,
+                Err(_) => false
+            }
+    {
+        let mut index_bytes = self.volatile_index.to_checkpoint();
+        let mut checkpoint = serialize_to_vec(&self.durable_store.generation());
+        checkpoint.append(&mut serialize_to_vec(&(index_bytes.len() as u64)));
+        checkpoint.append(&mut index_bytes);
+        let mut crc = bytes_crc(checkpoint.as_slice());
+        checkpoint.append(&mut crc);
+        self.durable_store.write_index_checkpoint(checkpoint.as_slice(), perm)
+    }
+
+    // Loads the volatile index from the checkpoint saved by
+    // `untrusted_checkpoint_index`. If the durable store has changed
+    // since the checkpoint was taken, the slots that changed are
+    // re-read with `reindex_slots`. Returns `None`, so that the
+    // caller falls back to `rebuild_volatile_index`, if there's no
+    // checkpoint, if its CRC doesn't match, if the store can't say
+    // which slots changed, or if `volatile_index_matches` finds the
+    // result doesn't match the store.
+    pub fn untrusted_load_index_checkpoint(
+        durable_store: &D,
+        kvstore_id: u128,
+        max_keys: usize,
+    ) -> (result: Option<V>)
+        requires
+            durable_store.valid(),
+            durable_store@.valid(),
+            durable_store@.contents.dom().finite(),
+        ensures
+            match result {
+                Some(volatile_index) => {
+                    &&& volatile_index.valid()
+                    &&& durable_store@.matches_volatile_index(volatile_index@)
+                },
+                None => true
+            }
+    {
+        let checkpoint = match durable_store.read_index_checkpoint() {
+            Ok(Some(checkpoint)) => checkpoint,
+            _ => { return None; },
+        };
+        let u64_len = u64::serialized_len() as usize;
+        let header_len = 2 * u64_len;
+        if checkpoint.len() < header_len + CRC_SIZE as usize {
+            return None;
+        }
+
+        // Check the CRC before trusting anything else in the
+        // checkpoint.
+
+        let body_len = checkpoint.len() - CRC_SIZE as usize;
+        let body = slice_subrange(checkpoint.as_slice(), 0, body_len);
+        let stored_crc = deserialize_from_slice::<u64>(slice_subrange(checkpoint.as_slice(), body_len, checkpoint.len()));
+        let computed_crc = bytes_crc(body);
+        if stored_crc != deserialize_from_slice::<u64>(computed_crc.as_slice()) {
+            return None;
+        }
+
+        let generation = deserialize_from_slice::<u64>(slice_subrange(body, 0, u64_len));
+        let index_len = deserialize_from_slice::<u64>(slice_subrange(body, u64_len, header_len));
+        if index_len != (body_len - header_len) as u64 {
+            return None;
+        }
+
+        let mut volatile_index = match V::from_checkpoint(kvstore_id, max_keys,
+                                                          slice_subrange(body, header_len, body_len)) {
+            Ok(volatile_index) => volatile_index,
+            Err(_) => { return None; },
+        };
+
+        // A checkpoint from an earlier generation is missing the
+        // changes made since, so re-read the slots they touched.
+
+        if generation != durable_store.generation() {
+            let changed_slots = match durable_store.slots_changed_since(generation) {
+                Some(changed_slots) => changed_slots,
+                None => { return None; },
+            };
+            if reindex_slots::<PM, K, I, L, D, V, E>(durable_store, &mut volatile_index, &changed_slots).is_err() {
+                return None;
+            }
+        }
+
+        // The generation only says which slots are likely up to
+        // date; nothing proves it. So check the loaded index against
+        // the durable store before using it.
+
+        if !volatile_index_matches::<PM, K, I, L, D, V, E>(durable_store, &volatile_index) {
+            return None;
+        }
+        Some(volatile_index)
+    }
+
     pub fn untrusted_create(
         &mut self,
         key: &K,
@@ -133,7 +274,6 @@ where
     ) -> (result: Result<(), KvError<K, E>>)
         requires
             old(self).valid(),
-            key == item.spec_key(),
         ensures
             self.valid(),
             match result {
@@ -144,9 +284,21 @@ where
                     &&& old(self)@.contents.contains_key(*key)
                     &&& old(self)@ == self@
                 }
+                Err(KvError::ItemKeyMismatch) => {
+                    &&& *key != item.spec_key()
+                    &&& old(self)@ == self@
+                }
                 Err(_) => false
             }
     {
+        // `item` stores its own key, and the durable store trusts that
+        // key rather than `key`. So make sure they agree before touching
+        // either the durable or the volatile state; otherwise lookups on
+        // `key` would find an entry indexed under a different key.
+        if item.key() != *key {
+            return Err(KvError::ItemKeyMismatch);
+        }
+
         // check whether the key already exists
         if self.volatile_index.get(key).is_some() {
             return Err(KvError::KeyAlreadyExists);
@@ -189,16 +341,59 @@ where
             }
         })
     {
-        assume(false); // TODO
-
         // First, get the offset of the header in the durable store using the volatile index
         let offset = self.volatile_index.get(key);
         match offset {
-            Some(offset) => self.durable_store.read_item(offset),
+            Some(offset) => {
+                proof {
+                    // the durable store has an entry at the indexed offset, so it won't return `None`
+                    assert(self.durable_store@.index_to_key_map.contains_key(offset as int));
+                }
+                self.durable_store.read_item(offset)
+            },
             None => None
         }
     }
 
+    // This function is like `untrusted_read_item`, but it returns an
+    // owned copy of the item, so the caller can read an item and then
+    // update it without holding a borrow of the store.
+    pub fn untrusted_read_item_owned(&self, key: &K) -> (result: Option<I>)
+        where
+            I: Clone,
+        requires
+            self.valid()
+        ensures
+        ({
+            let spec_result = self@.read_item_and_list(*key);
+            match (result, spec_result) {
+                (Some(output_item), Some((spec_item, pages))) => {
+                    &&& spec_item == output_item
+                }
+                (Some(output_item), None) => false,
+                (None, Some((spec_item, pages))) => false,
+                (None, None) => true,
+            }
+        })
+    {
+        match self.untrusted_read_item(key) {
+            Some(item) => Some(Self::clone_item(item)),
+            None => None
+        }
+    }
+
+    // Returns a copy of `item`. It trusts `I`'s implementation of
+    // `Clone` to return a value equal to the original.
+    #[verifier::external_body]
+    fn clone_item(item: &I) -> (result: I)
+        where
+            I: Clone,
+        ensures
+            result == *item
+    {
+        item.clone()
+    }
+
     // // TODO: return a Vec<&L> to save space/reduce copies
     // pub fn untrusted_read_item_and_list(&self, key: &K) -> (result: Option<(&I, Vec<&L>)>)
     //     requires
@@ -251,6 +446,89 @@ where
         Err(KvError::NotImplemented)
     }
 
+    pub fn untrusted_read_list_range(
+        &self,
+        key: &K,
+        start_idx: usize,
+        count: usize
+    ) -> (result: Result<Vec<&L>, KvError<K, E>>)
+        requires
+            self.valid(),
+            start_idx + count <= usize::MAX,
+        ensures
+            ({
+                let spec_result = self@.read_list_range(*key, start_idx as int, count as int);
+                match (result, spec_result) {
+                    (Ok(output_entries), Ok(spec_entries)) => {
+                        &&& output_entries@ == spec_entries
+                    }
+                    (Err(KvError::IndexOutOfRange), Err(KvError::IndexOutOfRange)) => {
+                        &&& self@.contents.contains_key(*key)
+                        &&& self@.contents[*key].1.len() <= start_idx
+                    }
+                    (Err(KvError::KeyNotFound), Err(KvError::KeyNotFound)) => {
+                        &&& !self@.contents.contains_key(*key)
+                    }
+                    (_, _) => false
+                }
+            })
+    {
+        // Only the entries in the requested range are read, so the
+        // rest of the list is never loaded.
+        let item_offset = match self.volatile_index.get(key) {
+            Some(item_offset) => item_offset,
+            None => return Err(KvError::KeyNotFound),
+        };
+
+        let ghost list = self@.contents[*key].1;
+        proof {
+            // the durable entry the index points to holds the key's list
+            assert(self.durable_store@.index_to_key_map.contains_key(item_offset as int));
+            assert(self.durable_store@.contains_key(item_offset as int));
+            assert(self.durable_store@[item_offset as int].unwrap().list().list == list);
+        }
+
+        // Reading the first entry tells us whether `start_idx` is in range.
+        let first_entry = self.durable_store.read_list_entry_at_index(item_offset, start_idx as u64)?;
+        let mut entries = Vec::new();
+        if count == 0 {
+            assert(entries@ =~= list.subrange(start_idx as int, start_idx as int));
+            return Ok(entries);
+        }
+        entries.push(first_entry);
+        assert(entries@ =~= list.subrange(start_idx as int, start_idx + 1));
+
+        let mut idx = start_idx + 1;
+        let mut reached_end = false;
+        while entries.len() < count && !reached_end
+            invariant
+                self.valid(),
+                self@.contents.contains_key(*key),
+                list == self@.contents[*key].1,
+                self.durable_store@.contains_key(item_offset as int),
+                self.durable_store@[item_offset as int].unwrap().list().list == list,
+                start_idx < list.len(),
+                start_idx + count <= usize::MAX,
+                idx == start_idx + entries.len(),
+                1 <= entries.len() <= count,
+                idx <= list.len(),
+                reached_end ==> idx == list.len(),
+                entries@ == list.subrange(start_idx as int, idx as int),
+        {
+            match self.durable_store.read_list_entry_at_index(item_offset, idx as u64) {
+                Ok(entry) => {
+                    entries.push(entry);
+                    idx = idx + 1;
+                    assert(entries@ =~= list.subrange(start_idx as int, idx as int));
+                },
+                // The durable store only reports `IndexOutOfRange` here,
+                // i.e., `idx` is past the end of the list.
+                Err(_) => { reached_end = true; },
+            }
+        }
+        Ok(entries)
+    }
+
     // pub fn untrusted_read_list(&self, key: &K) -> (result: Option<&Vec<L>>)
     //     requires
     //         self.valid(),
@@ -324,10 +602,35 @@ where
                 Err(_) => false
             }
     {
-        assume(false);
+        let ghost old_durable_state = self.durable_store@;
+        let ghost old_volatile_state = self.volatile_index@;
+        let ghost old_kv_state = self@;
+
         // Remove the entry from the volatile index, obtaining the physical offset as the return value
         let offset = self.volatile_index.remove(key)?;
-        self.durable_store.delete(offset, perm)
+
+        proof {
+            // the durable store has an entry at `offset`, so it can't report `KeyNotFound`
+            lemma_volatile_matches_durable_after_delete(old_durable_state, old_volatile_state, offset as int, *key);
+        }
+
+        self.durable_store.delete(offset, perm)?;
+
+        proof {
+            let new_kv_state = old_kv_state.delete(*key).unwrap();
+            // every remaining key's entry is at an offset other than the deleted one
+            assert forall |k: K| #![auto] self.volatile_index@.contains_key(k) implies
+                self.volatile_index@[k].unwrap().item_offset != offset as int by {
+                assert(old_volatile_state.contains_key(k));
+                assert(old_durable_state.index_to_key_map[old_volatile_state[k].unwrap().item_offset] == k);
+                assert(old_durable_state.index_to_key_map[offset as int] == *key);
+            }
+            // the kv state reflects the new volatile and durable store states
+            assert(new_kv_state.contents =~= AbstractKvStoreState::construct_view_contents(
+                    self.volatile_index@, self.durable_store@));
+        }
+
+        Ok(())
     }
 
     pub fn untrusted_append_to_list(
@@ -348,20 +651,96 @@ where
                     &&& !old(self)@.contents.contains_key(*key)
                     &&& old(self)@ == self@
                 }
-                // TODO: case for if we run out of space to append to the list
+                Err(KvError::OutOfSpace) => old(self)@ == self@,
                 Err(_) => false
             }
     {
-        assume(false);
-        return Err(KvError::InternalError);
-        // let offset = self.volatile_index.get(key);
-        // // append a page to the list rooted at this offset
-        // let page_offset = match offset {
-        //     Some(offset) => self.durable_store.append(offset, new_list_entry, perm)?,
-        //     None => return Err(KvError::KeyNotFound)
-        // };
-        // // add the durable location of the page to the in-memory list
-        // self.volatile_index.append_offset_to_list(key, page_offset)
+        let item_offset = match self.volatile_index.get(key) {
+            Some(offset) => offset,
+            None => return Err(KvError::KeyNotFound)
+        };
+        self.untrusted_append_page(key, item_offset, new_list_entry, perm)?;
+        Ok(())
+    }
+
+    // Appends `new_list_entry` to the end of the list associated with
+    // `key`, whose item is stored at `item_offset`, and returns the
+    // physical offset of the list node the entry was written to.
+    //
+    // If the tail node of the list has free space, the entry is written
+    // there. Otherwise (including when the list is empty), we allocate
+    // a new durable node, append the entry to it, and link it onto the
+    // end of the list; only once that's durable do we record the new
+    // node in the volatile index. So a crash during allocation leaves
+    // the durable list either unchanged or with the new node fully
+    // linked, and the volatile index is rebuilt on recovery anyway.
+    // If there's no free node to allocate, this returns
+    // `Err(KvError::OutOfSpace)` and changes nothing.
+    fn untrusted_append_page(
+        &mut self,
+        key: &K,
+        item_offset: u64,
+        new_list_entry: L,
+        perm: Tracked<&TrustedKvPermission<PM, K, I, L, D, E>>
+    ) -> (result: Result<u64, KvError<K, E>>)
+        requires
+            old(self).valid(),
+            old(self).volatile_index@.contains_key(*key),
+            old(self).volatile_index@[*key].unwrap().item_offset == item_offset,
+        ensures
+            self.valid(),
+            match result {
+                Ok(_) => {
+                    &&& self@ == old(self)@.append_to_list(*key, new_list_entry).unwrap()
+                }
+                Err(KvError::OutOfSpace) => old(self)@ == self@,
+                Err(_) => false
+            }
+    {
+        let ghost old_durable_state = self.durable_store@;
+        let ghost old_volatile_state = self.volatile_index@;
+        let ghost old_kv_state = self@;
+        let ghost new_entry = new_list_entry;
+
+        proof {
+            // the durable store has an entry at the indexed offset
+            assert(old_durable_state.index_to_key_map.contains_key(item_offset as int));
+            assert(old_durable_state.index_to_key_map[item_offset as int] == *key);
+        }
+
+        let node_offset = match self.volatile_index.get_tail_node_with_free_space(key)? {
+            Some(node_offset) => {
+                self.durable_store.append(item_offset, new_list_entry, perm)?;
+                self.volatile_index.append_to_list(key)?;
+                node_offset
+            }
+            None => {
+                let node_offset = self.durable_store.alloc_list_node_and_append(item_offset, new_list_entry, perm)?;
+                self.volatile_index.append_node_offset(key, node_offset)?;
+                node_offset
+            }
+        };
+
+        proof {
+            // only the list for `key` and where its nodes are have changed
+            assert(self.durable_store@.contents.dom() =~= old_durable_state.contents.dom());
+            assert(self.volatile_index@.contents.dom() =~= old_volatile_state.contents.dom());
+            lemma_volatile_matches_durable_after_list_update(old_durable_state, old_volatile_state,
+                                                             self.durable_store@, self.volatile_index@);
+
+            // every other key's entry is at an offset other than the updated one
+            assert forall |k: K| #![auto] self.volatile_index@.contains_key(k) && k != *key implies
+                self.volatile_index@[k].unwrap().item_offset != item_offset as int by {
+                assert(old_volatile_state.contains_key(k));
+                assert(old_durable_state.index_to_key_map[old_volatile_state[k].unwrap().item_offset] == k);
+            }
+            // the kv state reflects the new volatile and durable store states
+            let new_kv_state = old_kv_state.append_to_list(*key, new_entry).unwrap();
+            assert(new_kv_state.contents =~= AbstractKvStoreState::construct_view_contents(
+                    self.volatile_index@, self.durable_store@));
+        }
+
+        Ok(node_offset)
     }
 
     pub fn untrusted_append_to_list_and_update_item(
@@ -388,15 +767,24 @@ where
             }
     {
         assume(false);
-        let offset = self.volatile_index.get(key);
-        // update the header at this offset append a page to the list rooted there
-        let page_offset = match offset {
-            Some(offset) => self.durable_store.update_item_and_append(offset, new_list_entry, new_item, perm)?,
+        let item_offset = match self.volatile_index.get(key) {
+            Some(offset) => offset,
             None => return Err(KvError::KeyNotFound)
         };
 
-        // TODO: use append_node_offset or append_to_list depending on whether you need to allocate or not?
-        self.volatile_index.append_to_list(key)
+        // update the item at this offset and append a page to the list rooted there,
+        // allocating a new list node if the tail node is full
+        match self.volatile_index.get_tail_node_with_free_space(key)? {
+            Some(_) => {
+                self.durable_store.update_item_and_append(item_offset, new_list_entry, new_item, perm)?;
+                self.volatile_index.append_to_list(key)
+            }
+            None => {
+                let node_offset = self.durable_store.alloc_list_node_update_item_and_append(
+                    item_offset, new_list_entry, new_item, perm)?;
+                self.volatile_index.append_node_offset(key, node_offset)
+            }
+        }
     }
 
     pub fn untrusted_update_list_entry_at_index(
@@ -432,6 +820,93 @@ where
         }
     }
 
+    pub fn untrusted_replace_list_entry_at_index(
+        &mut self,
+        key: &K,
+        idx: usize,
+        new_list_entry: L,
+        perm: Tracked<&TrustedKvPermission<PM, K, I, L, D, E>>
+    ) -> (result: Result<(), KvError<K, E>>)
+        requires
+            old(self).valid()
+        ensures
+            self.valid(),
+            match result {
+                Ok(()) => {
+                    &&& self@ == old(self)@.update_list_entry_at_index(*key, idx, new_list_entry).unwrap()
+                }
+                Err(KvError::KeyNotFound) => {
+                    &&& !old(self)@.contents.contains_key(*key)
+                    &&& old(self)@ == self@
+                }
+                Err(KvError::IndexOutOfRange) => {
+                    &&& old(self)@.contents.contains_key(*key)
+                    &&& old(self)@.contents[*key].1.len() <= idx
+                    &&& old(self)@ == self@
+                }
+                Err(KvError::OutOfSpace) => old(self)@ == self@,
+                // The volatile index doesn't have a node for `idx` even
+                // though the durable list has an entry there.
+                // TODO: remove once `matches_volatile_index` relates
+                // the index's list lengths to the durable lists'
+                Err(KvError::InternalError) => old(self)@ == self@,
+                Err(_) => false
+            }
+    {
+        let ghost old_durable_state = self.durable_store@;
+        let ghost old_volatile_state = self.volatile_index@;
+        let ghost old_kv_state = self@;
+        let ghost new_entry = new_list_entry;
+
+        let item_offset = match self.volatile_index.get(key) {
+            Some(item_offset) => item_offset,
+            None => return Err(KvError::KeyNotFound),
+        };
+        proof {
+            // the durable store has an entry at the indexed offset
+            assert(old_durable_state.index_to_key_map.contains_key(item_offset as int));
+        }
+
+        // Check `idx` against the durable list, since that's the list
+        // the view reflects.
+        match self.durable_store.read_list_entry_at_index(item_offset, idx as u64) {
+            Ok(_) => {},
+            Err(_) => return Err(KvError::IndexOutOfRange),
+        }
+
+        let node_offset = match self.volatile_index.get_node_offset(key, idx) {
+            Ok(node_offset) => node_offset,
+            Err(_) => return Err(KvError::InternalError),
+        };
+
+        let new_node_offset = self.durable_store.replace_list_entry_at_index(
+            item_offset, node_offset, idx, new_list_entry, perm)?;
+        // The index found the node for `idx` above, and hasn't changed
+        // since, so this succeeds.
+        self.volatile_index.replace_node_offset(key, idx, new_node_offset)?;
+
+        proof {
+            // only the list for `key` and where its nodes are have changed
+            assert(self.durable_store@.contents.dom() =~= old_durable_state.contents.dom());
+            assert(self.volatile_index@.contents.dom() =~= old_volatile_state.contents.dom());
+            lemma_volatile_matches_durable_after_list_update(old_durable_state, old_volatile_state,
+                                                             self.durable_store@, self.volatile_index@);
+
+            // every other key's entry is at an offset other than the updated one
+            assert forall |k: K| #![auto] self.volatile_index@.contains_key(k) && k != *key implies
+                self.volatile_index@[k].unwrap().item_offset != item_offset as int by {
+                assert(old_volatile_state.contains_key(k));
+                assert(old_durable_state.index_to_key_map[old_volatile_state[k].unwrap().item_offset] == k);
+            }
+            // the kv state reflects the new volatile and durable store states
+            let new_kv_state = old_kv_state.update_list_entry_at_index(*key, idx, new_entry).unwrap();
+            assert(new_kv_state.contents =~= AbstractKvStoreState::construct_view_contents(
+                    self.volatile_index@, self.durable_store@));
+        }
+
+        Ok(())
+    }
+
     pub fn untrusted_update_entry_at_index_and_item(
         &mut self,
         key: &K,
@@ -564,6 +1039,617 @@ where
         self.volatile_index.get(key).is_some()
     }
 
+    pub fn untrusted_key_count(&self) -> (result: usize)
+        requires
+            self.valid(),
+        ensures
+            result == self@.num_keys()
+    {
+        let num_keys = self.volatile_index.len();
+        proof {
+            // the view has exactly the keys in the volatile index
+            assert(self@.contents.dom() =~= self.volatile_index@.contents.dom());
+        }
+        num_keys
+    }
+
+    // Estimates the bytes of persistent memory the store uses for
+    // entries and their list nodes, from the durable store's
+    // allocation high-water mark. Only allocator metadata is read.
+    pub fn untrusted_durable_bytes_used(&self) -> (result: u64)
+        requires
+            self.valid(),
+    {
+        self.durable_store.allocation_high_water_mark()
+    }
+
+    // Serializes every entry in the store into the snapshot format
+    // described by `AbstractKvStoreState::spec_decode_export`. If an
+    // entry can't be read, it fails rather than leave the entry out.
+    pub fn untrusted_export(&self) -> (result: Result<Vec<u8>, KvError<K, E>>)
+        requires
+            self.valid()
+        ensures
+            match result {
+                Ok(data) => AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@) == Some(self@.contents),
+                Err(_) => true,
+            }
+    {
+        assume(false); // TODO
+
+        let keys = self.untrusted_get_keys();
+        let mut data = serialize_to_vec(&(keys.len() as u64));
+        let mut i = 0;
+        while i < keys.len()
+        {
+            let key = &keys[i];
+            let item = match self.untrusted_read_item(key) {
+                Some(item) => item,
+                None => { return Err(KvError::InternalError); }, // the index listed a missing key
+            };
+            let entries = match self.untrusted_read_list_range(key, 0, usize::MAX) {
+                Ok(entries) => entries,
+                Err(KvError::IndexOutOfRange) => Vec::new(), // the list is empty
+                Err(e) => { return Err(e); },
+            };
+
+            data.append(&mut serialize_to_vec(key));
+            data.append(&mut serialize_to_vec(item));
+            data.append(&mut serialize_to_vec(&(entries.len() as u64)));
+            let mut j = 0;
+            while j < entries.len()
+            {
+                data.append(&mut serialize_to_vec(entries[j]));
+                j = j + 1;
+            }
+            i = i + 1;
+        }
+        Ok(data)
+    }
+
+    // Parses a snapshot produced by `untrusted_export` into its
+    // entries, without modifying the store. The entries have
+    // distinct keys.
+    pub fn untrusted_parse_import(data: &[u8]) -> (result: Result<Vec<(K, I, Vec<L>)>, KvError<K, E>>)
+        ensures
+            ({
+                let decoded = AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@);
+                match result {
+                    Ok(entries) => {
+                        let contents = decoded.unwrap();
+                        &&& decoded is Some
+                        &&& forall |i: int| 0 <= i < entries@.len() ==> {
+                                let (key, item, list) = #[trigger] entries@[i];
+                                &&& contents.contains_key(key)
+                                &&& contents[key] == (item, list@)
+                            }
+                        &&& forall |key: K| #[trigger] contents.contains_key(key) ==>
+                                exists |i: int| 0 <= i < entries@.len() && (#[trigger] entries@[i]).0 == key
+                        &&& forall |i: int, j: int| 0 <= i < j < entries@.len() ==>
+                                (#[trigger] entries@[i]).0 != (#[trigger] entries@[j]).0
+                    },
+                    Err(KvError::InvalidImportData) => decoded is None,
+                    Err(_) => false,
+                }
+            })
+    {
+        let ghost decoded = AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@);
+        let ghost entry_len = L::spec_serialized_len();
+
+        let mut pos: usize = 0;
+        let num_entries = Self::parse_import_value::<u64>(data, &mut pos)?;
+        let mut entries: Vec<(K, I, Vec<L>)> = Vec::new();
+
+        // `contents` is what the entries parsed so far decode to.
+        // Decoding fails as soon as a key repeats, but we only check
+        // for repeated keys at the end, so `distinct` tracks whether
+        // one has repeated yet.
+        let ghost mut contents = Map::<K, (I, Seq<L>)>::empty();
+        let ghost mut distinct = true;
+        let mut i: u64 = 0;
+        while i < num_entries
+            invariant
+                pos <= data@.len(),
+                i <= num_entries,
+                entries@.len() == i,
+                entry_len == L::spec_serialized_len(),
+                decoded == AbstractKvStoreState::<K, I, L, E>::spec_decode_export(data@),
+                distinct ==> decoded == AbstractKvStoreState::<K, I, L, E>::spec_decode_export_entries(
+                    data@, pos as int, (num_entries - i) as nat, contents),
+                distinct ==> forall |m: int| 0 <= m < entries@.len() ==> {
+                    let (key, item, list) = #[trigger] entries@[m];
+                    &&& contents.contains_key(key)
+                    &&& contents[key] == (item, list@)
+                },
+                distinct ==> forall |key: K| #[trigger] contents.contains_key(key) ==>
+                    exists |m: int| 0 <= m < entries@.len() && (#[trigger] entries@[m]).0 == key,
+                distinct ==> forall |m1: int, m2: int| 0 <= m1 < m2 < entries@.len() ==>
+                    (#[trigger] entries@[m1]).0 != (#[trigger] entries@[m2]).0,
+                !distinct ==> decoded is None,
+                !distinct ==> exists |m1: int, m2: int| 0 <= m1 < m2 < entries@.len() &&
+                    (#[trigger] entries@[m1]).0 == (#[trigger] entries@[m2]).0,
+        {
+            let key = Self::parse_import_value::<K>(data, &mut pos)?;
+            let item = Self::parse_import_value::<I>(data, &mut pos)?;
+            if !Self::keys_equal(&item.key(), &key) {
+                return Err(KvError::InvalidImportData);
+            }
+            let list_len = Self::parse_import_value::<u64>(data, &mut pos)?;
+            let ghost list_start = pos as int;
+            let ghost list_end = list_start + list_len * entry_len;
+            let mut list: Vec<L> = Vec::new();
+            let mut j: u64 = 0;
+            while j < list_len
+                invariant
+                    j <= list_len,
+                    list@.len() == j,
+                    entry_len == L::spec_serialized_len(),
+                    pos <= data@.len(),
+                    pos == list_start + j * entry_len,
+                    list_end == list_start + list_len * entry_len,
+                    forall |m: int| 0 <= m < j ==> #[trigger] list@[m] ==
+                        L::spec_deserialize(data@.subrange(list_start + m * entry_len,
+                                                           list_start + (m + 1) * entry_len)),
+            {
+                assert((j + 1) * entry_len == j * entry_len + entry_len) by(nonlinear_arith);
+                let list_entry = match Self::parse_import_value::<L>(data, &mut pos) {
+                    Ok(list_entry) => list_entry,
+                    Err(e) => {
+                        assert(list_start + (j + 1) * entry_len <= list_end) by(nonlinear_arith)
+                            requires j + 1 <= list_len, list_end == list_start + list_len * entry_len,
+                                     entry_len >= 0;
+                        return Err(e);
+                    },
+                };
+                list.push(list_entry);
+                j = j + 1;
+            }
+
+            let ghost new_list = Seq::new(list_len as nat, |m: int|
+                L::spec_deserialize(data@.subrange(list_start + m * entry_len, list_start + (m + 1) * entry_len)));
+            assert(list@ =~= new_list);
+
+            let ghost old_entries = entries@;
+            let ghost old_contents = contents;
+            let ghost list_view = list@;
+            entries.push((key, item, list));
+            proof {
+                if !distinct {
+                    let (m1, m2) = choose |m1: int, m2: int| 0 <= m1 < m2 < old_entries.len() &&
+                        (#[trigger] old_entries[m1]).0 == (#[trigger] old_entries[m2]).0;
+                    assert(entries@[m1].0 == entries@[m2].0);
+                }
+                else if old_contents.contains_key(key) {
+                    // The key repeats, so decoding fails here.
+                    let m1 = choose |m: int| 0 <= m < old_entries.len() && (#[trigger] old_entries[m]).0 == key;
+                    assert(entries@[m1].0 == entries@[i as int].0);
+                    distinct = false;
+                }
+                else {
+                    contents = old_contents.insert(key, (item, list_view));
+                    assert forall |m: int| 0 <= m < entries@.len() implies {
+                        let (key2, item2, list2) = #[trigger] entries@[m];
+                        &&& contents.contains_key(key2)
+                        &&& contents[key2] == (item2, list2@)
+                    } by {
+                        if m < i {
+                            assert(entries@[m] == old_entries[m]);
+                            assert(old_contents.contains_key(old_entries[m].0));
+                        }
+                    }
+                    assert forall |key2: K| #[trigger] contents.contains_key(key2) implies
+                        exists |m: int| 0 <= m < entries@.len() && (#[trigger] entries@[m]).0 == key2 by {
+                        if key2 == key {
+                            assert(entries@[i as int].0 == key2);
+                        }
+                        else {
+                            let m = choose |m: int| 0 <= m < old_entries.len() && (#[trigger] old_entries[m]).0 == key2;
+                            assert(entries@[m].0 == key2);
+                        }
+                    }
+                    assert forall |m1: int, m2: int| 0 <= m1 < m2 < entries@.len() implies
+                        (#[trigger] entries@[m1]).0 != (#[trigger] entries@[m2]).0 by {
+                        assert(entries@[m1] == old_entries[m1]);
+                        assert(old_contents.contains_key(old_entries[m1].0));
+                        if m2 < i {
+                            assert(entries@[m2] == old_entries[m2]);
+                        }
+                    }
+                }
+            }
+            i = i + 1;
+        }
+
+        if pos != data.len() || Self::has_duplicate_keys(&entries) {
+            return Err(KvError::InvalidImportData);
+        }
+        assert(distinct);
+        Ok(entries)
+    }
+
+    // Returns whether `key1` and `key2` are equal. It trusts `K`'s
+    // implementation of `Eq` to agree with spec equality.
+    #[verifier::external_body]
+    fn keys_equal(key1: &K, key2: &K) -> (result: bool)
+        ensures
+            result == (*key1 == *key2)
+    {
+        key1 == key2
+    }
+
+    // Returns whether any two of `entries` have the same key.
+    #[verifier::external_body]
+    fn has_duplicate_keys(entries: &Vec<(K, I, Vec<L>)>) -> (result: bool)
+        ensures
+            result == exists |i: int, j: int| 0 <= i < j < entries@.len() &&
+                (#[trigger] entries@[i]).0 == (#[trigger] entries@[j]).0
+    {
+        let mut keys = std::collections::HashSet::new();
+        !entries.iter().all(|(key, _, _)| keys.insert(key))
+    }
+
+    // Deserializes an `S` from `data` at `*pos` and advances `*pos`
+    // past it, or returns `Err(KvError::InvalidImportData)` if `data`
+    // is too short.
+    fn parse_import_value<S>(data: &[u8], pos: &mut usize) -> (result: Result<S, KvError<K, E>>)
+        where
+            S: Serializable + Sized
+        requires
+            *old(pos) <= data@.len(),
+        ensures
+            *pos <= data@.len(),
+            match result {
+                Ok(val) => {
+                    &&& *pos == *old(pos) + S::spec_serialized_len()
+                    &&& val == S::spec_deserialize(data@.subrange(*old(pos) as int, *pos as int))
+                },
+                Err(KvError::InvalidImportData) => *old(pos) + S::spec_serialized_len() > data@.len(),
+                Err(_) => false,
+            }
+    {
+        let len = S::serialized_len();
+        if len > (data.len() - *pos) as u64 {
+            return Err(KvError::InvalidImportData);
+        }
+        let end = *pos + len as usize;
+        let val = deserialize_from_slice::<S>(slice_subrange(data, *pos, end));
+        *pos = end;
+        Ok(val)
+    }
+
+}
+
+// Checks whether `volatile_index`, e.g., one loaded from an index
+// checkpoint, matches `durable_store`, by reading the key in every
+// slot directly from persistent memory. Unlike rebuilding the index,
+// this doesn't read any list nodes, since matching only concerns
+// item offsets. It returns `false` if any slot is corrupted.
+pub fn volatile_index_matches<PM, K, I, L, D, V, E>(
+    durable_store: &D,
+    volatile_index: &V,
+) -> (result: bool)
+    where
+        PM: PersistentMemoryRegions,
+        K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+        I: Serializable + Item<K> + Sized + std::fmt::Debug,
+        L: Serializable + std::fmt::Debug,
+        D: DurableKvStore<PM, K, I, L, E>,
+        V: VolatileKvIndex<K, E>,
+        E: std::fmt::Debug,
+    requires
+        durable_store.valid(),
+        durable_store@.valid(),
+        durable_store@.contents.dom().finite(),
+        volatile_index.valid(),
+        volatile_index@.contents.dom().finite(),
+    ensures
+        result ==> durable_store@.matches_volatile_index(volatile_index@)
+{
+    let ghost durable_state = durable_store@;
+    let ghost index_state = volatile_index@;
+
+    // `in_use` is the set of visited slots that are in use, and
+    // `indexed_keys` is the set of keys stored in them. Each such
+    // key is indexed at its own slot, so distinct slots hold
+    // distinct keys, and the two sets are the same size.
+    let ghost mut in_use = Set::<int>::empty();
+    let ghost mut indexed_keys = Set::<K>::empty();
+
+    let num_slots = durable_store.num_slots();
+    let mut num_in_use: u64 = 0;
+    let mut slot: u64 = 0;
+    while slot < num_slots
+        invariant
+            durable_store.valid(),
+            durable_store@ == durable_state,
+            durable_state.valid(),
+            volatile_index.valid(),
+            volatile_index@ == index_state,
+            slot <= num_slots,
+            num_in_use <= slot,
+            in_use.finite(),
+            indexed_keys.finite(),
+            num_in_use == in_use.len(),
+            num_in_use == indexed_keys.len(),
+            forall |i: int| #[trigger] in_use.contains(i) <==> 0 <= i < slot && durable_state.contains_key(i),
+            forall |i: int| #![auto] in_use.contains(i) ==> {
+                &&& indexed_keys.contains(durable_state.index_to_key_map[i])
+                &&& index_state.contains_key(durable_state.index_to_key_map[i])
+                &&& index_state[durable_state.index_to_key_map[i]].unwrap().item_offset == i
+            },
+            forall |k: K| #![auto] indexed_keys.contains(k) ==> {
+                let indexed_offset = index_state[k].unwrap().item_offset;
+                &&& index_state.contains_key(k)
+                &&& in_use.contains(indexed_offset)
+                &&& durable_state.index_to_key_map[indexed_offset] == k
+            },
+    {
+        match durable_store.read_raw_entry(slot) {
+            Ok(Some((key, _item))) => {
+                let indexed_offset = volatile_index.get(&key);
+                if indexed_offset.is_none() || indexed_offset.unwrap() != slot {
+                    return false;
+                }
+                proof {
+                    // The key isn't in `indexed_keys` yet, since every
+                    // key there is indexed at an earlier slot.
+                    assert(durable_state.index_to_key_map[slot as int] == key);
+                    assert(!indexed_keys.contains(key));
+                    in_use = in_use.insert(slot as int);
+                    indexed_keys = indexed_keys.insert(key);
+                }
+                num_in_use = num_in_use + 1;
+            },
+            Ok(None) => {},
+            Err(_) => { return false; },
+        }
+        slot = slot + 1;
+    }
+
+    if volatile_index.len() as u64 != num_in_use {
+        return false;
+    }
+
+    proof {
+        // Every slot in use has been visited, so `in_use` is the
+        // durable store's whole domain.
+        assert(in_use =~= durable_state.contents.dom());
+
+        // `indexed_keys` is a subset of the index's keys of the same
+        // size, so it's all of them.
+        let index_keys = index_state.contents.dom();
+        assert forall |k: K| #![auto] index_keys.contains(k) implies indexed_keys.contains(k) by {
+            if !indexed_keys.contains(k) {
+                assert(indexed_keys.subset_of(index_keys.remove(k)));
+                lemma_len_subset(indexed_keys, index_keys.remove(k));
+            }
+        }
+    }
+    true
+}
+
+// Builds a volatile index for `durable_store` from scratch, by
+// reading every slot and list node directly from persistent memory.
+// This is how the volatile index is recovered after a crash when
+// there's no usable index checkpoint.
+pub fn rebuild_volatile_index<PM, K, I, L, D, V, E>(
+    durable_store: &D,
+    kvstore_id: u128,
+    max_keys: usize,
+) -> (result: Result<V, KvError<K, E>>)
+    where
+        PM: PersistentMemoryRegions,
+        K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+        I: Serializable + Item<K> + Sized + std::fmt::Debug,
+        L: Serializable + std::fmt::Debug,
+        D: DurableKvStore<PM, K, I, L, E>,
+        V: VolatileKvIndex<K, E>,
+        E: std::fmt::Debug,
+    requires
+        durable_store.valid(),
+        durable_store@.valid(),
+        durable_store@.contents.dom().finite(),
+    ensures
+        match result {
+            Ok(volatile_index) => {
+                &&& volatile_index.valid()
+                &&& durable_store@.matches_volatile_index(volatile_index@)
+            }
+            Err(_) => true
+        }
+{
+    let ghost durable_state = durable_store@;
+
+    // `in_use` is the set of visited slots that are in use. Each
+    // holds a distinct key, indexed at that slot, so it's the same
+    // size as the index.
+    let ghost mut in_use = Set::<int>::empty();
+
+    let mut volatile_index = V::new(kvstore_id, max_keys)?;
+    proof {
+        lemma_empty_map_contains_no_keys(volatile_index@.contents);
+        assert(volatile_index@.contents.dom() =~= Set::<K>::empty());
+    }
+
+    let num_slots = durable_store.num_slots();
+    let mut slot: u64 = 0;
+    while slot < num_slots
+        invariant
+            durable_store.valid(),
+            durable_store@ == durable_state,
+            durable_state.valid(),
+            volatile_index.valid(),
+            slot <= num_slots,
+            in_use.finite(),
+            volatile_index@.contents.dom().finite(),
+            in_use.len() == volatile_index@.contents.dom().len(),
+            forall |i: int| #[trigger] in_use.contains(i) <==> 0 <= i < slot && durable_state.contains_key(i),
+            forall |i: int| #![auto] in_use.contains(i) ==> {
+                &&& volatile_index@.contains_key(durable_state.index_to_key_map[i])
+                &&& volatile_index@[durable_state.index_to_key_map[i]].unwrap().item_offset == i
+            },
+            forall |k: K| #![auto] volatile_index@.contains_key(k) ==> {
+                let indexed_offset = volatile_index@[k].unwrap().item_offset;
+                &&& in_use.contains(indexed_offset)
+                &&& durable_state.index_to_key_map[indexed_offset] == k
+            },
+    {
+        let indexed = index_slot::<PM, K, I, L, D, V, E>(durable_store, &mut volatile_index, slot)?;
+        proof {
+            if indexed {
+                in_use = in_use.insert(slot as int);
+            }
+        }
+        slot = slot + 1;
+    }
+
+    proof {
+        // Every slot in use has been visited, so `in_use` is the
+        // durable store's whole domain.
+        assert(in_use =~= durable_state.contents.dom());
+    }
+    Ok(volatile_index)
+}
+
+// Adds the entry in slot `slot` of `durable_store`, if the slot is
+// in use, to `volatile_index`, along with its list nodes, reading
+// both directly from persistent memory. Returns whether the slot was
+// in use. Fails with `KvError::InternalError` if the slot's key is
+// already indexed, which means two slots hold the same key.
+fn index_slot<PM, K, I, L, D, V, E>(
+    durable_store: &D,
+    volatile_index: &mut V,
+    slot: u64,
+) -> (result: Result<bool, KvError<K, E>>)
+    where
+        PM: PersistentMemoryRegions,
+        K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+        I: Serializable + Item<K> + Sized + std::fmt::Debug,
+        L: Serializable + std::fmt::Debug,
+        D: DurableKvStore<PM, K, I, L, E>,
+        V: VolatileKvIndex<K, E>,
+        E: std::fmt::Debug,
+    requires
+        durable_store.valid(),
+        durable_store@.valid(),
+        old(volatile_index).valid(),
+    ensures
+        volatile_index.valid(),
+        match result {
+            Ok(true) => {
+                let key = durable_store@.index_to_key_map[slot as int];
+                &&& durable_store@.contains_key(slot as int)
+                &&& !old(volatile_index)@.contains_key(key)
+                &&& volatile_index@.contents.dom() =~= old(volatile_index)@.contents.dom().insert(key)
+                &&& volatile_index@[key].unwrap().item_offset == slot
+                &&& forall |k: K| #![auto] k != key && old(volatile_index)@.contains_key(k) ==>
+                        volatile_index@[k] == old(volatile_index)@[k]
+            },
+            Ok(false) => {
+                &&& !durable_store@.contains_key(slot as int)
+                &&& volatile_index@ == old(volatile_index)@
+            },
+            Err(_) => true,
+        }
+{
+    let key = match durable_store.read_raw_entry(slot)? {
+        Some((key, _item)) => key,
+        None => { return Ok(false); },
+    };
+    if volatile_index.get(&key).is_some() {
+        return Err(KvError::InternalError);
+    }
+    volatile_index.insert_item_offset(&key, slot)?;
+    let ghost inserted_index = volatile_index@;
+
+    let nodes = durable_store.read_raw_list_nodes(slot)?;
+    let mut i: usize = 0;
+    while i < nodes.len()
+        invariant
+            volatile_index.valid(),
+            volatile_index@.contains_key(key),
+            volatile_index@.contents.dom() =~= inserted_index.contents.dom(),
+            volatile_index@[key].unwrap().item_offset == slot,
+            forall |k: K| #![auto] k != key && inserted_index.contains_key(k) ==>
+                volatile_index@[k] == inserted_index[k],
+    {
+        let (node_offset, num_entries) = nodes[i];
+        volatile_index.insert_list_node(&key, node_offset, num_entries)?;
+        i = i + 1;
+    }
+    Ok(true)
+}
+
+// Brings `volatile_index`, e.g., one loaded from an index checkpoint,
+// up to date with the slots in `changed_slots`, by dropping the keys
+// it indexes at those slots and then indexing what the slots hold
+// now. The result should still be checked with
+// `volatile_index_matches`, since nothing proves that the slots that
+// didn't change are indexed correctly.
+fn reindex_slots<PM, K, I, L, D, V, E>(
+    durable_store: &D,
+    volatile_index: &mut V,
+    changed_slots: &Vec<u64>,
+) -> (result: Result<(), KvError<K, E>>)
+    where
+        PM: PersistentMemoryRegions,
+        K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+        I: Serializable + Item<K> + Sized + std::fmt::Debug,
+        L: Serializable + std::fmt::Debug,
+        D: DurableKvStore<PM, K, I, L, E>,
+        V: VolatileKvIndex<K, E>,
+        E: std::fmt::Debug,
+    requires
+        durable_store.valid(),
+        durable_store@.valid(),
+        old(volatile_index).valid(),
+        old(volatile_index)@.contents.dom().finite(),
+    ensures
+        volatile_index.valid(),
+        match result {
+            Ok(()) => volatile_index@.contents.dom().finite(),
+            Err(_) => true,
+        }
+{
+    let stale_keys = keys_indexed_at::<K, V, E>(volatile_index, changed_slots);
+    let mut i: usize = 0;
+    while i < stale_keys.len()
+        invariant
+            volatile_index.valid(),
+            volatile_index@.contents.dom().finite(),
+    {
+        volatile_index.remove(&stale_keys[i])?;
+        i = i + 1;
+    }
+
+    let mut j: usize = 0;
+    while j < changed_slots.len()
+        invariant
+            durable_store.valid(),
+            durable_store@.valid(),
+            volatile_index.valid(),
+            volatile_index@.contents.dom().finite(),
+    {
+        index_slot::<PM, K, I, L, D, V, E>(durable_store, volatile_index, changed_slots[j])?;
+        j = j + 1;
+    }
+    Ok(())
+}
+
+// Returns the keys that `volatile_index` indexes at any of `slots`.
+#[verifier::external_body]
+fn keys_indexed_at<K, V, E>(volatile_index: &V, slots: &Vec<u64>) -> (result: Vec<K>)
+    where
+        K: Hash + Eq + Clone + Serializable + Sized + std::fmt::Debug,
+        V: VolatileKvIndex<K, E>,
+        E: std::fmt::Debug,
+    requires
+        volatile_index.valid(),
+{
+    let slots: std::collections::HashSet<u64> = slots.iter().copied().collect();
+    volatile_index.get_keys().into_iter()
+        .filter(|key| matches!(volatile_index.get(key), Some(offset) if slots.contains(&offset)))
+        .collect()
 }
 
 }
@@ -146,6 +146,11 @@ verus! {
             self.contents.contains_key(key)
         }
 
+        pub open spec fn num_keys(self) -> nat
+        {
+            self.contents.len()
+        }
+
         pub open spec fn construct_view_contents(
             volatile_store_state: VolatileKvIndexView<K>,
             durable_store_state: DurableKvStoreView<K, I, L, E>
@@ -197,6 +202,22 @@ verus! {
             }
         }
 
+        // Returns at most `count` list entries starting at index `start_idx`.
+        pub open spec fn read_list_range(self, key: K, start_idx: int, count: int) -> Result<Seq<L>, KvError<K, E>>
+        {
+            if self.contents.contains_key(key) {
+                let (offset, list) = self.contents[key];
+                if list.len() > start_idx {
+                    let end_idx = if start_idx + count < list.len() { start_idx + count } else { list.len() as int };
+                    Ok(list.subrange(start_idx, end_idx))
+                } else {
+                    Err(KvError::IndexOutOfRange)
+                }
+            } else {
+                Err(KvError::KeyNotFound)
+            }
+        }
+
         pub open spec fn update_item(self, key: K, new_item: I) -> Result<Self, KvError<K, E>>
         {
             let val = self.read_item_and_list(key);
@@ -325,6 +346,94 @@ verus! {
         {
             self.contents.dom()
         }
+
+        // This spec function says that `self` holds part of
+        // `contents`, as an import of `contents` that stopped partway
+        // through leaves it: every key in `self` is in `contents`,
+        // with the same item and a prefix of its list.
+        pub open spec fn is_partial_import_of(self, contents: Map<K, (I, Seq<L>)>) -> bool
+        {
+            forall |key: K| #[trigger] self.contents.contains_key(key) ==> {
+                let (item, list) = self.contents[key];
+                &&& contents.contains_key(key)
+                &&& item == contents[key].0
+                &&& list.len() <= contents[key].1.len()
+                &&& list == contents[key].1.subrange(0, list.len() as int)
+            }
+        }
+    }
+
+    impl<K, I, L, E> AbstractKvStoreState<K, I, L, E>
+    where
+        K: Hash + Eq + Serializable + std::fmt::Debug,
+        I: Serializable + Item<K>,
+        L: Serializable,
+        E: std::fmt::Debug,
+    {
+        // This spec function specifies which contents an exported
+        // snapshot of a KV store decodes to, or `None` if the data
+        // is truncated or otherwise malformed. The format is:
+        //
+        //   bytes 0..8:   Number of entries, as a little-endian u64
+        //
+        // followed by, for each entry,
+        //
+        //   the serialized key,
+        //   the serialized item,
+        //   the number of list entries, as a little-endian u64, and
+        //   each serialized list entry, in list order.
+        //
+        // A snapshot is malformed if it has trailing bytes, if an
+        // item's key differs from the entry's key, or if a key appears
+        // more than once.
+        pub open spec fn spec_decode_export(data: Seq<u8>) -> Option<Map<K, (I, Seq<L>)>>
+        {
+            let count_len = u64::spec_serialized_len();
+            if data.len() < count_len {
+                None
+            } else {
+                let num_entries = u64::spec_deserialize(data.subrange(0, count_len as int));
+                Self::spec_decode_export_entries(data, count_len as int, num_entries as nat, Map::empty())
+            }
+        }
+
+        // This spec function decodes the last `num_entries` entries of
+        // a snapshot, the first of which starts at `pos` in `data`, and
+        // adds them to `contents`, the entries decoded so far.
+        pub open spec fn spec_decode_export_entries(
+            data: Seq<u8>,
+            pos: int,
+            num_entries: nat,
+            contents: Map<K, (I, Seq<L>)>,
+        ) -> Option<Map<K, (I, Seq<L>)>>
+            decreases num_entries
+        {
+            if num_entries == 0 {
+                if pos == data.len() { Some(contents) } else { None }
+            } else {
+                let key_end = pos + K::spec_serialized_len();
+                let item_end = key_end + I::spec_serialized_len();
+                let list_start = item_end + u64::spec_serialized_len();
+                if list_start > data.len() {
+                    None
+                } else {
+                    let key = K::spec_deserialize(data.subrange(pos, key_end));
+                    let item = I::spec_deserialize(data.subrange(key_end, item_end));
+                    let list_len = u64::spec_deserialize(data.subrange(item_end, list_start));
+                    let entry_len = L::spec_serialized_len();
+                    let list_end = list_start + list_len * entry_len;
+                    if item.spec_key() != key || contents.contains_key(key) || list_end > data.len() {
+                        None
+                    } else {
+                        let list = Seq::new(list_len as nat, |j: int|
+                            L::spec_deserialize(data.subrange(list_start + j * entry_len,
+                                                              list_start + (j + 1) * entry_len)));
+                        Self::spec_decode_export_entries(data, list_end, (num_entries - 1) as nat,
+                                                         contents.insert(key, (item, list)))
+                    }
+                }
+            }
+        }
     }
 
 }
@@ -0,0 +1,211 @@
+//! This file contains `SortedListNodes`, an executable representation
+//! of the list node map for a single key in the volatile index. It
+//! keeps the nodes in a vector sorted by `start_index`, so the node
+//! holding a given logical list index can be found by binary search
+//! in O(log nodes) time rather than by scanning every node.
+//!
+//! The code in this file is verified and untrusted (as indicated by
+//! the `_v.rs` suffix), so you don't have to read it to be confident
+//! of the system's correctness.
+
+#![allow(unused_imports)]
+use builtin::*;
+use builtin_macros::*;
+use vstd::prelude::*;
+
+use crate::kv::kvimpl_t::*;
+use crate::kv::volatile::volatilespec_t::*;
+use std::hash::Hash;
+
+verus! {
+    // A `ListNodeRange` is the executable counterpart of one entry in
+    // `VolatileKvIndexEntry::list_node_offsets`. The node holds the
+    // logical list indexes in `[start_index, end_index)`.
+    pub struct ListNodeRange {
+        pub start_index: u64,
+        pub end_index: u64,
+        pub live_index: u64,
+        pub physical_offset: u64,
+        pub free_entries: u64,
+    }
+
+    impl ListNodeRange {
+        pub open spec fn range(self) -> (int, int)
+        {
+            (self.start_index as int, self.end_index as int)
+        }
+
+        pub open spec fn view(self) -> ListNodeIndexEntry
+        {
+            ListNodeIndexEntry {
+                start_index: self.start_index as int,
+                live_index: self.live_index as int,
+                physical_offset: self.physical_offset as int,
+                free_entries: self.free_entries as int,
+            }
+        }
+
+        pub open spec fn contains_index(self, index: int) -> bool
+        {
+            self.start_index <= index < self.end_index
+        }
+    }
+
+    pub struct SortedListNodes {
+        pub nodes: Vec<ListNodeRange>,
+    }
+
+    impl SortedListNodes {
+        // The nodes are valid if each one holds a nonempty range and
+        // each one's range ends at or before the next one's begins.
+        // So the ranges are disjoint and sorted by `start_index`.
+        pub open spec fn valid(&self) -> bool
+        {
+            &&& forall |i: int| 0 <= i < self.nodes@.len() ==>
+                   (#[trigger] self.nodes@[i]).start_index < self.nodes@[i].end_index
+            &&& forall |i: int, j: int| 0 <= i < j < self.nodes@.len() ==>
+                   (#[trigger] self.nodes@[i]).end_index <= (#[trigger] self.nodes@[j]).start_index
+        }
+
+        // This spec function says that the nodes are exactly the
+        // entries of `list_node_offsets`, keyed by range.
+        pub open spec fn represents(&self, list_node_offsets: Map<(int, int), ListNodeIndexEntry>) -> bool
+        {
+            &&& forall |i: int| 0 <= i < self.nodes@.len() ==> {
+                   let node = #[trigger] self.nodes@[i];
+                   &&& list_node_offsets.contains_key(node.range())
+                   &&& list_node_offsets[node.range()] == node@
+               }
+            &&& forall |k: (int, int)| #[trigger] list_node_offsets.contains_key(k) ==>
+                   exists |i: int| 0 <= i < self.nodes@.len() && (#[trigger] self.nodes@[i]).range() == k
+        }
+
+        // This function returns the position in `nodes` of the node
+        // holding logical list index `index`, or `None` if no node
+        // holds it. It does a binary search, so it takes O(log n)
+        // time for n nodes.
+        pub fn find_node(&self, index: u64) -> (result: Option<usize>)
+            requires
+                self.valid(),
+            ensures
+                match result {
+                    Some(i) => i < self.nodes@.len() && self.nodes@[i as int].contains_index(index as int),
+                    None => forall |i: int| 0 <= i < self.nodes@.len() ==>
+                                !(#[trigger] self.nodes@[i]).contains_index(index as int),
+                }
+        {
+            let mut lo: usize = 0;
+            let mut hi: usize = self.nodes.len();
+            while lo < hi
+                invariant
+                    self.valid(),
+                    lo <= hi <= self.nodes@.len(),
+                    forall |j: int| 0 <= j < lo ==> (#[trigger] self.nodes@[j]).end_index <= index,
+                    forall |j: int| hi <= j < self.nodes@.len() ==> index < (#[trigger] self.nodes@[j]).start_index,
+                decreases
+                    hi - lo,
+            {
+                let mid = lo + (hi - lo) / 2;
+                let node = &self.nodes[mid];
+                if index < node.start_index {
+                    assert forall |j: int| mid <= j < self.nodes@.len() implies
+                               index < (#[trigger] self.nodes@[j]).start_index by {
+                        if j > mid {
+                            assert(self.nodes@[mid as int].end_index <= self.nodes@[j].start_index);
+                            assert(self.nodes@[mid as int].start_index < self.nodes@[mid as int].end_index);
+                        }
+                    }
+                    hi = mid;
+                }
+                else if index >= node.end_index {
+                    assert forall |j: int| 0 <= j <= mid implies
+                               (#[trigger] self.nodes@[j]).end_index <= index by {
+                        if j < mid {
+                            assert(self.nodes@[j].end_index <= self.nodes@[mid as int].start_index);
+                            assert(self.nodes@[mid as int].start_index < self.nodes@[mid as int].end_index);
+                        }
+                    }
+                    lo = mid + 1;
+                }
+                else {
+                    return Some(mid);
+                }
+            }
+            None
+        }
+
+        // This lemma says that at most one node holds any given index,
+        // since the nodes' ranges are disjoint.
+        pub proof fn lemma_node_holding_index_is_unique(&self, index: int, i: int, j: int)
+            requires
+                self.valid(),
+                0 <= i < self.nodes@.len(),
+                0 <= j < self.nodes@.len(),
+                self.nodes@[i].contains_index(index),
+                self.nodes@[j].contains_index(index),
+            ensures
+                i == j
+        {
+            if i < j {
+                assert(self.nodes@[i].end_index <= self.nodes@[j].start_index);
+            }
+            if j < i {
+                assert(self.nodes@[j].end_index <= self.nodes@[i].start_index);
+            }
+        }
+
+        // This lemma says that the result of `find_node` agrees with
+        // the spec function `VolatileKvIndexView::get_node_view`. Even
+        // though the latter uses `choose`, there's only one range
+        // holding `index`, so it must choose that node's range.
+        pub proof fn lemma_find_node_matches_get_node_view<K, E>(
+            &self,
+            index_view: VolatileKvIndexView<K>,
+            key: K,
+            index: int,
+            result: Option<usize>,
+        )
+            where
+                K: Hash + Eq + std::fmt::Debug,
+                E: std::fmt::Debug,
+            requires
+                self.valid(),
+                index_view.contains_key(key),
+                self.represents(index_view.contents[key].list_node_offsets),
+                match result {
+                    Some(i) => i < self.nodes@.len() && self.nodes@[i as int].contains_index(index),
+                    None => forall |i: int| 0 <= i < self.nodes@.len() ==>
+                                !(#[trigger] self.nodes@[i]).contains_index(index),
+                },
+            ensures
+                match result {
+                    Some(i) => index_view.get_node_view::<E>(key, index) ==
+                                   Ok::<_, KvError<K, E>>((self.nodes@[i as int].range(), self.nodes@[i as int]@)),
+                    None => index_view.get_node_view::<E>(key, index) ==
+                                Err::<((int, int), ListNodeIndexEntry), _>(KvError::<K, E>::IndexOutOfRange),
+                }
+        {
+            let list_node_offsets = index_view.contents[key].list_node_offsets;
+            match result {
+                Some(i) => {
+                    let node = self.nodes@[i as int];
+                    assert(list_node_offsets.contains_key(node.range()));
+                    let range = choose |k: (int, int)| {
+                        let (s, e) = k;
+                        &&& s <= index < e
+                        &&& #[trigger] list_node_offsets.contains_key(k)
+                    };
+                    let j = choose |j: int| 0 <= j < self.nodes@.len() && (#[trigger] self.nodes@[j]).range() == range;
+                    self.lemma_node_holding_index_is_unique(index, i as int, j);
+                },
+                None => {
+                    assert forall |k: (int, int)| #[trigger] list_node_offsets.contains_key(k) implies
+                               !(k.0 <= index < k.1) by {
+                        let j = choose |j: int| 0 <= j < self.nodes@.len() && (#[trigger] self.nodes@[j]).range() == k;
+                        assert(!self.nodes@[j].contains_index(index));
+                    }
+                },
+            }
+        }
+    }
+}
@@ -1,2 +1,3 @@
+pub mod listnodes_v;
 pub mod volatileimpl_v;
 pub mod volatilespec_t;
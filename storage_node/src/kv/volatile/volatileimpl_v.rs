@@ -84,6 +84,83 @@ verus! {
                 })
         ;
 
+        // Records a newly-allocated list node at the end of the list for
+        // `key`. This should be called only after the durable store has
+        // successfully allocated the node and appended an entry to it.
+        fn append_node_offset(
+            &mut self,
+            key: &K,
+            node_offset: u64,
+        ) -> (result: Result<(), KvError<K, E>>)
+            requires
+                old(self).valid(),
+                old(self)@.contains_key(*key),
+            ensures
+                self.valid(),
+                match result {
+                    Ok(()) => self@ == old(self)@.append_node_offset(*key, node_offset as int,
+                                                                     old(self)@.list_len(*key)),
+                    Err(_) => false // TODO
+                }
+        ;
+
+        // Records an existing list node holding `num_entries` entries at
+        // the end of the list for `key`. This is meant for rebuilding the
+        // index from the durable store, where each node is read whole.
+        fn insert_list_node(
+            &mut self,
+            key: &K,
+            node_offset: u64,
+            num_entries: u64,
+        ) -> (result: Result<(), KvError<K, E>>)
+            requires
+                old(self).valid(),
+                old(self)@.contains_key(*key),
+            ensures
+                self.valid(),
+                match result {
+                    Ok(()) => self@ == old(self)@.insert_list_node(*key, node_offset as int, num_entries as int),
+                    Err(_) => true // TODO
+                }
+        ;
+
+        // Returns the physical offset of the last list node for `key` if
+        // that node has room for another entry, or `None` if appending
+        // requires allocating a new node. The latter includes the case
+        // where the list is empty.
+        fn get_tail_node_with_free_space(
+            &self,
+            key: &K,
+        ) -> (result: Result<Option<u64>, KvError<K, E>>)
+            requires
+                self.valid(),
+            ensures
+                match result {
+                    Ok(Some(node_offset)) => {
+                        &&& self@.contains_key(*key)
+                        &&& self@.list_len(*key) > 0
+                        &&& ({
+                               let (_, node_view) = self@.get_node_view::<E>(*key, self@.list_len(*key) - 1).unwrap();
+                               &&& node_view.has_free_space()
+                               &&& node_view.physical_offset == node_offset
+                           })
+                    },
+                    Ok(None) => {
+                        &&& self@.contains_key(*key)
+                        &&& {
+                               ||| self@.list_len(*key) == 0
+                               ||| {
+                                      let (_, node_view) =
+                                          self@.get_node_view::<E>(*key, self@.list_len(*key) - 1).unwrap();
+                                      !node_view.has_free_space()
+                                  }
+                           }
+                    },
+                    Err(KvError::KeyNotFound) => !self@.contains_key(*key),
+                    Err(_) => false,
+                }
+        ;
+
         fn get(
             &self,
             key: &K
@@ -125,7 +202,9 @@ verus! {
                 }
         ;
 
-        // returns a pointer to the list node that contains the specified index
+        // returns a pointer to the list node that contains the specified index.
+        // Implementations can keep each key's nodes in a `SortedListNodes`
+        // to find the node in O(log nodes) time.
         fn get_node_offset(
             &self,
             key: &K,
@@ -158,15 +237,49 @@ verus! {
                         match old(self)@[*key] {
                             Some(entry) => {
                                 &&& entry.item_offset == offset as int
-                                &&& self@[*key].is_None()
+                                &&& self@ == old(self)@.remove(*key)
                             }
                             None => false
                         }
                     }
-                    Err(_) => true // TODO
+                    Err(KvError::KeyNotFound) => {
+                        &&& old(self)@[*key].is_None()
+                        &&& self@ == old(self)@
+                    }
+                    Err(_) => false // TODO
                 }
         ;
 
+        // Records that the list node containing index `idx` of the list
+        // for `key` now lives at `new_node_offset`. This should be called
+        // only after the durable store has swapped the copy into the list.
+        fn replace_node_offset(
+            &mut self,
+            key: &K,
+            idx: usize,
+            new_node_offset: u64,
+        ) -> (result: Result<(), KvError<K, E>>)
+            requires
+                old(self).valid(),
+            ensures
+                self.valid(),
+                ({
+                    let spec_result = old(self)@.replace_node_offset::<E>(*key, idx as int, new_node_offset as int);
+                    match (result, spec_result) {
+                        (Ok(()), Ok(spec_self)) => self@ == spec_self,
+                        (Err(KvError::KeyNotFound), Err(KvError::KeyNotFound)) => {
+                            &&& !old(self)@.contains_key(*key)
+                            &&& self@ == old(self)@
+                        }
+                        (Err(KvError::IndexOutOfRange), Err(KvError::IndexOutOfRange)) => {
+                            &&& old(self)@.contains_key(*key)
+                            &&& self@ == old(self)@
+                        }
+                        _ => false
+                    }
+                })
+        ;
+
         // trims the volatile index for the list associated with the key
         fn trim_list(
             &mut self,
@@ -204,5 +317,40 @@ verus! {
                 self@.keys() == result@.to_set()
         ;
 
+        // Returns the number of keys in the index.
+        fn len(
+            &self
+        ) -> (result: usize)
+            requires
+                self.valid(),
+            ensures
+                result == self@.contents.len()
+        ;
+
+        // Serializes the whole index, so that `from_checkpoint` can
+        // rebuild it without reading the durable store.
+        fn to_checkpoint(
+            &self
+        ) -> (result: Vec<u8>)
+            requires
+                self.valid(),
+        ;
+
+        // Rebuilds an index from bytes produced by `to_checkpoint`.
+        fn from_checkpoint(
+            kvstore_id: u128,
+            max_keys: usize,
+            bytes: &[u8],
+        ) -> (result: Result<Self, KvError<K, E>>)
+            ensures
+                match result {
+                    Ok(volatile_index) => {
+                        &&& volatile_index.valid()
+                        &&& volatile_index@.contents.dom().finite()
+                    },
+                    Err(_) => true // TODO
+                }
+        ;
+
     }
 }
@@ -116,7 +116,7 @@ verus! {
                                 start_index,
                                 live_index: 0,
                                 physical_offset: node_offset,
-                                free_entries: self.list_entries_per_node
+                                free_entries: self.list_entries_per_node - 1
                             }),
                         list_len: current_entry.list_len + 1
                     }),
@@ -152,6 +152,33 @@ verus! {
             }
         }
 
+        // Adds a list node holding `num_entries` entries to the end of the
+        // list for `key`, e.g., when rebuilding the index from a durable
+        // node that's already been written. Appending a node with one
+        // entry is the same as `append_node_offset`.
+        pub open spec fn insert_list_node(&self, key: K, node_offset: int, num_entries: int) -> Self
+        {
+            let current_entry = self.contents[key];
+            let start_index = current_entry.list_len;
+            Self {
+                contents: self.contents.insert(
+                    key,
+                    VolatileKvIndexEntry {
+                        item_offset: current_entry.item_offset,
+                        list_node_offsets: current_entry.list_node_offsets.insert(
+                            (start_index, start_index + num_entries),
+                            ListNodeIndexEntry {
+                                start_index,
+                                live_index: 0,
+                                physical_offset: node_offset,
+                                free_entries: self.list_entries_per_node - num_entries
+                            }),
+                        list_len: current_entry.list_len + num_entries
+                    }),
+                list_entries_per_node: self.list_entries_per_node,
+            }
+        }
+
         // returns the offset of the node that contains the specified logical list index
         pub open spec fn get_node_offset<E>(&self, key: K, index: int) -> Result<int, KvError<K, E>>
             where
@@ -163,6 +190,32 @@ verus! {
             }
         }
 
+        // Updates the index to reflect that the list node containing the
+        // specified logical list index has been replaced by a copy at
+        // `new_node_offset`. The node covers the same indexes as before.
+        pub open spec fn replace_node_offset<E>(self, key: K, index: int, new_node_offset: int) -> Result<Self, KvError<K, E>>
+            where
+                E: std::fmt::Debug
+        {
+            match self.get_node_view(key, index) {
+                Ok((range, node_view)) => {
+                    let old_index_entry = self.contents[key];
+                    let new_index_entry = VolatileKvIndexEntry {
+                        list_node_offsets: old_index_entry.list_node_offsets.insert(
+                            range,
+                            ListNodeIndexEntry { physical_offset: new_node_offset, ..node_view }
+                        ),
+                        ..old_index_entry
+                    };
+                    Ok(Self {
+                        contents: self.contents.insert(key, new_index_entry),
+                        list_entries_per_node: self.list_entries_per_node
+                    })
+                }
+                Err(e) => Err(e)
+            }
+        }
+
         // returns the length of the list associated with this key
         // TODO: should maintain as an invariant that this actually matches the
         // number of entries in all associated nodes
@@ -175,7 +228,8 @@ verus! {
 
         // Updates the index to reflect that an entry has been appended to the end of the list.
         // It doesn't actually matter what the entry is -- we just need to update the index
-        // to reflect that something new has been added
+        // to reflect that something new has been added. The tail node's range grows by one
+        // to cover the new entry, so that `get_node_view` finds it.
         pub open spec fn append_to_list<E>(self, key: K) -> Result<Self, KvError<K, E>>
             where
                 E: std::fmt::Debug
@@ -189,9 +243,11 @@ verus! {
                         let new_node_view = old_node_view.append_entry();
                         match new_node_view {
                             Ok(new_node_view) => {
+                                let (start, end) = range;
                                 let new_index_entry = VolatileKvIndexEntry {
                                     item_offset: old_index_entry.item_offset,
-                                    list_node_offsets: old_index_entry.list_node_offsets.insert(range, new_node_view),
+                                    list_node_offsets: old_index_entry.list_node_offsets.remove(range)
+                                                                                        .insert((start, end + 1), new_node_view),
                                     list_len: old_index_entry.list_len + 1
                                 };
 
@@ -283,7 +339,7 @@ verus! {
             }
         }
 
-        pub closed spec fn remove(&self, key: K) -> Self
+        pub open spec fn remove(&self, key: K) -> Self
         {
             Self {
                 contents: self.contents.remove(key),
@@ -46,6 +46,2037 @@ fn check_layout() {
     assert!(log_metadata_size == LENGTH_OF_LOG_METADATA.try_into().unwrap());
 }
 
+/// This test is like `check_layout`, but for the single-log layout.
+#[test]
+fn check_log_layout() {
+    use crate::log::layout_v as log_layout;
+
+    let global_metadata_size = core::mem::size_of::<log_layout::GlobalMetadata>();
+    let region_metadata_size = core::mem::size_of::<log_layout::RegionMetadata>();
+    let log_metadata_size = core::mem::size_of::<log_layout::LogMetadata>();
+
+    assert!(global_metadata_size == log_layout::LENGTH_OF_GLOBAL_METADATA.try_into().unwrap());
+    assert!(region_metadata_size == log_layout::LENGTH_OF_REGION_METADATA.try_into().unwrap());
+    assert!(log_metadata_size == log_layout::LENGTH_OF_LOG_METADATA.try_into().unwrap());
+}
+
+/// This test round-trips a pair through persistent memory, checking
+/// that `(A, B)` is serialized as `A`'s bytes followed by `B`'s.
+#[test]
+fn check_pair_serialization_round_trip() {
+    use crate::pmem::serialization_t::Serializable;
+
+    let mut region_sizes = Vec::<u64>::new();
+    region_sizes.push(64);
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(region_sizes.as_slice());
+
+    let pair: (u64, u64) = (0x0123456789abcdef, 42);
+    assert!(<(u64, u64)>::serialized_len() == 16);
+    regions.serialize_and_write(0, 8, &pair);
+
+    let bytes = regions.read(0, 8, 16);
+    assert!(bytes[..8] == pair.0.to_le_bytes());
+    assert!(bytes[8..] == pair.1.to_le_bytes());
+
+    let read_pair: &(u64, u64) = regions.read_and_deserialize(0, 8);
+    assert!(*read_pair == pair);
+}
+
+/// This test round-trips a `u16` through persistent memory, checking
+/// that it's serialized as its two little-endian bytes.
+#[test]
+fn check_u16_serialization_round_trip() {
+    use crate::pmem::serialization_t::Serializable;
+
+    let mut region_sizes = Vec::<u64>::new();
+    region_sizes.push(64);
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(region_sizes.as_slice());
+
+    let val: u16 = 0xbeef;
+    assert!(u16::serialized_len() == 2);
+    regions.serialize_and_write(0, 6, &val);
+
+    let bytes = regions.read(0, 6, 2);
+    assert!(bytes[..] == val.to_le_bytes());
+
+    let read_val: &u16 = regions.read_and_deserialize(0, 6);
+    assert!(*read_val == val);
+}
+
+verus! {
+    #[repr(transparent)]
+    #[derive(Debug, PartialEq, Eq)]
+    struct Timestamp(u64);
+
+    impl crate::pmem::serialization_t::TransparentSerializable for Timestamp {
+        type Inner = u64;
+
+        open spec fn spec_inner(self) -> u64
+        {
+            self.0
+        }
+
+        open spec fn spec_from_inner(inner: u64) -> Self
+        {
+            Timestamp(inner)
+        }
+
+        proof fn lemma_auto_from_inner_inverts_inner()
+        {
+        }
+    }
+}
+
+/// This test round-trips a `#[repr(transparent)]` newtype through
+/// persistent memory, checking that it's serialized exactly like the
+/// type it wraps.
+#[test]
+fn check_transparent_newtype_serializes_like_inner_type() {
+    use crate::pmem::serialization_t::*;
+
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[64]);
+
+    let timestamp = Timestamp(0x0123456789abcdef);
+    assert!(Timestamp::serialized_len() == u64::serialized_len());
+    assert!(serialize_to_vec(&timestamp) == serialize_to_vec(&timestamp.0));
+    assert!(calculate_crc(&timestamp) == calculate_crc(&timestamp.0));
+    regions.serialize_and_write(0, 8, &timestamp);
+
+    let bytes = regions.read(0, 8, 8);
+    assert!(bytes[..] == timestamp.0.to_le_bytes());
+
+    let read_timestamp: &Timestamp = regions.read_and_deserialize(0, 8);
+    assert!(*read_timestamp == timestamp);
+}
+
+verus! {
+    // A `Page` is stored as a `Tagged<(u64, u64)>`. Discriminants
+    // other than 1 and 2 are read back as `Free`.
+    #[derive(Debug, PartialEq, Eq)]
+    enum Page {
+        Free,
+        Leaf(u64),
+        Interior(u64, u64),
+    }
+
+    impl crate::pmem::serialization_t::SerializableEnum for Page {
+        type Payload = (u64, u64);
+
+        open spec fn spec_discriminant(self) -> u32
+        {
+            match self {
+                Page::Free => 0,
+                Page::Leaf(_) => 1,
+                Page::Interior(_, _) => 2,
+            }
+        }
+
+        open spec fn spec_payload(self) -> (u64, u64)
+        {
+            match self {
+                Page::Free => (0, 0),
+                Page::Leaf(value) => (value, 0),
+                Page::Interior(left, right) => (left, right),
+            }
+        }
+
+        open spec fn spec_from_parts(discriminant: u32, payload: (u64, u64)) -> Self
+        {
+            if discriminant == 1 { Page::Leaf(payload.0) }
+            else if discriminant == 2 { Page::Interior(payload.0, payload.1) }
+            else { Page::Free }
+        }
+
+        proof fn lemma_auto_from_parts_inverts_parts()
+        {
+        }
+
+        fn to_tagged(&self) -> (result: crate::pmem::serialization_t::Tagged<(u64, u64)>)
+        {
+            let (discriminant, payload) = match self {
+                Page::Free => (0, (0, 0)),
+                Page::Leaf(value) => (1, (*value, 0)),
+                Page::Interior(left, right) => (2, (*left, *right)),
+            };
+            crate::pmem::serialization_t::Tagged { discriminant, _padding: 0, payload }
+        }
+
+        fn from_tagged(tagged: &crate::pmem::serialization_t::Tagged<(u64, u64)>) -> (result: Self)
+        {
+            if tagged.discriminant == 1 { Page::Leaf(tagged.payload.0) }
+            else if tagged.discriminant == 2 { Page::Interior(tagged.payload.0, tagged.payload.1) }
+            else { Page::Free }
+        }
+    }
+}
+
+/// This test round-trips each variant of an enum through persistent
+/// memory in its tagged form, and checks that an unknown
+/// discriminant is read back as the default variant.
+#[test]
+fn check_serializable_enum_round_trips_every_variant() {
+    use crate::pmem::serialization_t::*;
+
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[64]);
+    assert!(Tagged::<(u64, u64)>::serialized_len() == 24);
+
+    for page in [Page::Free, Page::Leaf(7), Page::Interior(0x0123456789abcdef, 42)] {
+        regions.serialize_and_write(0, 8, &page.to_tagged());
+        let tagged: &Tagged<(u64, u64)> = regions.read_and_deserialize(0, 8);
+        assert_eq!(Page::from_tagged(tagged), page);
+    }
+
+    regions.write(0, 8, &99u32.to_le_bytes());
+    let tagged: &Tagged<(u64, u64)> = regions.read_and_deserialize(0, 8);
+    assert_eq!(Page::from_tagged(tagged), Page::Free);
+}
+
+#[test]
+fn check_read_array_reads_consecutive_records() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(64);
+    let records: [(u64, u64); 3] = [(1, 2), (3, 4), (5, 6)];
+    for (i, record) in records.iter().enumerate() {
+        pm_region.serialize_and_write(8 + 16 * i as u64, record);
+    }
+    pm_region.flush().unwrap();
+
+    assert_eq!(read_array::<_, (u64, u64)>(&pm_region, 8, 3), records.to_vec());
+    assert_eq!(read_array::<_, (u64, u64)>(&pm_region, 24, 2), records[1..].to_vec());
+    assert!(read_array::<_, u64>(&pm_region, 8, 0).is_empty());
+}
+
+#[test]
+fn check_checked_read_rejects_out_of_range_reads() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(64);
+    pm_region.write(60, &[1, 2, 3, 4]);
+    pm_region.flush().unwrap();
+
+    assert_eq!(checked_read(&pm_region, 60, 4).unwrap(), vec![1, 2, 3, 4]);
+    assert!(checked_read(&pm_region, 64, 0).unwrap().is_empty());
+    assert!(matches!(checked_read(&pm_region, 61, 4), Err(PmemError::AccessOutOfRange)));
+    assert!(matches!(checked_read(&pm_region, 0, 65), Err(PmemError::AccessOutOfRange)));
+    assert!(matches!(checked_read(&pm_region, u64::MAX, 2), Err(PmemError::AccessOutOfRange)));
+}
+
+#[test]
+fn check_crc_digest_matches_crc_of_concatenation() {
+    let bytes: Vec<u8> = (0..100u8).collect();
+    for split in [0, 1, 37, 100] {
+        let mut digest = CrcDigest::new();
+        digest.update(&bytes[..split]);
+        digest.update(&bytes[split..]);
+        assert_eq!(digest.finalize(), bytes_crc(&bytes));
+    }
+    assert_eq!(CrcDigest::new().finalize(), bytes_crc(&[]));
+}
+
+#[test]
+fn check_serialize_into_assembles_record_in_buffer() {
+    use crate::pmem::serialization_t::*;
+
+    let mut buf = vec![0xffu8; 20];
+    serialize_into(&0x1122334455667788u64, &mut buf, 2);
+    serialize_into(&0xabcdu16, &mut buf, 10);
+    assert_eq!(buf[..2], [0xff, 0xff]);
+    assert_eq!(buf[2..10], 0x1122334455667788u64.to_le_bytes());
+    assert_eq!(buf[10..12], 0xabcdu16.to_le_bytes());
+    assert_eq!(buf[12..], [0xff; 8]);
+
+    assert_eq!(deserialize_from::<u64>(&buf, 2), 0x1122334455667788);
+    assert_eq!(deserialize_from::<u16>(&buf, 10), 0xabcd);
+}
+
+#[test]
+fn check_region_metadata_template_comparison() {
+    use crate::pmem::serialization_t::{serialization_matches_bytes, serialize_to_vec, serialized_bytes_equal};
+
+    let template = RegionMetadata {
+        num_logs: 2, which_log: 1, _padding0: 0, region_size: 4096, log_area_len: 3840,
+        creation_timestamp: 1700000000, _padding1: 0, multilog_id: 0x1234, label_lo: 0, label_hi: 0,
+    };
+    let same = RegionMetadata { ..template };
+    let different = RegionMetadata { which_log: 0, ..template };
+    assert!(serialized_bytes_equal(&template, &same));
+    assert!(!serialized_bytes_equal(&template, &different));
+
+    // Comparing against raw bytes also catches a length mismatch.
+    let template_bytes = serialize_to_vec(&template);
+    assert!(serialization_matches_bytes(&template, &template_bytes));
+    assert!(!serialization_matches_bytes(&different, &template_bytes));
+    assert!(!serialization_matches_bytes(&template, &template_bytes[1..]));
+}
+
+/// This test drives a log through random appends, commits, and
+/// head advancements on a crash-simulating mock. Then, for every
+/// crash point, it simulates crashes with random subsets of chunks
+/// persisted and checks that the log recovers to either the committed
+/// state before the operation in progress or the one after it.
+#[test]
+fn check_log_crash_consistency() {
+    use crate::pmem::crashmock_t::*;
+    use deps_hack::rand::rngs::StdRng;
+    use deps_hack::rand::{Rng, SeedableRng};
+
+    const NUM_TRIALS: u64 = 8;
+    const NUM_OPS: usize = 40;
+    const CRASHES_PER_POINT: usize = 3;
+    const REGION_SIZE: u64 = 512;
+
+    // The committed state of the log: its head position and the
+    // bytes between the head and the tail.
+    #[derive(Clone, Debug, PartialEq)]
+    struct CommittedLog {
+        head: u128,
+        bytes: Vec<u8>,
+    }
+
+    fn committed_log<PMRegion: PersistentMemoryRegion>(log: &LogImpl<PMRegion>) -> CommittedLog {
+        let (head, tail, _capacity) = log.get_head_tail_and_capacity().unwrap();
+        let bytes = if tail > head { log.read(head, (tail - head) as u64).unwrap() } else { Vec::new() };
+        CommittedLog { head, bytes }
+    }
+
+    for seed in 0..NUM_TRIALS {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(REGION_SIZE);
+        let history = pm_region.history();
+        let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+        let mut log = LogImpl::start(pm_region, log_id).unwrap();
+
+        // For each operation, record the range of crash points it
+        // produced and the committed states before and after it.
+        let mut committed = CommittedLog { head: 0, bytes: Vec::new() };
+        let mut pending = Vec::<u8>::new();
+        let mut ops = Vec::new();
+        for _ in 0..NUM_OPS {
+            let first_point = history.num_crash_points();
+            let before = committed.clone();
+            match rng.gen_range(0..3) {
+                0 => {
+                    let len = rng.gen_range(1..=32);
+                    let bytes: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+                    if log.tentatively_append(bytes.as_slice()).is_ok() {
+                        pending.extend(bytes);
+                    }
+                },
+                1 => {
+                    log.commit().unwrap();
+                    committed.bytes.extend(pending.drain(..));
+                },
+                _ => {
+                    let tail = committed.head + committed.bytes.len() as u128;
+                    let new_head = rng.gen_range(committed.head..=tail);
+                    log.advance_head(new_head).unwrap();
+                    committed.bytes.drain(..(new_head - committed.head) as usize);
+                    committed.head = new_head;
+                },
+            }
+            assert!(committed_log(&log) == committed);
+            ops.push((first_point..history.num_crash_points(), before, committed.clone()));
+        }
+
+        for (points, before, after) in ops {
+            for point in points {
+                for _ in 0..CRASHES_PER_POINT {
+                    let crashed = history.crash(point, &mut rng);
+                    let recovered = LogImpl::start(crashed, log_id).unwrap_or_else(
+                        |e| panic!("seed {} crash point {}: recovery failed with {:?}", seed, point, e)
+                    );
+                    let state = committed_log(&recovered);
+                    assert!(state == before || state == after,
+                            "seed {} crash point {}: recovered {:?}, expected {:?} or {:?}",
+                            seed, point, state, before, after);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn check_buffered_log_coalesces_appends() {
+    use crate::log::buffered_v::BufferedLog;
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let history = pm_region.history();
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let log = LogImpl::start(pm_region, log_id).unwrap();
+    let mut buffered = BufferedLog::new(log, 16);
+
+    // Small appends stay in the buffer and don't touch persistent
+    // memory until the buffer fills.
+    let points_before = history.num_crash_points();
+    for i in 0..5u8 {
+        assert_eq!(buffered.append(&[i, i]).unwrap(), 2 * i as u128);
+    }
+    assert_eq!(history.num_crash_points(), points_before);
+    assert_eq!(buffered.num_buffered_bytes(), 10);
+
+    // This append would overflow the buffer, so the buffered bytes
+    // are flushed first.
+    assert_eq!(buffered.append(&[9; 8]).unwrap(), 10);
+    assert_eq!(buffered.num_buffered_bytes(), 8);
+    buffered.flush().unwrap();
+    assert_eq!(buffered.num_buffered_bytes(), 0);
+
+    let log = buffered.into_log().unwrap();
+    let mut expected = vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4];
+    expected.extend([9; 8]);
+    assert_eq!(log.read(0, 18).unwrap(), expected);
+}
+
+#[test]
+fn check_caching_log_reader_invalidates_on_commit() {
+    use crate::log::cache_v::CachingLogReader;
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[1, 2, 3, 4]).unwrap();
+    log.commit().unwrap();
+    let mut reader = CachingLogReader::new(log, 2);
+
+    assert_eq!(reader.read(0, 2).unwrap(), vec![1, 2]);
+    assert_eq!(reader.read(0, 2).unwrap(), vec![1, 2]);
+    assert_eq!(reader.num_cached_reads(), 1);
+
+    // Once the head advances past the cached bytes, reading them
+    // must fail rather than hit the stale entry.
+    reader.tentatively_append(&[5]).unwrap();
+    reader.commit().unwrap();
+    assert_eq!(reader.num_cached_reads(), 0);
+    reader.read(0, 2).unwrap();
+    reader.advance_head(2).unwrap();
+    assert!(matches!(reader.read(0, 2), Err(LogErr::PositionTrimmed { requested: 0, current_head: 2 })));
+    assert_eq!(reader.read(2, 3).unwrap(), vec![3, 4, 5]);
+
+    // The least recently used read is evicted when the cache is full.
+    // Re-reading (2, 3) makes (3, 1) the least recently used.
+    reader.read(3, 1).unwrap();
+    reader.read(2, 3).unwrap();
+    reader.read(4, 1).unwrap();
+    assert_eq!(reader.num_cached_reads(), 2);
+    assert!(!reader.is_cached(3, 1));
+    assert!(reader.is_cached(2, 3));
+    assert!(reader.is_cached(4, 1));
+}
+
+verus! {
+    use crate::pmem::crashmock_t::*;
+    use crate::pmem::serialization_t::Serializable;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // A region that wraps a crash-simulating region and fails every
+    // flush while `fail_flushes` is set. A failed flush leaves the
+    // outstanding writes outstanding, as the trait allows.
+    #[verifier::external_body]
+    struct FailingFlushRegion {
+        inner: CrashSimulatingPersistentMemoryRegion,
+        fail_flushes: Rc<Cell<bool>>,
+    }
+
+    impl PersistentMemoryRegion for FailingFlushRegion {
+        closed spec fn view(&self) -> PersistentMemoryRegionView;
+
+        closed spec fn inv(&self) -> bool;
+
+        closed spec fn constants(&self) -> PersistentMemoryConstants;
+
+        #[verifier::external_body]
+        fn get_region_size(&self) -> (result: u64)
+        {
+            self.inner.get_region_size()
+        }
+
+        #[verifier::external_body]
+        fn known_impervious_to_corruption(&self) -> (result: bool)
+        {
+            self.inner.known_impervious_to_corruption()
+        }
+
+        #[verifier::external_body]
+        fn read(&self, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
+        {
+            self.inner.read(addr, num_bytes)
+        }
+
+        #[verifier::external_body]
+        fn read_and_deserialize<S>(&self, addr: u64) -> &S
+            where
+                S: Serializable + Sized
+        {
+            self.inner.read_and_deserialize(addr)
+        }
+
+        #[verifier::external_body]
+        fn write(&mut self, addr: u64, bytes: &[u8])
+        {
+            self.inner.write(addr, bytes)
+        }
+
+        #[verifier::external_body]
+        fn serialize_and_write<S>(&mut self, addr: u64, to_write: &S)
+            where
+                S: Serializable + Sized
+        {
+            self.inner.serialize_and_write(addr, to_write)
+        }
+
+        #[verifier::external_body]
+        fn flush(&mut self) -> Result<(), PmemError>
+        {
+            if self.fail_flushes.get() {
+                return Err(PmemError::FlushFailed);
+            }
+            self.inner.flush()
+        }
+
+        #[verifier::external_body]
+        fn grow_region(&mut self, new_size: u64) -> (result: Result<(), PmemError>)
+        {
+            self.inner.grow_region(new_size)
+        }
+    }
+}
+
+#[test]
+fn check_failed_flush_poisons_log() {
+    use crate::log::buffered_v::BufferedLog;
+    use crate::log::cache_v::CachingLogReader;
+    use deps_hack::rand::rngs::StdRng;
+    use deps_hack::rand::SeedableRng;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let fail_flushes = Rc::new(Cell::new(false));
+    let mut inner = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let history = inner.history();
+    let (_capacity, log_id) = LogImpl::setup(&mut inner).unwrap();
+    let pm_region = FailingFlushRegion { inner, fail_flushes: fail_flushes.clone() };
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[1, 2]).unwrap();
+    log.commit().unwrap();
+
+    // Once a commit fails to flush, every later operation fails, even
+    // after flushing works again.
+    fail_flushes.set(true);
+    log.tentatively_append(&[3]).unwrap();
+    assert!(matches!(log.commit(), Err(LogErr::FlushFailed)));
+    assert!(log.is_poisoned());
+    fail_flushes.set(false);
+    assert!(matches!(log.commit(), Err(LogErr::Poisoned)));
+    assert!(matches!(log.tentatively_append(&[4]), Err(LogErr::Poisoned)));
+    assert!(matches!(log.advance_head(1), Err(LogErr::Poisoned)));
+    assert!(matches!(log.read(0, 2), Err(LogErr::Poisoned)));
+    assert!(matches!(log.get_head_tail_and_capacity(), Err(LogErr::Poisoned)));
+    assert!(matches!(log.acquire_writer(), Err(LogErr::Poisoned)));
+
+    // Restarting the log after a crash recovers a state from before
+    // or after the failed commit.
+    let last_point = history.num_crash_points() - 1;
+    let crashed = history.crash(last_point, &mut StdRng::seed_from_u64(0));
+    let recovered = LogImpl::start(crashed, log_id).unwrap();
+    assert!(!recovered.is_poisoned());
+    let (head, tail, _capacity) = recovered.get_head_tail_and_capacity().unwrap();
+    assert_eq!(head, 0);
+    assert!(tail == 2 || tail == 3);
+    assert_eq!(recovered.read(0, 2).unwrap(), vec![1, 2]);
+
+    // The wrappers are poisoned along with their logs. A poisoned
+    // `CachingLogReader` doesn't serve reads from its cache.
+    let mut inner = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut inner).unwrap();
+    let pm_region = FailingFlushRegion { inner, fail_flushes: fail_flushes.clone() };
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[1, 2]).unwrap();
+    log.commit().unwrap();
+    let mut reader = CachingLogReader::new(log, 2);
+    assert_eq!(reader.read(0, 2).unwrap(), vec![1, 2]);
+    fail_flushes.set(true);
+    assert!(matches!(reader.advance_head(1), Err(LogErr::FlushFailed)));
+    assert!(matches!(reader.read(0, 2), Err(LogErr::Poisoned)));
+
+    fail_flushes.set(false);
+    let mut inner = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut inner).unwrap();
+    let pm_region = FailingFlushRegion { inner, fail_flushes: fail_flushes.clone() };
+    let log = LogImpl::start(pm_region, log_id).unwrap();
+    let mut buffered = BufferedLog::new(log, 16);
+    buffered.append(&[1, 2]).unwrap();
+    fail_flushes.set(true);
+    assert!(matches!(buffered.flush(), Err(LogErr::FlushFailed)));
+    fail_flushes.set(false);
+    assert!(matches!(buffered.flush(), Err(LogErr::Poisoned)));
+    assert!(matches!(buffered.append(&[3]), Err(LogErr::Poisoned)));
+}
+
+#[test]
+fn check_writer_guard_leaves_poisoned_log_alone() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let fail_flushes = Rc::new(Cell::new(false));
+    let mut inner = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let history = inner.history();
+    let (_capacity, log_id) = LogImpl::setup(&mut inner).unwrap();
+    let pm_region = FailingFlushRegion { inner, fail_flushes: fail_flushes.clone() };
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+
+    // Dropping the guard of a poisoned log doesn't write the marker.
+    let mut guard = log.acquire_writer().unwrap();
+    fail_flushes.set(true);
+    guard.tentatively_append(&[1]).unwrap();
+    assert!(matches!(guard.commit(), Err(LogErr::FlushFailed)));
+    let points_before = history.num_crash_points();
+    drop(guard);
+    assert_eq!(history.num_crash_points(), points_before);
+    assert!(log.is_poisoned());
+}
+
+#[test]
+fn check_adaptive_flush_strategy_flushes_before_buffer_fills() {
+    use crate::log::buffered_v::BufferedLog;
+    use crate::log::flushstrategy_t::FlushStrategy;
+    use crate::pmem::crashmock_t::*;
+    use std::time::Duration;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let log = LogImpl::start(pm_region, log_id).unwrap();
+
+    // With a target far above any flush's latency, the batch size
+    // stays at one append, so every append is flushed right away
+    // even though the buffer has plenty of room.
+    let strategy = FlushStrategy::Adaptive { target_latency: Duration::from_secs(3600) };
+    let mut buffered = BufferedLog::new_with_flush_strategy(log, 256, strategy);
+    for i in 0..4u8 {
+        assert_eq!(buffered.append(&[i]).unwrap(), i as u128);
+        assert_eq!(buffered.num_buffered_bytes(), 0);
+    }
+    assert_eq!(buffered.batch_size(), 1);
+    assert_eq!(buffered.log().read(0, 4).unwrap(), vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn check_pending_length_counts_uncommitted_bytes() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    assert_eq!(log.get_pending_length(), 0);
+    log.tentatively_append(&[1, 2, 3]).unwrap();
+    log.tentatively_append(&[4, 5]).unwrap();
+    assert_eq!(log.get_pending_length(), 5);
+    log.commit().unwrap();
+    assert_eq!(log.get_pending_length(), 0);
+}
+
+#[test]
+fn check_commit_append_commits_earlier_tentative_appends() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[1, 2]).unwrap();
+    assert_eq!(log.commit_append(&[3, 4, 5]).unwrap(), 2);
+    assert_eq!(log.get_pending_length(), 0);
+    assert_eq!(log.get_head_tail_and_capacity().unwrap(), (0, 5, capacity));
+    assert_eq!(log.read(0, 5).unwrap(), vec![1, 2, 3, 4, 5]);
+
+    // An append that doesn't fit leaves the log alone.
+    let too_big = vec![0u8; capacity as usize];
+    assert!(matches!(log.commit_append(too_big.as_slice()),
+                     Err(LogErr::InsufficientSpaceForAppend { .. })));
+    assert_eq!(log.get_head_tail_and_capacity().unwrap(), (0, 5, capacity));
+}
+
+#[test]
+fn check_reading_trimmed_position_reports_current_head() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[1, 2, 3, 4]).unwrap();
+    assert!(!log.position_is_live(0));
+    log.commit().unwrap();
+    assert!(log.position_is_live(0));
+    assert!(!log.position_is_live(4));
+
+    log.advance_head(3).unwrap();
+    assert!(!log.position_is_live(1));
+    assert!(log.position_is_live(3));
+    assert!(matches!(log.read(1, 1), Err(LogErr::PositionTrimmed { requested: 1, current_head: 3 })));
+}
+
+#[test]
+fn check_read_range_uses_absolute_positions() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[1, 2, 3, 4, 5]).unwrap();
+    log.commit().unwrap();
+    log.advance_head(2).unwrap();
+
+    assert_eq!(log.read_range(2, 5).unwrap(), vec![3, 4, 5]);
+    assert_eq!(log.read_range(4, 4).unwrap(), Vec::<u8>::new());
+    assert!(matches!(log.read_range(1, 3), Err(LogErr::PositionTrimmed { requested: 1, current_head: 2 })));
+    assert!(matches!(log.read_range(3, 6), Err(LogErr::CantReadPastTail { tail: 5 })));
+}
+
+#[test]
+fn check_setup_if_needed_keeps_existing_log() {
+    use crate::pmem::crashmock_t::*;
+    use deps_hack::rand::rngs::StdRng;
+    use deps_hack::rand::SeedableRng;
+
+    let log_id = 0x1234u128;
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let history = pm_region.history();
+    let (capacity, fresh) = LogImpl::setup_if_needed(&mut pm_region, log_id, 1024).unwrap();
+    assert!(fresh);
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[1, 2, 3]).unwrap();
+    log.commit().unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let last_point = history.num_crash_points() - 1;
+
+    // Re-running setup with the same ID keeps the log's contents.
+    let mut pm_region = history.crash(last_point, &mut rng);
+    assert_eq!(LogImpl::setup_if_needed(&mut pm_region, log_id, 1024).unwrap(), (capacity, false));
+    let log = LogImpl::start(pm_region, log_id).unwrap();
+    assert_eq!(log.read(0, 3).unwrap(), vec![1, 2, 3]);
+
+    // A size mismatch is reported without touching the region, and a
+    // different ID gets a fresh log.
+    let mut pm_region = history.crash(last_point, &mut rng);
+    assert!(matches!(
+        LogImpl::setup_if_needed(&mut pm_region, log_id, 2048),
+        Err(LogErr::StartFailedDueToRegionSizeMismatch { region_size_expected: 2048, region_size_read: 1024 })
+    ));
+    assert_eq!(LogImpl::setup_if_needed(&mut pm_region, log_id + 1, 1024).unwrap(), (capacity, true));
+    let log = LogImpl::start(pm_region, log_id + 1).unwrap();
+    assert_eq!(log.get_head_tail_and_capacity().unwrap(), (0, 0, capacity));
+}
+
+/// This test checks that `setup_if_needed` reports a bad global
+/// metadata CRC as an error and leaves the region alone, rather than
+/// wiping what might be a recoverable log.
+#[test]
+fn check_setup_if_needed_keeps_log_with_bad_global_crc() {
+    use crate::log::layout_v::ABSOLUTE_POS_OF_GLOBAL_CRC;
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let byte = pm_region.read(ABSOLUTE_POS_OF_GLOBAL_CRC, 1)[0];
+    pm_region.write(ABSOLUTE_POS_OF_GLOBAL_CRC, &[byte ^ 0x01]);
+    pm_region.flush().unwrap();
+    let before = pm_region.read(0, 1024);
+    assert!(matches!(LogImpl::setup_if_needed(&mut pm_region, log_id, 1024), Err(LogErr::CRCMismatch)));
+    assert_eq!(pm_region.read(0, 1024), before);
+}
+
+/// This test checks that a commit with read-after-write verification
+/// succeeds on a healthy region, and commits just like `commit`.
+#[test]
+fn check_commit_with_verification_reads_back_metadata() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[4, 5, 6]).unwrap();
+    log.commit_with_verification(CommitVerification::ReadAfterWrite).unwrap();
+    log.tentatively_append(&[7]).unwrap();
+    log.commit_with_verification(CommitVerification::None).unwrap();
+    assert_eq!(log.read(0, 4).unwrap(), vec![4, 5, 6, 7]);
+}
+
+/// This test checks that recovery reads the log metadata from the
+/// slot the corruption-detecting boolean selects, whichever way it's
+/// set.
+#[test]
+fn check_recovery_reads_metadata_slot_selected_by_cdb() {
+    use crate::log::layout_v::{
+        LogMetadata, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE,
+        ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE,
+    };
+    use crate::log::observer_t::RecoveryObserverRef;
+    use crate::log::setup_v::force_set_cdb;
+    use crate::log::start_v::{read_all_metadata, read_both_log_metadata_slots, read_cdb, read_log_variables};
+    use crate::pmem::crashmock_t::*;
+    use crate::pmem::serialization_t::calculate_crc;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+
+    // Write different metadata to the two slots.
+    let slots = [
+        (false, ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE, 3u128),
+        (true, ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE, 7u128),
+    ];
+    for (_cdb, metadata_pos, crc_pos, head) in slots {
+        let log_metadata = LogMetadata { log_length: 0, _padding: 0, head };
+        pm_region.serialize_and_write(metadata_pos, &log_metadata);
+        pm_region.serialize_and_write(crc_pos, &calculate_crc(&log_metadata));
+    }
+    pm_region.flush().unwrap();
+
+    let observer = RecoveryObserverRef::none();
+    for (cdb, _metadata_pos, _crc_pos, head) in slots {
+        force_set_cdb(&mut pm_region, cdb).unwrap();
+        assert_eq!(read_cdb(&pm_region, &observer).unwrap(), cdb);
+        let info = read_log_variables(&pm_region, log_id, cdb, &observer).unwrap();
+        assert_eq!(info.head, head);
+        assert_eq!(info.log_length, 0);
+
+        let bundle = read_all_metadata(&pm_region, cdb).unwrap();
+        assert_eq!(bundle.region.log_id, log_id);
+        assert_eq!(bundle.log.head, head);
+        assert_eq!(bundle.log_crc, calculate_crc(&bundle.log));
+
+        // Both slots are readable whichever one the CDB selects.
+        let (false_slot, true_slot) = read_both_log_metadata_slots(&pm_region);
+        assert_eq!(false_slot.unwrap().head, 3);
+        assert_eq!(true_slot.unwrap().head, 7);
+    }
+}
+
+#[test]
+fn check_grown_region_preserves_bytes_and_zeroes_tail() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(64);
+    pm_region.write(56, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    pm_region.flush().unwrap();
+    pm_region.grow_region(128).unwrap();
+    assert_eq!(pm_region.get_region_size(), 128);
+    assert_eq!(pm_region.read(56, 8), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(pm_region.read(64, 64), vec![0; 64]);
+}
+
+#[test]
+fn check_aligned_appends_start_on_cache_lines() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup_with_append_alignment(&mut pm_region,
+                                                                  LogAppendAlignment::CacheLine).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    assert_eq!(log.append_alignment(), LogAppendAlignment::CacheLine);
+    assert_eq!(log.tentatively_append_aligned(&[1, 2, 3]).unwrap(), 0);
+    assert_eq!(log.tentatively_append_aligned(&[4, 5]).unwrap(), 64);
+    log.commit().unwrap();
+    assert_eq!(log.read(0, 3).unwrap(), vec![1, 2, 3]);
+    assert_eq!(log.read(64, 2).unwrap(), vec![4, 5]);
+
+    // The padding is part of the log, and an empty append isn't
+    // padded.
+    assert_eq!(log.read(3, 61).unwrap(), vec![0; 61]);
+    assert_eq!(log.tentatively_append_aligned(&[]).unwrap(), 66);
+
+    // A log set up without alignment packs aligned appends together.
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    assert_eq!(log.append_alignment(), LogAppendAlignment::None);
+    assert_eq!(log.tentatively_append_aligned(&[1, 2, 3]).unwrap(), 0);
+    assert_eq!(log.tentatively_append_aligned(&[4, 5]).unwrap(), 3);
+
+    // Cache-line alignment needs a log area whose length is a
+    // multiple of 64.
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1000);
+    let result = LogImpl::setup_with_append_alignment(&mut pm_region, LogAppendAlignment::CacheLine);
+    assert!(matches!(result, Err(LogErr::LogAreaNotAligned { log_area_size: 744, alignment: 64 })));
+}
+
+/// This test checks that the append alignment is covered by the
+/// region metadata's CRC, so a corrupted alignment is reported
+/// rather than silently changing where appends go.
+#[test]
+fn check_append_alignment_is_crc_protected() {
+    use crate::log::layout_v::{ABSOLUTE_POS_OF_REGION_METADATA, RELATIVE_POS_OF_REGION_APPEND_ALIGNMENT};
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup_with_append_alignment(&mut pm_region,
+                                                                  LogAppendAlignment::CacheLine).unwrap();
+    pm_region.serialize_and_write(ABSOLUTE_POS_OF_REGION_METADATA + RELATIVE_POS_OF_REGION_APPEND_ALIGNMENT, &0u64);
+    pm_region.flush().unwrap();
+    assert!(matches!(LogImpl::start(pm_region, log_id), Err(LogErr::CRCMismatch)));
+}
+
+#[test]
+fn check_setup_enforces_min_log_area_size() {
+    use crate::log::layout_v::ABSOLUTE_POS_OF_LOG_AREA;
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(ABSOLUTE_POS_OF_LOG_AREA + 64);
+    let result = LogImpl::setup_with_min_log_area_size(&mut pm_region, 128);
+    assert!(matches!(result, Err(LogErr::RegionTooSmall { min_log_area_size: 128, log_area_size: 64 })));
+
+    let (capacity, _log_id) = LogImpl::setup_with_min_log_area_size(&mut pm_region, 64).unwrap();
+    assert!(capacity == 64);
+}
+
+#[test]
+fn check_recovery_observer_reports_decisions() {
+    use crate::log::observer_t::*;
+    use crate::pmem::crashmock_t::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: RefCell<Vec<String>>,
+    }
+
+    impl RecoveryObserver for RecordingObserver {
+        fn on_log_id_mismatch(&self, log_id_expected: u128, log_id_read: u128) {
+            self.events.borrow_mut().push(format!("log id {} != {}", log_id_read, log_id_expected));
+        }
+
+        fn on_recovered(&self, cdb: bool, head: u128, log_length: u64) {
+            self.events.borrow_mut().push(format!("recovered {} {} {}", cdb, head, log_length));
+        }
+    }
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let observer = RecordingObserver::default();
+    let result = LogImpl::start_with_observer(pm_region, log_id, RecoveryObserverRef::new(&observer));
+    assert!(result.is_ok());
+    assert_eq!(*observer.events.borrow(), vec!["recovered false 0 0".to_string()]);
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let observer = RecordingObserver::default();
+    let result = LogImpl::start_with_observer(pm_region, log_id + 1, RecoveryObserverRef::new(&observer));
+    assert!(matches!(result, Err(LogErr::StartFailedDueToLogIDMismatch { .. })));
+    assert_eq!(*observer.events.borrow(), vec![format!("log id {} != {}", log_id, log_id + 1)]);
+}
+
+#[test]
+fn check_flush_policy_retries_only_transient_errors() {
+    use std::time::Duration;
+
+    const TRANSIENT: u32 = 1;
+    const PERMANENT: u32 = 2;
+    let policy = FlushPolicy::new(3, Duration::from_micros(1));
+
+    // Transient failures are retried until the flush succeeds.
+    let mut attempts = 0;
+    let result = policy.run(|| { attempts += 1; if attempts < 3 { Err(TRANSIENT) } else { Ok(()) } },
+                            |code| code == TRANSIENT);
+    assert!(result.is_ok());
+    assert_eq!(attempts, 3);
+
+    // They're given up on once the retries run out.
+    let mut attempts = 0;
+    let result = policy.run(|| { attempts += 1; Err(TRANSIENT) }, |code| code == TRANSIENT);
+    assert!(matches!(result, Err(PmemError::FlushFailed)));
+    assert_eq!(attempts, 4);
+
+    // Other failures aren't retried at all.
+    let mut attempts = 0;
+    let result = policy.run(|| { attempts += 1; Err(PERMANENT) }, |code| code == TRANSIENT);
+    assert!(matches!(result, Err(PmemError::FlushFailed)));
+    assert_eq!(attempts, 1);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn check_creating_existing_file_reports_already_exists() {
+    let file_name = vstd::string::new_strlit("test_already_exists");
+    let _ = std::fs::remove_file("test_already_exists");
+    let pm_region = FileBackedPersistentMemoryRegion::new(
+        &file_name, 1024, PersistentMemoryCheck::DontCheckForPersistentMemory,
+    );
+    assert!(pm_region.is_ok());
+    drop(pm_region);
+    let result = FileBackedPersistentMemoryRegion::new(
+        &file_name, 1024, PersistentMemoryCheck::DontCheckForPersistentMemory,
+    );
+    assert!(matches!(result, Err(PmemError::AlreadyExists)));
+    assert!(FileBackedPersistentMemoryRegion::restore(&file_name, 1024).is_ok());
+    std::fs::remove_file("test_already_exists").unwrap();
+}
+
+/// This test checks that regions can exactly fill their file, both as
+/// a single region and as two regions that tile it.
+#[cfg(target_os = "linux")]
+#[test]
+fn check_regions_can_exactly_fill_file() {
+    let file_name = vstd::string::new_strlit("test_exact_fill");
+    let _ = std::fs::remove_file("test_exact_fill");
+    let pm_regions = FileBackedPersistentMemoryRegions::new(
+        &file_name, &[4096], PersistentMemoryCheck::DontCheckForPersistentMemory,
+    ).unwrap();
+    assert_eq!(pm_regions.get_region_size(0), 4096);
+    drop(pm_regions);
+
+    let pm_regions = FileBackedPersistentMemoryRegions::restore(
+        &file_name, &[1024, 3072], PersistentMemoryCheck::DontCheckForPersistentMemory,
+    ).unwrap();
+    assert_eq!(pm_regions.get_region_size(0), 1024);
+    assert_eq!(pm_regions.get_region_size(1), 3072);
+    drop(pm_regions);
+    std::fs::remove_file("test_exact_fill").unwrap();
+}
+
+/// This test checks that a read-only handle can be shared with
+/// another thread, copies out what was written through a writable
+/// handle, and rejects reads past the end of the region.
+#[cfg(target_os = "linux")]
+#[test]
+fn check_read_only_region_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync + Clone>() {}
+    assert_send_sync::<ReadOnlyFileBackedPersistentMemoryRegion>();
+
+    let file_name = vstd::string::new_strlit("test_read_only");
+    let _ = std::fs::remove_file("test_read_only");
+    let mut pm_region = FileBackedPersistentMemoryRegion::new(
+        &file_name, 1024, PersistentMemoryCheck::DontCheckForPersistentMemory,
+    ).unwrap();
+    pm_region.write(100, &[1, 2, 3]);
+    pm_region.flush().unwrap();
+
+    let reader = ReadOnlyFileBackedPersistentMemoryRegion::open("test_read_only", 1024).unwrap();
+    let other = reader.clone();
+    let bytes = std::thread::spawn(move || other.read(100, 3).unwrap()).join().unwrap();
+    assert_eq!(bytes, vec![1, 2, 3]);
+    assert_eq!(reader.get_region_size(), 1024);
+    assert!(matches!(reader.read(1022, 3), Err(PmemError::AccessOutOfRange)));
+    drop(reader);
+    drop(pm_region);
+    std::fs::remove_file("test_read_only").unwrap();
+}
+
+/// This test checks that `truncate_region_file` refuses to shrink a
+/// log's region past its committed data, and otherwise shrinks both
+/// the region metadata and the file, so that the log restarts on the
+/// smaller file with its data intact.
+#[cfg(target_os = "linux")]
+#[test]
+fn check_truncate_region_file_shrinks_log_region() {
+    let file_name = vstd::string::new_strlit("test_truncate_region");
+    let _ = std::fs::remove_file("test_truncate_region");
+    let mut pm_region = FileBackedPersistentMemoryRegion::new(
+        &file_name, 4096, PersistentMemoryCheck::DontCheckForPersistentMemory,
+    ).unwrap();
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let mut log = LogImpl::start(pm_region, log_id).unwrap();
+    log.tentatively_append(&[7; 600]).unwrap();
+    log.commit().unwrap();
+
+    // The committed data doesn't fit in a 512-byte log area, so the
+    // region and the file are left alone.
+    let result = log.truncate_region_file("test_truncate_region", 256 + 512);
+    assert!(matches!(result, Err(LogErr::CantShrinkBelowLiveData { head: 0, tail: 600 })));
+    assert_eq!(std::fs::metadata("test_truncate_region").unwrap().len(), 4096);
+
+    let pm_region = FileBackedPersistentMemoryRegion::restore(&file_name, 4096).unwrap();
+    let log = LogImpl::start(pm_region, log_id).unwrap();
+    log.truncate_region_file("test_truncate_region", 256 + 768).unwrap();
+    assert_eq!(std::fs::metadata("test_truncate_region").unwrap().len(), 1024);
+
+    let pm_region = FileBackedPersistentMemoryRegion::restore(&file_name, 1024).unwrap();
+    let log = LogImpl::start(pm_region, log_id).unwrap();
+    assert_eq!(log.get_head_tail_and_capacity().unwrap(), (0, 600, 768));
+    assert_eq!(log.read(0, 600).unwrap(), vec![7; 600]);
+    drop(log);
+    std::fs::remove_file("test_truncate_region").unwrap();
+}
+
+#[test]
+fn check_multilog_diff_reports_new_bytes() {
+    use crate::multilog::diff_v::*;
+
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[512, 512, 512]);
+    let (_capacities, multilog_id) = MultiLogImpl::setup(&mut regions).unwrap();
+    let mut multilog = MultiLogImpl::start(regions, multilog_id).unwrap();
+    multilog.tentatively_append(0, &[1, 2, 3]).unwrap();
+    multilog.tentatively_append(2, &[4]).unwrap();
+    multilog.commit().unwrap();
+
+    // Take a snapshot of the tails, then grow logs 0 and 1.
+    let old_tails: Vec<u128> = (0..3).map(|i| multilog.get_head_tail_and_capacity(i).unwrap().1).collect();
+    multilog.tentatively_append(0, &[5, 6]).unwrap();
+    multilog.tentatively_append(1, &[7]).unwrap();
+    multilog.commit().unwrap();
+
+    let entries = diff(&multilog, &old_tails).unwrap();
+    assert_eq!(entries, vec![(0, 3, 2), (1, 0, 1)]);
+    assert_eq!(read_diff_bytes(&multilog, entries[0]).unwrap(), vec![5, 6]);
+    assert_eq!(read_diff_bytes(&multilog, entries[1]).unwrap(), vec![7]);
+
+    // Once a log's head passes the old tail, the diff can't be
+    // computed.
+    multilog.advance_head(0, 4).unwrap();
+    assert!(matches!(diff(&multilog, &old_tails), Err(MultiLogErr::CantReadBeforeHead { head: 4 })));
+}
+
+#[test]
+fn check_creation_timestamp_survives_restart() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut regions = CrashSimulatingPersistentMemoryRegions::new(&[1024, 1024]);
+    let history = regions.history();
+    let before_setup = current_time_in_seconds();
+    let (_capacities, multilog_id) = MultiLogImpl::setup(&mut regions).unwrap();
+    let after_setup = current_time_in_seconds();
+
+    let mut multilog = MultiLogImpl::start(regions, multilog_id).unwrap();
+    let creation_timestamp = multilog.get_creation_timestamp();
+    assert!(before_setup <= creation_timestamp && creation_timestamp <= after_setup);
+    multilog.tentatively_append(0, &[1, 2, 3]).unwrap();
+    multilog.commit().unwrap();
+
+    // The last crash point follows the commit's final flush, so
+    // crashing there restarts from the committed state.
+    let regions = history.simulate_crash(history.num_crash_points() - 1, &[]);
+    let multilog = MultiLogImpl::start(regions, multilog_id).unwrap();
+    assert_eq!(multilog.get_creation_timestamp(), creation_timestamp);
+}
+
+/// This test writes a version-1 multilog image by hand, checks that
+/// `start` rejects it, and checks that migrating it yields a
+/// multilog with the same logs and a creation timestamp of 0.
+#[test]
+fn check_migration_from_version_1() {
+    use crate::multilog::fixture_t::*;
+    use crate::multilog::migrate_t::*;
+    use crate::pmem::serialization_t::calculate_crc;
+
+    let multilog_id = 0x5678u128;
+    let region_size = 512u64;
+    let log_area_len = region_size - ABSOLUTE_POS_OF_LOG_AREA;
+    let logs: [(u128, &[u8]); 2] = [(3, &[1, 2, 3, 4]), (0, &[])];
+    let mut old_regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[region_size, region_size]);
+    for (which_log, (head, bytes)) in logs.iter().enumerate() {
+        let global_metadata = GlobalMetadata {
+            version_number: 1,
+            length_of_region_metadata: V1_LENGTH_OF_REGION_METADATA,
+            program_guid: MULTILOG_PROGRAM_GUID,
+        };
+        let mut region_metadata = Vec::new();
+        region_metadata.extend_from_slice(&2u32.to_le_bytes());
+        region_metadata.extend_from_slice(&(which_log as u32).to_le_bytes());
+        region_metadata.extend_from_slice(&0u64.to_le_bytes());
+        region_metadata.extend_from_slice(&region_size.to_le_bytes());
+        region_metadata.extend_from_slice(&log_area_len.to_le_bytes());
+        region_metadata.extend_from_slice(&multilog_id.to_le_bytes());
+        let log_metadata = LogMetadata { log_length: bytes.len() as u64, _padding: 0, head: *head };
+        let mut log_area = vec![0u8; log_area_len as usize];
+        for (i, byte) in bytes.iter().enumerate() {
+            log_area[(*head as usize + i) % log_area_len as usize] = *byte;
+        }
+
+        old_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_GLOBAL_METADATA, &global_metadata);
+        old_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_GLOBAL_CRC, &calculate_crc(&global_metadata));
+        old_regions.write(which_log, ABSOLUTE_POS_OF_REGION_METADATA, region_metadata.as_slice());
+        old_regions.write(which_log, V1_ABSOLUTE_POS_OF_REGION_CRC, bytes_crc(region_metadata.as_slice()).as_slice());
+        old_regions.serialize_and_write(which_log, V1_ABSOLUTE_POS_OF_LOG_CDB, &CDB_FALSE);
+        old_regions.serialize_and_write(which_log, V1_ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, &log_metadata);
+        old_regions.serialize_and_write(which_log, V1_ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE,
+                                        &calculate_crc(&log_metadata));
+        old_regions.write(which_log, ABSOLUTE_POS_OF_LOG_AREA, log_area.as_slice());
+    }
+    old_regions.flush().unwrap();
+
+    let mut new_regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[region_size, region_size]);
+    migrate_from_version_1(&old_regions, &mut new_regions, multilog_id).unwrap();
+    assert!(matches!(MultiLogImpl::start(old_regions, multilog_id),
+                     Err(MultiLogErr::StartFailedDueToProgramVersionNumberUnsupported { version_number: 1, .. })));
+    let multilog = MultiLogImpl::start(new_regions, multilog_id).unwrap();
+    assert_eq!(multilog.get_creation_timestamp(), 0);
+    assert_eq!(observed_state(&multilog, 2), vec![
+        ExpectedLogState { head: 3, log: vec![1, 2, 3, 4], capacity: log_area_len },
+        ExpectedLogState { head: 0, log: vec![], capacity: log_area_len },
+    ]);
+
+    // Migrating a multilog that's already current is refused.
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[region_size, region_size]);
+    let (_capacities, multilog_id) = MultiLogImpl::setup(&mut regions).unwrap();
+    let mut new_regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[region_size, region_size]);
+    assert!(matches!(migrate_from_version_1(&regions, &mut new_regions, multilog_id),
+                     Err(MultiLogErr::StartFailedDueToProgramVersionNumberUnsupported { version_number: 2, .. })));
+}
+
+#[test]
+fn check_label_survives_restart() {
+    use crate::pmem::crashmock_t::*;
+
+    let mut regions = CrashSimulatingPersistentMemoryRegions::new(&[1024, 1024]);
+    let history = regions.history();
+    let (_capacities, multilog_id) =
+        MultiLogImpl::setup_with_label(&mut regions, "wal-shard-3").unwrap();
+    let multilog = MultiLogImpl::start(regions, multilog_id).unwrap();
+    assert_eq!(multilog.get_label(), "wal-shard-3");
+
+    let regions = history.simulate_crash(history.num_crash_points() - 1, &[]);
+    let multilog = MultiLogImpl::start(regions, multilog_id).unwrap();
+    assert_eq!(multilog.get_label(), "wal-shard-3");
+
+    // Labels are truncated to `LABEL_SIZE` bytes, at a character
+    // boundary, and `setup` records an empty one.
+    let long_label = format!("{}\u{e9}", "x".repeat(LABEL_SIZE - 1));
+    assert_eq!(decode_label_of(&long_label), "x".repeat(LABEL_SIZE - 1));
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[1024]);
+    let (_capacities, multilog_id) = MultiLogImpl::setup(&mut regions).unwrap();
+    assert_eq!(MultiLogImpl::start(regions, multilog_id).unwrap().get_label(), "");
+
+    fn decode_label_of(label: &str) -> String {
+        let (label_lo, label_hi) = encode_label(label);
+        decode_label(label_lo, label_hi)
+    }
+}
+
+#[test]
+fn check_probe_cdb_tolerates_non_multilog_regions() {
+    use crate::multilog::start_v::probe_cdb;
+    use crate::pmem::serialization_t::calculate_crc;
+
+    // A region too short for a multilog's metadata probes as not being
+    // a multilog region. One that was never set up fails its CRC
+    // check if the memory might be corrupted, and otherwise probes as
+    // not being a multilog region, since the GUID read is all zeros.
+    let regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[ABSOLUTE_POS_OF_LOG_CDB]);
+    assert!(matches!(probe_cdb(&regions), Ok(None)));
+    let crash_regions = crate::pmem::crashmock_t::CrashSimulatingPersistentMemoryRegions::new(&[1024]);
+    assert!(matches!(probe_cdb(&crash_regions), Err(MultiLogErr::CRCMismatch)));
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[1024]);
+    assert!(matches!(probe_cdb(&regions), Ok(None)));
+
+    let (_capacities, _multilog_id) = MultiLogImpl::setup(&mut regions).unwrap();
+    assert!(matches!(probe_cdb(&regions), Ok(Some(false))));
+
+    // Global metadata with a valid CRC but another program's GUID
+    // probes as not being a multilog region.
+    let global_metadata = GlobalMetadata {
+        version_number: MULTILOG_PROGRAM_VERSION_NUMBER,
+        length_of_region_metadata: LENGTH_OF_REGION_METADATA,
+        program_guid: MULTILOG_PROGRAM_GUID + 1,
+    };
+    regions.serialize_and_write(0, ABSOLUTE_POS_OF_GLOBAL_METADATA, &global_metadata);
+    regions.serialize_and_write(0, ABSOLUTE_POS_OF_GLOBAL_CRC, &calculate_crc(&global_metadata));
+    regions.flush().unwrap();
+    assert!(matches!(probe_cdb(&regions), Ok(None)));
+}
+
+/// This test checks that recovery notices a flipped byte in a log's
+/// metadata rather than trusting it.
+#[test]
+fn check_flipped_metadata_byte_is_crc_mismatch() {
+    use crate::log::layout_v::ABSOLUTE_POS_OF_REGION_METADATA;
+    use crate::pmem::crashmock_t::*;
+
+    let mut pm_region = CrashSimulatingPersistentMemoryRegion::new(1024);
+    let (_capacity, log_id) = LogImpl::setup(&mut pm_region).unwrap();
+    let byte = pm_region.read(ABSOLUTE_POS_OF_REGION_METADATA, 1)[0];
+    pm_region.write(ABSOLUTE_POS_OF_REGION_METADATA, &[byte ^ 0x01]);
+    pm_region.flush().unwrap();
+    assert!(matches!(LogImpl::start(pm_region, log_id), Err(LogErr::CRCMismatch)));
+}
+
+/// This test writes images of several multilog states, including
+/// ones whose logs wrap around the end of the log area, and checks
+/// that each recovers to the state it was built from.
+#[test]
+fn check_recovery_of_built_multilog_states() {
+    use crate::multilog::fixture_t::*;
+
+    let bytes: Vec<u8> = (0..40).collect();
+    let cases = vec![
+        (MultiLogStateBuilder::new(64).add_log(0, &[]), false),
+        (MultiLogStateBuilder::new(64).add_log(0, &[1, 2, 3]).add_log(5, &[4]), true),
+        (MultiLogStateBuilder::new(64).add_log(1000, &bytes).add_log(60, &bytes[..10]), false),
+        (MultiLogStateBuilder::new(64).add_log(u64::MAX as u128, &bytes), true),
+    ];
+    for (builder, cdb) in cases {
+        let multilog_id = generate_fresh_multilog_id();
+        let expected = builder.expected_state();
+        let pm_regions = builder.build_mock(multilog_id, cdb);
+        let multilog = MultiLogImpl::start(pm_regions, multilog_id).unwrap();
+        assert_eq!(observed_state(&multilog, expected.len() as u32), expected);
+    }
+}
+
+/// This test checks that reads of a multilog return the bytes
+/// `extract_log` says are in the log, including when the log wraps
+/// around the end of its log area.
+#[test]
+fn check_reads_match_extract_log_across_wraparound() {
+    use crate::multilog::fixture_t::*;
+
+    // This is a transcription of the spec functions
+    // `relative_log_pos_to_log_area_offset` and `extract_log`.
+    fn extract_log(mem: &[u8], log_area_len: u64, head: u128, log_length: u64) -> Vec<u8> {
+        let head_log_area_offset = (head % log_area_len as u128) as u64;
+        (0..log_length).map(|pos_relative_to_head| {
+            let mut log_area_offset = head_log_area_offset + pos_relative_to_head;
+            if log_area_offset >= log_area_len {
+                log_area_offset -= log_area_len;
+            }
+            mem[(ABSOLUTE_POS_OF_LOG_AREA + log_area_offset) as usize]
+        }).collect()
+    }
+
+    // Each case is `(log_area_len, head, log_length)`. All but the
+    // first two wrap around, i.e., have
+    // `head % log_area_len + log_length > log_area_len`.
+    let cases: Vec<(u64, u128, u64)> = vec![
+        (64, 0, 40),
+        (64, 30, 34),
+        (64, 30, 35),
+        (64, 63, 2),
+        (64, 1000, 64),
+        (100, u64::MAX as u128, 50),
+    ];
+    for (log_area_len, head, log_length) in cases {
+        let builder = MultiLogStateBuilder::new(log_area_len).add_log(head, &vec![0u8; log_length as usize]);
+        let multilog_id = generate_fresh_multilog_id();
+        let mut pm_regions = builder.build_mock(multilog_id, false);
+
+        // Overwrite the log area with a pattern in which every byte
+        // is distinct, so a read from the wrong offset can't go
+        // unnoticed.
+        let pattern: Vec<u8> = (0..log_area_len).map(|i| (i * 7 + 3) as u8).collect();
+        pm_regions.write(0, ABSOLUTE_POS_OF_LOG_AREA, pattern.as_slice());
+        pm_regions.flush().unwrap();
+        let region_size = pm_regions.get_region_size(0);
+        let mem = pm_regions.read(0, 0, region_size);
+        let expected = extract_log(mem.as_slice(), log_area_len, head, log_length);
+
+        let multilog = MultiLogImpl::start(pm_regions, multilog_id).unwrap();
+        assert_eq!(multilog.read(0, head, log_length).unwrap(), expected);
+
+        // Also read each suffix and prefix, so that some reads start
+        // or end exactly at the physical end of the log area.
+        for split in 1..log_length {
+            assert_eq!(multilog.read(0, head, split).unwrap(), expected[..split as usize].to_vec());
+            assert_eq!(multilog.read(0, head + split as u128, log_length - split).unwrap(),
+                       expected[split as usize..].to_vec());
+        }
+    }
+}
+
+/// This test checks that `check_crc` skips computing the CRC only
+/// when the memory is known to be impervious to corruption, and that
+/// the volatile mock, unlike the crash-simulating one, says it is.
+#[test]
+fn check_crc_fast_path_for_impervious_memory() {
+    use crate::pmem::crashmock_t::*;
+
+    let regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[512]);
+    assert!(regions.known_impervious_to_corruption());
+    assert!(regions.regions[0].known_impervious_to_corruption());
+    let regions = CrashSimulatingPersistentMemoryRegions::new(&[512]);
+    assert!(!regions.known_impervious_to_corruption());
+    assert!(!CrashSimulatingPersistentMemoryRegion::new(512).known_impervious_to_corruption());
+
+    let data = [1u8, 2, 3];
+    let crc = bytes_crc(&data);
+    let mut wrong_crc = crc.clone();
+    wrong_crc[0] ^= 0x01;
+    let check = |crc_c: &[u8], known_impervious: bool| check_crc(
+        &data, crc_c, Ghost::assume_new(), Ghost::assume_new(), known_impervious,
+        Ghost::assume_new(), Ghost::assume_new(), Ghost::assume_new(),
+    );
+    assert!(check(crc.as_slice(), false));
+    assert!(!check(wrong_crc.as_slice(), false));
+    // On impervious memory the CRC read is the one written, so it
+    // isn't compared.
+    assert!(check(wrong_crc.as_slice(), true));
+}
+
+#[test]
+fn check_mock_capabilities() {
+    use crate::pmem::crashmock_t::*;
+
+    let regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[512]);
+    assert_eq!(regions.capabilities(), PmemCapabilities { targeted_flush: false, persistent: false });
+    let regions = CrashSimulatingPersistentMemoryRegions::new(&[512]);
+    assert_eq!(regions.capabilities(), PmemCapabilities { targeted_flush: true, persistent: false });
+}
+
+#[test]
+fn check_commit_commits_every_log() {
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[512, 512, 512]);
+    let (_capacities, multilog_id) = MultiLogImpl::setup(&mut regions).unwrap();
+    let mut multilog = MultiLogImpl::start(regions, multilog_id).unwrap();
+    multilog.tentatively_append(0, &[1, 2]).unwrap();
+    multilog.tentatively_append(2, &[3]).unwrap();
+    multilog.commit().unwrap();
+    let tails: Vec<u128> = (0..3).map(|i| multilog.get_head_tail_and_capacity(i).unwrap().1).collect();
+    assert_eq!(tails, vec![2, 0, 1]);
+    assert_eq!(multilog.read(2, 0, 1).unwrap(), vec![3]);
+}
+
+/// This test enumerates every crash state reachable during a
+/// multilog `commit` that covers several logs, i.e., every subset of
+/// the outstanding chunks at every crash point, and checks that each
+/// one recovers to either the state before the commit or the state
+/// after it.
+#[test]
+fn check_commit_crash_states_recover_atomically() {
+    use crate::pmem::crashmock_t::*;
+
+    // The committed state of each log: its head and its bytes.
+    fn committed_logs<PMRegions: PersistentMemoryRegions>(
+        multilog: &MultiLogImpl<PMRegions>
+    ) -> Vec<(u128, Vec<u8>)> {
+        (0..2).map(|which_log| {
+            let (head, tail, _capacity) = multilog.get_head_tail_and_capacity(which_log).unwrap();
+            let bytes = if tail > head { multilog.read(which_log, head, (tail - head) as u64).unwrap() }
+                        else { Vec::new() };
+            (head, bytes)
+        }).collect()
+    }
+
+    let mut regions = CrashSimulatingPersistentMemoryRegions::new(&[512, 512]);
+    let history = regions.history();
+    let (_capacities, multilog_id) = MultiLogImpl::setup(&mut regions).unwrap();
+    let mut multilog = MultiLogImpl::start(regions, multilog_id).unwrap();
+    multilog.tentatively_append(0, &[1, 2]).unwrap();
+    multilog.commit().unwrap();
+
+    multilog.tentatively_append(0, &[3, 4, 5]).unwrap();
+    multilog.tentatively_append(1, &[6; 12]).unwrap();
+    let before = committed_logs(&multilog);
+    let first_point = history.num_crash_points();
+    multilog.commit().unwrap();
+    let after = committed_logs(&multilog);
+    assert_ne!(before, after);
+
+    for point in first_point..history.num_crash_points() {
+        let chunks = history.outstanding_chunks(point);
+        assert!(chunks.len() <= 16, "too many outstanding chunks to enumerate");
+        for subset in 0u32..(1 << chunks.len()) {
+            let persisted: Vec<(usize, usize)> =
+                (0..chunks.len()).filter(|i| subset & (1 << i) != 0).map(|i| chunks[i]).collect();
+            let crashed = history.simulate_crash(point, &persisted);
+            let recovered = MultiLogImpl::start(crashed, multilog_id).unwrap_or_else(
+                |e| panic!("crash point {} chunks {:?}: recovery failed with {:?}", point, persisted, e)
+            );
+            let state = committed_logs(&recovered);
+            assert!(state == before || state == after,
+                    "crash point {} chunks {:?}: recovered {:?}", point, persisted, state);
+        }
+    }
+}
+
+#[test]
+fn check_kv_error_is_std_error() {
+    use crate::kv::kvimpl_t::KvError;
+
+    let err: Box<dyn std::error::Error> = Box::new(KvError::<u64, ()>::InvalidKey { key: 7 });
+    assert_eq!(err.to_string(), "invalid key 7");
+    let err = KvError::<u64, ()>::RegionTooSmall { required: 4096, actual: 512 };
+    assert_eq!(err.to_string(), "persistent memory region too small: 4096 bytes required, 512 available");
+}
+
+verus! {
+    use crate::kv::durable::durableimpl_v::*;
+    use crate::kv::durable::durablespec_t::*;
+    use crate::kv::kvimpl_t::*;
+    use crate::kv::kvspec_t::*;
+    use crate::kv::volatile::volatileimpl_v::*;
+    use crate::kv::volatile::volatilespec_t::*;
+
+    // An item whose only field is its own key.
+    #[repr(transparent)]
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    struct KeyedItem(u64);
+
+    impl crate::pmem::serialization_t::TransparentSerializable for KeyedItem {
+        type Inner = u64;
+
+        open spec fn spec_inner(self) -> u64
+        {
+            self.0
+        }
+
+        open spec fn spec_from_inner(inner: u64) -> Self
+        {
+            KeyedItem(inner)
+        }
+
+        proof fn lemma_auto_from_inner_inverts_inner()
+        {
+        }
+    }
+
+    impl Item<u64> for KeyedItem {
+        open spec fn spec_key(self) -> u64
+        {
+            self.0
+        }
+
+        open spec fn spec_serialized_size(self) -> nat
+        {
+            8
+        }
+
+        fn key(&self) -> (out: u64)
+        {
+            self.0
+        }
+
+        fn serialized_size(&self) -> (out: u64)
+        {
+            8
+        }
+    }
+
+    // A durable store that keeps its state in `MOCK_KV_DISK`, keyed
+    // by store ID, rather than in persistent memory, so that a test
+    // can reopen a store it dropped. It counts the changes to each
+    // slot by generation, like a real store would have to for
+    // `slots_changed_since`. Operations the tests don't need panic.
+    #[derive(Clone)]
+    struct MockDurableStore {
+        kvstore_id: u128,
+        slots: Vec<Option<(u64, KeyedItem, Vec<(u64, Vec<u64>)>)>>,
+        changed_at: Vec<u64>,
+        generation: u64,
+        next_node_offset: u64,
+        checkpoint: Option<Vec<u8>>,
+    }
+
+    type MockPerm = TrustedKvPermission<VolatileMemoryMockingPersistentMemoryRegions, u64, KeyedItem, u64,
+                                        MockDurableStore, ()>;
+
+    impl DurableKvStore<VolatileMemoryMockingPersistentMemoryRegions, u64, KeyedItem, u64, ()> for MockDurableStore {
+        closed spec fn view(&self) -> DurableKvStoreView<u64, KeyedItem, u64, ()>
+        {
+            vstd::pervasive::arbitrary()
+        }
+
+        closed spec fn recover_to_kv_state(bytes: Seq<Seq<u8>>, id: u128) -> Option<AbstractKvStoreState<u64, KeyedItem, u64, ()>>
+        {
+            None
+        }
+
+        closed spec fn valid(self) -> bool
+        {
+            true
+        }
+
+        #[verifier::external_body]
+        fn new(pmem: VolatileMemoryMockingPersistentMemoryRegions, kvstore_id: u128, max_keys: usize,
+               lower_bound_on_max_pages: usize) -> (result: Result<Self, KvError<u64, ()>>)
+        {
+            let store = Self {
+                kvstore_id,
+                slots: vec![None; max_keys],
+                changed_at: vec![0; max_keys],
+                generation: 0,
+                next_node_offset: 0,
+                checkpoint: None,
+            };
+            store.persist();
+            Ok(store)
+        }
+
+        #[verifier::external_body]
+        fn contains_store(pmem: &VolatileMemoryMockingPersistentMemoryRegions, kvstore_id: u128) -> (result: bool)
+        {
+            MOCK_KV_DISK.with(|disk| disk.borrow().contains_key(&kvstore_id))
+        }
+
+        #[verifier::external_body]
+        fn restore(pmem: VolatileMemoryMockingPersistentMemoryRegions, kvstore_id: u128) -> (result: Result<Self, KvError<u64, ()>>)
+        {
+            MOCK_KV_DISK.with(|disk| disk.borrow().get(&kvstore_id).cloned())
+                .ok_or(KvError::InvalidPersistentMemoryRegionProvided)
+        }
+
+        #[verifier::external_body]
+        fn create(&mut self, item: KeyedItem, perm: Tracked<&MockPerm>) -> (result: Result<u64, KvError<u64, ()>>)
+        {
+            let slot = self.slots.iter().position(|entry| entry.is_none()).ok_or(KvError::OutOfSpace)?;
+            self.slots[slot] = Some((item.key(), item, Vec::new()));
+            self.record_change(slot);
+            Ok(slot as u64)
+        }
+
+        #[verifier::external_body]
+        fn num_slots(&self) -> (result: u64)
+        {
+            self.slots.len() as u64
+        }
+
+        #[verifier::external_body]
+        fn read_raw_entry(&self, slot: u64) -> (result: Result<Option<(u64, &KeyedItem)>, KvError<u64, ()>>)
+        {
+            Ok(self.slots[slot as usize].as_ref().map(|(key, item, _)| (*key, item)))
+        }
+
+        #[verifier::external_body]
+        fn read_raw_list_nodes(&self, slot: u64) -> (result: Result<Vec<(u64, u64)>, KvError<u64, ()>>)
+        {
+            MOCK_KV_RAW_LIST_READS.with(|reads| reads.set(reads.get() + 1));
+            let (_, _, nodes) = self.slots[slot as usize].as_ref().unwrap();
+            Ok(nodes.iter().map(|(node_offset, entries)| (*node_offset, entries.len() as u64)).collect())
+        }
+
+        #[verifier::external_body]
+        fn generation(&self) -> (result: u64)
+        {
+            self.generation
+        }
+
+        #[verifier::external_body]
+        fn slots_changed_since(&self, generation: u64) -> (result: Option<Vec<u64>>)
+        {
+            Some((0..self.slots.len() as u64).filter(|slot| self.changed_at[*slot as usize] > generation).collect())
+        }
+
+        #[verifier::external_body]
+        fn allocation_high_water_mark(&self) -> (result: u64)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn write_index_checkpoint(&mut self, checkpoint: &[u8], perm: Tracked<&MockPerm>) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            self.checkpoint = Some(checkpoint.to_vec());
+            self.persist();
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn read_index_checkpoint(&self) -> (result: Result<Option<Vec<u8>>, KvError<u64, ()>>)
+        {
+            Ok(self.checkpoint.clone())
+        }
+
+        #[verifier::external_body]
+        fn read_item(&self, offset: u64) -> (result: Option<&KeyedItem>)
+        {
+            self.slots[offset as usize].as_ref().map(|(_, item, _)| item)
+        }
+
+        #[verifier::external_body]
+        fn read_list_entry_at_index(&self, offset: u64, idx: u64) -> (result: Result<&u64, KvError<u64, ()>>)
+        {
+            let (_, _, nodes) = self.slots[offset as usize].as_ref().ok_or(KvError::KeyNotFound)?;
+            nodes.iter().flat_map(|(_, entries)| entries.iter()).nth(idx as usize).ok_or(KvError::IndexOutOfRange)
+        }
+
+        #[verifier::external_body]
+        fn update_item(&mut self, offset: u64, new_item: KeyedItem) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn delete(&mut self, offset: u64, perm: Tracked<&MockPerm>) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            self.slots[offset as usize] = None;
+            self.record_change(offset as usize);
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn append(&mut self, offset: u64, new_entry: u64, perm: Tracked<&MockPerm>) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            let (_, _, nodes) = self.slots[offset as usize].as_mut().unwrap();
+            nodes.last_mut().unwrap().1.push(new_entry);
+            self.record_change(offset as usize);
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn alloc_list_node_and_append(&mut self, offset: u64, new_entry: u64, perm: Tracked<&MockPerm>)
+            -> (result: Result<u64, KvError<u64, ()>>)
+        {
+            let node_offset = self.next_node_offset;
+            self.next_node_offset += 1;
+            let (_, _, nodes) = self.slots[offset as usize].as_mut().unwrap();
+            nodes.push((node_offset, vec![new_entry]));
+            self.record_change(offset as usize);
+            Ok(node_offset)
+        }
+
+        #[verifier::external_body]
+        fn update_item_and_append(&mut self, offset: u64, new_entry: u64, new_item: KeyedItem, perm: Tracked<&MockPerm>)
+            -> (result: Result<u64, KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn alloc_list_node_update_item_and_append(&mut self, offset: u64, new_entry: u64, new_item: KeyedItem,
+                                                  perm: Tracked<&MockPerm>) -> (result: Result<u64, KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn update_list_entry_at_index(&mut self, item_offset: u64, entry_offset: u64, new_entry: u64,
+                                      perm: Tracked<&MockPerm>) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn update_entry_at_index_and_item(&mut self, item_offset: u64, entry_offset: u64, new_item: KeyedItem,
+                                          new_entry: u64, perm: Tracked<&MockPerm>) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn replace_list_entry_at_index(&mut self, item_offset: u64, old_node_offset: u64, idx: usize, new_entry: u64,
+                                       perm: Tracked<&MockPerm>) -> (result: Result<u64, KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn trim_list(&mut self, item_offset: u64, old_head_node_offset: u64, new_head_node_offset: u64,
+                     trim_length: usize, perm: Tracked<&MockPerm>) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn trim_list_and_update_item(&mut self, item_offset: u64, old_head_node_offset: u64,
+                                     new_head_node_offset: u64, trim_length: usize, new_item: KeyedItem,
+                                     perm: Tracked<&MockPerm>) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+    }
+
+    // A volatile index that maps each key to its item offset and to
+    // the offset and entry count of each of its list nodes, which hold
+    // `MOCK_ENTRIES_PER_LIST_NODE` entries each. Operations the tests
+    // don't need panic.
+    struct MockVolatileIndex {
+        entries: std::collections::HashMap<u64, (u64, Vec<(u64, u64)>)>,
+    }
+
+    impl VolatileKvIndex<u64, ()> for MockVolatileIndex {
+        closed spec fn view(&self) -> VolatileKvIndexView<u64>
+        {
+            vstd::pervasive::arbitrary()
+        }
+
+        closed spec fn valid(&self) -> bool
+        {
+            true
+        }
+
+        #[verifier::external_body]
+        fn new(kvstore_id: u128, max_keys: usize) -> (result: Result<Self, KvError<u64, ()>>)
+        {
+            Ok(Self { entries: std::collections::HashMap::new() })
+        }
+
+        #[verifier::external_body]
+        fn insert_item_offset(&mut self, key: &u64, offset: u64) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            self.entries.insert(*key, (offset, Vec::new()));
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn append_to_list(&mut self, key: &u64) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            let (_, nodes) = self.entries.get_mut(key).ok_or(KvError::KeyNotFound)?;
+            nodes.last_mut().unwrap().1 += 1;
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn append_node_offset(&mut self, key: &u64, node_offset: u64) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            self.insert_list_node(key, node_offset, 1)
+        }
+
+        #[verifier::external_body]
+        fn insert_list_node(&mut self, key: &u64, node_offset: u64, num_entries: u64) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            let (_, nodes) = self.entries.get_mut(key).ok_or(KvError::KeyNotFound)?;
+            nodes.push((node_offset, num_entries));
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn get_tail_node_with_free_space(&self, key: &u64) -> (result: Result<Option<u64>, KvError<u64, ()>>)
+        {
+            let (_, nodes) = self.entries.get(key).ok_or(KvError::KeyNotFound)?;
+            Ok(nodes.last().filter(|(_, num_entries)| *num_entries < MOCK_ENTRIES_PER_LIST_NODE)
+                    .map(|(node_offset, _)| *node_offset))
+        }
+
+        #[verifier::external_body]
+        fn get(&self, key: &u64) -> (result: Option<u64>)
+        {
+            self.entries.get(key).map(|(item_offset, _)| *item_offset)
+        }
+
+        #[verifier::external_body]
+        fn get_entry_location_by_index(&self, key: &u64, idx: usize) -> (result: Result<u64, KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn get_node_offset(&self, key: &u64, idx: usize) -> (result: Result<u64, KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn remove(&mut self, key: &u64) -> (result: Result<u64, KvError<u64, ()>>)
+        {
+            self.entries.remove(key).map(|(item_offset, _)| item_offset).ok_or(KvError::KeyNotFound)
+        }
+
+        #[verifier::external_body]
+        fn replace_node_offset(&mut self, key: &u64, idx: usize, new_node_offset: u64) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn trim_list(&mut self, key: &u64, trim_length: usize) -> (result: Result<(), KvError<u64, ()>>)
+        {
+            unimplemented!()
+        }
+
+        #[verifier::external_body]
+        fn get_keys(&self) -> (result: Vec<u64>)
+        {
+            self.entries.keys().copied().collect()
+        }
+
+        #[verifier::external_body]
+        fn len(&self) -> (result: usize)
+        {
+            self.entries.len()
+        }
+
+        // The checkpoint is a sequence of little-endian `u64`s: for
+        // each key, the key, its item offset, and its number of list
+        // nodes, followed by each node's offset and entry count.
+        #[verifier::external_body]
+        fn to_checkpoint(&self) -> (result: Vec<u8>)
+        {
+            let mut words = Vec::new();
+            for (key, (item_offset, nodes)) in self.entries.iter() {
+                words.extend([*key, *item_offset, nodes.len() as u64]);
+                for (node_offset, num_entries) in nodes.iter() {
+                    words.extend([*node_offset, *num_entries]);
+                }
+            }
+            words.iter().flat_map(|word| word.to_le_bytes()).collect()
+        }
+
+        #[verifier::external_body]
+        fn from_checkpoint(kvstore_id: u128, max_keys: usize, bytes: &[u8]) -> (result: Result<Self, KvError<u64, ()>>)
+        {
+            if bytes.len() % 8 != 0 {
+                return Err(KvError::InternalError);
+            }
+            let mut words = bytes.chunks(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+            let mut entries = std::collections::HashMap::new();
+            while let Some(key) = words.next() {
+                let item_offset = words.next().ok_or(KvError::InternalError)?;
+                let num_nodes = words.next().ok_or(KvError::InternalError)?;
+                let mut nodes = Vec::new();
+                for _ in 0..num_nodes {
+                    let node_offset = words.next().ok_or(KvError::InternalError)?;
+                    let num_entries = words.next().ok_or(KvError::InternalError)?;
+                    nodes.push((node_offset, num_entries));
+                }
+                entries.insert(key, (item_offset, nodes));
+            }
+            Ok(Self { entries })
+        }
+    }
+}
+
+const MOCK_ENTRIES_PER_LIST_NODE: u64 = 2;
+
+thread_local! {
+    static MOCK_KV_DISK: std::cell::RefCell<std::collections::HashMap<u128, MockDurableStore>> =
+        std::cell::RefCell::new(std::collections::HashMap::new());
+    static MOCK_KV_RAW_LIST_READS: std::cell::Cell<u64> = std::cell::Cell::new(0);
+}
+
+impl MockDurableStore {
+    // Records that `slot` changed in a new generation and saves the
+    // store to `MOCK_KV_DISK`.
+    fn record_change(&mut self, slot: usize) {
+        self.generation += 1;
+        self.changed_at[slot] = self.generation;
+        self.persist();
+    }
+
+    fn persist(&self) {
+        MOCK_KV_DISK.with(|disk| disk.borrow_mut().insert(self.kvstore_id, self.clone()));
+    }
+}
+
+type MockKvStore = crate::kv::kvimpl_v::UntrustedKvStoreImpl<VolatileMemoryMockingPersistentMemoryRegions, u64,
+                                                              KeyedItem, u64, MockDurableStore, MockVolatileIndex, ()>;
+
+/// This test reopens a KV store that was written after its last index
+/// checkpoint, checking that the checkpoint is brought up to date by
+/// re-reading only the slots that changed since it was taken.
+#[test]
+fn check_kv_reopen_after_post_checkpoint_writes() {
+    let kvstore_id = 1;
+    let mut kv = MockKvStore::untrusted_new(VolatileMemoryMockingPersistentMemoryRegions::new(&[64]),
+                                            kvstore_id, 8, 2).unwrap();
+    kv.untrusted_create(&1, KeyedItem(1), Tracked::assume_new()).unwrap();
+    kv.untrusted_create(&2, KeyedItem(2), Tracked::assume_new()).unwrap();
+    kv.untrusted_create(&4, KeyedItem(4), Tracked::assume_new()).unwrap();
+    kv.untrusted_append_to_list(&1, 10, Tracked::assume_new()).unwrap();
+    kv.untrusted_checkpoint_index(Tracked::assume_new()).unwrap();
+
+    // Key 3 reuses key 2's slot, and key 1's list grows into a
+    // second node. Key 4 is untouched.
+    kv.untrusted_delete(&2, Tracked::assume_new()).unwrap();
+    kv.untrusted_create(&3, KeyedItem(3), Tracked::assume_new()).unwrap();
+    kv.untrusted_append_to_list(&1, 11, Tracked::assume_new()).unwrap();
+    kv.untrusted_append_to_list(&1, 12, Tracked::assume_new()).unwrap();
+    drop(kv);
+
+    MOCK_KV_RAW_LIST_READS.with(|reads| reads.set(0));
+    let kv = MockKvStore::untrusted_new(VolatileMemoryMockingPersistentMemoryRegions::new(&[64]),
+                                        kvstore_id, 8, 2).unwrap();
+    assert_eq!(MOCK_KV_RAW_LIST_READS.with(|reads| reads.get()), 2);
+    assert_eq!(kv.untrusted_key_count(), 3);
+    assert_eq!(kv.untrusted_read_item(&1), Some(&KeyedItem(1)));
+    assert_eq!(kv.untrusted_read_item(&2), None);
+    assert_eq!(kv.untrusted_read_item(&3), Some(&KeyedItem(3)));
+    assert_eq!(kv.untrusted_read_item(&4), Some(&KeyedItem(4)));
+    assert_eq!(kv.untrusted_read_list_range(&1, 0, usize::MAX).unwrap(), vec![&10, &11, &12]);
+}
+
+/// This test reopens a KV store that was never checkpointed, checking
+/// that its volatile index is rebuilt from every slot.
+#[test]
+fn check_kv_reopen_without_checkpoint_rebuilds_index() {
+    let kvstore_id = 2;
+    let mut kv = MockKvStore::untrusted_new(VolatileMemoryMockingPersistentMemoryRegions::new(&[64]),
+                                            kvstore_id, 8, 2).unwrap();
+    kv.untrusted_create(&1, KeyedItem(1), Tracked::assume_new()).unwrap();
+    kv.untrusted_create(&2, KeyedItem(2), Tracked::assume_new()).unwrap();
+    kv.untrusted_append_to_list(&2, 20, Tracked::assume_new()).unwrap();
+    kv.untrusted_append_to_list(&2, 21, Tracked::assume_new()).unwrap();
+    kv.untrusted_append_to_list(&2, 22, Tracked::assume_new()).unwrap();
+    drop(kv);
+
+    MOCK_KV_RAW_LIST_READS.with(|reads| reads.set(0));
+    let mut kv = MockKvStore::untrusted_new(VolatileMemoryMockingPersistentMemoryRegions::new(&[64]),
+                                            kvstore_id, 8, 2).unwrap();
+    assert_eq!(MOCK_KV_RAW_LIST_READS.with(|reads| reads.get()), 2);
+    assert_eq!(kv.untrusted_key_count(), 2);
+    assert_eq!(kv.untrusted_read_list_range(&2, 0, usize::MAX).unwrap(), vec![&20, &21, &22]);
+
+    // The rebuilt index knows the tail node is full.
+    kv.untrusted_append_to_list(&2, 23, Tracked::assume_new()).unwrap();
+    assert_eq!(kv.untrusted_read_list_range(&2, 0, usize::MAX).unwrap(), vec![&20, &21, &22, &23]);
+}
+
+#[test]
+fn check_import_parsing_rejects_malformed_snapshots() {
+    use crate::kv::kvimpl_t::KvError;
+
+    // Keys, items, and list entries are all `u64`s here, so a
+    // snapshot is just a sequence of them.
+    fn snapshot(values: &[u64]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    let entries = MockKvStore::untrusted_parse_import(&snapshot(&[2, 1, 1, 0, 2, 2, 2, 20, 21])).unwrap();
+    assert_eq!(entries, vec![(1, KeyedItem(1), vec![]), (2, KeyedItem(2), vec![20, 21])]);
+
+    for malformed in [
+        snapshot(&[2, 1, 1, 0, 1, 1, 0]),  // repeated key
+        snapshot(&[1, 1, 2, 0]),           // item's key differs
+        snapshot(&[1, 1, 1, 2, 10]),       // truncated list
+        snapshot(&[1, 1, 1, 0, 0]),        // trailing bytes
+    ] {
+        assert!(matches!(MockKvStore::untrusted_parse_import(&malformed), Err(KvError::InvalidImportData)));
+    }
+}
+
+#[cfg(feature = "parallel-health-check")]
+#[test]
+fn check_parallel_health_check_matches_serial() {
+    use crate::multilog::health_t::*;
+
+    let mut regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[512, 1024, 512]);
+    let (_capacities, multilog_id) = MultiLogImpl::setup(&mut regions).unwrap();
+    // Corrupt region 1's region metadata so the regions don't all
+    // report the same thing.
+    regions.write(1, ABSOLUTE_POS_OF_REGION_METADATA, &[0xff]);
+    regions.flush().unwrap();
+
+    let serial = health_check(&regions, multilog_id);
+    let parallel = health_check_parallel(&regions, multilog_id);
+    assert_eq!(serial.cdb_ok, parallel.cdb_ok);
+    assert_eq!(serial.healthy, parallel.healthy);
+    assert!(!parallel.healthy);
+    assert_eq!(serial.regions.len(), parallel.regions.len());
+    for (s, p) in serial.regions.iter().zip(parallel.regions.iter()) {
+        assert_eq!((s.global_crc_ok, s.region_crc_ok, s.log_crc_ok, s.recovers),
+                   (p.global_crc_ok, p.region_crc_ok, p.log_crc_ok, p.recovers));
+    }
+    assert!(!parallel.regions[1].region_crc_ok);
+}
+
+#[test]
+fn check_regions_plausibly_sized_rejects_small_regions() {
+    use crate::multilog::start_v::regions_plausibly_sized;
+
+    let min_size = ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE;
+    let regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[512, min_size]);
+    assert!(regions_plausibly_sized(&regions));
+    let regions = VolatileMemoryMockingPersistentMemoryRegions::new(&[512, min_size - 1]);
+    assert!(!regions_plausibly_sized(&regions));
+}
+
+/// This test checks that custom media types round-trip through the
+/// strings recorded with region files, that a custom media type needs
+/// at least one flush step, and that the preset media types keep
+/// their flush behavior.
+#[cfg(target_os = "windows")]
+#[test]
+fn check_custom_media_type_round_trips() {
+    let behavior = MediaFlushBehavior::new(true, true, false).unwrap();
+    let media_type = MemoryMappedFileMediaType::Custom(behavior);
+    assert_eq!(media_type.to_string(), "custom:cache-lines+fence");
+    assert!(media_type.to_string().parse::<MemoryMappedFileMediaType>().unwrap() == media_type);
+    assert!("custom:bogus".parse::<MemoryMappedFileMediaType>().is_err());
+
+    assert!(MediaFlushBehavior::new(false, false, false).is_none());
+    assert!("custom:".parse::<MemoryMappedFileMediaType>().is_err());
+
+    assert!(MemoryMappedFileMediaType::BatteryBackedDRAM.flush_behavior().is_fence_only());
+    assert!(MemoryMappedFileMediaType::SSD.flush_behavior().flush_view());
+    assert!(!MemoryMappedFileMediaType::SSD.flush_behavior().is_fence_only());
+}
+
 #[test]
 fn check_multilog_in_volatile_memory() {
     assert!(test_multilog_in_volatile_memory());
@@ -156,10 +2187,8 @@ fn test_multilog_on_memory_mapped_file() -> Option<()>
     runtime_assert(pos == 0);
 
     // Now commit the tentative appends. This causes log #0 to have tail 3
-    // and log #1 to have tail 4.
-    if multilog.commit().is_err() {
-        runtime_assert(false); // can't fail
-    }
+    // and log #1 to have tail 4. This can only fail if flushing fails.
+    multilog.commit().ok()?;
     match multilog.get_head_tail_and_capacity(0) {
         Ok((head, tail, _capacity)) => {
             runtime_assert(head == 0);
@@ -184,11 +2213,9 @@ fn test_multilog_on_memory_mapped_file() -> Option<()>
     }
 
     // We now advance the head of log #0 to position 2. This causes the
-    // head to become 2 and the tail stays at 3.
-    match multilog.advance_head(0, 2) {
-        Ok(()) => runtime_assert(true),
-        _ => runtime_assert(false) // can't fail
-    }
+    // head to become 2 and the tail stays at 3. This can only fail if
+    // flushing fails.
+    multilog.advance_head(0, 2).ok()?;
     match multilog.get_head_tail_and_capacity(0) {
         Ok((head, tail, _capacity)) => {
             runtime_assert(head == 2);
@@ -252,9 +2279,8 @@ fn test_log_on_memory_mapped_file() -> Option<()>
     runtime_assert(tail == 0);
 
     // Now commit the tentative appends. This causes the log to have tail 3.
-    if log.commit().is_err() {
-        runtime_assert(false); // can't fail
-    }
+    // This can only fail if flushing fails.
+    log.commit().ok()?;
     match log.get_head_tail_and_capacity() {
         Ok((head, tail, _capacity)) => {
             runtime_assert(head == 0);
@@ -272,11 +2298,9 @@ fn test_log_on_memory_mapped_file() -> Option<()>
     }
 
     // We now advance the head of the log to position 2. This causes the
-    // head to become 2 and the tail stays at 3.
-    match log.advance_head(2) {
-        Ok(()) => runtime_assert(true),
-        _ => runtime_assert(false) // can't fail
-    }
+    // head to become 2 and the tail stays at 3. This can only fail if
+    // flushing fails.
+    log.advance_head(2).ok()?;
     match log.get_head_tail_and_capacity() {
         Ok((head, tail, capacity)) => {
             runtime_assert(head == 2);
@@ -294,7 +2318,7 @@ fn test_log_on_memory_mapped_file() -> Option<()>
     // But if we try to read from position 0, we get an
     // error because we're not allowed to read from before the head.
     match log.read(0, 1) {
-        Err(LogErr::CantReadBeforeHead{head}) => runtime_assert(head == 2),
+        Err(LogErr::PositionTrimmed{requested, current_head}) => runtime_assert(requested == 0 && current_head == 2),
         _ => runtime_assert(false) // can't succeed, and can't fail with any other error
     }
     Some(())
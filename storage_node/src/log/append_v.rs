@@ -284,4 +284,95 @@ verus! {
         }
     }
 
+    // This function describes the persistent memory region view after
+    // tentatively appending `bytes_to_append` to the log described by
+    // `info`. If the bytes fit before the end of the log area, that
+    // takes one write. Otherwise, it takes two: one up to the end of
+    // the log area and one for the rest at its beginning.
+    pub open spec fn view_after_tentative_append(
+        pm_region_view: PersistentMemoryRegionView,
+        info: LogInfo,
+        bytes_to_append: Seq<u8>,
+    ) -> PersistentMemoryRegionView
+    {
+        let log_area_len = info.log_area_len;
+        let max_len_without_wrapping = log_area_len -
+            relative_log_pos_to_log_area_offset(info.log_plus_pending_length as int,
+                                                info.head_log_area_offset as int, log_area_len as int);
+        let write_addr = ABSOLUTE_POS_OF_LOG_AREA +
+            relative_log_pos_to_log_area_offset(info.log_plus_pending_length as int,
+                                                info.head_log_area_offset as int, log_area_len as int);
+        if bytes_to_append.len() <= max_len_without_wrapping {
+            pm_region_view.write(write_addr, bytes_to_append)
+        }
+        else {
+            pm_region_view.write(write_addr, bytes_to_append.subrange(0, max_len_without_wrapping))
+                          .write(ABSOLUTE_POS_OF_LOG_AREA as int,
+                                 bytes_to_append.subrange(max_len_without_wrapping, bytes_to_append.len() as int))
+        }
+    }
+
+    // This lemma proves that a tentative append, whether or not it
+    // wraps around the end of the log area, preserves the invariants
+    // relating `LogInfo` to persistent memory, provided `info` and
+    // `state` are updated as `UntrustedLogImpl::tentatively_append`
+    // updates them. It packages `lemma_tentatively_append` and
+    // `lemma_tentatively_append_wrapping` for code building on the
+    // log that only needs the resulting invariants, not the
+    // per-write crash-safety facts.
+    //
+    // Parameters:
+    //
+    // `pm_region_view` -- the view of the persistent memory region
+    // before the append
+    //
+    // `log_id` -- the ID of the log stored on that memory
+    //
+    // `bytes_to_append` -- what bytes are being tentatively appended
+    //
+    // `cdb` -- the current corruption-detecting boolean value
+    //
+    // `prev_info` -- the pre-append `info` value
+    //
+    // `prev_state` -- the pre-append abstract state
+    pub proof fn lemma_append_preserves_log_info(
+        pm_region_view: PersistentMemoryRegionView,
+        log_id: u128,
+        bytes_to_append: Seq<u8>,
+        cdb: bool,
+        prev_info: LogInfo,
+        prev_state: AbstractLogState,
+    )
+        requires
+            memory_matches_deserialized_cdb(pm_region_view, cdb),
+            metadata_consistent_with_info(pm_region_view, log_id, cdb, prev_info),
+            info_consistent_with_log_area(pm_region_view, prev_info, prev_state),
+            0 < bytes_to_append.len() <= prev_info.log_area_len - prev_info.log_plus_pending_length,
+            prev_info.head + prev_info.log_plus_pending_length + bytes_to_append.len() <= u128::MAX,
+        ensures
+            ({
+                let new_info = LogInfo{
+                    log_plus_pending_length: (prev_info.log_plus_pending_length + bytes_to_append.len()) as u64,
+                    ..prev_info
+                };
+                let new_state = prev_state.tentatively_append(bytes_to_append);
+                let new_view = view_after_tentative_append(pm_region_view, prev_info, bytes_to_append);
+                &&& memory_matches_deserialized_cdb(new_view, cdb)
+                &&& metadata_consistent_with_info(new_view, log_id, cdb, new_info)
+                &&& info_consistent_with_log_area(new_view, new_info, new_state)
+                &&& new_state.drop_pending_appends() == prev_state.drop_pending_appends()
+            }),
+    {
+        let log_area_len = prev_info.log_area_len;
+        let max_len_without_wrapping = log_area_len -
+            relative_log_pos_to_log_area_offset(prev_info.log_plus_pending_length as int,
+                                                prev_info.head_log_area_offset as int, log_area_len as int);
+        if bytes_to_append.len() <= max_len_without_wrapping {
+            lemma_tentatively_append(pm_region_view, log_id, bytes_to_append, cdb, prev_info, prev_state);
+        }
+        else {
+            lemma_tentatively_append_wrapping(pm_region_view, log_id, bytes_to_append, cdb, prev_info, prev_state);
+        }
+    }
+
 }
@@ -0,0 +1,332 @@
+//! This file contains `BufferedLog`, a wrapper around `LogImpl` that
+//! coalesces many small appends into one. Appended bytes are kept in
+//! a volatile buffer, and are only written to persistent memory, and
+//! committed, when the buffer fills or when `flush` is called. This
+//! trades durability latency for far fewer persistent-memory writes
+//! and commits when appending many tiny records.
+//!
+//! Buffered bytes behave exactly like pending appends: a crash before
+//! they're flushed discards them. A `FlushStrategy` can make the
+//! buffer flush before it fills, e.g., after a number of appends
+//! that adapts to how slow flushes are.
+//!
+//! The code in this file is verified and untrusted (as indicated by
+//! the `_v.rs` suffix), so you don't have to read it to be confident
+//! of the system's correctness.
+
+use crate::log::flushstrategy_t::*;
+use crate::log::logimpl_t::*;
+use crate::log::logspec_t::AbstractLogState;
+use crate::pmem::pmemspec_t::*;
+use builtin::*;
+use builtin_macros::*;
+use vstd::prelude::*;
+use vstd::slice::*;
+
+verus! {
+
+    // This helper function appends `bytes` to the end of `v`.
+    fn append_to_vec(v: &mut Vec<u8>, bytes: &[u8])
+        ensures
+            v@ == old(v)@ + bytes@,
+    {
+        let mut i: usize = 0;
+        while i < bytes.len()
+            invariant
+                i <= bytes@.len(),
+                v@ == old(v)@ + bytes@.subrange(0, i as int),
+        {
+            v.push(*slice_index_get(bytes, i));
+            i = i + 1;
+            assert(v@ =~= old(v)@ + bytes@.subrange(0, i as int));
+        }
+        assert(bytes@.subrange(0, bytes@.len() as int) =~= bytes@);
+    }
+
+    // A `BufferedLog` wraps a `LogImpl` whose pending appends are
+    // kept in the volatile `buffer` rather than in the log. The
+    // wrapped log never has pending appends of its own.
+    //
+    // `buffer_capacity` is how many bytes can be buffered before
+    // they're flushed.
+    //
+    // `scheduler` decides whether to flush before the buffer fills.
+    pub struct BufferedLog<PMRegion: PersistentMemoryRegion> {
+        log: LogImpl<PMRegion>,
+        buffer: Vec<u8>,
+        buffer_capacity: usize,
+        scheduler: FlushScheduler,
+    }
+
+    impl<PMRegion: PersistentMemoryRegion> BufferedLog<PMRegion> {
+        // The view of a `BufferedLog` is the view of the wrapped log
+        // with the buffered bytes as its pending appends.
+        pub closed spec fn view(&self) -> AbstractLogState
+        {
+            self.log@.tentatively_append(self.buffer@)
+        }
+
+        pub closed spec fn valid(&self) -> bool
+        {
+            &&& self.log.valid()
+            &&& self.log@.pending.len() == 0
+            &&& self.log@.try_tentatively_append(self.buffer@) is Some
+        }
+
+        // A `BufferedLog` is poisoned once its wrapped log is, i.e.,
+        // once a flush fails with `LogErr::FlushFailed`. After that,
+        // it isn't valid and its buffered bytes are lost; the caller
+        // has to drop it and restart the log with `start`. See
+        // `LogImpl::poisoned`.
+        pub closed spec fn poisoned(&self) -> bool
+        {
+            self.log.poisoned()
+        }
+
+        // This predicate describes what we guarantee about crashes
+        // after a flush fails with `LogErr::FlushFailed`. See
+        // `LogImpl::can_only_crash_as_one_of`.
+        pub closed spec fn can_only_crash_as_one_of(&self, s1: AbstractLogState, s2: AbstractLogState) -> bool
+        {
+            self.log.can_only_crash_as_one_of(s1, s2)
+        }
+
+        // This function wraps `log` in a `BufferedLog` that buffers
+        // up to `buffer_capacity` bytes. `log` mustn't have any
+        // pending appends; commit them first.
+        pub fn new(log: LogImpl<PMRegion>, buffer_capacity: usize) -> (result: Self)
+            requires
+                log.valid(),
+                log@.pending.len() == 0,
+            ensures
+                result.valid(),
+                result@ == log@,
+        {
+            Self::new_with_scheduler(log, buffer_capacity, FlushScheduler::when_full())
+        }
+
+        // This function is like `new`, except that the buffer is also
+        // flushed whenever `strategy` says to.
+        pub fn new_with_flush_strategy(
+            log: LogImpl<PMRegion>,
+            buffer_capacity: usize,
+            strategy: FlushStrategy,
+        ) -> (result: Self)
+            requires
+                log.valid(),
+                log@.pending.len() == 0,
+            ensures
+                result.valid(),
+                result@ == log@,
+        {
+            Self::new_with_scheduler(log, buffer_capacity, FlushScheduler::new(strategy))
+        }
+
+        fn new_with_scheduler(
+            log: LogImpl<PMRegion>,
+            buffer_capacity: usize,
+            scheduler: FlushScheduler,
+        ) -> (result: Self)
+            requires
+                log.valid(),
+                log@.pending.len() == 0,
+            ensures
+                result.valid(),
+                result@ == log@,
+        {
+            let result = Self { log, buffer: Vec::new(), buffer_capacity, scheduler };
+            assert(result@ =~= log@);
+            assert(log@.try_tentatively_append(Seq::<u8>::empty()) is Some);
+            result
+        }
+
+        // This function returns the wrapped log, for reading. Reads
+        // only see committed bytes, so they don't see buffered bytes.
+        pub fn log(&self) -> (result: &LogImpl<PMRegion>)
+            requires
+                self.valid(),
+            ensures
+                result.valid(),
+                result@ == self@.drop_pending_appends(),
+        {
+            assert(self.log@ =~= self@.drop_pending_appends());
+            &self.log
+        }
+
+        // This function returns how many bytes are buffered, i.e.,
+        // would be lost if a crash happened now.
+        pub fn num_buffered_bytes(&self) -> (result: usize)
+            requires
+                self.valid(),
+            ensures
+                result == self@.pending.len(),
+        {
+            self.buffer.len()
+        }
+
+        // This function returns how many appends the flush strategy
+        // currently batches per flush. It's only meaningful for
+        // `FlushStrategy::Adaptive`.
+        pub fn batch_size(&self) -> usize
+        {
+            self.scheduler.batch_size()
+        }
+
+        // This function appends `bytes_to_append` to the buffer, and
+        // returns the virtual position where they'll start in the log.
+        // If that would overflow the buffer, it first flushes what's
+        // already buffered. If the buffer is then at least full, it
+        // flushes again. So, on success, each of the buffered
+        // records, including `bytes_to_append`, is either still
+        // buffered or has been committed in order. If a flush fails
+        // with `LogErr::FlushFailed`, `self` is poisoned.
+        pub fn append(&mut self, bytes_to_append: &[u8]) -> (result: Result<u128, LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                match result {
+                    Ok(offset) => {
+                        let state = old(self)@;
+                        &&& self.valid()
+                        &&& offset == state.head + state.log.len() + state.pending.len()
+                        &&& self@.commit() == old(self)@.tentatively_append(bytes_to_append@).commit()
+                        &&& {
+                               ||| self@ == old(self)@.tentatively_append(bytes_to_append@)
+                               ||| self@ == old(self)@.commit().tentatively_append(bytes_to_append@)
+                               ||| self@ == old(self)@.tentatively_append(bytes_to_append@).commit()
+                           }
+                    },
+                    Err(LogErr::InsufficientSpaceForAppend { available_space }) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@
+                        &&& available_space < bytes_to_append@.len()
+                        &&& available_space == self@.capacity - self@.log.len() - self@.pending.len()
+                    },
+                    Err(LogErr::LogPositionOverflow) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@
+                        &&& self@.head + self@.log.len() + self@.pending.len() + bytes_to_append@.len() > u128::MAX
+                    },
+                    Err(LogErr::FlushFailed) => self.poisoned(),
+                    _ => false
+                }
+        {
+            let ghost old_view = self@;
+            let ghost appended = old_view.tentatively_append(bytes_to_append@);
+            let num_bytes: u64 = bytes_to_append.len() as u64;
+            let num_buffered: u64 = self.buffer.len() as u64;
+
+            // Check that the bytes fit, taking the buffered bytes into
+            // account. The wrapped log has no pending appends of its
+            // own, so the buffered bytes are all that's pending.
+
+            let (head, tail, capacity) = match self.log.get_head_tail_and_capacity() {
+                Ok(t) => t,
+                Err(e) => { return Err(e); },
+            };
+            let available_space: u64 = capacity - (tail - head) as u64 - num_buffered;
+            if num_bytes > available_space {
+                return Err(LogErr::InsufficientSpaceForAppend{ available_space });
+            }
+            if num_bytes as u128 > u128::MAX - tail - num_buffered as u128 {
+                return Err(LogErr::LogPositionOverflow);
+            }
+            let offset = tail + num_buffered as u128;
+
+            // If the bytes don't fit in the buffer, flush it first.
+
+            if num_buffered + num_bytes > self.buffer_capacity as u64 && num_buffered > 0 {
+                self.flush()?;
+            }
+            let ghost before_buffering = self@;
+
+            append_to_vec(&mut self.buffer, bytes_to_append);
+            assert(self@ =~= before_buffering.tentatively_append(bytes_to_append@));
+            assert(self.log@.try_tentatively_append(self.buffer@) is Some);
+
+            // If the buffer is now full, or the flush strategy says
+            // enough appends have been batched, flush it.
+
+            let flush_scheduled = self.scheduler.note_append();
+            if self.buffer.len() >= self.buffer_capacity || flush_scheduled {
+                self.flush()?;
+                assert(self@ =~= appended.commit());
+            }
+
+            assert(self@.commit() =~= appended.commit());
+            assert(old_view.commit().tentatively_append(bytes_to_append@).commit() =~= appended.commit());
+            Ok(offset)
+        }
+
+        // This function writes all buffered bytes to the wrapped log
+        // and commits them. On success, the committed state is as if
+        // every buffered record had been appended in order and then
+        // committed. If it fails with `LogErr::FlushFailed`, `self`
+        // is poisoned, and a crash recovers to either the committed
+        // state before the call or the state with every buffered
+        // record committed.
+        pub fn flush(&mut self) -> (result: Result<(), LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                match result {
+                    Ok(()) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@.commit()
+                        &&& self@.pending.len() == 0
+                    },
+                    Err(LogErr::FlushFailed) => {
+                        &&& self.poisoned()
+                        &&& self.can_only_crash_as_one_of(old(self)@.drop_pending_appends(),
+                                                         old(self)@.commit().drop_pending_appends())
+                    },
+                    _ => false
+                }
+        {
+            if self.log.is_poisoned() {
+                return Err(LogErr::Poisoned);
+            }
+            if self.buffer.len() == 0 {
+                assert(self@ =~= old(self)@.commit());
+                return Ok(());
+            }
+
+            self.scheduler.flush_starting();
+            match self.log.tentatively_append(self.buffer.as_slice()) {
+                Ok(_) => {},
+                Err(e) => { return Err(e); },
+            }
+            assert(self.log@ =~= old(self)@);
+            self.log.commit()?;
+            self.scheduler.flush_finished();
+            self.buffer = Vec::new();
+            assert(self@ =~= old(self)@.commit());
+            assert(self.log@.try_tentatively_append(self.buffer@) is Some);
+            Ok(())
+        }
+
+        // This function flushes any buffered bytes and returns the
+        // wrapped log. If the flush fails, the log is dropped along
+        // with `self`, so nothing poisoned is left to misuse; the
+        // caller has to restart the log with `start`.
+        pub fn into_log(self) -> (result: Result<LogImpl<PMRegion>, LogErr>)
+            requires
+                self.valid(),
+            ensures
+                match result {
+                    Ok(log) => {
+                        &&& log.valid()
+                        &&& log@ == self@.commit()
+                    },
+                    Err(LogErr::FlushFailed) => true,
+                    _ => false
+                }
+        {
+            let mut buffered = self;
+            buffered.flush()?;
+            proof { assert(buffered.log@ =~= buffered@); }
+            Ok(buffered.log)
+        }
+    }
+
+}
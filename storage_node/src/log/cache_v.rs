@@ -0,0 +1,336 @@
+//! This file contains `CachingLogReader`, a wrapper around `LogImpl`
+//! that remembers the results of recent reads. A reader that keeps
+//! re-reading the same positions, e.g., polling near the tail for
+//! new records, is then served from volatile memory rather than
+//! reading persistent memory each time.
+//!
+//! The cache is a small least-recently-used list keyed by virtual
+//! position and length. It's emptied whenever the wrapped log
+//! commits or advances its head, since each of those flips the
+//! log's CDB. So a cached read is only ever returned while the log
+//! is in the same committed state it was in when the read was done,
+//! and it satisfies the same postcondition as a fresh `read`.
+//!
+//! The code in this file is verified and untrusted (as indicated by
+//! the `_v.rs` suffix), so you don't have to read it to be confident
+//! of the system's correctness.
+
+use crate::log::logimpl_t::*;
+use crate::log::logspec_t::AbstractLogState;
+use crate::pmem::pmemspec_t::*;
+use builtin::*;
+use builtin_macros::*;
+use vstd::prelude::*;
+use vstd::slice::*;
+
+verus! {
+
+    // A `CacheEntry` holds the bytes returned by reading `len` bytes
+    // at virtual position `pos`.
+    pub struct CacheEntry {
+        pos: u128,
+        len: u64,
+        bytes: Vec<u8>,
+    }
+
+    // A `CachingLogReader` wraps a `LogImpl` and caches up to
+    // `capacity` of its most recent reads in `entries`, least
+    // recently used first.
+    pub struct CachingLogReader<PMRegion: PersistentMemoryRegion> {
+        log: LogImpl<PMRegion>,
+        entries: Vec<CacheEntry>,
+        capacity: usize,
+    }
+
+    impl<PMRegion: PersistentMemoryRegion> CachingLogReader<PMRegion> {
+        // The view of a `CachingLogReader` is the view of the wrapped
+        // log. The cache doesn't affect it.
+        pub closed spec fn view(&self) -> AbstractLogState
+        {
+            self.log@
+        }
+
+        pub closed spec fn constants(&self) -> PersistentMemoryConstants
+        {
+            self.log.constants()
+        }
+
+        // This function describes when `entry` may be returned in
+        // place of reading the log whose view is `state`: it must be
+        // something `LogImpl::read` could have returned.
+        spec fn entry_correct(entry: CacheEntry, state: AbstractLogState,
+                              impervious_to_corruption: bool) -> bool
+        {
+            &&& entry.pos >= state.head
+            &&& entry.pos + entry.len <= state.head + state.log.len()
+            &&& read_correct_modulo_corruption(entry.bytes@, state.read(entry.pos as int, entry.len as int),
+                                              impervious_to_corruption)
+        }
+
+        pub closed spec fn valid(&self) -> bool
+        {
+            &&& self.log.valid()
+            &&& self.entries.len() <= self.capacity
+            &&& forall |i: int| 0 <= i < self.entries.len() ==>
+                   Self::entry_correct(#[trigger] self.entries[i], self.log@,
+                                       self.log.constants().impervious_to_corruption)
+        }
+
+        // This function wraps `log` in a `CachingLogReader` that
+        // caches up to `capacity` reads. A `capacity` of 0 disables
+        // caching.
+        pub fn new(log: LogImpl<PMRegion>, capacity: usize) -> (result: Self)
+            requires
+                log.valid(),
+            ensures
+                result.valid(),
+                result@ == log@,
+                result.constants() == log.constants(),
+        {
+            Self { log, entries: Vec::new(), capacity }
+        }
+
+        // This function returns the wrapped log, for operations that
+        // don't change it.
+        pub fn log(&self) -> (result: &LogImpl<PMRegion>)
+            requires
+                self.valid(),
+            ensures
+                result.valid(),
+                result@ == self@,
+                result.constants() == self.constants(),
+        {
+            &self.log
+        }
+
+        // This function returns the wrapped log, discarding the cache.
+        pub fn into_log(self) -> (result: LogImpl<PMRegion>)
+            requires
+                self.valid(),
+            ensures
+                result.valid(),
+                result@ == self@,
+                result.constants() == self.constants(),
+        {
+            self.log
+        }
+
+        // This function returns how many reads are currently cached.
+        pub fn num_cached_reads(&self) -> (result: usize)
+        {
+            self.entries.len()
+        }
+
+        // This function returns whether a read of `len` bytes at
+        // virtual position `pos` is currently cached.
+        pub fn is_cached(&self, pos: u128, len: u64) -> (result: bool)
+        {
+            let mut i: usize = 0;
+            while i < self.entries.len()
+                invariant
+                    i <= self.entries.len(),
+            {
+                if self.entries[i].pos == pos && self.entries[i].len == len {
+                    return true;
+                }
+                i = i + 1;
+            }
+            false
+        }
+
+        // A `CachingLogReader` is poisoned once its wrapped log is,
+        // i.e., once `commit` or `advance_head` fails with
+        // `LogErr::FlushFailed`. After that, it isn't valid, and
+        // `read` fails with `LogErr::Poisoned` rather than serving
+        // cached bytes. See `LogImpl::poisoned`.
+        pub closed spec fn poisoned(&self) -> bool
+        {
+            self.log.poisoned()
+        }
+
+        // This function says that every state the wrapped log's
+        // persistent memory can crash into recovers to `s1` or `s2`.
+        // It's what `commit` and `advance_head` guarantee if a flush
+        // fails, just as for `LogImpl`.
+        pub closed spec fn can_only_crash_as_one_of(&self, s1: AbstractLogState, s2: AbstractLogState) -> bool
+        {
+            self.log.can_only_crash_as_one_of(s1, s2)
+        }
+
+        // This function reads `len` bytes at virtual position `pos`,
+        // like `LogImpl::read`. If an identical read is cached, it
+        // returns a copy of the cached bytes without reading
+        // persistent memory. Otherwise, it reads the log and caches
+        // the result, evicting the least recently used entry if the
+        // cache is full. Either way, it guarantees what
+        // `LogImpl::read` does.
+        pub fn read(&mut self, pos: u128, len: u64) -> (result: Result<Vec<u8>, LogErr>)
+            requires
+                old(self).valid(),
+                pos + len <= u128::MAX,
+            ensures
+                self.valid(),
+                self@ == old(self)@,
+                self.constants() == old(self).constants(),
+                ({
+                    let state = self@;
+                    let head = state.head;
+                    let log = state.log;
+                    match result {
+                        Ok(bytes) => {
+                            let true_bytes = self@.read(pos as int, len as int);
+                            &&& pos >= head
+                            &&& pos + len <= head + log.len()
+                            &&& read_correct_modulo_corruption(bytes@, true_bytes,
+                                                             self.constants().impervious_to_corruption)
+                        },
+                        Err(LogErr::PositionTrimmed{ requested, current_head }) => {
+                            &&& pos < head
+                            &&& requested == pos
+                            &&& current_head == head
+                        },
+                        Err(LogErr::CantReadPastTail{ tail }) => {
+                            &&& pos + len > tail
+                            &&& tail == head + log.len()
+                        },
+                        _ => false
+                    }
+                })
+        {
+            let ghost impervious = self.log.constants().impervious_to_corruption;
+
+            if self.log.is_poisoned() {
+                return Err(LogErr::Poisoned);
+            }
+
+            // Look for a cached read. On a hit, move the entry to the
+            // end, since it's now the most recently used.
+
+            let mut i: usize = 0;
+            while i < self.entries.len()
+                invariant
+                    self == old(self),
+                    self.valid(),
+                    i <= self.entries.len(),
+            {
+                if self.entries[i].pos == pos && self.entries[i].len == len {
+                    let bytes = slice_to_vec(self.entries[i].bytes.as_slice());
+                    assert(Self::entry_correct(self.entries[i as int], self.log@, impervious));
+                    let entry = self.entries.remove(i);
+                    self.entries.push(entry);
+                    assert forall |j: int| 0 <= j < self.entries.len() implies
+                           Self::entry_correct(#[trigger] self.entries[j], self.log@, impervious) by {
+                        if j < i {
+                            assert(self.entries[j] == old(self).entries[j]);
+                        }
+                        else if j < self.entries.len() - 1 {
+                            assert(self.entries[j] == old(self).entries[j + 1]);
+                        }
+                    }
+                    return Ok(bytes);
+                }
+                i = i + 1;
+            }
+
+            // On a miss, read the log, and cache the result if the
+            // read succeeded.
+
+            let bytes = self.log.read(pos, len)?;
+            if self.capacity == 0 {
+                return Ok(bytes);
+            }
+            if self.entries.len() == self.capacity {
+                let ghost before_eviction = self.entries@;
+                self.entries.remove(0);
+                assert forall |j: int| 0 <= j < self.entries.len() implies
+                       Self::entry_correct(#[trigger] self.entries[j], self.log@, impervious) by {
+                    assert(self.entries[j] == before_eviction[j + 1]);
+                }
+            }
+            let entry = CacheEntry { pos, len, bytes: slice_to_vec(bytes.as_slice()) };
+            self.entries.push(entry);
+            Ok(bytes)
+        }
+
+        // This function tentatively appends `bytes_to_append`, like
+        // `LogImpl::tentatively_append`. Tentative appends don't
+        // change what can be read, so the cache is kept.
+        pub fn tentatively_append(&mut self, bytes_to_append: &[u8]) -> (result: Result<u128, LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                self.valid(),
+                self.constants() == old(self).constants(),
+                match result {
+                    Ok(offset) => {
+                        let state = old(self)@;
+                        &&& offset == state.head + state.log.len() + state.pending.len()
+                        &&& self@ == old(self)@.tentatively_append(bytes_to_append@)
+                    },
+                    Err(_) => self@ == old(self)@,
+                }
+        {
+            let ghost impervious = self.log.constants().impervious_to_corruption;
+            let result = self.log.tentatively_append(bytes_to_append);
+            assert forall |j: int| 0 <= j < self.entries.len() implies
+                   Self::entry_correct(#[trigger] self.entries[j], self.log@, impervious) by {
+                assert(old(self).entries[j] == self.entries[j]);
+            }
+            result
+        }
+
+        // This function commits all tentative appends, like
+        // `LogImpl::commit`. A commit flips the CDB, so it empties
+        // the cache.
+        pub fn commit(&mut self) -> (result: Result<(), LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                self.constants() == old(self).constants(),
+                match result {
+                    Ok(()) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@.commit()
+                    },
+                    Err(LogErr::FlushFailed) => {
+                        &&& self.poisoned()
+                        &&& self.can_only_crash_as_one_of(old(self)@.drop_pending_appends(),
+                                                         old(self)@.commit().drop_pending_appends())
+                    },
+                    _ => false
+                }
+        {
+            self.entries.clear();
+            self.log.commit()
+        }
+
+        // This function advances the head, like
+        // `LogImpl::advance_head`. That flips the CDB, so it empties
+        // the cache.
+        pub fn advance_head(&mut self, new_head: u128) -> (result: Result<(), LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                self.constants() == old(self).constants(),
+                match result {
+                    Ok(()) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@.advance_head(new_head as int)
+                    },
+                    Err(LogErr::FlushFailed) => {
+                        &&& self.poisoned()
+                        &&& self.can_only_crash_as_one_of(old(self)@.drop_pending_appends(),
+                                                         old(self)@.advance_head(new_head as int).drop_pending_appends())
+                    },
+                    Err(_) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@
+                    },
+                }
+        {
+            self.entries.clear();
+            self.log.advance_head(new_head)
+        }
+    }
+
+}
@@ -0,0 +1,127 @@
+//! This file contains `FlushStrategy`, which says when a
+//! `BufferedLog` flushes its buffered appends, and `FlushScheduler`,
+//! which carries out that decision. With `FlushStrategy::Adaptive`,
+//! the scheduler measures how long each flush takes and adjusts how
+//! many appends it batches per flush, so slow media batch many
+//! appends per flush and fast media flush nearly every one.
+//!
+//! The scheduler only decides *when* to flush. The verified
+//! `BufferedLog` code performs every flush, so its abstract
+//! durability guarantee still only advances on an actual flush,
+//! whatever the scheduler decides.
+//!
+//! The code in this file is trusted and unverified (as indicated by
+//! the `_t.rs` suffix), so it must be audited. Since the verified
+//! code assumes nothing about its results, auditing only needs to
+//! check that it doesn't panic.
+
+use builtin::*;
+use builtin_macros::*;
+use std::time::{Duration, Instant};
+use vstd::prelude::*;
+
+verus! {
+
+    // This enumeration says when a `BufferedLog` flushes, besides
+    // when its buffer fills or when `flush` is called.
+    //
+    // `WhenFull` -- never; appends are only flushed when the buffer
+    // fills. This is the default.
+    //
+    // `Adaptive { target_latency }` -- after a number of appends that
+    // adapts to the measured flush latency. The scheduler aims for
+    // each buffered append's share of a flush's latency to be about
+    // `target_latency`: if it's more, the number of appends batched
+    // per flush doubles, and if it's less than half, that number
+    // halves.
+    #[verifier::external_body]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum FlushStrategy {
+        WhenFull,
+        Adaptive { target_latency: Duration },
+    }
+
+    // This struct tracks the state a `FlushStrategy` needs to decide
+    // when to flush. It's opaque to the verifier.
+    #[verifier::external_body]
+    pub struct FlushScheduler {
+        strategy: FlushStrategy,
+        batch_size: usize,
+        appends_since_flush: usize,
+        flush_started: Option<Instant>,
+    }
+
+    impl FlushScheduler {
+        #[verifier::external_body]
+        pub fn new(strategy: FlushStrategy) -> Self
+        {
+            Self { strategy, batch_size: 1, appends_since_flush: 0, flush_started: None }
+        }
+
+        // This function returns a scheduler for the default strategy,
+        // `FlushStrategy::WhenFull`.
+        #[verifier::external_body]
+        pub fn when_full() -> Self
+        {
+            Self::new(FlushStrategy::WhenFull)
+        }
+
+        // This function records that an append was buffered, and
+        // returns whether the buffer should be flushed now.
+        #[verifier::external_body]
+        pub fn note_append(&mut self) -> bool
+        {
+            self.appends_since_flush = self.appends_since_flush.saturating_add(1);
+            match self.strategy {
+                FlushStrategy::WhenFull => false,
+                FlushStrategy::Adaptive { .. } => self.appends_since_flush >= self.batch_size,
+            }
+        }
+
+        // This function records that a flush of the buffered appends
+        // is starting.
+        #[verifier::external_body]
+        pub fn flush_starting(&mut self)
+        {
+            if let FlushStrategy::Adaptive { .. } = self.strategy {
+                self.flush_started = Some(Instant::now());
+            }
+        }
+
+        // This function records that the flush started by the last
+        // call to `flush_starting` succeeded, and adapts the batch
+        // size to its latency.
+        #[verifier::external_body]
+        pub fn flush_finished(&mut self)
+        {
+            if let (FlushStrategy::Adaptive { target_latency }, Some(started)) =
+                (self.strategy, self.flush_started.take()) {
+                let appends = self.appends_since_flush.clamp(1, u32::MAX as usize) as u32;
+                let latency_per_append = started.elapsed() / appends;
+                if latency_per_append > target_latency {
+                    self.batch_size = self.batch_size.saturating_mul(2);
+                }
+                else if latency_per_append < target_latency / 2 {
+                    self.batch_size = (self.batch_size / 2).max(1);
+                }
+            }
+            self.appends_since_flush = 0;
+        }
+
+        // This function returns how many appends are currently
+        // batched per flush under `FlushStrategy::Adaptive`.
+        #[verifier::external_body]
+        pub fn batch_size(&self) -> usize
+        {
+            self.batch_size
+        }
+    }
+
+}
+
+impl Default for FlushStrategy {
+    fn default() -> Self
+    {
+        FlushStrategy::WhenFull
+    }
+}
@@ -479,6 +479,57 @@ verus! {
         }
     }
 
+    // This lemma establishes that, if one writes to the writer
+    // marker in a region, this will maintain various invariants.
+    // Recovery never reads the writer marker, so this holds no
+    // matter what's written there.
+    //
+    // `pm_region_view` -- the persistent memory region view
+    // `log_id` -- the ID of the log
+    // `cdb` -- the current value of the corruption-detecting boolean
+    // `info` -- the log information
+    // `state` -- the abstract log state
+    // `addr` -- the address to write to, within the writer marker
+    // `bytes_to_write` -- bytes to be written to the writer marker
+    pub proof fn lemma_updating_writer_marker_maintains_invariants(
+        pm_region_view: PersistentMemoryRegionView,
+        log_id: u128,
+        cdb: bool,
+        info: LogInfo,
+        state: AbstractLogState,
+        addr: int,
+        bytes_to_write: Seq<u8>,
+    )
+        requires
+            memory_matches_deserialized_cdb(pm_region_view, cdb),
+            metadata_consistent_with_info(pm_region_view, log_id, cdb, info),
+            info_consistent_with_log_area(pm_region_view, info, state),
+            ABSOLUTE_POS_OF_WRITER_MARKER <= addr,
+            addr + bytes_to_write.len() <= ABSOLUTE_POS_OF_WRITER_MARKER + LENGTH_OF_WRITER_MARKER,
+       ensures
+            ({
+                let pm_region_view2 = pm_region_view.write(addr, bytes_to_write);
+                &&& memory_matches_deserialized_cdb(pm_region_view2, cdb)
+                &&& metadata_consistent_with_info(pm_region_view2, log_id, cdb, info)
+                &&& info_consistent_with_log_area(pm_region_view2, info, state)
+            })
+    {
+        let pm_region_view2 = pm_region_view.write(addr, bytes_to_write);
+
+        assert(memory_matches_deserialized_cdb(pm_region_view2, cdb)) by {
+            assert(extract_log_cdb(pm_region_view2.committed()) =~=
+                   extract_log_cdb(pm_region_view.committed()));
+        }
+
+        // The writer marker lies between the log metadata and the
+        // log area, so it doesn't overlap anything `extract_bytes`
+        // is used to read.
+
+        assert(metadata_consistent_with_info(pm_region_view2, log_id, cdb, info)) by {
+            lemma_establish_extract_bytes_equivalence(pm_region_view.committed(), pm_region_view2.committed());
+        }
+    }
+
     // This lemma establishes that, if one flushes persistent memory,
     // this will maintain various invariants.
     //
@@ -523,4 +574,29 @@ verus! {
         }
     }
 
+    // This lemma proves that committing preserves
+    // `info_consistent_with_log_area` once the pending appends are
+    // flushed, provided `info` and `state` are updated as
+    // `UntrustedLogImpl::commit` updates them: the pending appends
+    // become part of the log. Code building on the log can use it
+    // without re-deriving how the log area relates to `info`.
+    //
+    // `pm_region_view` -- the persistent memory region view before
+    // the flush
+    // `prev_info` -- the log information before the commit
+    // `prev_state` -- the abstract log state before the commit
+    pub proof fn lemma_commit_preserves_log_info(
+        pm_region_view: PersistentMemoryRegionView,
+        prev_info: LogInfo,
+        prev_state: AbstractLogState,
+    )
+        requires
+            info_consistent_with_log_area(pm_region_view, prev_info, prev_state),
+        ensures
+            info_consistent_with_log_area(pm_region_view.flush(),
+                                          LogInfo{ log_length: prev_info.log_plus_pending_length, ..prev_info },
+                                          prev_state.commit()),
+    {
+    }
+
 }
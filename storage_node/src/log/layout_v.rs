@@ -29,7 +29,9 @@
 //!   bytes 40..48:   This region's size
 //!   bytes 48..56:   Length of log area (LoLA)
 //!   bytes 56..72:   Log ID
-//!   bytes 72..80:   CRC of the above 32 bytes
+//!   bytes 72..80:   Append alignment: 64 if each aligned append should start on a cache-line boundary, 0 otherwise
+//!   bytes 80..88:   Unused padding bytes
+//!   bytes 88..96:   CRC of the above 48 bytes
 //!
 //! Log metadata (relative offsets):
 //!   bytes 0..8:     Log length
@@ -37,6 +39,21 @@
 //!   bytes 16..32:   Log head virtual position
 //!   bytes 32..40:   CRC of the above 32 bytes
 //!
+//! Writer marker (absolute offsets):
+//!   bytes 184..192: Process ID of the writer holding the advisory writer lock, or 0 if none
+//!   bytes 192..200: Time the marker was last written, in seconds since the Unix epoch
+//!
+//! The writer marker is advisory. Recovery never reads it, so a stale
+//! marker left behind by a crashed writer has no effect on the
+//! recovered log.
+//!
+//! The append alignment is written once, at setup, and recovery
+//! doesn't interpret it. Padding written to align an append is
+//! ordinary log data, so the alignment can't affect what's recovered,
+//! only where future aligned appends are placed. A log set up with
+//! cache-line alignment has a LoLA that's a multiple of 64, so an
+//! aligned virtual position is also aligned in the log area.
+//!
 //! Log area (relative offsets):
 //!   bytes 0..LoLA:   Byte #n is the one whose virtual log position modulo LoLA is n
 //!
@@ -58,6 +75,7 @@ use crate::pmem::serialization_t::*;
 use builtin::*;
 use builtin_macros::*;
 use core::fmt::Debug;
+use vstd::arithmetic::div_mod::*;
 use vstd::bytes::*;
 use vstd::prelude::*;
 
@@ -79,21 +97,36 @@ verus! {
     pub const RELATIVE_POS_OF_REGION_REGION_SIZE: u64 = 0;
     pub const RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA: u64 = 8;
     pub const RELATIVE_POS_OF_REGION_LOG_ID: u64 = 16;
-    pub const LENGTH_OF_REGION_METADATA: u64 = 32;
-    pub const ABSOLUTE_POS_OF_REGION_CRC: u64 = 72;
-
-    pub const ABSOLUTE_POS_OF_LOG_CDB: u64 = 80;
-    pub const ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE: u64 = 88;
-    pub const ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE: u64 = 128;
+    pub const RELATIVE_POS_OF_REGION_APPEND_ALIGNMENT: u64 = 32;
+    pub const RELATIVE_POS_OF_REGION_PADDING: u64 = 40;
+    pub const LENGTH_OF_REGION_METADATA: u64 = 48;
+    pub const ABSOLUTE_POS_OF_REGION_CRC: u64 = 88;
+
+    pub const ABSOLUTE_POS_OF_LOG_CDB: u64 = 96;
+    pub const ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE: u64 = 104;
+    pub const ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE: u64 = 144;
     pub const RELATIVE_POS_OF_LOG_LOG_LENGTH: u64 = 0;
     pub const RELATIVE_POS_OF_LOG_PADDING: u64 = 8;
     pub const RELATIVE_POS_OF_LOG_HEAD: u64 = 16;
     pub const LENGTH_OF_LOG_METADATA: u64 = 32;
-    pub const ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE: u64 = 120;
-    pub const ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE: u64 = 160;
+    pub const ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE: u64 = 136;
+    pub const ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE: u64 = 176;
+    pub const ABSOLUTE_POS_OF_WRITER_MARKER: u64 = 184;
+    pub const RELATIVE_POS_OF_WRITER_MARKER_PID: u64 = 0;
+    pub const RELATIVE_POS_OF_WRITER_MARKER_TIMESTAMP: u64 = 8;
+    pub const LENGTH_OF_WRITER_MARKER: u64 = 16;
     pub const ABSOLUTE_POS_OF_LOG_AREA: u64 = 256;
     pub const MIN_LOG_AREA_SIZE: u64 = 1;
 
+    // These are the values that can be stored as the append
+    // alignment. Any other value is treated as
+    // `APPEND_ALIGNMENT_NONE`. A log set up with
+    // `APPEND_ALIGNMENT_CACHE_LINE` has a log area length that's a
+    // multiple of it.
+
+    pub const APPEND_ALIGNMENT_NONE: u64 = 0;
+    pub const APPEND_ALIGNMENT_CACHE_LINE: u64 = 64;
+
     // This GUID was generated randomly and is meant to describe the
     // multilog program, even if it has future versions.
 
@@ -102,7 +135,14 @@ verus! {
     // The current version number, and the only one whose contents
     // this program can read, is the following:
 
-    pub const LOG_PROGRAM_VERSION_NUMBER: u64 = 1;
+    // Version 2 added the append alignment and explicit padding to
+    // the region metadata, which moved everything after it up to the
+    // log area. There's no migration from version 1, so `start`
+    // rejects a version-1 log with
+    // `LogErr::StartFailedDueToProgramVersionNumberUnsupported`, and
+    // it has to be set up again.
+
+    pub const LOG_PROGRAM_VERSION_NUMBER: u64 = 2;
 
     // These structs represent the different levels of metadata.
     // TODO: confirm with runtime checks that the sizes and offsets are as expected
@@ -165,8 +205,8 @@ verus! {
             lemma_auto_spec_u128_to_from_le_bytes();
         }
 
-        open spec fn spec_serialized_len() -> u64 {
-            LENGTH_OF_GLOBAL_METADATA
+        open spec fn spec_serialized_len() -> nat {
+            LENGTH_OF_GLOBAL_METADATA as nat
         }
 
         closed spec fn spec_crc(self) -> u64;
@@ -182,13 +222,16 @@ verus! {
         pub region_size: u64,
         pub log_area_len: u64,
         pub log_id: u128,
+        pub append_alignment: u64,
+        pub _padding: u64,
     }
 
     impl Serializable for RegionMetadata {
         open spec fn spec_serialize(self) -> Seq<u8>
         {
             spec_u64_to_le_bytes(self.region_size) + spec_u64_to_le_bytes(self.log_area_len) +
-                spec_u128_to_le_bytes(self.log_id)
+                spec_u128_to_le_bytes(self.log_id) + spec_u64_to_le_bytes(self.append_alignment) +
+                spec_u64_to_le_bytes(self._padding)
         }
 
         open spec fn spec_deserialize(bytes: Seq<u8>) -> Self
@@ -200,6 +243,11 @@ verus! {
                     bytes.subrange(RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA as int, RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA + 8)),
                 log_id: spec_u128_from_le_bytes(
                     bytes.subrange(RELATIVE_POS_OF_REGION_LOG_ID as int, RELATIVE_POS_OF_REGION_LOG_ID + 16)),
+                append_alignment: spec_u64_from_le_bytes(
+                    bytes.subrange(RELATIVE_POS_OF_REGION_APPEND_ALIGNMENT as int,
+                                   RELATIVE_POS_OF_REGION_APPEND_ALIGNMENT + 8)),
+                _padding: spec_u64_from_le_bytes(
+                    bytes.subrange(RELATIVE_POS_OF_REGION_PADDING as int, RELATIVE_POS_OF_REGION_PADDING + 8)),
             }
         }
 
@@ -212,6 +260,8 @@ verus! {
                 let serialized_region_size = #[trigger] spec_u64_to_le_bytes(s.region_size);
                 let serialized_len = #[trigger] spec_u64_to_le_bytes(s.log_area_len);
                 let serialized_id = #[trigger] spec_u128_to_le_bytes(s.log_id);
+                let serialized_alignment = #[trigger] spec_u64_to_le_bytes(s.append_alignment);
+                let serialized_padding = #[trigger] spec_u64_to_le_bytes(s._padding);
                 let serialized_metadata = #[trigger] s.spec_serialize();
                 &&& serialized_metadata.subrange(
                         RELATIVE_POS_OF_REGION_REGION_SIZE as int,
@@ -225,6 +275,14 @@ verus! {
                         RELATIVE_POS_OF_REGION_LOG_ID as int,
                         RELATIVE_POS_OF_REGION_LOG_ID + 16
                     ) == serialized_id
+                &&& serialized_metadata.subrange(
+                        RELATIVE_POS_OF_REGION_APPEND_ALIGNMENT as int,
+                        RELATIVE_POS_OF_REGION_APPEND_ALIGNMENT + 8
+                    ) == serialized_alignment
+                &&& serialized_metadata.subrange(
+                        RELATIVE_POS_OF_REGION_PADDING as int,
+                        RELATIVE_POS_OF_REGION_PADDING + 8
+                    ) == serialized_padding
             });
         }
 
@@ -235,9 +293,9 @@ verus! {
             lemma_auto_spec_u128_to_from_le_bytes();
         }
 
-        open spec fn spec_serialized_len() -> u64
+        open spec fn spec_serialized_len() -> nat
         {
-            LENGTH_OF_REGION_METADATA
+            LENGTH_OF_REGION_METADATA as nat
         }
 
         closed spec fn spec_crc(self) -> u64;
@@ -273,9 +331,9 @@ verus! {
             }
         }
 
-        open spec fn spec_serialized_len() -> u64
+        open spec fn spec_serialized_len() -> nat
         {
-            LENGTH_OF_LOG_METADATA
+            LENGTH_OF_LOG_METADATA as nat
         }
 
         closed spec fn spec_crc(self) -> u64;
@@ -516,7 +574,8 @@ verus! {
         let region_size = parse_u64(bytes, RELATIVE_POS_OF_REGION_REGION_SIZE as int);
         let log_id = parse_u128(bytes, RELATIVE_POS_OF_REGION_LOG_ID as int);
         let log_area_len = parse_u64(bytes, RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA as int);
-        RegionMetadata { region_size, log_id, log_area_len }
+        let append_alignment = parse_u64(bytes, RELATIVE_POS_OF_REGION_APPEND_ALIGNMENT as int);
+        RegionMetadata { region_size, log_id, log_area_len, append_alignment, _padding: 0 }
     }
 
     // This function returns the log metadata encoded as the given
@@ -813,6 +872,59 @@ verus! {
 
     /// Useful utility proofs about layout that other files use.
 
+    // This lemma establishes that the log area offsets of the
+    // `log_length` positions starting at virtual position `head` are
+    // all in bounds, and that each is `(head + i) % log_area_len`. It
+    // also establishes that consecutive positions occupy consecutive
+    // offsets, except where they wrap from the last offset to 0. Code
+    // computing physical offsets with
+    // `relative_log_pos_to_log_area_offset` can invoke this instead
+    // of reasoning about the modulo operation itself.
+    pub proof fn lemma_log_area_offsets_in_bounds(head: int, log_area_len: int, log_length: int)
+        requires
+            0 <= head,
+            0 < log_area_len,
+            0 <= log_length <= log_area_len,
+        ensures
+            0 <= head % log_area_len < log_area_len,
+            forall |i: int| 0 <= i < log_length ==> {
+                let offset = #[trigger] relative_log_pos_to_log_area_offset(i, head % log_area_len, log_area_len);
+                &&& 0 <= offset < log_area_len
+                &&& offset == (head + i) % log_area_len
+            },
+            forall |i: int, j: int|
+                #![trigger relative_log_pos_to_log_area_offset(i, head % log_area_len, log_area_len),
+                           relative_log_pos_to_log_area_offset(j, head % log_area_len, log_area_len)]
+                0 <= i && j == i + 1 && j < log_length ==> {
+                    let offset_i = relative_log_pos_to_log_area_offset(i, head % log_area_len, log_area_len);
+                    let offset_j = relative_log_pos_to_log_area_offset(j, head % log_area_len, log_area_len);
+                    offset_j == if offset_i == log_area_len - 1 { 0 } else { offset_i + 1 }
+                },
+    {
+        let head_mod = head % log_area_len;
+        lemma_mod_bound(head, log_area_len);
+
+        assert forall |i: int| 0 <= i < log_length implies {
+            let offset = #[trigger] relative_log_pos_to_log_area_offset(i, head_mod, log_area_len);
+            &&& 0 <= offset < log_area_len
+            &&& offset == (head + i) % log_area_len
+        } by {
+            // `(head + i) % log_area_len` is `(head_mod + i) % log_area_len`,
+            // and `head_mod + i` is less than twice `log_area_len`, so
+            // taking it modulo `log_area_len` subtracts `log_area_len`
+            // at most once.
+            lemma_small_mod(i as nat, log_area_len as nat);
+            lemma_add_mod_noop(head, i, log_area_len);
+            if head_mod + i < log_area_len {
+                lemma_small_mod((head_mod + i) as nat, log_area_len as nat);
+            }
+            else {
+                lemma_mod_sub_multiples_vanish(head_mod + i, log_area_len);
+                lemma_small_mod((head_mod + i - log_area_len) as nat, log_area_len as nat);
+            }
+        }
+    }
+
     // This lemma establishes that if a persistent memory region view
     // `pm_region_view` has no outstanding writes, and if its committed byte
     // sequence recovers to abstract state `state`, then any state
@@ -38,10 +38,22 @@
 //! method, and demand that the method preserve that invariant.
 
 use std::fmt::Write;
+use std::ops::{Deref, DerefMut};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::log::layout_v::{
+    ABSOLUTE_POS_OF_LOG_AREA, ABSOLUTE_POS_OF_REGION_CRC, ABSOLUTE_POS_OF_REGION_METADATA,
+    APPEND_ALIGNMENT_CACHE_LINE, APPEND_ALIGNMENT_NONE, LENGTH_OF_REGION_METADATA, MIN_LOG_AREA_SIZE,
+    RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA, RELATIVE_POS_OF_REGION_REGION_SIZE,
+};
 use crate::log::logimpl_v::UntrustedLogImpl;
 use crate::log::logspec_t::AbstractLogState;
+use crate::log::observer_t::RecoveryObserverRef;
+#[cfg(target_os = "linux")]
+use crate::pmem::linux_pmemfile_t::truncate_region_file;
 use crate::pmem::pmemspec_t::*;
+#[cfg(target_os = "windows")]
+use crate::pmem::windows_pmemfile_t::truncate_region_file;
 use crate::pmem::wrpm_t::*;
 use builtin::*;
 use builtin_macros::*;
@@ -160,19 +172,83 @@ verus! {
     #[derive(Debug)]
     pub enum LogErr {
         InsufficientSpaceForSetup { required_space: u64 },
+        RegionTooSmall { min_log_area_size: u64, log_area_size: u64 },
+        LogAreaNotAligned { log_area_size: u64, alignment: u64 },
         StartFailedDueToLogIDMismatch { log_id_expected: u128, log_id_read: u128 },
         StartFailedDueToRegionSizeMismatch { region_size_expected: u64, region_size_read: u64 },
         StartFailedDueToProgramVersionNumberUnsupported { version_number: u64, max_supported: u64 },
         StartFailedDueToInvalidMemoryContents,
         CRCMismatch,
         InsufficientSpaceForAppend { available_space: u64 },
-        CantReadBeforeHead { head: u128 },
+        LogPositionOverflow,
+        PositionTrimmed { requested: u128, current_head: u128 },
         CantReadPastTail { tail: u128 },
         CantAdvanceHeadPositionBeforeHead { head: u128 },
         CantAdvanceHeadPositionBeyondTail { tail: u128 },
+        CantShrinkBelowLiveData { head: u128, tail: u128 },
+        FlushFailed,
+        Poisoned,
+        Busy,
+        WriteVerificationFailed,
         PmemErr { err: PmemError } // janky workaround so that callers can handle PmemErrors as LogErrors
     }
 
+    // This enumeration says where tentative appends made with
+    // `tentatively_append_aligned` start. With `CacheLine`, each one
+    // is preceded by enough zero padding to start on a 64-byte
+    // boundary, so that on media like Optane DAX no append straddles
+    // a cache line it shares with the previous one. This trades a
+    // little space for write throughput. A log can only be set up
+    // with `CacheLine` if its log area length is a multiple of 64, so
+    // that aligned log positions are also aligned in the region.
+    //
+    // The padding is ordinary log data: it's part of the abstract
+    // log, counts against its capacity, and is returned by reads that
+    // span it. Readers that read each record at the position its
+    // append returned skip it, but readers that scan the log
+    // sequentially see the zeros and must be able to skip them.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum LogAppendAlignment {
+        None,
+        CacheLine,
+    }
+
+    // This enumeration says what `commit_with_verification` does
+    // after a commit succeeds. With `ReadAfterWrite`, it reads back
+    // the CDB and the log metadata it selects and checks them against
+    // their CRC and the expected head and length. This catches media
+    // that acknowledge flushes without persisting the data, at the
+    // cost of a read of the metadata per commit.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum CommitVerification {
+        None,
+        ReadAfterWrite,
+    }
+
+    impl LogAppendAlignment {
+        // This function returns how the alignment is stored in the
+        // region.
+        pub exec fn to_stored_value(self) -> (result: u64)
+            ensures
+                match self {
+                    LogAppendAlignment::None => result == APPEND_ALIGNMENT_NONE,
+                    LogAppendAlignment::CacheLine => result == APPEND_ALIGNMENT_CACHE_LINE,
+                },
+        {
+            match self {
+                LogAppendAlignment::None => APPEND_ALIGNMENT_NONE,
+                LogAppendAlignment::CacheLine => APPEND_ALIGNMENT_CACHE_LINE,
+            }
+        }
+
+        // This function returns the alignment stored in the region as
+        // `value`. Unrecognized values mean no alignment.
+        pub exec fn from_stored_value(value: u64) -> (result: Self)
+        {
+            if value == APPEND_ALIGNMENT_CACHE_LINE { LogAppendAlignment::CacheLine } else { LogAppendAlignment::None }
+        }
+    }
+
     // This executable method can be called to compute a random GUID.
     // It uses the external `rand` crate.
     #[verifier::external_body]
@@ -195,11 +271,26 @@ verus! {
     /// tracked `TrustedPermission`. So we can pass `wrpm_region` to an
     /// untrusted method, along with a restricting
     /// `TrustedPermission`, to limit what it's allowed to do.
+    ///
+    /// The fields are private, and `start` is the only way to obtain
+    /// a `LogImpl`. (`setup` only writes the region and returns its
+    /// capacity and ID.) So every `LogImpl` holds log information that
+    /// `start` has validated against the region, and operations on a
+    /// log that was never started can't be written, let alone
+    /// return an error at runtime.
+    ///
+    /// The `poisoned` field is set once an operation fails with
+    /// `LogErr::FlushFailed`. See `poisoned`.
+    ///
+    /// Dropping a `LogImpl` discards its tentative appends, as a crash
+    /// would. A caller that wants to catch a forgotten `commit` can
+    /// check `get_pending_length` before dropping the log.
 
     pub struct LogImpl<PMRegion: PersistentMemoryRegion> {
         untrusted_log_impl: UntrustedLogImpl,
         log_id: Ghost<u128>,
-        wrpm_region: WriteRestrictedPersistentMemoryRegion<TrustedPermission, PMRegion>
+        wrpm_region: WriteRestrictedPersistentMemoryRegion<TrustedPermission, PMRegion>,
+        poisoned: bool,
     }
 
     impl <PMRegion: PersistentMemoryRegion> LogImpl<PMRegion> {
@@ -227,12 +318,51 @@ verus! {
         // One element of `valid` is that the untrusted `inv` function
         // holds.
         //
-        // The other element of `valid` is that the persistent memory,
+        // Another element of `valid` is that the persistent memory,
         // if it crashes and recovers, must represent the current
         // abstract state with pending tentative appends dropped.
+        //
+        // The last element of `valid` is that `self` isn't poisoned.
         pub closed spec fn valid(self) -> bool {
             &&& self.untrusted_log_impl.inv(&self.wrpm_region, self.log_id@)
             &&& can_only_crash_as_state(self.wrpm_region@, self.log_id@, self@.drop_pending_appends())
+            &&& !self.poisoned
+        }
+
+        // A log is poisoned once an operation on it fails with
+        // `LogErr::FlushFailed`. Its volatile state may then disagree
+        // with persistent memory, e.g., about which copy of the log
+        // metadata is active, so writing through it could break the
+        // crash guarantee. A poisoned log isn't valid, and every
+        // later operation on it that returns a `Result` fails with
+        // `LogErr::Poisoned` without accessing persistent memory.
+        // The only way to use the log again is to drop `self` and
+        // restart the log with `start`.
+        pub closed spec fn poisoned(self) -> bool {
+            self.poisoned
+        }
+
+        // This predicate describes what we guarantee about crashes
+        // after an operation fails with `LogErr::FlushFailed`. In
+        // that case, `self` is poisoned, so the caller has to drop
+        // it and restart the log with `start`. But the persistent
+        // memory, if it crashes and recovers, must represent either
+        // `s1` or `s2`.
+        pub closed spec fn can_only_crash_as_one_of(self, s1: AbstractLogState, s2: AbstractLogState) -> bool {
+            forall |s| #[trigger] self.wrpm_region@.can_crash_as(s) ==> {
+                ||| UntrustedLogImpl::recover(s, self.log_id@) == Some(s1)
+                ||| UntrustedLogImpl::recover(s, self.log_id@) == Some(s2)
+            }
+        }
+
+        // The `is_poisoned` method returns whether `self` is
+        // poisoned, i.e., whether an operation on it has failed with
+        // `LogErr::FlushFailed`. See `poisoned`.
+        pub exec fn is_poisoned(&self) -> (result: bool)
+            ensures
+                result == self.poisoned(),
+        {
+            self.poisoned
         }
 
         proof fn lemma_valid_implies_wrpm_inv(self)
@@ -246,7 +376,8 @@ verus! {
 
         proof fn lemma_untrusted_log_inv_implies_valid(self)
             requires
-                self.untrusted_log_impl.inv(&self.wrpm_region, self.log_id@)
+                self.untrusted_log_impl.inv(&self.wrpm_region, self.log_id@),
+                !self.poisoned,
             ensures
                 self.valid()
         {
@@ -263,10 +394,10 @@ verus! {
                 old(pm_region).inv(),
             ensures
                 pm_region.inv(),
-                pm_region@.no_outstanding_writes(),
                 match result {
                     Ok((log_capacity, log_id)) => {
                         let state = AbstractLogState::initialize(log_capacity as int);
+                        &&& pm_region@.no_outstanding_writes()
                         &&& log_capacity <= pm_region@.len()
                         &&& pm_region@.len() == old(pm_region)@.len()
                         &&& can_only_crash_as_state(pm_region@, log_id, state)
@@ -277,17 +408,160 @@ verus! {
                         &&& state == state.drop_pending_appends()
                     },
                     Err(LogErr::InsufficientSpaceForSetup { required_space }) => {
+                        &&& pm_region@.no_outstanding_writes()
                         &&& pm_region@ == old(pm_region)@.flush()
                         &&& pm_region@.len() < required_space
                     },
+                    Err(LogErr::FlushFailed) => true,
+                    _ => false
+                }
+        {
+            Self::setup_with_min_log_area_size(pm_region, MIN_LOG_AREA_SIZE)
+        }
+
+        // The `setup_with_min_log_area_size` method is like `setup`,
+        // except that it fails with `LogErr::RegionTooSmall` if the
+        // log area would be smaller than `min_log_area_size` bytes.
+        // This guards against accidentally creating a degenerate log
+        // on a region that's barely bigger than the metadata.
+        pub exec fn setup_with_min_log_area_size(
+            pm_region: &mut PMRegion,
+            min_log_area_size: u64,
+        ) -> (result: Result<(u64, u128), LogErr>)
+            requires
+                old(pm_region).inv(),
+            ensures
+                pm_region.inv(),
+                match result {
+                    Ok((log_capacity, log_id)) => {
+                        let state = AbstractLogState::initialize(log_capacity as int);
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& log_capacity <= pm_region@.len()
+                        &&& pm_region@.len() == old(pm_region)@.len()
+                        &&& can_only_crash_as_state(pm_region@, log_id, state)
+                        &&& UntrustedLogImpl::recover(pm_region@.committed(), log_id) == Some(state)
+                        // Required by the `start` function's precondition. Putting this in the
+                        // postcond of `setup` ensures that the trusted caller doesn't have to prove it
+                        &&& UntrustedLogImpl::recover(pm_region@.flush().committed(), log_id) == Some(state)
+                        &&& state == state.drop_pending_appends()
+                    },
+                    Err(LogErr::InsufficientSpaceForSetup { required_space }) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                        &&& pm_region@.len() < required_space
+                    },
+                    Err(LogErr::RegionTooSmall { min_log_area_size: min, log_area_size }) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                        &&& pm_region@.len() >= ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE
+                        &&& min == min_log_area_size
+                        &&& log_area_size == pm_region@.len() - ABSOLUTE_POS_OF_LOG_AREA
+                        &&& log_area_size < min_log_area_size
+                    },
+                    Err(LogErr::FlushFailed) => true,
+                    _ => false
+                }
+        {
+            let log_id = generate_fresh_log_id();
+            let capacities = UntrustedLogImpl::setup(pm_region, log_id, min_log_area_size, APPEND_ALIGNMENT_NONE)?;
+            Ok((capacities, log_id))
+        }
+
+        // The `setup_with_append_alignment` method is like `setup`,
+        // except that it records `append_alignment` in the region's
+        // metadata, to be used by `tentatively_append_aligned` on
+        // every later `start` of the log. With
+        // `LogAppendAlignment::CacheLine`, it fails with
+        // `LogErr::LogAreaNotAligned` unless the log area length is a
+        // multiple of 64.
+        pub exec fn setup_with_append_alignment(
+            pm_region: &mut PMRegion,
+            append_alignment: LogAppendAlignment,
+        ) -> (result: Result<(u64, u128), LogErr>)
+            requires
+                old(pm_region).inv(),
+            ensures
+                pm_region.inv(),
+                match result {
+                    Ok((log_capacity, log_id)) => {
+                        let state = AbstractLogState::initialize(log_capacity as int);
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& log_capacity <= pm_region@.len()
+                        &&& pm_region@.len() == old(pm_region)@.len()
+                        &&& can_only_crash_as_state(pm_region@, log_id, state)
+                        &&& UntrustedLogImpl::recover(pm_region@.committed(), log_id) == Some(state)
+                        // Required by the `start` function's precondition. Putting this in the
+                        // postcond of `setup` ensures that the trusted caller doesn't have to prove it
+                        &&& UntrustedLogImpl::recover(pm_region@.flush().committed(), log_id) == Some(state)
+                        &&& state == state.drop_pending_appends()
+                    },
+                    Err(LogErr::InsufficientSpaceForSetup { required_space }) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                        &&& pm_region@.len() < required_space
+                    },
+                    Err(LogErr::LogAreaNotAligned { log_area_size, alignment }) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                        &&& append_alignment == LogAppendAlignment::CacheLine
+                        &&& alignment == APPEND_ALIGNMENT_CACHE_LINE
+                        &&& log_area_size == pm_region@.len() - ABSOLUTE_POS_OF_LOG_AREA
+                        &&& log_area_size % alignment != 0
+                    },
+                    Err(LogErr::FlushFailed) => true,
                     _ => false
                 }
         {
             let log_id = generate_fresh_log_id();
-            let capacities = UntrustedLogImpl::setup(pm_region, log_id)?;
+            let capacities = UntrustedLogImpl::setup(pm_region, log_id, MIN_LOG_AREA_SIZE,
+                                                     append_alignment.to_stored_value())?;
             Ok((capacities, log_id))
         }
 
+        // The `setup_if_needed` method is an idempotent `setup`, for
+        // deployment scripts that may run more than once. If
+        // `pm_region` already holds a log with ID `log_id`, it leaves
+        // the region alone, so the log's abstract state is unchanged.
+        // Otherwise, it sets up an empty log with ID `log_id`. It
+        // returns the log's capacity and whether it set up a fresh
+        // log. It never overwrites a region it can't read cleanly:
+        // see `README.md` for which failures it reports instead.
+        pub exec fn setup_if_needed(
+            pm_region: &mut PMRegion,
+            log_id: u128,
+            region_size: u64,
+        ) -> (result: Result<(u64, bool), LogErr>)
+            requires
+                old(pm_region).inv(),
+            ensures
+                pm_region.inv(),
+                match result {
+                    Ok((log_capacity, true)) => {
+                        let state = AbstractLogState::initialize(log_capacity as int);
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& log_capacity <= pm_region@.len()
+                        &&& pm_region@.len() == old(pm_region)@.len()
+                        &&& can_only_crash_as_state(pm_region@, log_id, state)
+                        &&& UntrustedLogImpl::recover(pm_region@.committed(), log_id) == Some(state)
+                        &&& UntrustedLogImpl::recover(pm_region@.flush().committed(), log_id) == Some(state)
+                        &&& state == state.drop_pending_appends()
+                    },
+                    Ok((_, false)) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                        &&& UntrustedLogImpl::recover(pm_region@.committed(), log_id) ==
+                               UntrustedLogImpl::recover(old(pm_region)@.flush().committed(), log_id)
+                    },
+                    Err(LogErr::FlushFailed) => true,
+                    Err(_) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                    },
+                }
+        {
+            UntrustedLogImpl::setup_if_needed(pm_region, log_id, region_size)
+        }
+
         // The `start` method creates an `UntrustedLogImpl` out of a
         // persistent memory region. It's assumed that the region was
         // initialized with `setup` and then only log operations were
@@ -306,6 +580,36 @@ verus! {
                                                                                log_id)
                     },
                     Err(LogErr::CRCMismatch) => !pm_region.constants().impervious_to_corruption,
+                    Err(LogErr::FlushFailed) => true,
+                    _ => false
+                }
+        {
+            Self::start_with_observer(pm_region, log_id, RecoveryObserverRef::none())
+        }
+
+        // The `start_with_observer` method is like `start`, except
+        // that it reports each decision made during recovery (e.g.,
+        // which metadata block failed its CRC check) to `observer`.
+        // The observer can't affect the outcome, so this has the
+        // same specification as `start`.
+        pub exec fn start_with_observer(
+            pm_region: PMRegion,
+            log_id: u128,
+            observer: RecoveryObserverRef,
+        ) -> (result: Result<LogImpl<PMRegion>, LogErr>)
+            requires
+                pm_region.inv(),
+                UntrustedLogImpl::recover(pm_region@.flush().committed(), log_id).is_Some(),
+            ensures
+                match result {
+                    Ok(trusted_log_impl) => {
+                        &&& trusted_log_impl.valid()
+                        &&& trusted_log_impl.constants() == pm_region.constants()
+                        &&& Some(trusted_log_impl@) == UntrustedLogImpl::recover(pm_region@.flush().committed(),
+                                                                               log_id)
+                    },
+                    Err(LogErr::CRCMismatch) => !pm_region.constants().impervious_to_corruption,
+                    Err(LogErr::FlushFailed) => true,
                     _ => false
                 }
         {
@@ -320,12 +624,13 @@ verus! {
             let mut wrpm_region = WriteRestrictedPersistentMemoryRegion::new(pm_region);
             let tracked perm = TrustedPermission::new_one_possibility(log_id, state);
             let untrusted_log_impl =
-                UntrustedLogImpl::start(&mut wrpm_region, log_id, Tracked(&perm), Ghost(state))?;
+                UntrustedLogImpl::start(&mut wrpm_region, log_id, Tracked(&perm), Ghost(state), &observer)?;
             Ok(
                 LogImpl {
                     untrusted_log_impl,
                     log_id:  Ghost(log_id),
-                    wrpm_region
+                    wrpm_region,
+                    poisoned: false,
                 },
             )
         }
@@ -346,14 +651,16 @@ verus! {
                         let state = old(self)@;
                         &&& offset == state.head + state.log.len() + state.pending.len()
                         &&& self@ == old(self)@.tentatively_append(bytes_to_append@)
+                        &&& old(self)@.try_tentatively_append(bytes_to_append@) == Some(self@)
                     },
                     Err(LogErr::InsufficientSpaceForAppend { available_space }) => {
                         &&& self@ == old(self)@
                         &&& available_space < bytes_to_append@.len()
-                        &&& {
-                               ||| available_space == self@.capacity - self@.log.len() - self@.pending.len()
-                               ||| available_space == u128::MAX - self@.head - self@.log.len() - self@.pending.len()
-                           }
+                        &&& available_space == self@.capacity - self@.log.len() - self@.pending.len()
+                    },
+                    Err(LogErr::LogPositionOverflow) => {
+                        &&& self@ == old(self)@
+                        &&& self@.head + self@.log.len() + self@.pending.len() + bytes_to_append@.len() > u128::MAX
                     },
                     _ => false
                 }
@@ -364,25 +671,138 @@ verus! {
             // the view of the persistent state is either the current
             // state or the current state with `bytes_to_append`
             // appended.
+            if self.poisoned {
+                return Err(LogErr::Poisoned);
+            }
             let tracked perm = TrustedPermission::new_one_possibility(self.log_id@, self@.drop_pending_appends());
             self.untrusted_log_impl.tentatively_append(&mut self.wrpm_region, bytes_to_append,
                                                        self.log_id, Tracked(&perm))
         }
 
+        // The `append_alignment` method returns the append alignment
+        // the log was set up with.
+        pub exec fn append_alignment(&self) -> (result: LogAppendAlignment)
+            requires
+                self.valid()
+        {
+            let value = self.untrusted_log_impl.read_append_alignment(&self.wrpm_region, self.log_id);
+            LogAppendAlignment::from_stored_value(value)
+        }
+
+        // The `tentatively_append_aligned` method is like
+        // `tentatively_append`, except that if the log was set up
+        // with `LogAppendAlignment::CacheLine`, it first tentatively
+        // appends enough zeros that `bytes_to_append` starts on a
+        // 64-byte boundary. It returns where `bytes_to_append`
+        // starts, after the padding. If there isn't room for both
+        // the padding and `bytes_to_append`, it appends neither. An
+        // empty `bytes_to_append` is never padded, since there's no
+        // record to align.
+        //
+        // The padding is part of the log, so a read that spans it
+        // returns its zeros. See `LogAppendAlignment`.
+        pub exec fn tentatively_append_aligned(&mut self, bytes_to_append: &[u8]) -> (result: Result<u128, LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                self.valid(),
+                self.constants() == old(self).constants(),
+                match result {
+                    Ok(offset) => {
+                        let state = old(self)@;
+                        let pending_tail = state.head + state.log.len() + state.pending.len();
+                        let padding = Seq::new((offset - pending_tail) as nat, |i: int| 0u8);
+                        &&& pending_tail <= offset
+                        &&& self@ == state.tentatively_append(padding).tentatively_append(bytes_to_append@)
+                    },
+                    Err(LogErr::InsufficientSpaceForAppend { available_space }) => {
+                        &&& self@ == old(self)@
+                        &&& available_space < bytes_to_append@.len()
+                    },
+                    Err(LogErr::LogPositionOverflow) => self@ == old(self)@,
+                    _ => false
+                }
+        {
+            let ghost state = self@;
+            let pending_tail = self.untrusted_log_impl.get_pending_tail(&self.wrpm_region, self.log_id);
+            let alignment = APPEND_ALIGNMENT_CACHE_LINE as u128;
+            let padding_len: u64 =
+                if bytes_to_append.len() == 0 {
+                    0
+                }
+                else {
+                    match self.append_alignment() {
+                        LogAppendAlignment::None => 0,
+                        LogAppendAlignment::CacheLine => ((alignment - pending_tail % alignment) % alignment) as u64,
+                    }
+                };
+
+            if padding_len > 0 {
+                // Make sure both the padding and the bytes fit before
+                // appending either, so that a failure leaves the log
+                // unchanged.
+
+                let (head, _tail, capacity) = match self.get_head_tail_and_capacity() {
+                    Ok(t) => t,
+                    Err(e) => { return Err(e); },
+                };
+                let available_space: u64 = capacity - (pending_tail - head) as u64;
+                if padding_len > available_space {
+                    return Err(LogErr::InsufficientSpaceForAppend{ available_space: 0 });
+                }
+                if bytes_to_append.len() as u64 > available_space - padding_len {
+                    return Err(LogErr::InsufficientSpaceForAppend{ available_space: available_space - padding_len });
+                }
+                if bytes_to_append.len() as u128 > u128::MAX - pending_tail - padding_len as u128 {
+                    return Err(LogErr::LogPositionOverflow);
+                }
+
+                let mut padding: Vec<u8> = Vec::new();
+                while (padding.len() as u64) < padding_len
+                    invariant
+                        padding.len() <= padding_len,
+                        forall |j: int| 0 <= j < padding@.len() ==> #[trigger] padding@[j] == 0,
+                {
+                    padding.push(0);
+                }
+                assert(padding@ =~= Seq::new(padding_len as nat, |i: int| 0u8));
+                match self.tentatively_append(padding.as_slice()) {
+                    Ok(_) => {},
+                    Err(e) => { return Err(e); },
+                }
+            }
+            else {
+                assert(state.tentatively_append(Seq::new(0nat, |i: int| 0u8)) =~= state);
+            }
+
+            self.tentatively_append(bytes_to_append)
+        }
+
         // The `commit` method atomically commits all tentative
         // appends that have been done to `self` since the last
         // commit. The commit is atomic in that even if there's a
         // crash in the middle, the recovered-to state either reflects
         // all those tentative appends or none of them. See `README.md`
         // for more documentation and examples of use.
+        //
+        // If flushing fails, this returns `Err(LogErr::FlushFailed)`.
+        // In that case, the commit may or may not have become durable,
+        // and `self` is poisoned. See `poisoned`.
         pub exec fn commit(&mut self) -> (result: Result<(), LogErr>)
             requires
                 old(self).valid(),
             ensures
-                self.valid(),
                 self.constants() == old(self).constants(),
                 match result {
-                    Ok(()) => self@ == old(self)@.commit(),
+                    Ok(()) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@.commit()
+                    },
+                    Err(LogErr::FlushFailed) => {
+                        &&& self.poisoned()
+                        &&& self.can_only_crash_as_one_of(old(self)@.drop_pending_appends(),
+                                                         old(self)@.commit().drop_pending_appends())
+                    },
                     _ => false
                 }
         {
@@ -392,9 +812,105 @@ verus! {
             // the view of the persistent state is either the current
             // state or the current state with all uncommitted appends
             // committed.
+            if self.poisoned {
+                return Err(LogErr::Poisoned);
+            }
             let tracked perm = TrustedPermission::new_two_possibilities(self.log_id@, self@.drop_pending_appends(),
                                                                         self@.commit().drop_pending_appends());
-            self.untrusted_log_impl.commit(&mut self.wrpm_region, self.log_id, Tracked(&perm))
+            let result = self.untrusted_log_impl.commit(&mut self.wrpm_region, self.log_id, Tracked(&perm));
+            match result {
+                Err(LogErr::FlushFailed) => { self.poisoned = true; },
+                _ => {},
+            }
+            result
+        }
+
+        // The `commit_with_verification` method is like `commit`, but
+        // with `CommitVerification::ReadAfterWrite` it also reads back
+        // the committed metadata to check that it landed. If the
+        // check fails, it returns `Err(LogErr::WriteVerificationFailed)`.
+        // This can only happen if the memory isn't impervious to
+        // corruption. As far as the proof is concerned, the commit
+        // still happened, so the caller may want to drop `self` and
+        // restart the log with `start` to see what actually persisted.
+        pub exec fn commit_with_verification(&mut self, verification: CommitVerification)
+                                             -> (result: Result<(), LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                self.constants() == old(self).constants(),
+                match result {
+                    Ok(()) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@.commit()
+                    },
+                    Err(LogErr::WriteVerificationFailed) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@.commit()
+                        &&& !self.constants().impervious_to_corruption
+                    },
+                    Err(LogErr::FlushFailed) => {
+                        &&& self.poisoned()
+                        &&& self.can_only_crash_as_one_of(old(self)@.drop_pending_appends(),
+                                                         old(self)@.commit().drop_pending_appends())
+                    },
+                    _ => false
+                }
+        {
+            self.commit()?;
+            match verification {
+                CommitVerification::None => {},
+                CommitVerification::ReadAfterWrite =>
+                    self.untrusted_log_impl.verify_committed_metadata(&self.wrpm_region, Ghost(self.log_id@))?,
+            }
+            Ok(())
+        }
+
+        // The `commit_append` method tentatively appends
+        // `bytes_to_append` and then commits it, along with any
+        // earlier tentative appends, as one operation. It writes the
+        // bytes to the log area before the commit flushes and flips
+        // the CDB, so the caller can't get the order of those steps
+        // wrong. A crash in the middle recovers to either the state
+        // before the call or the state with everything committed.
+        //
+        // It returns the position where `bytes_to_append` starts. If
+        // there isn't room for them, nothing is appended or committed.
+        pub exec fn commit_append(&mut self, bytes_to_append: &[u8]) -> (result: Result<u128, LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                self.constants() == old(self).constants(),
+                match result {
+                    Ok(offset) => {
+                        let state = old(self)@;
+                        &&& self.valid()
+                        &&& offset == state.head + state.log.len() + state.pending.len()
+                        &&& self@ == old(self)@.tentatively_append(bytes_to_append@).commit()
+                    },
+                    Err(LogErr::InsufficientSpaceForAppend { available_space }) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@
+                        &&& available_space < bytes_to_append@.len()
+                    },
+                    Err(LogErr::LogPositionOverflow) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@
+                    },
+                    Err(LogErr::FlushFailed) => {
+                        &&& self.poisoned()
+                        &&& self.can_only_crash_as_one_of(
+                                old(self)@.drop_pending_appends(),
+                                old(self)@.tentatively_append(bytes_to_append@).commit().drop_pending_appends()
+                            )
+                    },
+                    _ => false
+                }
+        {
+            let offset = self.tentatively_append(bytes_to_append)?;
+            assert(self@.drop_pending_appends() =~= old(self)@.drop_pending_appends());
+            self.commit()?;
+            Ok(offset)
         }
 
         // The `advance_head` method advances the head of the log to
@@ -403,28 +919,38 @@ verus! {
         // However, `advance_head` doesn't commit tentative appends;
         // to do that, you need a separate call to `commit`. See
         // `README.md` for more documentation and examples of use.
+        //
+        // As with `commit`, if flushing fails, this returns
+        // `Err(LogErr::FlushFailed)` and `self` is poisoned.
         pub exec fn advance_head(&mut self, new_head: u128) -> (result: Result<(), LogErr>)
             requires
                 old(self).valid(),
             ensures
-                self.valid(),
                 self.constants() == old(self).constants(),
                 match result {
                     Ok(()) => {
                         let state = old(self)@;
+                        &&& self.valid()
                         &&& state.head <= new_head <= state.head + state.log.len()
                         &&& self@ == old(self)@.advance_head(new_head as int)
                     },
                     Err(LogErr::CantAdvanceHeadPositionBeforeHead { head }) => {
+                        &&& self.valid()
                         &&& self@ == old(self)@
                         &&& head == self@.head
                         &&& new_head < head
                     },
                     Err(LogErr::CantAdvanceHeadPositionBeyondTail { tail }) => {
+                        &&& self.valid()
                         &&& self@ == old(self)@
                         &&& tail == self@.head + self@.log.len()
                         &&& new_head > tail
                     },
+                    Err(LogErr::FlushFailed) => {
+                        &&& self.poisoned()
+                        &&& self.can_only_crash_as_one_of(old(self)@.drop_pending_appends(),
+                                                         old(self)@.advance_head(new_head as int).drop_pending_appends())
+                    },
                     _ => false,
                 }
         {
@@ -433,19 +959,30 @@ verus! {
             // such that, if a crash happens in the middle of a write,
             // the view of the persistent state is either the current
             // state or the current state with the head advanced.
+            if self.poisoned {
+                return Err(LogErr::Poisoned);
+            }
             let tracked perm = TrustedPermission::new_two_possibilities(
                 self.log_id@,
                 self@.drop_pending_appends(),
                 self@.advance_head(new_head as int).drop_pending_appends()
             );
-            self.untrusted_log_impl.advance_head(&mut self.wrpm_region, new_head,
-                                                 self.log_id, Tracked(&perm))
+            let result = self.untrusted_log_impl.advance_head(&mut self.wrpm_region, new_head,
+                                                              self.log_id, Tracked(&perm));
+            match result {
+                Err(LogErr::FlushFailed) => { self.poisoned = true; },
+                _ => {},
+            }
+            result
         }
 
         // The `read` method reads `len` bytes from the log starting
         // at virtual position `pos`. It isn't allowed to read earlier
-        // than the head or past the committed tail. See `README.md` for
-        // more documentation and examples of use.
+        // than the head or past the committed tail. Reading earlier
+        // than the head fails with `LogErr::PositionTrimmed`, which
+        // tells a reader holding an old position that the bytes there
+        // have been trimmed away. See `README.md` for more
+        // documentation and examples of use.
         pub exec fn read(&self, pos: u128, len: u64) -> (result: Result<Vec<u8>, LogErr>)
             requires
                 self.valid(),
@@ -463,9 +1000,10 @@ verus! {
                             &&& read_correct_modulo_corruption(bytes@, true_bytes,
                                                              self.constants().impervious_to_corruption)
                         },
-                        Err(LogErr::CantReadBeforeHead{ head: head_pos }) => {
+                        Err(LogErr::PositionTrimmed{ requested, current_head }) => {
                             &&& pos < head
-                            &&& head_pos == head
+                            &&& requested == pos
+                            &&& current_head == head
                         },
                         Err(LogErr::CantReadPastTail{ tail }) => {
                             &&& pos + len > tail
@@ -475,9 +1013,56 @@ verus! {
                     }
                 })
         {
+            if self.poisoned {
+                return Err(LogErr::Poisoned);
+            }
             self.untrusted_log_impl.read(&self.wrpm_region, pos, len, self.log_id)
         }
 
+        // The `read_range` method reads the bytes at virtual
+        // positions `from` through `to`, including `from` but not
+        // `to`. It's like `read`, but for callers that track the
+        // start and end positions of what they want to read rather
+        // than a start and a length. It fails the same ways `read`
+        // does: with `LogErr::PositionTrimmed` if `from` is before
+        // the head, and with `LogErr::CantReadPastTail` if `to` is
+        // past the committed tail.
+        pub exec fn read_range(&self, from: u128, to: u128) -> (result: Result<Vec<u8>, LogErr>)
+            requires
+                self.valid(),
+                from <= to,
+                to - from <= u64::MAX,
+            ensures
+                ({
+                    let state = self@;
+                    let head = state.head;
+                    let log = state.log;
+                    match result {
+                        Ok(bytes) => {
+                            let true_bytes = self@.read_range(from as int, to as int);
+                            &&& from >= head
+                            &&& to <= head + log.len()
+                            &&& read_correct_modulo_corruption(bytes@, true_bytes,
+                                                             self.constants().impervious_to_corruption)
+                        },
+                        Err(LogErr::PositionTrimmed{ requested, current_head }) => {
+                            &&& from < head
+                            &&& requested == from
+                            &&& current_head == head
+                        },
+                        Err(LogErr::CantReadPastTail{ tail }) => {
+                            &&& to > tail
+                            &&& tail == head + log.len()
+                        },
+                        _ => false
+                    }
+                })
+        {
+            let result = self.read(from, (to - from) as u64);
+            assert(self@.read(from as int, (to - from) as int) =~= self@.read_range(from as int, to as int));
+            result
+        }
+
         // The `get_head_tail_and_capacity` method returns three
         // pieces of metadata about the log: the virtual head
         // position, the virtual tail position, and the capacity. The
@@ -498,8 +1083,293 @@ verus! {
                     _ => false
                 }
         {
+            if self.poisoned {
+                return Err(LogErr::Poisoned);
+            }
             self.untrusted_log_impl.get_head_tail_and_capacity(&self.wrpm_region, self.log_id)
         }
+
+        // The `max_appendable_len` method returns the largest number
+        // of bytes a single call to `tentatively_append` can
+        // currently append, taking earlier pending appends into
+        // account. This is useful for sizing a batch of records
+        // before appending it.
+        //
+        // This is the log's total free space, not its contiguous
+        // free space. An append that runs past the end of the log
+        // area wraps around to its beginning, so it doesn't have to
+        // fit before the end. In the unlikely event that appending
+        // the free space would make the tail exceed `u128::MAX`, the
+        // result is smaller.
+        pub exec fn max_appendable_len(&self) -> (result: u64)
+            requires
+                self.valid()
+            ensures
+                ({
+                    let log = self@;
+                    &&& result <= log.capacity - log.log.len() - log.pending.len()
+                    &&& result <= u128::MAX - log.head - log.log.len() - log.pending.len()
+                    &&& {
+                           ||| result == log.capacity - log.log.len() - log.pending.len()
+                           ||| result == u128::MAX - log.head - log.log.len() - log.pending.len()
+                       }
+                })
+        {
+            self.untrusted_log_impl.max_appendable_len(&self.wrpm_region, self.log_id)
+        }
+
+        // The `get_head` method returns the virtual head position of
+        // the log. Pending appends don't affect the head, so this is
+        // also the head of the state the log would recover to if it
+        // crashed now. It's cheaper than `get_head_tail_and_capacity`
+        // when only the head is needed, and it can't fail.
+        pub exec fn get_head(&self) -> (result: u128)
+            requires
+                self.valid()
+            ensures
+                result == self@.head,
+                result == self@.drop_pending_appends().head,
+        {
+            self.untrusted_log_impl.get_head(&self.wrpm_region, self.log_id)
+        }
+
+        // The `get_pending_length` method returns the number of bytes
+        // tentatively appended since the last commit, i.e., the number
+        // a crash now would discard. A writer can use it to decide
+        // when to commit, e.g., once enough bytes are pending. It
+        // doesn't access persistent memory and can't fail.
+        pub exec fn get_pending_length(&self) -> (result: u64)
+            requires
+                self.valid()
+            ensures
+                result == self@.pending.len(),
+        {
+            self.untrusted_log_impl.get_pending_length(&self.wrpm_region, self.log_id)
+        }
+
+        // The `position_is_live` method returns whether virtual
+        // position `pos` is in `[head, tail)`, i.e., whether the log
+        // holds a committed byte there. A reader holding a position
+        // from a while ago can use it to check whether the head has
+        // been advanced past that position before reading.
+        pub exec fn position_is_live(&self, pos: u128) -> (result: bool)
+            requires
+                self.valid()
+            ensures
+                result == (self@.head <= pos < self@.head + self@.log.len()),
+        {
+            self.untrusted_log_impl.position_is_live(&self.wrpm_region, pos, self.log_id)
+        }
+
+        // The `read_writer_marker` method returns the process ID and
+        // timestamp stored in the writer marker. It's only used by
+        // `acquire_writer` and `WriterGuard`.
+        exec fn read_writer_marker(&self) -> (result: (u64, u64))
+            requires
+                self.valid()
+        {
+            self.untrusted_log_impl.read_writer_marker(&self.wrpm_region, self.log_id)
+        }
+
+        // The `write_writer_marker` method durably stores `pid` and
+        // `timestamp` in the writer marker. It doesn't change the
+        // abstract state. It's only used by `acquire_writer` and
+        // `WriterGuard`.
+        exec fn write_writer_marker(&mut self, pid: u64, timestamp: u64) -> (result: Result<(), LogErr>)
+            requires
+                old(self).valid(),
+            ensures
+                self.constants() == old(self).constants(),
+                self@ == old(self)@,
+                match result {
+                    Ok(()) => self.valid(),
+                    Err(LogErr::FlushFailed) => {
+                        &&& self.poisoned()
+                        &&& self.can_only_crash_as_one_of(old(self)@.drop_pending_appends(),
+                                                         old(self)@.drop_pending_appends())
+                    },
+                    _ => false
+                }
+        {
+            // For crash safety, we must restrict the untrusted code's
+            // writes to persistent memory. We must only let it write
+            // such that, if a crash happens in the middle of a write,
+            // the view of the persistent state is the current state.
+            if self.poisoned {
+                return Err(LogErr::Poisoned);
+            }
+            let tracked perm = TrustedPermission::new_one_possibility(self.log_id@, self@.drop_pending_appends());
+            let result = self.untrusted_log_impl.write_writer_marker(&mut self.wrpm_region, pid, timestamp,
+                                                                     self.log_id, Tracked(&perm));
+            match result {
+                Err(LogErr::FlushFailed) => { self.poisoned = true; },
+                _ => {},
+            }
+            result
+        }
     }
 
 }
+
+// A writer marker is considered live if it was written less than
+// this many seconds ago. Older markers are assumed to have been left
+// behind by a writer that crashed, so they're ignored.
+pub const WRITER_MARKER_LIFETIME_SECS: u64 = 60;
+
+fn current_timestamp() -> u64
+{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl<PMRegion: PersistentMemoryRegion> LogImpl<PMRegion> {
+    // The `acquire_writer` method takes the advisory writer lock on
+    // the log. It returns `Err(LogErr::Busy)` if another writer's
+    // marker is live, i.e., nonzero and written less than
+    // `WRITER_MARKER_LIFETIME_SECS` ago. Otherwise, it durably writes
+    // a marker with this process's ID and the current time, and
+    // returns a guard that clears the marker when dropped.
+    //
+    // The lock is advisory and isn't crash-safe mutual exclusion:
+    // reading and writing the marker aren't atomic, and nothing
+    // stops code from writing without acquiring it. But it catches
+    // the common bug of two handles writing to the same region.
+    // Recovery never reads the marker, so a stale one is harmless.
+    pub fn acquire_writer(&mut self) -> Result<WriterGuard<'_, PMRegion>, LogErr>
+    {
+        if self.is_poisoned() {
+            return Err(LogErr::Poisoned);
+        }
+        let (pid, timestamp) = self.read_writer_marker();
+        let now = current_timestamp();
+        if pid != 0 && now.saturating_sub(timestamp) < WRITER_MARKER_LIFETIME_SECS {
+            return Err(LogErr::Busy);
+        }
+        self.write_writer_marker(std::process::id() as u64, now)?;
+        Ok(WriterGuard { log: self })
+    }
+}
+
+// A `WriterGuard` holds the advisory writer lock on a `LogImpl`, and
+// dereferences to it. Dropping the guard clears the writer marker.
+pub struct WriterGuard<'a, PMRegion: PersistentMemoryRegion> {
+    log: &'a mut LogImpl<PMRegion>,
+}
+
+impl<'a, PMRegion: PersistentMemoryRegion> WriterGuard<'a, PMRegion> {
+    // The `refresh` method rewrites the writer marker with the
+    // current time. A writer holding the guard for longer than
+    // `WRITER_MARKER_LIFETIME_SECS` should call it periodically so
+    // its marker isn't mistaken for a stale one.
+    pub fn refresh(&mut self) -> Result<(), LogErr>
+    {
+        self.log.write_writer_marker(std::process::id() as u64, current_timestamp())
+    }
+}
+
+impl<'a, PMRegion: PersistentMemoryRegion> Deref for WriterGuard<'a, PMRegion> {
+    type Target = LogImpl<PMRegion>;
+
+    fn deref(&self) -> &LogImpl<PMRegion>
+    {
+        self.log
+    }
+}
+
+impl<'a, PMRegion: PersistentMemoryRegion> DerefMut for WriterGuard<'a, PMRegion> {
+    fn deref_mut(&mut self) -> &mut LogImpl<PMRegion>
+    {
+        self.log
+    }
+}
+
+impl<'a, PMRegion: PersistentMemoryRegion> Drop for WriterGuard<'a, PMRegion> {
+    fn drop(&mut self)
+    {
+        // Clearing the marker is best-effort. If it fails, or the log
+        // is poisoned so it mustn't be written, the marker becomes
+        // stale after `WRITER_MARKER_LIFETIME_SECS`.
+        if !self.log.is_poisoned() {
+            let _ = self.log.write_writer_marker(0, 0);
+        }
+    }
+}
+
+impl<PMRegion: PersistentMemoryRegion> LogImpl<PMRegion> {
+    // The `truncate_region_file` method shrinks this log's region to
+    // `new_size` bytes and returns the space in the file at `path`
+    // past that point to the file system. It's for a region that's a
+    // whole file, starting at offset 0.
+    //
+    // It first rewrites the region metadata's `region_size` and
+    // `log_area_len`, and its CRC, to describe the smaller region,
+    // and flushes. Only then does it unmap the region, by consuming
+    // the log, and truncate the file. A crash after the flush leaves
+    // a file at least `new_size` bytes long, so the log can be
+    // restarted on its first `new_size` bytes, after which calling
+    // this method again with the same `new_size` finishes the
+    // truncation.
+    //
+    // The region metadata isn't protected by a CDB, so the rewrite
+    // itself isn't atomic: a crash in the middle of it can leave a
+    // region whose metadata doesn't match its CRC, and that `start`
+    // refuses. That's why this method is trusted code that bypasses
+    // the write restriction, and must be audited, rather than being
+    // a verified operation.
+    //
+    // Tentative appends are discarded. The committed log data must
+    // stay where it is in the smaller log area, so this fails with
+    // `LogErr::CantShrinkBelowLiveData` unless that data lies below
+    // the new end of the log area without wrapping, and the head
+    // maps to the same log area offset under the new log area
+    // length. An empty log, e.g., one whose head has been advanced to
+    // its tail, can always be shrunk. It fails with `PmemError::AccessOutOfRange` if `new_size`
+    // is bigger than the region or leaves a log area smaller than
+    // `MIN_LOG_AREA_SIZE`, and with `LogErr::LogAreaNotAligned` if the
+    // log appends with `LogAppendAlignment::CacheLine` and the new
+    // log area length isn't a multiple of 64.
+    pub fn truncate_region_file(self, path: &str, new_size: u64) -> Result<(), LogErr>
+    {
+        let region_size = self.wrpm_region.get_pm_region_ref().get_region_size();
+        let (head, tail, capacity) = self.get_head_tail_and_capacity()?;
+        if new_size > region_size || new_size < ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE {
+            return Err(LogErr::PmemErr { err: PmemError::AccessOutOfRange });
+        }
+
+        let new_log_area_len = new_size - ABSOLUTE_POS_OF_LOG_AREA;
+        if self.append_alignment() == LogAppendAlignment::CacheLine
+            && new_log_area_len % APPEND_ALIGNMENT_CACHE_LINE != 0 {
+            return Err(LogErr::LogAreaNotAligned {
+                log_area_size: new_log_area_len,
+                alignment: APPEND_ALIGNMENT_CACHE_LINE,
+            });
+        }
+
+        // Log position `head + i` is at log area offset
+        // `(head + i) % log_area_len`. For the committed data to stay
+        // put, the head has to map to the same offset under both
+        // lengths, and the data can't reach the new end of the log
+        // area, since it would wrap there rather than at the old end.
+        // An empty log has no data to keep in place.
+        let head_log_area_offset = head % (capacity as u128);
+        if tail != head && (head % (new_log_area_len as u128) != head_log_area_offset
+                            || head_log_area_offset + (tail - head) > new_log_area_len as u128) {
+            return Err(LogErr::CantShrinkBelowLiveData { head, tail });
+        }
+
+        let LogImpl { wrpm_region, .. } = self;
+        let mut pm_region = wrpm_region.into_pm_region();
+        let mut region_metadata = pm_region.read(ABSOLUTE_POS_OF_REGION_METADATA, LENGTH_OF_REGION_METADATA);
+        let region_size_pos = RELATIVE_POS_OF_REGION_REGION_SIZE as usize;
+        let log_area_len_pos = RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA as usize;
+        region_metadata[region_size_pos..region_size_pos + 8].copy_from_slice(&new_size.to_le_bytes());
+        region_metadata[log_area_len_pos..log_area_len_pos + 8].copy_from_slice(&new_log_area_len.to_le_bytes());
+        let crc = bytes_crc(region_metadata.as_slice());
+        pm_region.write(ABSOLUTE_POS_OF_REGION_METADATA, region_metadata.as_slice());
+        pm_region.write(ABSOLUTE_POS_OF_REGION_CRC, crc.as_slice());
+        pm_region.flush().map_err(|err| LogErr::PmemErr { err })?;
+
+        // The region has to be unmapped before the file is truncated.
+        std::mem::drop(pm_region);
+        truncate_region_file(path, new_size).map_err(|err| LogErr::PmemErr { err })
+    }
+}
@@ -10,8 +10,9 @@ use crate::log::inv_v::*;
 use crate::log::layout_v::*;
 use crate::log::logimpl_t::*;
 use crate::log::logspec_t::AbstractLogState;
+use crate::log::observer_t::RecoveryObserverRef;
 use crate::log::setup_v::write_setup_metadata;
-use crate::log::start_v::{read_cdb, read_log_variables};
+use crate::log::start_v::{read_cdb, read_log_variables, try_read_cdb};
 use crate::pmem::pmemspec_t::*;
 use crate::pmem::pmemutil_v::*;
 use crate::pmem::serialization_t::*;
@@ -136,9 +137,21 @@ verus! {
         // The `setup` method sets up persistent memory objects `pm_region`
         // to store an initial empty log. It returns the capacity of the log.
         // See `README.md` for more documentation.
+        //
+        // `min_log_area_size` is the smallest log area the caller is
+        // willing to accept. It's only checked here; recovery just
+        // enforces the hard floor `MIN_LOG_AREA_SIZE`.
+        //
+        // `append_alignment` is recorded in the region metadata for
+        // `read_append_alignment` to return. Recovery doesn't
+        // interpret it. If it's `APPEND_ALIGNMENT_CACHE_LINE`, the
+        // log area length must be a multiple of it, so that aligned
+        // log positions are aligned in the region too.
         pub exec fn setup<PMRegion>(
             pm_region: &mut PMRegion,
             log_id: u128,
+            min_log_area_size: u64,
+            append_alignment: u64,
         ) -> (result: Result<u64, LogErr>)
             where
                 PMRegion: PersistentMemoryRegion
@@ -147,21 +160,41 @@ verus! {
             ensures
                 pm_region.inv(),
                 pm_region.constants() == old(pm_region).constants(),
-                pm_region@.no_outstanding_writes(),
                 match result {
                     Ok(log_capacity) => {
                         let state = AbstractLogState::initialize(log_capacity as int);
+                        &&& pm_region@.no_outstanding_writes()
                         &&& log_capacity@ <= pm_region@.len()
                         &&& pm_region@.len() == old(pm_region)@.len()
                         &&& can_only_crash_as_state(pm_region@, log_id, state)
                         &&& Self::recover(pm_region@.committed(), log_id) == Some(state)
                         &&& Self::recover(pm_region@.flush().committed(), log_id) == Some(state)
                         &&& state == state.drop_pending_appends()
+                        &&& forall |i: int| ABSOLUTE_POS_OF_LOG_AREA <= i < pm_region@.len() ==>
+                               #[trigger] pm_region@.committed()[i] == 0
                     },
                     Err(LogErr::InsufficientSpaceForSetup { required_space }) => {
+                        &&& pm_region@.no_outstanding_writes()
                         &&& pm_region@ == old(pm_region)@.flush()
                         &&& pm_region@.len() < required_space
                     },
+                    Err(LogErr::RegionTooSmall { min_log_area_size: min, log_area_size }) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                        &&& pm_region@.len() >= ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE
+                        &&& min == min_log_area_size
+                        &&& log_area_size == pm_region@.len() - ABSOLUTE_POS_OF_LOG_AREA
+                        &&& log_area_size < min_log_area_size
+                    },
+                    Err(LogErr::LogAreaNotAligned { log_area_size, alignment }) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                        &&& append_alignment == APPEND_ALIGNMENT_CACHE_LINE
+                        &&& alignment == append_alignment
+                        &&& log_area_size == pm_region@.len() - ABSOLUTE_POS_OF_LOG_AREA
+                        &&& log_area_size % alignment != 0
+                    },
+                    Err(LogErr::FlushFailed) => true,
                     _ => false
                 }
         {
@@ -187,7 +220,10 @@ verus! {
             // might be invalid. So we need to flush before writing
             // anything anyway.
 
-            pm_region.flush();
+            match pm_region.flush() {
+                Ok(()) => {},
+                Err(_) => { return Err(LogErr::FlushFailed); },
+            }
 
             // Get the list of region sizes and make sure they support
             // storing a log. If not, return an appropriate
@@ -200,13 +236,39 @@ verus! {
                 });
             }
 
+            // Also make sure the log area would be at least as big as
+            // the caller asked for.
+
+            if region_size - ABSOLUTE_POS_OF_LOG_AREA < min_log_area_size {
+                return Err(LogErr::RegionTooSmall{
+                    min_log_area_size,
+                    log_area_size: region_size - ABSOLUTE_POS_OF_LOG_AREA,
+                });
+            }
+
+            // If appends are to be aligned to cache lines, make sure
+            // the log area length is a multiple of the cache-line
+            // size. Otherwise, log positions that are multiples of it
+            // wouldn't map to aligned addresses once the log wraps.
+
+            if append_alignment == APPEND_ALIGNMENT_CACHE_LINE
+               && (region_size - ABSOLUTE_POS_OF_LOG_AREA) % APPEND_ALIGNMENT_CACHE_LINE != 0 {
+                return Err(LogErr::LogAreaNotAligned{
+                    log_area_size: region_size - ABSOLUTE_POS_OF_LOG_AREA,
+                    alignment: APPEND_ALIGNMENT_CACHE_LINE,
+                });
+            }
+
             // Compute log capacities so we can return them.
 
             let log_capacity = region_size - ABSOLUTE_POS_OF_LOG_AREA;
 
-            // Write setup metadata.
+            // Zero the log area and write setup metadata.
 
-            write_setup_metadata(pm_region, region_size, Ghost(log_capacity), log_id);
+            match write_setup_metadata(pm_region, region_size, Ghost(log_capacity), log_id, append_alignment) {
+                Ok(()) => {},
+                Err(_) => { return Err(LogErr::FlushFailed); },
+            }
 
             proof {
                 // Prove various postconditions about how we can
@@ -225,6 +287,96 @@ verus! {
             Ok(log_capacity)
         }
 
+        // The `setup_if_needed` static method is like `setup`, except
+        // that it leaves the region untouched if it already holds a
+        // log with ID `log_id`. It returns the log's capacity and
+        // whether it set up a fresh, empty log.
+        //
+        // It only sets up a fresh log if the region doesn't look like
+        // a log region at all, or holds a log with a different ID.
+        // Any other problem reading the existing log, e.g., a CRC
+        // mismatch, is returned as an error, since setting up a fresh
+        // log would destroy whatever is there.
+        //
+        // `region_size` is the size the caller expects the region to
+        // have. If it doesn't, this fails with
+        // `LogErr::StartFailedDueToRegionSizeMismatch` without
+        // touching the region.
+        pub exec fn setup_if_needed<PMRegion>(
+            pm_region: &mut PMRegion,
+            log_id: u128,
+            region_size: u64,
+        ) -> (result: Result<(u64, bool), LogErr>)
+            where
+                PMRegion: PersistentMemoryRegion
+            requires
+                old(pm_region).inv(),
+            ensures
+                pm_region.inv(),
+                pm_region.constants() == old(pm_region).constants(),
+                match result {
+                    Ok((log_capacity, true)) => {
+                        let state = AbstractLogState::initialize(log_capacity as int);
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& log_capacity@ <= pm_region@.len()
+                        &&& pm_region@.len() == old(pm_region)@.len()
+                        &&& can_only_crash_as_state(pm_region@, log_id, state)
+                        &&& Self::recover(pm_region@.committed(), log_id) == Some(state)
+                        &&& Self::recover(pm_region@.flush().committed(), log_id) == Some(state)
+                        &&& state == state.drop_pending_appends()
+                    },
+                    Ok((_, false)) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                    },
+                    Err(LogErr::FlushFailed) => true,
+                    Err(_) => {
+                        &&& pm_region@.no_outstanding_writes()
+                        &&& pm_region@ == old(pm_region)@.flush()
+                    },
+                }
+        {
+            // Flush first, both so we can read the region and so that
+            // an existing log is left exactly as recovery would see it.
+
+            match pm_region.flush() {
+                Ok(()) => {},
+                Err(_) => { return Err(LogErr::FlushFailed); },
+            }
+            proof {
+                lemma_if_no_outstanding_writes_to_region_then_flush_is_idempotent(pm_region@);
+            }
+
+            let actual_region_size = pm_region.get_region_size();
+            if actual_region_size != region_size {
+                return Err(LogErr::StartFailedDueToRegionSizeMismatch{
+                    region_size_expected: region_size,
+                    region_size_read: actual_region_size,
+                });
+            }
+
+            // See whether the region already holds a log with this ID.
+            // If it holds a log with another ID, or `try_read_cdb`
+            // positively recognizes it as not a log region, fall
+            // through to setting up a fresh log. A CRC mismatch might
+            // mean a damaged log, so it's returned as an error.
+
+            match try_read_cdb(pm_region) {
+                Ok(Some(cdb)) => {
+                    match read_log_variables(pm_region, log_id, cdb, &RecoveryObserverRef::none()) {
+                        Ok(info) => { return Ok((info.log_area_len, false)); },
+                        Err(LogErr::StartFailedDueToLogIDMismatch{ .. }) => {},
+                        Err(e) => { return Err(e); },
+                    }
+                },
+                Ok(None) => {},
+                Err(e) => { return Err(e); },
+            }
+
+            let log_capacity = Self::setup(pm_region, log_id, MIN_LOG_AREA_SIZE, APPEND_ALIGNMENT_NONE)?;
+            Ok((log_capacity, true))
+        }
+
         // The `start` static method creates an
         // `UntrustedLogImpl` out of a set of persistent memory
         // regions. It's assumed that those regions were initialized
@@ -236,11 +388,14 @@ verus! {
         // persistent memory regions `wrpm_region`. This restricts
         // how we can write `wrpm_region`. This is moot, though,
         // because we don't ever write to the memory.
+        //
+        // Each recovery decision is reported to `observer`.
         pub exec fn start<PMRegion>(
             wrpm_region: &mut WriteRestrictedPersistentMemoryRegion<TrustedPermission, PMRegion>,
             log_id: u128,
             Tracked(perm): Tracked<&TrustedPermission>,
             Ghost(state): Ghost<AbstractLogState>,
+            observer: &RecoveryObserverRef,
         ) -> (result: Result<Self, LogErr>)
             where
                 PMRegion: PersistentMemoryRegion
@@ -258,6 +413,7 @@ verus! {
                         &&& can_only_crash_as_state(wrpm_region@, log_id, state.drop_pending_appends())
                     },
                     Err(LogErr::CRCMismatch) => !wrpm_region.constants().impervious_to_corruption,
+                    Err(LogErr::FlushFailed) => true,
                     _ => false
                 }
         {
@@ -265,7 +421,10 @@ verus! {
             // writes to various location. To make sure of this, we
             // flush all memory regions.
 
-            wrpm_region.flush();
+            match wrpm_region.flush() {
+                Ok(()) => {},
+                Err(_) => { return Err(LogErr::FlushFailed); },
+            }
 
             // Out of paranoia, we check to make sure that the number
             // of regions is sensible. Both cases are technically
@@ -280,12 +439,12 @@ verus! {
             // First, we read the corruption-detecting boolean and
             // return an error if that fails.
 
-            let cdb = read_cdb(pm_region)?;
+            let cdb = read_cdb(pm_region, observer)?;
 
             // Second, we read the log variables to store in `info`.
             // If that fails, we return an error.
 
-            let info = read_log_variables(pm_region, log_id, cdb)?;
+            let info = read_log_variables(pm_region, log_id, cdb, observer)?;
             proof {
                 // We have to prove that we can only crash as the given abstract
                 // state with all pending appends dropped. We prove this with two
@@ -335,14 +494,16 @@ verus! {
                         let state = old(self)@;
                         &&& offset == state.head + state.log.len() + state.pending.len()
                         &&& self@ == old(self)@.tentatively_append(bytes_to_append@)
+                        &&& old(self)@.try_tentatively_append(bytes_to_append@) == Some(self@)
                     },
                     Err(LogErr::InsufficientSpaceForAppend { available_space }) => {
                         &&& self@ == old(self)@
                         &&& available_space < bytes_to_append@.len()
-                        &&& {
-                               ||| available_space == self@.capacity - self@.log.len() - self@.pending.len()
-                               ||| available_space == u128::MAX - self@.head - self@.log.len() - self@.pending.len()
-                           }
+                        &&& available_space == self@.capacity - self@.log.len() - self@.pending.len()
+                    },
+                    Err(LogErr::LogPositionOverflow) => {
+                        &&& self@ == old(self)@
+                        &&& self@.head + self@.log.len() + self@.pending.len() + bytes_to_append@.len() > u128::MAX
                     },
                     _ => false
                 }
@@ -357,21 +518,21 @@ verus! {
             let available_space: u64 = info.log_area_len - info.log_plus_pending_length as u64;
 
             // Check to make sure we have enough available space, and
-            // return an error otherwise. There are two ways we might
-            // not have available space. The first is that doing the
-            // append would overfill the log area. The second (which
-            // will probably never happen) is that doing this append
-            // and a subsequent commit would make the logical tail
-            // exceed u128::MAX.
+            // return an error otherwise.
 
             let num_bytes: u64 = bytes_to_append.len() as u64;
             if num_bytes > available_space {
                 return Err(LogErr::InsufficientSpaceForAppend{ available_space })
             }
+
+            // Also check that doing this append and a subsequent
+            // commit wouldn't make the logical tail exceed
+            // `u128::MAX`. This will probably never happen, but
+            // rejecting it here means `commit` never has to wrap the
+            // virtual position.
+
             if num_bytes as u128 > u128::MAX - info.log_plus_pending_length as u128 - info.head {
-                return Err(LogErr::InsufficientSpaceForAppend{
-                    available_space: (u128::MAX - info.log_plus_pending_length as u128 - info.head) as u64
-                })
+                return Err(LogErr::LogPositionOverflow)
             }
 
             // Compute the current logical offset of the end of the
@@ -526,6 +687,10 @@ verus! {
         // after the next flush, since we're going to be doing a flush.
         // This weaker requirement allows a performance optimization: the
         // caller doesn't have to flush before calling this function.
+        //
+        // If either flush fails, this returns `Err(LogErr::FlushFailed)`
+        // without flipping `self.cdb`. The invariant no longer holds in
+        // that case, but any crash is still permitted by `perm`.
         exec fn update_log_metadata<PMRegion>(
             &mut self,
             wrpm_region: &mut WriteRestrictedPersistentMemoryRegion<TrustedPermission, PMRegion>,
@@ -533,7 +698,7 @@ verus! {
             Ghost(prev_info): Ghost<LogInfo>,
             Ghost(prev_state): Ghost<AbstractLogState>,
             Tracked(perm): Tracked<&TrustedPermission>,
-        )
+        ) -> (result: Result<(), LogErr>)
             where
                 PMRegion: PersistentMemoryRegion
             requires
@@ -549,9 +714,15 @@ verus! {
                           ||| Self::recover(s, log_id) == Some(old(self).state@.drop_pending_appends())
                       } ==> #[trigger] perm.check_permission(s),
             ensures
-                self.inv(wrpm_region, log_id),
+                wrpm_region.inv(),
                 wrpm_region.constants() == old(wrpm_region).constants(),
                 self.state == old(self).state,
+                match result {
+                    Ok(()) => self.inv(wrpm_region, log_id),
+                    Err(LogErr::FlushFailed) =>
+                        forall |s| #[trigger] wrpm_region@.can_crash_as(s) ==> perm.check_permission(s),
+                    _ => false
+                },
         {
             // Set the `unused_metadata_pos` to be the position corresponding to !self.cdb
             // since we're writing in the inactive part of the metadata.
@@ -666,8 +837,16 @@ verus! {
 
             // Next, flush all outstanding writes to memory. This is
             // necessary so that those writes are ordered before the update
-            // to the CDB.
-            wrpm_region.flush();
+            // to the CDB. If the flush fails, the metadata writes may or
+            // may not have reached memory, but we showed above that
+            // crashing in either case is permitted.
+            match wrpm_region.flush() {
+                Ok(()) => {},
+                Err(_) => {
+                    assert(wrpm_region@ == wrpm_region_new);
+                    return Err(LogErr::FlushFailed);
+                },
+            }
 
             // Next, compute the new encoded CDB to write.
 
@@ -758,8 +937,15 @@ verus! {
             // non-persistent memory so if we crash it'll be lost anyway.
             // wrpm_region.write(0, ABSOLUTE_POS_OF_LOG_CDB, new_cdb.as_slice(), Tracked(perm));
             wrpm_region.serialize_and_write(ABSOLUTE_POS_OF_LOG_CDB, &new_cdb, Tracked(perm));
-            wrpm_region.flush();
+            match wrpm_region.flush() {
+                Ok(()) => {},
+                Err(_) => {
+                    assert(wrpm_region@ == pm_region_after_write);
+                    return Err(LogErr::FlushFailed);
+                },
+            }
             self.cdb = !self.cdb;
+            Ok(())
         }
 
         // The `commit` method commits all tentative appends that have been
@@ -789,11 +975,20 @@ verus! {
                     ||| Self::recover(s, log_id) == Some(old(self)@.commit().drop_pending_appends())
                 },
             ensures
-                self.inv(wrpm_region, log_id),
+                wrpm_region.inv(),
                 wrpm_region.constants() == old(wrpm_region).constants(),
-                can_only_crash_as_state(wrpm_region@, log_id, self@.drop_pending_appends()),
-                result is Ok,
-                self@ == old(self)@.commit(),
+                match result {
+                    Ok(()) => {
+                        &&& self.inv(wrpm_region, log_id)
+                        &&& can_only_crash_as_state(wrpm_region@, log_id, self@.drop_pending_appends())
+                        &&& self@ == old(self)@.commit()
+                    },
+                    Err(LogErr::FlushFailed) => forall |s| #[trigger] wrpm_region@.can_crash_as(s) ==> {
+                        ||| Self::recover(s, log_id) == Some(old(self)@.drop_pending_appends())
+                        ||| Self::recover(s, log_id) == Some(old(self)@.commit().drop_pending_appends())
+                    },
+                    _ => false
+                }
         {
             let ghost prev_info = self.info;
             let ghost prev_state = self.state@;
@@ -806,15 +1001,16 @@ verus! {
             assert(metadata_consistent_with_info(wrpm_region@, log_id, self.cdb, prev_info));
             assert(info_consistent_with_log_area(wrpm_region@, prev_info, prev_state));
             assert(self.state@ == prev_state.commit());
+            proof {
+                lemma_commit_preserves_log_info(wrpm_region@, prev_info, prev_state);
+            }
             assert(info_consistent_with_log_area(wrpm_region@.flush(), self.info, self.state@));
 
             // Update the inactive metadata on all regions and flush, then
             // swap the CDB to its opposite.
 
             self.update_log_metadata(wrpm_region, Ghost(log_id), Ghost(prev_info),
-                                     Ghost(prev_state), Tracked(perm));
-
-            Ok(())
+                                     Ghost(prev_state), Tracked(perm))
         }
 
         // The `advance_head` method advances the head of the log,
@@ -850,24 +1046,34 @@ verus! {
                         Some(old(self)@.advance_head(new_head as int).drop_pending_appends())
                 },
             ensures
-                self.inv(wrpm_region, log_id),
+                wrpm_region.inv(),
                 wrpm_region.constants() == old(wrpm_region).constants(),
-                can_only_crash_as_state(wrpm_region@, log_id, self@.drop_pending_appends()),
                 match result {
                     Ok(()) => {
+                        &&& self.inv(wrpm_region, log_id)
+                        &&& can_only_crash_as_state(wrpm_region@, log_id, self@.drop_pending_appends())
                         &&& old(self)@.head <= new_head <= old(self)@.head + old(self)@.log.len()
                         &&& self@ == old(self)@.advance_head(new_head as int)
                     },
                     Err(LogErr::CantAdvanceHeadPositionBeforeHead { head }) => {
+                        &&& self.inv(wrpm_region, log_id)
+                        &&& can_only_crash_as_state(wrpm_region@, log_id, self@.drop_pending_appends())
                         &&& self@ == old(self)@
                         &&& head == self@.head
                         &&& new_head < head
                     },
                     Err(LogErr::CantAdvanceHeadPositionBeyondTail { tail }) => {
+                        &&& self.inv(wrpm_region, log_id)
+                        &&& can_only_crash_as_state(wrpm_region@, log_id, self@.drop_pending_appends())
                         &&& self@ == old(self)@
                         &&& tail == self@.head + self@.log.len()
                         &&& new_head > tail
                     },
+                    Err(LogErr::FlushFailed) => forall |s| #[trigger] wrpm_region@.can_crash_as(s) ==> {
+                        ||| Self::recover(s, log_id) == Some(old(self)@.drop_pending_appends())
+                        ||| Self::recover(s, log_id) ==
+                            Some(old(self)@.advance_head(new_head as int).drop_pending_appends())
+                    },
                     _ => false
                 }
         {
@@ -1000,9 +1206,7 @@ verus! {
             // to update the inactive metadata on all regions.
 
             self.update_log_metadata(wrpm_region, Ghost(log_id), Ghost(prev_info), Ghost(prev_state),
-                                     Tracked(perm));
-
-            Ok(())
+                                     Tracked(perm))
         }
 
         // This local helper method proves that we can read a portion of
@@ -1086,9 +1290,10 @@ verus! {
                             &&& read_correct_modulo_corruption(bytes@, true_bytes,
                                                               wrpm_region.constants().impervious_to_corruption)
                         },
-                        Err(LogErr::CantReadBeforeHead{ head: head_pos }) => {
+                        Err(LogErr::PositionTrimmed{ requested, current_head }) => {
                             &&& pos < log.head
-                            &&& head_pos == log.head
+                            &&& requested == pos
+                            &&& current_head == log.head
                         },
                         Err(LogErr::CantReadPastTail{ tail }) => {
                             &&& pos + len > log.head + log.log.len()
@@ -1103,7 +1308,7 @@ verus! {
 
             let info = &self.info;
             if pos < info.head {
-                return Err(LogErr::CantReadBeforeHead{ head: info.head })
+                return Err(LogErr::PositionTrimmed{ requested: pos, current_head: info.head })
             }
             if len > info.log_length { // We have to do this check first to avoid underflow in the next comparison
                 return Err(LogErr::CantReadPastTail{ tail: info.head + info.log_length as u128 })
@@ -1233,6 +1438,213 @@ verus! {
             Ok(part1)
         }
 
+        // The `verify_committed_metadata` method reads back the CDB
+        // and the log metadata it selects, and checks that they're
+        // what the last commit or head advance wrote: the CDB is
+        // `self.cdb`, the log metadata matches its CRC, and it records
+        // the head and length in `self.info`. It returns
+        // `Err(LogErr::WriteVerificationFailed)` if not, which can only
+        // happen if the memory isn't impervious to corruption.
+        #[allow(unused_variables)]
+        pub exec fn verify_committed_metadata<Perm, PMRegion>(
+            &self,
+            wrpm_region: &WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>,
+            Ghost(log_id): Ghost<u128>,
+        ) -> (result: Result<(), LogErr>)
+            where
+                Perm: CheckPermission<Seq<u8>>,
+                PMRegion: PersistentMemoryRegion
+            requires
+                self.inv(wrpm_region, log_id),
+            ensures
+                match result {
+                    Ok(()) => true,
+                    Err(LogErr::WriteVerificationFailed) => !wrpm_region.constants().impervious_to_corruption,
+                    _ => false,
+                }
+        {
+            let pm_region = wrpm_region.get_pm_region_ref();
+            let ghost mem = pm_region@.committed();
+
+            // Check the CDB. If the memory is impervious to
+            // corruption, we read exactly what the invariant says is
+            // there.
+
+            let log_cdb = *pm_region.read_and_deserialize::<u64>(ABSOLUTE_POS_OF_LOG_CDB);
+            let expected_cdb = if self.cdb { CDB_TRUE } else { CDB_FALSE };
+            if log_cdb != expected_cdb {
+                return Err(LogErr::WriteVerificationFailed);
+            }
+
+            // Check the log metadata the CDB selects against its CRC,
+            // and against the head and length we expect it to hold.
+
+            let log_metadata_pos = if self.cdb { ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE }
+                                      else { ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE };
+            let log_crc_pos = if self.cdb { ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE }
+                                 else { ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE };
+            let log_metadata = pm_region.read_and_deserialize::<LogMetadata>(log_metadata_pos);
+            let log_crc = pm_region.read_and_deserialize::<u64>(log_crc_pos);
+            if !check_crc_deserialized(log_metadata, log_crc, Ghost(mem),
+                                       Ghost(pm_region.constants().impervious_to_corruption),
+                                       pm_region.known_impervious_to_corruption(),
+                                       Ghost(log_metadata_pos), Ghost(LENGTH_OF_LOG_METADATA), Ghost(log_crc_pos)) {
+                return Err(LogErr::WriteVerificationFailed);
+            }
+            if log_metadata.head != self.info.head || log_metadata.log_length != self.info.log_length {
+                return Err(LogErr::WriteVerificationFailed);
+            }
+            Ok(())
+        }
+
+        // The `read_writer_marker` method returns the process ID and
+        // timestamp stored in the writer marker. Recovery never reads
+        // the writer marker, so nothing is guaranteed about what it
+        // holds; it's only used as an advisory lock.
+        #[allow(unused_variables)]
+        pub exec fn read_writer_marker<Perm, PMRegion>(
+            &self,
+            wrpm_region: &WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>,
+            Ghost(log_id): Ghost<u128>,
+        ) -> (result: (u64, u64))
+            where
+                Perm: CheckPermission<Seq<u8>>,
+                PMRegion: PersistentMemoryRegion
+            requires
+                self.inv(wrpm_region, log_id)
+        {
+            let pm_region = wrpm_region.get_pm_region_ref();
+            proof {
+                u64::lemma_auto_serialized_len();
+            }
+            let pid = *pm_region.read_and_deserialize::<u64>(
+                ABSOLUTE_POS_OF_WRITER_MARKER + RELATIVE_POS_OF_WRITER_MARKER_PID
+            );
+            let timestamp = *pm_region.read_and_deserialize::<u64>(
+                ABSOLUTE_POS_OF_WRITER_MARKER + RELATIVE_POS_OF_WRITER_MARKER_TIMESTAMP
+            );
+            (pid, timestamp)
+        }
+
+        // The `read_append_alignment` method returns the append
+        // alignment recorded in the region metadata at setup.
+        // Recovery doesn't interpret it, but it's covered by the
+        // region metadata's CRC, which `start` checked.
+        #[allow(unused_variables)]
+        pub exec fn read_append_alignment<Perm, PMRegion>(
+            &self,
+            wrpm_region: &WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>,
+            Ghost(log_id): Ghost<u128>,
+        ) -> (result: u64)
+            where
+                Perm: CheckPermission<Seq<u8>>,
+                PMRegion: PersistentMemoryRegion
+            requires
+                self.inv(wrpm_region, log_id)
+        {
+            let pm_region = wrpm_region.get_pm_region_ref();
+            proof {
+                RegionMetadata::lemma_auto_serialized_len();
+            }
+            let region_metadata = pm_region.read_and_deserialize::<RegionMetadata>(ABSOLUTE_POS_OF_REGION_METADATA);
+            region_metadata.append_alignment
+        }
+
+        // The `write_writer_marker` method durably stores `pid` and
+        // `timestamp` in the writer marker. It doesn't change the
+        // abstract state of the log, since recovery never reads the
+        // writer marker.
+        //
+        // This method is passed a write-restricted persistent memory
+        // region `wrpm_region`. It's only given permission (in
+        // `perm`) to write if any crash after initiating the write
+        // recovers as the current abstract state with all pending
+        // appends dropped.
+        pub exec fn write_writer_marker<PMRegion>(
+            &mut self,
+            wrpm_region: &mut WriteRestrictedPersistentMemoryRegion<TrustedPermission, PMRegion>,
+            pid: u64,
+            timestamp: u64,
+            Ghost(log_id): Ghost<u128>,
+            Tracked(perm): Tracked<&TrustedPermission>,
+        ) -> (result: Result<(), LogErr>)
+            where
+                PMRegion: PersistentMemoryRegion
+            requires
+                old(self).inv(&*old(wrpm_region), log_id),
+                forall |s| #[trigger] perm.check_permission(s) <==>
+                    Self::recover(s, log_id) == Some(old(self)@.drop_pending_appends()),
+            ensures
+                wrpm_region.inv(),
+                wrpm_region.constants() == old(wrpm_region).constants(),
+                self@ == old(self)@,
+                match result {
+                    Ok(()) => self.inv(wrpm_region, log_id),
+                    Err(LogErr::FlushFailed) => forall |s| #[trigger] wrpm_region@.can_crash_as(s) ==>
+                        Self::recover(s, log_id) == Some(old(self)@.drop_pending_appends()),
+                    _ => false
+                }
+        {
+            let ghost pid_bytes = pid.spec_serialize();
+            let ghost timestamp_bytes = timestamp.spec_serialize();
+            let pid_pos = ABSOLUTE_POS_OF_WRITER_MARKER + RELATIVE_POS_OF_WRITER_MARKER_PID;
+            let timestamp_pos = ABSOLUTE_POS_OF_WRITER_MARKER + RELATIVE_POS_OF_WRITER_MARKER_TIMESTAMP;
+
+            // Prove that writing the process ID, and then the
+            // timestamp, maintains all invariants, so any crash along
+            // the way is permitted.
+
+            proof {
+                u64::lemma_auto_serialized_len();
+                lemma_updating_writer_marker_maintains_invariants(
+                    wrpm_region@, log_id, self.cdb, self.info, self.state@, pid_pos as int, pid_bytes
+                );
+            }
+
+            let ghost wrpm_region_new = wrpm_region@.write(pid_pos as int, pid_bytes);
+            assert forall |crash_bytes| wrpm_region_new.can_crash_as(crash_bytes)
+                       implies #[trigger] perm.check_permission(crash_bytes) by {
+                lemma_invariants_imply_crash_recover_forall(wrpm_region_new, log_id, self.cdb,
+                                                            self.info, self.state@);
+            }
+            wrpm_region.serialize_and_write(pid_pos, &pid, Tracked(perm));
+
+            proof {
+                lemma_updating_writer_marker_maintains_invariants(
+                    wrpm_region@, log_id, self.cdb, self.info, self.state@, timestamp_pos as int, timestamp_bytes
+                );
+            }
+
+            let ghost wrpm_region_new = wrpm_region@.write(timestamp_pos as int, timestamp_bytes);
+            assert forall |crash_bytes| wrpm_region_new.can_crash_as(crash_bytes)
+                       implies #[trigger] perm.check_permission(crash_bytes) by {
+                lemma_invariants_imply_crash_recover_forall(wrpm_region_new, log_id, self.cdb,
+                                                            self.info, self.state@);
+            }
+            wrpm_region.serialize_and_write(timestamp_pos, &timestamp, Tracked(perm));
+
+            // Flush so that the marker is durable and there are no
+            // outstanding writes to the metadata. If the flush fails,
+            // we showed above that crashing is permitted either way.
+
+            proof {
+                lemma_flushing_metadata_maintains_invariants(wrpm_region@, log_id, self.cdb, self.info, self.state@);
+                lemma_invariants_imply_crash_recover_forall(wrpm_region@, log_id, self.cdb, self.info, self.state@);
+            }
+            match wrpm_region.flush() {
+                Ok(()) => {},
+                Err(_) => {
+                    assert(wrpm_region@ == wrpm_region_new);
+                    return Err(LogErr::FlushFailed);
+                },
+            }
+
+            proof {
+                lemma_invariants_imply_crash_recover_forall(wrpm_region@, log_id, self.cdb, self.info, self.state@);
+            }
+            Ok(())
+        }
+
         // The `get_head_tail_and_capacity` method returns the head,
         // tail, and capacity of the log. See `README.md` for more
         // documentation and examples of its use.
@@ -1267,6 +1679,126 @@ verus! {
             Ok((info.head, info.head + info.log_length as u128, info.log_area_len))
         }
 
+        // The `max_appendable_len` method returns the largest number
+        // of bytes a single `tentatively_append` can currently
+        // accept. See the trusted wrapper in `logimpl_t.rs` for
+        // details.
+        #[allow(unused_variables)]
+        pub exec fn max_appendable_len<Perm, PMRegion>(
+            &self,
+            wrpm_region: &WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>,
+            Ghost(log_id): Ghost<u128>,
+        ) -> (result: u64)
+            where
+                Perm: CheckPermission<Seq<u8>>,
+                PMRegion: PersistentMemoryRegion
+            requires
+                self.inv(wrpm_region, log_id)
+            ensures
+                ({
+                    let log = self@;
+                    let free_space = log.capacity - log.log.len() - log.pending.len();
+                    let room_before_overflow = u128::MAX - log.head - log.log.len() - log.pending.len();
+                    &&& result <= free_space
+                    &&& result <= room_before_overflow
+                    &&& result == free_space || result == room_before_overflow
+                })
+        {
+            let info = &self.info;
+
+            // As in `tentatively_append`, the available space is
+            // limited both by the log area's free space and by the
+            // logical tail not exceeding `u128::MAX`.
+
+            let free_space: u64 = info.log_area_len - info.log_plus_pending_length;
+            let room_before_overflow: u128 = u128::MAX - info.log_plus_pending_length as u128 - info.head;
+            if free_space as u128 <= room_before_overflow {
+                free_space
+            }
+            else {
+                room_before_overflow as u64
+            }
+        }
+
+        // The `get_head` method returns the virtual head position of
+        // the log. Like `get_head_tail_and_capacity`, it reads it from
+        // the cached `self.info` rather than from persistent memory.
+        #[allow(unused_variables)]
+        pub exec fn get_head<Perm, PMRegion>(
+            &self,
+            wrpm_region: &WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>,
+            Ghost(log_id): Ghost<u128>,
+        ) -> (result: u128)
+            where
+                Perm: CheckPermission<Seq<u8>>,
+                PMRegion: PersistentMemoryRegion
+            requires
+                self.inv(wrpm_region, log_id)
+            ensures
+                result == self@.head
+        {
+            self.info.head
+        }
+
+        // The `get_pending_tail` method returns the virtual position
+        // just past the last pending append, i.e., where the next
+        // tentative append will start.
+        #[allow(unused_variables)]
+        pub exec fn get_pending_tail<Perm, PMRegion>(
+            &self,
+            wrpm_region: &WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>,
+            Ghost(log_id): Ghost<u128>,
+        ) -> (result: u128)
+            where
+                Perm: CheckPermission<Seq<u8>>,
+                PMRegion: PersistentMemoryRegion
+            requires
+                self.inv(wrpm_region, log_id)
+            ensures
+                result == self@.head + self@.log.len() + self@.pending.len()
+        {
+            self.info.head + self.info.log_plus_pending_length as u128
+        }
+
+        // The `get_pending_length` method returns the number of bytes
+        // tentatively appended but not yet committed.
+        #[allow(unused_variables)]
+        pub exec fn get_pending_length<Perm, PMRegion>(
+            &self,
+            wrpm_region: &WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>,
+            Ghost(log_id): Ghost<u128>,
+        ) -> (result: u64)
+            where
+                Perm: CheckPermission<Seq<u8>>,
+                PMRegion: PersistentMemoryRegion
+            requires
+                self.inv(wrpm_region, log_id)
+            ensures
+                result == self@.pending.len()
+        {
+            self.info.log_plus_pending_length - self.info.log_length
+        }
+
+        // The `position_is_live` method returns whether virtual
+        // position `pos` is at or past the head and before the
+        // committed tail.
+        pub exec fn position_is_live<Perm, PMRegion>(
+            &self,
+            wrpm_region: &WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>,
+            pos: u128,
+            Ghost(log_id): Ghost<u128>,
+        ) -> (result: bool)
+            where
+                Perm: CheckPermission<Seq<u8>>,
+                PMRegion: PersistentMemoryRegion
+            requires
+                self.inv(wrpm_region, log_id)
+            ensures
+                result == (self@.head <= pos < self@.head + self@.log.len())
+        {
+            pos >= self.info.head && pos - self.info.head < self.info.log_length as u128
+        }
+
     }
 
 }
@@ -17,6 +17,13 @@
 //! This method tentatively appends the given bytes to the end of the
 //! log.
 //!
+//! `try_tentatively_append(self, bytes_to_append: Seq<u8>) -> Option<Self>`
+//!
+//! This method is like `tentatively_append`, but returns `None` if
+//! the append would make the log plus pending appends exceed the
+//! log's capacity, or would make the virtual tail exceed
+//! `u128::MAX`.
+//!
 //! `commit(self) -> Self`
 //!
 //! This method commits all outstanding tentative appends atomically.
@@ -80,6 +87,26 @@ verus! {
             Self { pending: self.pending + bytes, ..self }
         }
 
+        // This is the specification for what it means to tentatively
+        // append to a log while respecting its capacity. It fails,
+        // returning `None`, if the log and its pending appends
+        // wouldn't fit in the log's capacity after appending the
+        // given bytes, or if the virtual tail would then exceed
+        // `u128::MAX`. These are the spec counterparts of the
+        // `InsufficientSpaceForAppend` and `LogPositionOverflow`
+        // errors.
+        pub open spec fn try_tentatively_append(self, bytes: Seq<u8>) -> Option<Self> {
+            if self.log.len() + self.pending.len() + bytes.len() > self.capacity {
+                None
+            }
+            else if self.head + self.log.len() + self.pending.len() + bytes.len() > u128::MAX {
+                None
+            }
+            else {
+                Some(self.tentatively_append(bytes))
+            }
+        }
+
         // This is the specification for what it means to commit a
         // log.  It adds all pending bytes to the log and clears the
         // pending bytes.
@@ -103,6 +130,14 @@ verus! {
             self.log.subrange(pos - self.head, pos - self.head + len)
         }
 
+        // This is the specification for what it means to read the
+        // bytes at virtual positions `from` (inclusive) through `to`
+        // (exclusive) in the abstract log.
+        pub open spec fn read_range(self, from: int, to: int) -> Seq<u8>
+        {
+            self.log.subrange(from - self.head, to - self.head)
+        }
+
         // This is the specification for what it means to drop pending
         // appends. (This isn't a user-invokable operation; it's what
         // happens on a crash.)
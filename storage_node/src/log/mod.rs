@@ -1,8 +1,14 @@
 pub mod append_v;
+pub mod buffered_v;
+pub mod cache_v;
+pub mod flushstrategy_t;
 pub mod inv_v;
 pub mod layout_v;
 pub mod logimpl_t;
 pub mod logimpl_v;
 pub mod logspec_t;
+pub mod observer_t;
+#[cfg(feature = "repair")]
+pub mod repair_t;
 pub mod setup_v;
 pub mod start_v;
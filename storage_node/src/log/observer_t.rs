@@ -0,0 +1,182 @@
+//! This file contains the `RecoveryObserver` trait, which lets the
+//! user of a log be told about each decision made while starting a
+//! log from persistent memory. For instance, an operator can install
+//! an observer that records exactly which metadata block failed its
+//! CRC check and at which address, rather than only learning that
+//! `start` returned `LogErr::CRCMismatch`.
+//!
+//! Observers are purely informational: they can't influence the
+//! outcome of recovery. So the verified start path only ever hands
+//! them copies of values it has already read.
+//!
+//! The code in this file is trusted and unverified (as indicated by
+//! the `_t.rs` suffix), so it must be audited.
+
+use builtin::*;
+use builtin_macros::*;
+use vstd::prelude::*;
+
+verus! {
+
+    // This enumeration identifies one of the CRC-protected pieces of
+    // log metadata. For log metadata, there are two copies, one for
+    // each value of the corruption-detecting boolean, so `cdb` says
+    // which one.
+    #[derive(Clone, Copy, Debug)]
+    pub enum MetadataKind {
+        Global,
+        Region,
+        Log { cdb: bool },
+    }
+
+    // This struct is a possibly-absent reference to a
+    // `RecoveryObserver`. It's opaque to the verifier, which only
+    // needs to know that reporting to it has no effect on anything
+    // the verified code depends on. Use
+    // `RecoveryObserverRef::none()` to not observe recovery at all,
+    // in which case each report is a single branch.
+    #[verifier::external_body]
+    #[derive(Clone, Copy)]
+    pub struct RecoveryObserverRef<'a> {
+        observer: Option<&'a dyn RecoveryObserver>,
+    }
+
+    impl<'a> RecoveryObserverRef<'a> {
+        // This function returns a reference to no observer.
+        #[verifier::external_body]
+        pub fn none() -> Self
+        {
+            Self { observer: None }
+        }
+
+        #[verifier::external_body]
+        pub fn report_region_too_small(&self, region_size: u64, min_size: u64)
+        {
+            if let Some(o) = self.observer {
+                o.on_region_too_small(region_size, min_size);
+            }
+        }
+
+        #[verifier::external_body]
+        pub fn report_crc_mismatch(&self, block: MetadataKind, addr: u64)
+        {
+            if let Some(o) = self.observer {
+                o.on_crc_mismatch(block, addr);
+            }
+        }
+
+        #[verifier::external_body]
+        pub fn report_cdb_invalid(&self, addr: u64, value_read: u64)
+        {
+            if let Some(o) = self.observer {
+                o.on_cdb_invalid(addr, value_read);
+            }
+        }
+
+        #[verifier::external_body]
+        pub fn report_program_guid_mismatch(&self, guid_expected: u128, guid_read: u128)
+        {
+            if let Some(o) = self.observer {
+                o.on_program_guid_mismatch(guid_expected, guid_read);
+            }
+        }
+
+        #[verifier::external_body]
+        pub fn report_version_unsupported(&self, version_number: u64, max_supported: u64)
+        {
+            if let Some(o) = self.observer {
+                o.on_version_unsupported(version_number, max_supported);
+            }
+        }
+
+        #[verifier::external_body]
+        pub fn report_region_size_mismatch(&self, region_size_expected: u64, region_size_read: u64)
+        {
+            if let Some(o) = self.observer {
+                o.on_region_size_mismatch(region_size_expected, region_size_read);
+            }
+        }
+
+        #[verifier::external_body]
+        pub fn report_log_id_mismatch(&self, log_id_expected: u128, log_id_read: u128)
+        {
+            if let Some(o) = self.observer {
+                o.on_log_id_mismatch(log_id_expected, log_id_read);
+            }
+        }
+
+        #[verifier::external_body]
+        pub fn report_invalid_metadata(&self, block: MetadataKind, reason: &str)
+        {
+            if let Some(o) = self.observer {
+                o.on_invalid_metadata(block, reason);
+            }
+        }
+
+        #[verifier::external_body]
+        pub fn report_recovered(&self, cdb: bool, head: u128, log_length: u64)
+        {
+            if let Some(o) = self.observer {
+                o.on_recovered(cdb, head, log_length);
+            }
+        }
+    }
+
+}
+
+// This trait is implemented by anything that wants to be told about
+// the decisions made while starting a log. Every method has a no-op
+// default, so an implementation only needs to override the ones it
+// cares about. Methods are called at most once per check, in the
+// order the checks are made, and recovery stops after the first
+// failing check.
+pub trait RecoveryObserver {
+    // The region is smaller than the smallest possible log region.
+    fn on_region_too_small(&self, _region_size: u64, _min_size: u64) {}
+
+    // The CRC stored for metadata block `block` doesn't match the
+    // metadata. `addr` is the address of the metadata block.
+    fn on_crc_mismatch(&self, _block: MetadataKind, _addr: u64) {}
+
+    // The corruption-detecting boolean at `addr` is neither
+    // `CDB_FALSE` nor `CDB_TRUE`.
+    fn on_cdb_invalid(&self, _addr: u64, _value_read: u64) {}
+
+    // The global metadata names a different program than the log.
+    fn on_program_guid_mismatch(&self, _guid_expected: u128, _guid_read: u128) {}
+
+    // The global metadata has a version number this code doesn't
+    // know how to recover from.
+    fn on_version_unsupported(&self, _version_number: u64, _max_supported: u64) {}
+
+    // The region metadata records a different region size than the
+    // region actually has.
+    fn on_region_size_mismatch(&self, _region_size_expected: u64, _region_size_read: u64) {}
+
+    // The region metadata records a different log ID than the one
+    // passed to `start`.
+    fn on_log_id_mismatch(&self, _log_id_expected: u128, _log_id_read: u128) {}
+
+    // Metadata block `block` passed its CRC check but is
+    // internally inconsistent, as described by `reason`.
+    fn on_invalid_metadata(&self, _block: MetadataKind, _reason: &str) {}
+
+    // Every check passed, and the log was recovered with the given
+    // CDB, head, and length.
+    fn on_recovered(&self, _cdb: bool, _head: u128, _log_length: u64) {}
+}
+
+impl<'a> RecoveryObserverRef<'a> {
+    // This function returns a reference to `observer`.
+    pub fn new(observer: &'a dyn RecoveryObserver) -> Self
+    {
+        Self { observer: Some(observer) }
+    }
+}
+
+impl<'a> From<Option<&'a dyn RecoveryObserver>> for RecoveryObserverRef<'a> {
+    fn from(observer: Option<&'a dyn RecoveryObserver>) -> Self
+    {
+        Self { observer }
+    }
+}
@@ -0,0 +1,149 @@
+//! This file contains a repair utility for recomputing the CRC of a
+//! piece of log metadata in place. It's only compiled when the
+//! `repair` feature is enabled.
+//!
+//! It's meant for repair tools, e.g., when an external tool has
+//! edited a metadata field and left its CRC stale. It rewrites the
+//! CRC to match whatever metadata bytes are currently stored, so it
+//! will just as happily mask real corruption of those bytes. Only use
+//! it when the metadata is known to be good.
+//!
+//! The code in this file is trusted (as indicated by the `_t.rs`
+//! suffix), so it must be audited. The repair goes through
+//! `WriteRestrictedPersistentMemoryRegion`, so the caller must supply
+//! a permission authorizing every state the CRC write can crash into.
+
+use crate::log::layout_v::*;
+pub use crate::log::observer_t::MetadataKind;
+use crate::pmem::pmemspec_t::*;
+use crate::pmem::wrpm_t::*;
+use builtin::*;
+use builtin_macros::*;
+use vstd::prelude::*;
+
+verus! {
+
+    // This specification function gives the position of the metadata
+    // identified by `which`, its length, and the position of its CRC.
+    pub open spec fn spec_metadata_and_crc_positions(which: MetadataKind) -> (u64, u64, u64)
+    {
+        match which {
+            MetadataKind::Global =>
+                (ABSOLUTE_POS_OF_GLOBAL_METADATA, LENGTH_OF_GLOBAL_METADATA, ABSOLUTE_POS_OF_GLOBAL_CRC),
+            MetadataKind::Region =>
+                (ABSOLUTE_POS_OF_REGION_METADATA, LENGTH_OF_REGION_METADATA, ABSOLUTE_POS_OF_REGION_CRC),
+            MetadataKind::Log { cdb: false } =>
+                (ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, LENGTH_OF_LOG_METADATA,
+                 ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE),
+            MetadataKind::Log { cdb: true } =>
+                (ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE, LENGTH_OF_LOG_METADATA,
+                 ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE),
+        }
+    }
+
+    pub exec fn metadata_and_crc_positions(which: MetadataKind) -> (result: (u64, u64, u64))
+        ensures
+            result == spec_metadata_and_crc_positions(which),
+            result.0 + result.1 <= result.2,
+    {
+        match which {
+            MetadataKind::Global =>
+                (ABSOLUTE_POS_OF_GLOBAL_METADATA, LENGTH_OF_GLOBAL_METADATA, ABSOLUTE_POS_OF_GLOBAL_CRC),
+            MetadataKind::Region =>
+                (ABSOLUTE_POS_OF_REGION_METADATA, LENGTH_OF_REGION_METADATA, ABSOLUTE_POS_OF_REGION_CRC),
+            MetadataKind::Log { cdb: false } =>
+                (ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, LENGTH_OF_LOG_METADATA,
+                 ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE),
+            MetadataKind::Log { cdb: true } =>
+                (ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE, LENGTH_OF_LOG_METADATA,
+                 ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE),
+        }
+    }
+
+    impl<Perm, PMRegion> WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>
+        where
+            Perm: CheckPermission<Seq<u8>>,
+            PMRegion: PersistentMemoryRegion
+    {
+        // This executable function reads the metadata identified by
+        // `which`, computes its CRC with `bytes_crc`, and writes that
+        // CRC to the corresponding CRC location. It flushes before
+        // reading and after writing.
+        //
+        // `which` -- which metadata's CRC to recompute
+        //
+        // `perm` -- permission to write the CRC. Since the CRC is
+        // computed from possibly corrupted bytes, the caller must
+        // prove that `perm` authorizes every crash state of writing
+        // any CRC there.
+        //
+        // Returns `Err(PmemError::AccessOutOfRange)`, changing
+        // nothing, if the region is too small to hold the metadata
+        // and its CRC. If a flush fails, it returns that error, and
+        // the CRC may or may not have been written.
+        pub exec fn repair_metadata_crc(
+            &mut self,
+            which: MetadataKind,
+            perm: Tracked<&Perm>,
+        ) -> (result: Result<(), PmemError>)
+            requires
+                old(self).inv(),
+                ({
+                    let (_, _, crc_pos) = spec_metadata_and_crc_positions(which);
+                    forall |crc: Seq<u8>, s| crc.len() == CRC_SIZE &&
+                        #[trigger] old(self)@.flush().write(crc_pos as int, crc).can_crash_as(s)
+                        ==> perm@.check_permission(s)
+                }),
+            ensures
+                self.inv(),
+                self.constants() == old(self).constants(),
+                ({
+                    let (metadata_pos, metadata_len, crc_pos) = spec_metadata_and_crc_positions(which);
+                    let metadata = old(self)@.flush().committed().subrange(metadata_pos as int,
+                                                                           metadata_pos + metadata_len);
+                    match result {
+                        Ok(()) => exists |crc: Seq<u8>| {
+                            &&& crc.len() == CRC_SIZE
+                            &&& #[trigger] old(self)@.flush().write(crc_pos as int, crc).flush() == self@
+                            &&& old(self).constants().impervious_to_corruption ==> crc == spec_crc_bytes(metadata)
+                        },
+                        Err(PmemError::AccessOutOfRange) => {
+                            &&& old(self)@.len() < crc_pos + CRC_SIZE
+                            &&& self@ == old(self)@
+                        },
+                        Err(PmemError::FlushFailed) => crc_pos + CRC_SIZE <= old(self)@.len(),
+                        Err(_) => false,
+                    }
+                }),
+        {
+            let (metadata_pos, metadata_len, crc_pos) = metadata_and_crc_positions(which);
+
+            if self.get_pm_region_ref().get_region_size() < crc_pos + CRC_SIZE {
+                return Err(PmemError::AccessOutOfRange);
+            }
+
+            // Flush first so there are no outstanding writes to the
+            // bytes we're about to read and write.
+            match self.flush() {
+                Ok(()) => {},
+                Err(_) => return Err(PmemError::FlushFailed),
+            }
+            let ghost flushed_state = self@;
+            assert(flushed_state.no_outstanding_writes_in_range(metadata_pos as int, crc_pos + CRC_SIZE));
+
+            let metadata_bytes = self.get_pm_region_ref().read(metadata_pos, metadata_len);
+            let crc = bytes_crc(metadata_bytes.as_slice());
+            self.write(crc_pos, crc.as_slice(), perm);
+            match self.flush() {
+                Ok(()) => {},
+                Err(_) => return Err(PmemError::FlushFailed),
+            }
+
+            proof {
+                assert(old(self)@.flush().write(crc_pos as int, crc@).flush() == self@);
+            }
+            Ok(())
+        }
+    }
+
+}
@@ -9,14 +9,22 @@ use crate::log::layout_v::*;
 use crate::log::logimpl_t::LogErr;
 use crate::log::logspec_t::AbstractLogState;
 use crate::pmem::pmemspec_t::*;
+use crate::pmem::pmemutil_v::append_serialized;
 use crate::pmem::serialization_t::*;
 use builtin::*;
 use builtin_macros::*;
 use vstd::bytes::*;
 use vstd::prelude::*;
+use vstd::slice::*;
 
 verus! {
 
+    // This is the size of each write `zero_log_area` issues when
+    // zeroing the log area. Larger writes mean fewer calls into the
+    // persistent memory layer, at the cost of a larger temporary
+    // buffer of zeros.
+    const ZEROING_CHUNK_SIZE: u64 = 65536;
+
     // This function evaluates whether memory was correctly set up on
     // a region. It's a helpful specification function for use in
     // later functions in this file.
@@ -64,6 +72,7 @@ verus! {
     //
     // `region_size`: how big this region is
     // `log_id`: the GUID of the log it's being used for
+    // `append_alignment`: the append alignment to record
     //
     // It also needs the parameter `pm_region` that gives the
     // persistent memory region for us to write to.
@@ -81,10 +90,11 @@ verus! {
         pm_region: &mut PMRegion,
         region_size: u64,
         log_id: u128,
+        append_alignment: u64,
     )
         requires
             old(pm_region).inv(),
-            old(pm_region)@.no_outstanding_writes(),
+            old(pm_region)@.no_outstanding_writes_in_range(0, ABSOLUTE_POS_OF_LOG_AREA as int),
             old(pm_region)@.len() == region_size,
             region_size >= ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE,
         ensures
@@ -93,50 +103,61 @@ verus! {
             memory_correctly_set_up_on_region(
                 pm_region@.flush().committed(), // it'll be correct after the next flush
                 region_size, log_id),
+            // The log area isn't touched.
+            forall |i: int| ABSOLUTE_POS_OF_LOG_AREA <= i < region_size ==>
+                #[trigger] pm_region@.state[i] == old(pm_region)@.state[i],
     {
-        // Initialize global metadata and compute its CRC
-        // TODO: might be faster to write to PM first, then compute CRC on that?
+        // Initialize global metadata
         let global_metadata = GlobalMetadata {
             program_guid: LOG_PROGRAM_GUID,
             version_number: LOG_PROGRAM_VERSION_NUMBER,
             length_of_region_metadata: LENGTH_OF_REGION_METADATA,
         };
-        let global_crc = calculate_crc(&global_metadata);
 
-        // Initialize region metadata and compute its CRC
+        // Initialize region metadata
         let region_metadata = RegionMetadata {
             region_size,
             log_id,
             log_area_len: region_size - ABSOLUTE_POS_OF_LOG_AREA,
+            append_alignment,
+            _padding: 0,
         };
-        let region_crc = calculate_crc(&region_metadata);
 
         // Obtain the initial CDB value
         let cdb = CDB_FALSE;
 
-        // Initialize log metadata and compute its CRC
+        // Initialize log metadata
         let log_metadata = LogMetadata {
             head: 0,
             _padding: 0,
             log_length: 0
         };
-        let log_crc = calculate_crc(&log_metadata);
 
-        // Write all metadata structures and their CRCs to memory
-        // TODO: put these all in a serializable structure so you can write them with one line?
+        // Clear the writer marker, so that no writer is present
+        let no_writer: u64 = 0;
+
+        // Write all metadata structures and their CRCs to memory.
+        // The global metadata, region metadata, CDB, and log
+        // metadata for CDB false are laid out back to back, each
+        // metadata structure followed by its CRC, so we write them by
+        // advancing a cursor.
         proof {
             u64::lemma_auto_serialized_len();
             GlobalMetadata::lemma_auto_serialized_len();
             RegionMetadata::lemma_auto_serialized_len();
             LogMetadata::lemma_auto_serialized_len();
         }
-        pm_region.serialize_and_write(ABSOLUTE_POS_OF_GLOBAL_METADATA, &global_metadata);
-        pm_region.serialize_and_write(ABSOLUTE_POS_OF_GLOBAL_CRC, &global_crc);
-        pm_region.serialize_and_write(ABSOLUTE_POS_OF_REGION_METADATA, &region_metadata);
-        pm_region.serialize_and_write(ABSOLUTE_POS_OF_REGION_CRC, &region_crc);
-        pm_region.serialize_and_write(ABSOLUTE_POS_OF_LOG_CDB, &cdb);
-        pm_region.serialize_and_write(ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, &log_metadata);
-        pm_region.serialize_and_write(ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE, &log_crc);
+        let cursor = append_serialized(pm_region, ABSOLUTE_POS_OF_GLOBAL_METADATA, &global_metadata);
+        assert(cursor == ABSOLUTE_POS_OF_REGION_METADATA);
+        let cursor = append_serialized(pm_region, cursor, &region_metadata);
+        assert(cursor == ABSOLUTE_POS_OF_LOG_CDB);
+        pm_region.serialize_and_write(cursor, &cdb);
+        assert(cursor + u64::spec_serialized_len() == ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE);
+        let cursor = append_serialized(pm_region, ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, &log_metadata);
+        assert(cursor == ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE);
+        pm_region.serialize_and_write(ABSOLUTE_POS_OF_WRITER_MARKER + RELATIVE_POS_OF_WRITER_MARKER_PID, &no_writer);
+        pm_region.serialize_and_write(ABSOLUTE_POS_OF_WRITER_MARKER + RELATIVE_POS_OF_WRITER_MARKER_TIMESTAMP,
+                                      &no_writer);
 
         proof {
             // We want to prove that if we parse the result of
@@ -158,18 +179,18 @@ verus! {
             assert(extract_bytes(mem, ABSOLUTE_POS_OF_GLOBAL_METADATA as int, LENGTH_OF_GLOBAL_METADATA as int)
                    =~= global_metadata.spec_serialize());
             assert(extract_bytes(mem, ABSOLUTE_POS_OF_GLOBAL_CRC as int, CRC_SIZE as int)
-                   =~= global_crc.spec_serialize());
+                   =~= global_metadata.spec_crc().spec_serialize());
             assert(extract_bytes(mem, ABSOLUTE_POS_OF_REGION_METADATA as int, LENGTH_OF_REGION_METADATA as int)
                    =~= region_metadata.spec_serialize());
             assert(extract_bytes(mem, ABSOLUTE_POS_OF_REGION_CRC as int, CRC_SIZE as int)
-                   =~= region_crc.spec_serialize());
+                   =~= region_metadata.spec_crc().spec_serialize());
             assert(extract_bytes(mem, ABSOLUTE_POS_OF_LOG_CDB as int, CRC_SIZE as int)
                    =~= CDB_FALSE.spec_serialize());
             assert(extract_bytes(mem, ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE as int,
                                  LENGTH_OF_LOG_METADATA as int)
                    =~= log_metadata.spec_serialize());
             assert (extract_bytes(mem, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE as int, CRC_SIZE as int)
-                    =~= log_crc.spec_serialize());
+                    =~= log_metadata.spec_crc().spec_serialize());
 
             // Part 2:
             // Prove that if we parse the little-endian-encoded value
@@ -181,6 +202,83 @@ verus! {
         }
     }
 
+    // This executable function writes zeros to the entire log area
+    // of a region, i.e., to `[ABSOLUTE_POS_OF_LOG_AREA, region_size)`.
+    // This way, stale bytes from a previous use of the region can't
+    // be mistaken for log data. It uses large chunked writes and
+    // doesn't flush; the caller is expected to do a single flush
+    // afterward.
+    //
+    // `region_size`: how big this region is
+    //
+    // The main postcondition is that, after the next flush, every
+    // byte of the log area is zero. Bytes before the log area are
+    // left untouched.
+    fn zero_log_area<PMRegion: PersistentMemoryRegion>(
+        pm_region: &mut PMRegion,
+        region_size: u64,
+    )
+        requires
+            old(pm_region).inv(),
+            old(pm_region)@.len() == region_size,
+            region_size >= ABSOLUTE_POS_OF_LOG_AREA,
+            old(pm_region)@.no_outstanding_writes_in_range(ABSOLUTE_POS_OF_LOG_AREA as int, region_size as int),
+        ensures
+            pm_region.inv(),
+            pm_region.constants() == old(pm_region).constants(),
+            pm_region@.len() == old(pm_region)@.len(),
+            forall |i: int| 0 <= i < ABSOLUTE_POS_OF_LOG_AREA ==>
+                #[trigger] pm_region@.state[i] == old(pm_region)@.state[i],
+            forall |i: int| ABSOLUTE_POS_OF_LOG_AREA <= i < region_size ==>
+                #[trigger] pm_region@.state[i].flush_byte() == 0,
+    {
+        // Build a buffer of zeros to write from. It needn't be larger
+        // than the log area.
+
+        let log_area_len = region_size - ABSOLUTE_POS_OF_LOG_AREA;
+        let chunk_len = if log_area_len < ZEROING_CHUNK_SIZE { log_area_len } else { ZEROING_CHUNK_SIZE };
+        let mut zeros: Vec<u8> = Vec::new();
+        while (zeros.len() as u64) < chunk_len
+            invariant
+                zeros.len() <= chunk_len,
+                forall |j: int| 0 <= j < zeros@.len() ==> #[trigger] zeros@[j] == 0,
+        {
+            zeros.push(0);
+        }
+
+        // Write the zeros one chunk at a time, starting at the
+        // beginning of the log area.
+
+        let mut addr = ABSOLUTE_POS_OF_LOG_AREA;
+        while addr < region_size
+            invariant
+                pm_region.inv(),
+                pm_region.constants() == old(pm_region).constants(),
+                pm_region@.len() == region_size,
+                ABSOLUTE_POS_OF_LOG_AREA <= addr <= region_size,
+                zeros@.len() == chunk_len,
+                chunk_len > 0 || addr == region_size,
+                chunk_len <= region_size - ABSOLUTE_POS_OF_LOG_AREA,
+                forall |j: int| 0 <= j < zeros@.len() ==> #[trigger] zeros@[j] == 0,
+                forall |i: int| 0 <= i < ABSOLUTE_POS_OF_LOG_AREA ==>
+                    #[trigger] pm_region@.state[i] == old(pm_region)@.state[i],
+                forall |i: int| addr <= i < region_size ==>
+                    #[trigger] pm_region@.state[i] == old(pm_region)@.state[i],
+                forall |i: int| ABSOLUTE_POS_OF_LOG_AREA <= i < addr ==>
+                    #[trigger] pm_region@.state[i].flush_byte() == 0,
+                old(pm_region)@.no_outstanding_writes_in_range(ABSOLUTE_POS_OF_LOG_AREA as int, region_size as int),
+        {
+            let len = if region_size - addr < chunk_len { region_size - addr } else { chunk_len };
+            let chunk = slice_subrange(zeros.as_slice(), 0, len as usize);
+            assert(pm_region@.no_outstanding_writes_in_range(addr as int, addr + len)) by {
+                assert(forall |i: int| addr <= i < addr + len ==>
+                       #[trigger] pm_region@.state[i] == old(pm_region)@.state[i]);
+            }
+            pm_region.write(addr, chunk);
+            addr = addr + len;
+        }
+    }
+
     // This exported executable function writes to persistent memory
     // all the metadata necessary to set up a log. To do so, it
     // needs some parameters:
@@ -193,6 +291,9 @@ verus! {
     //
     // `log_id`: the GUID of the log it's being used for
     //
+    // `append_alignment`: the append alignment to record in the
+    // region metadata, which recovery doesn't interpret
+    //
     // It also needs the parameter `pm_region` that gives the
     // persistent memory region for us to write to.
     //
@@ -207,12 +308,21 @@ verus! {
     // the resulting recovered abstract state will be the valid
     // initial value
     // `AbstractLogState::initialize(log_capacity)`.
+    //
+    // It also zeroes the log area, so every byte of it is zero
+    // afterward. That isn't needed for recovery, since the log
+    // metadata says the log is empty, but it keeps stale bytes from
+    // a previous use of the region from lingering.
+    //
+    // These postconditions only hold if the final flush succeeds. If
+    // it fails, this returns the error from that flush.
     pub fn write_setup_metadata<PMRegion: PersistentMemoryRegion>(
         pm_region: &mut PMRegion,
         region_size: u64,
         Ghost(log_capacity): Ghost<u64>,
         log_id: u128,
-    )
+        append_alignment: u64,
+    ) -> (result: Result<(), PmemError>)
         requires
             old(pm_region).inv(),
             old(pm_region)@.len() == region_size,
@@ -223,10 +333,23 @@ verus! {
             pm_region.inv(),
             pm_region.constants() == old(pm_region).constants(),
             pm_region@.len() == old(pm_region)@.len(),
-            pm_region@.no_outstanding_writes(),
-            recover_state(pm_region@.committed(), log_id) == Some(AbstractLogState::initialize(log_capacity as int)),
+            match result {
+                Ok(()) => {
+                    &&& pm_region@.no_outstanding_writes()
+                    &&& recover_state(pm_region@.committed(), log_id) ==
+                           Some(AbstractLogState::initialize(log_capacity as int))
+                    &&& forall |i: int| ABSOLUTE_POS_OF_LOG_AREA <= i < region_size ==>
+                           #[trigger] pm_region@.committed()[i] == 0
+                },
+                Err(_) => true,
+            },
     {
-        write_setup_metadata_to_region(pm_region, region_size, log_id);
+        // Zero the log area first. This doesn't touch the metadata
+        // area, so it leaves that free of outstanding writes for
+        // `write_setup_metadata_to_region`.
+
+        zero_log_area(pm_region, region_size);
+        write_setup_metadata_to_region(pm_region, region_size, log_id, append_alignment);
 
         proof {
             // First, establish that recovering after a flush will get
@@ -245,9 +368,32 @@ verus! {
             // Second, establish that the flush we're about to do
             // won't change regions' lengths.
             assert(pm_region@.len() == flushed_region.len());
+
+            // Third, establish that the flush will leave the log
+            // area zeroed.
+            assert(forall |i: int| ABSOLUTE_POS_OF_LOG_AREA <= i < region_size ==>
+                   #[trigger] flushed_region.committed()[i] == pm_region@.state[i].flush_byte());
         }
 
         pm_region.flush()
     }
 
+
+    // This test hook overwrites the corruption-detecting boolean with
+    // the canonical encoding of `value`, i.e., `CDB_TRUE` or
+    // `CDB_FALSE`, and flushes. It lets tests put a region in either
+    // CDB state without going through `commit`. Since it bypasses the
+    // write restrictions that keep the region recoverable, it only
+    // exists in test builds.
+    #[cfg(test)]
+    #[verifier::external_body]
+    pub fn force_set_cdb<PMRegion: PersistentMemoryRegion>(
+        pm_region: &mut PMRegion,
+        value: bool,
+    ) -> Result<(), PmemError>
+    {
+        let cdb = if value { CDB_TRUE } else { CDB_FALSE };
+        pm_region.serialize_and_write(ABSOLUTE_POS_OF_LOG_CDB, &cdb);
+        pm_region.flush()
+    }
 }
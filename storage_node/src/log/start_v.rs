@@ -11,7 +11,8 @@ use crate::log::layout_v::*;
 use crate::log::logimpl_t::LogErr;
 use crate::log::logimpl_v::LogInfo;
 use crate::log::logspec_t::AbstractLogState;
-use crate::pmem::pmemspec_t::{PersistentMemoryRegion, CRC_SIZE};
+use crate::log::observer_t::{MetadataKind, RecoveryObserverRef};
+use crate::pmem::pmemspec_t::{PersistentMemoryRegion, CDB_FALSE, CDB_TRUE, CRC_SIZE};
 use crate::pmem::pmemutil_v::{check_cdb, check_crc, check_crc_deserialized};
 use crate::pmem::serialization_t::*;
 use builtin::*;
@@ -28,13 +29,18 @@ verus! {
     //
     // `pm_region` -- the persistent-memory region to read from
     //
+    // `observer` -- told if the CDB can't be read
+    //
     // The result is a `Result<bool, LogErr>` with the following meanings:
     //
     // `Err(LogErr::CRCMismatch)` -- The CDB couldn't be read due
     // to a CRC error.
     //
     // `Ok(b)` -- The CDB could be read and represents the boolean `b`.
-    pub fn read_cdb<PMRegion: PersistentMemoryRegion>(pm_region: &PMRegion) -> (result: Result<bool, LogErr>)
+    pub fn read_cdb<PMRegion: PersistentMemoryRegion>(
+        pm_region: &PMRegion,
+        observer: &RecoveryObserverRef,
+    ) -> (result: Result<bool, LogErr>)
         requires
             pm_region.inv(),
             recover_cdb(pm_region@.committed()).is_Some(),
@@ -58,8 +64,365 @@ verus! {
                                Ghost(ABSOLUTE_POS_OF_LOG_CDB));
         match result {
             Some(b) => Ok(b),
-            None => Err(LogErr::CRCMismatch)
+            None => {
+                observer.report_cdb_invalid(ABSOLUTE_POS_OF_LOG_CDB, *log_cdb);
+                Err(LogErr::CRCMismatch)
+            },
+        }
+    }
+
+    // This exported function is like `read_cdb`, except that it
+    // doesn't require the caller to already know that the region
+    // holds a log. So it can be used to probe a region of unknown
+    // provenance.
+    //
+    // `pm_region` -- the persistent-memory region to read from
+    //
+    // The result is a `Result<Option<bool>, LogErr>` with the
+    // following meanings:
+    //
+    // `Ok(None)` -- The region isn't recognizable as a log region:
+    // it's too small, its global metadata and CRC are all zero, or
+    // its global metadata has a valid CRC but the wrong program GUID
+    // or an unsupported version number. If the memory is impervious
+    // to corruption, this means `recover_cdb` would return `None`.
+    //
+    // `Err(LogErr::CRCMismatch)` -- The global metadata has a bad
+    // CRC but isn't all zero, so it may be a corrupted log region;
+    // or the region looks like a log region, but the CDB couldn't
+    // be read due to a CRC error.
+    //
+    // `Ok(Some(b))` -- The CDB could be read and represents the
+    // boolean `b`.
+    pub fn try_read_cdb<PMRegion: PersistentMemoryRegion>(pm_region: &PMRegion)
+                                                          -> (result: Result<Option<bool>, LogErr>)
+        requires
+            pm_region.inv(),
+            pm_region@.no_outstanding_writes(),
+        ensures
+            ({
+                let cdb = recover_cdb(pm_region@.committed());
+                match result {
+                    Ok(Some(b)) => cdb.is_Some() ==> cdb == Some(b),
+                    Ok(None) => cdb.is_Some() ==> !pm_region.constants().impervious_to_corruption,
+                    Err(LogErr::CRCMismatch) => cdb.is_Some() ==> !pm_region.constants().impervious_to_corruption,
+                    _ => false,
+                }
+            })
+    {
+        let ghost mem = pm_region@.committed();
+        let ghost cdb = recover_cdb(mem);
+
+        // If the region is too small to hold the global metadata,
+        // its CRC, and the CDB, it can't be a log region.
+
+        let region_size = pm_region.get_region_size();
+        if region_size < ABSOLUTE_POS_OF_LOG_CDB + CRC_SIZE {
+            assert(cdb.is_None());
+            return Ok(None);
+        }
+
+        // Read the global metadata and its CRC. If the CRC doesn't
+        // match, we can't tell whether the region was never a log
+        // region or is a corrupted one. The only case we treat as
+        // unrecognizable is all-zero bytes, as in a freshly created
+        // file; anything else is reported as a CRC mismatch so that
+        // callers don't overwrite a log that might be recoverable.
+
+        let global_metadata = pm_region.read_and_deserialize::<GlobalMetadata>(ABSOLUTE_POS_OF_GLOBAL_METADATA);
+        let global_crc = pm_region.read_and_deserialize(ABSOLUTE_POS_OF_GLOBAL_CRC);
+        if !check_crc_deserialized(global_metadata, global_crc,
+                      Ghost(mem), Ghost(pm_region.constants().impervious_to_corruption),
+                      pm_region.known_impervious_to_corruption(),
+                      Ghost(ABSOLUTE_POS_OF_GLOBAL_METADATA), Ghost(LENGTH_OF_GLOBAL_METADATA),
+                      Ghost(ABSOLUTE_POS_OF_GLOBAL_CRC)) {
+            let bytes = pm_region.read(ABSOLUTE_POS_OF_GLOBAL_METADATA, ABSOLUTE_POS_OF_GLOBAL_CRC + CRC_SIZE);
+            let mut i: usize = 0;
+            while i < bytes.len()
+                invariant
+                    i <= bytes.len(),
+            {
+                if bytes[i] != 0 {
+                    return Err(LogErr::CRCMismatch);
+                }
+                i = i + 1;
+            }
+            return Ok(None);
+        }
+
+        if global_metadata.program_guid != LOG_PROGRAM_GUID {
+            assert(cdb.is_None());
+            return Ok(None);
+        }
+
+        if global_metadata.version_number != LOG_PROGRAM_VERSION_NUMBER {
+            assert(cdb.is_None());
+            return Ok(None);
+        }
+
+        // The global metadata says this is a log region, so read the
+        // CDB. We can't use `check_cdb` since we don't know that the
+        // stored CDB is valid, so we do its check inline.
+
+        let log_cdb = pm_region.read_and_deserialize::<u64>(ABSOLUTE_POS_OF_LOG_CDB);
+        let cdb_val = *log_cdb;
+        proof {
+            // If recovery would succeed, the stored CDB is valid, so
+            // if we read a valid CDB we can use
+            // `axiom_corruption_detecting_boolean_serialized` to
+            // conclude it wasn't corrupted.
+            if {
+                &&& cdb.is_Some()
+                &&& !pm_region.constants().impervious_to_corruption
+                &&& (cdb_val == CDB_FALSE || cdb_val == CDB_TRUE)
+            } {
+                let true_cdb = u64::spec_deserialize(
+                    mem.subrange(ABSOLUTE_POS_OF_LOG_CDB as int, ABSOLUTE_POS_OF_LOG_CDB + CRC_SIZE));
+                axiom_corruption_detecting_boolean_serialized(cdb_val, true_cdb, ABSOLUTE_POS_OF_LOG_CDB as int);
+            }
+        }
+
+        if cdb_val == CDB_FALSE {
+            Ok(Some(false))
+        }
+        else if cdb_val == CDB_TRUE {
+            Ok(Some(true))
         }
+        else {
+            Err(LogErr::CRCMismatch)
+        }
+    }
+
+    // A `MetadataBundle` holds every metadata block of a log region,
+    // as read by `read_all_metadata`, along with each block's CRC and
+    // the CDB used to pick the log metadata.
+    pub struct MetadataBundle {
+        pub global: GlobalMetadata,
+        pub region: RegionMetadata,
+        pub log: LogMetadata,
+        pub global_crc: u64,
+        pub region_crc: u64,
+        pub log_crc: u64,
+        pub cdb: bool,
+    }
+
+    // This exported function reads and CRC-checks the global metadata,
+    // the region metadata, and the log metadata selected by `cdb`, so
+    // tools inspecting a region don't have to compute each offset. It
+    // doesn't check that the metadata is valid for any particular log.
+    //
+    // `pm_region` -- the persistent memory region to read from
+    //
+    // `cdb` -- the corruption-detection boolean
+    //
+    // The result is a `Result<MetadataBundle, LogErr>` with the
+    // following meanings:
+    //
+    // `Ok(bundle)` -- Every block was read and matches its CRC. Each
+    // block whose stored CRC is correct is exactly the block stored
+    // in persistent memory.
+    //
+    // `Err(LogErr::CRCMismatch)` -- Some block didn't match its CRC.
+    // If every stored CRC is correct, this means the memory isn't
+    // impervious to corruption.
+    //
+    // `Err(LogErr::StartFailedDueToInvalidMemoryContents)` -- The
+    // region is too small to hold the metadata.
+    pub fn read_all_metadata<PMRegion: PersistentMemoryRegion>(
+        pm_region: &PMRegion,
+        cdb: bool,
+    ) -> (result: Result<MetadataBundle, LogErr>)
+        requires
+            pm_region.inv(),
+            pm_region@.no_outstanding_writes(),
+        ensures
+            ({
+                let mem = pm_region@.committed();
+                let global = deserialize_global_metadata(mem);
+                let region = deserialize_region_metadata(mem);
+                let log = deserialize_log_metadata(mem, cdb);
+                let crcs_correct = {
+                    &&& deserialize_global_crc(mem) == global.spec_crc()
+                    &&& deserialize_region_crc(mem) == region.spec_crc()
+                    &&& deserialize_log_crc(mem, cdb) == log.spec_crc()
+                };
+                match result {
+                    Ok(bundle) => {
+                        &&& bundle.cdb == cdb
+                        &&& deserialize_global_crc(mem) == global.spec_crc() ==>
+                               bundle.global == global && bundle.global_crc == deserialize_global_crc(mem)
+                        &&& deserialize_region_crc(mem) == region.spec_crc() ==>
+                               bundle.region == region && bundle.region_crc == deserialize_region_crc(mem)
+                        &&& deserialize_log_crc(mem, cdb) == log.spec_crc() ==>
+                               bundle.log == log && bundle.log_crc == deserialize_log_crc(mem, cdb)
+                    },
+                    Err(LogErr::CRCMismatch) => crcs_correct ==> !pm_region.constants().impervious_to_corruption,
+                    Err(LogErr::StartFailedDueToInvalidMemoryContents) => mem.len() < ABSOLUTE_POS_OF_LOG_AREA,
+                    _ => false,
+                }
+            })
+    {
+        let ghost mem = pm_region@.committed();
+
+        let region_size = pm_region.get_region_size();
+        if region_size < ABSOLUTE_POS_OF_LOG_AREA {
+            return Err(LogErr::StartFailedDueToInvalidMemoryContents);
+        }
+
+        let global_metadata = pm_region.read_and_deserialize::<GlobalMetadata>(ABSOLUTE_POS_OF_GLOBAL_METADATA);
+        let global_crc = pm_region.read_and_deserialize::<u64>(ABSOLUTE_POS_OF_GLOBAL_CRC);
+        if !check_crc_deserialized(global_metadata, global_crc,
+                      Ghost(mem), Ghost(pm_region.constants().impervious_to_corruption),
+                      pm_region.known_impervious_to_corruption(),
+                      Ghost(ABSOLUTE_POS_OF_GLOBAL_METADATA), Ghost(LENGTH_OF_GLOBAL_METADATA),
+                      Ghost(ABSOLUTE_POS_OF_GLOBAL_CRC)) {
+            return Err(LogErr::CRCMismatch);
+        }
+
+        let region_metadata = pm_region.read_and_deserialize::<RegionMetadata>(ABSOLUTE_POS_OF_REGION_METADATA);
+        let region_crc = pm_region.read_and_deserialize::<u64>(ABSOLUTE_POS_OF_REGION_CRC);
+        if !check_crc_deserialized(region_metadata, region_crc,
+                      Ghost(mem), Ghost(pm_region.constants().impervious_to_corruption),
+                      pm_region.known_impervious_to_corruption(),
+                      Ghost(ABSOLUTE_POS_OF_REGION_METADATA), Ghost(LENGTH_OF_REGION_METADATA),
+                      Ghost(ABSOLUTE_POS_OF_REGION_CRC)) {
+            return Err(LogErr::CRCMismatch);
+        }
+
+        let log_metadata_pos = if cdb { ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE }
+                                  else { ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE };
+        let log_crc_pos = if cdb { ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE }
+                             else { ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE };
+        let log_metadata = pm_region.read_and_deserialize::<LogMetadata>(log_metadata_pos);
+        let log_crc = pm_region.read_and_deserialize::<u64>(log_crc_pos);
+        if !check_crc_deserialized(log_metadata, log_crc, Ghost(mem),
+                                   Ghost(pm_region.constants().impervious_to_corruption),
+                                   pm_region.known_impervious_to_corruption(),
+                                   Ghost(log_metadata_pos), Ghost(LENGTH_OF_LOG_METADATA), Ghost(log_crc_pos)) {
+            return Err(LogErr::CRCMismatch);
+        }
+
+        // The metadata structs aren't `Copy`, so copy them field by
+        // field out of persistent memory.
+
+        Ok(MetadataBundle {
+            global: GlobalMetadata {
+                version_number: global_metadata.version_number,
+                length_of_region_metadata: global_metadata.length_of_region_metadata,
+                program_guid: global_metadata.program_guid,
+            },
+            region: RegionMetadata {
+                region_size: region_metadata.region_size,
+                log_area_len: region_metadata.log_area_len,
+                log_id: region_metadata.log_id,
+                append_alignment: region_metadata.append_alignment,
+                _padding: region_metadata._padding,
+            },
+            log: LogMetadata {
+                log_length: log_metadata.log_length,
+                _padding: log_metadata._padding,
+                head: log_metadata.head,
+            },
+            global_crc: *global_crc,
+            region_crc: *region_crc,
+            log_crc: *log_crc,
+            cdb,
+        })
+    }
+
+    // This spec function describes what `read_log_metadata_slot`
+    // guarantees about reading the log metadata slot selected by
+    // `cdb` from `mem`.
+    pub open spec fn log_metadata_slot_read_correctly(
+        mem: Seq<u8>,
+        impervious_to_corruption: bool,
+        cdb: bool,
+        result: Result<LogMetadata, LogErr>,
+    ) -> bool
+    {
+        let log = deserialize_log_metadata(mem, cdb);
+        let crc_correct = deserialize_log_crc(mem, cdb) == log.spec_crc();
+        match result {
+            Ok(log_metadata) => crc_correct ==> log_metadata == log,
+            Err(LogErr::CRCMismatch) => crc_correct ==> !impervious_to_corruption,
+            Err(LogErr::StartFailedDueToInvalidMemoryContents) => mem.len() < ABSOLUTE_POS_OF_LOG_AREA,
+            _ => false,
+        }
+    }
+
+    // This function reads the log metadata slot selected by `cdb` and
+    // checks it against its CRC. Unlike `read_log_variables`, it
+    // doesn't check that the slot's contents make sense.
+    fn read_log_metadata_slot<PMRegion: PersistentMemoryRegion>(
+        pm_region: &PMRegion,
+        cdb: bool,
+    ) -> (result: Result<LogMetadata, LogErr>)
+        requires
+            pm_region.inv(),
+            pm_region@.no_outstanding_writes(),
+        ensures
+            log_metadata_slot_read_correctly(pm_region@.committed(),
+                                             pm_region.constants().impervious_to_corruption, cdb, result),
+    {
+        let ghost mem = pm_region@.committed();
+
+        if pm_region.get_region_size() < ABSOLUTE_POS_OF_LOG_AREA {
+            return Err(LogErr::StartFailedDueToInvalidMemoryContents);
+        }
+
+        let log_metadata_pos = if cdb { ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE }
+                                  else { ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE };
+        let log_crc_pos = if cdb { ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE }
+                             else { ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE };
+        let log_metadata = pm_region.read_and_deserialize::<LogMetadata>(log_metadata_pos);
+        let log_crc = pm_region.read_and_deserialize::<u64>(log_crc_pos);
+        if !check_crc_deserialized(log_metadata, log_crc, Ghost(mem),
+                                   Ghost(pm_region.constants().impervious_to_corruption),
+                                   pm_region.known_impervious_to_corruption(),
+                                   Ghost(log_metadata_pos), Ghost(LENGTH_OF_LOG_METADATA), Ghost(log_crc_pos)) {
+            return Err(LogErr::CRCMismatch);
+        }
+
+        Ok(LogMetadata {
+            log_length: log_metadata.log_length,
+            _padding: log_metadata._padding,
+            head: log_metadata.head,
+        })
+    }
+
+    // This exported function reads both log metadata slots, the one
+    // for CDB false and the one for CDB true, regardless of which one
+    // the CDB currently selects. It's a diagnostic for debugging the
+    // commit protocol: a commit interrupted by a crash leaves the
+    // inactive slot partly written, which shows up here as a CRC
+    // mismatch in that slot.
+    //
+    // `pm_region` -- the persistent memory region to read from
+    //
+    // The result is a pair of `Result<LogMetadata, LogErr>`, the
+    // first for the CDB-false slot and the second for the CDB-true
+    // slot, each with the following meanings:
+    //
+    // `Ok(log_metadata)` -- The slot matches its CRC. If the stored
+    // CRC is correct, `log_metadata` is exactly what's stored.
+    //
+    // `Err(LogErr::CRCMismatch)` -- The slot doesn't match its CRC.
+    //
+    // `Err(LogErr::StartFailedDueToInvalidMemoryContents)` -- The
+    // region is too small to hold the metadata.
+    pub fn read_both_log_metadata_slots<PMRegion: PersistentMemoryRegion>(
+        pm_region: &PMRegion,
+    ) -> (result: (Result<LogMetadata, LogErr>, Result<LogMetadata, LogErr>))
+        requires
+            pm_region.inv(),
+            pm_region@.no_outstanding_writes(),
+        ensures
+            log_metadata_slot_read_correctly(pm_region@.committed(),
+                                             pm_region.constants().impervious_to_corruption, false, result.0),
+            log_metadata_slot_read_correctly(pm_region@.committed(),
+                                             pm_region.constants().impervious_to_corruption, true, result.1),
+    {
+        (read_log_metadata_slot(pm_region, false), read_log_metadata_slot(pm_region, true))
     }
 
     // This function reads the log information for a single log from
@@ -71,6 +434,9 @@ verus! {
     //
     // `cdb` -- the corruption-detection boolean
     //
+    // `observer` -- told the outcome of each check made, up to and
+    // including the first one that fails
+    //
     // The result is a `Result<LogInfo, LogErr>` with the following meanings:
     //
     // `Ok(log_info)` -- The information `log_info` has been
@@ -103,6 +469,7 @@ verus! {
         pm_region: &PMRegion,
         log_id: u128,
         cdb: bool,
+        observer: &RecoveryObserverRef,
     ) -> (result: Result<LogInfo, LogErr>)
         requires
             pm_region.inv(),
@@ -130,6 +497,7 @@ verus! {
         let region_size = pm_region.get_region_size();
         if region_size < ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_region_too_small(region_size, ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE);
             return Err(LogErr::StartFailedDueToInvalidMemoryContents)
         }
 
@@ -140,8 +508,10 @@ verus! {
         let global_crc = pm_region.read_and_deserialize(ABSOLUTE_POS_OF_GLOBAL_CRC);
         if !check_crc_deserialized(global_metadata, global_crc,
                       Ghost(mem), Ghost(pm_region.constants().impervious_to_corruption),
+                      pm_region.known_impervious_to_corruption(),
                       Ghost(ABSOLUTE_POS_OF_GLOBAL_METADATA), Ghost(LENGTH_OF_GLOBAL_METADATA),
                       Ghost(ABSOLUTE_POS_OF_GLOBAL_CRC)) {
+            observer.report_crc_mismatch(MetadataKind::Global, ABSOLUTE_POS_OF_GLOBAL_METADATA);
             return Err(LogErr::CRCMismatch);
         }
 
@@ -152,11 +522,13 @@ verus! {
 
         if global_metadata.program_guid != LOG_PROGRAM_GUID {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_program_guid_mismatch(LOG_PROGRAM_GUID, global_metadata.program_guid);
             return Err(LogErr::StartFailedDueToInvalidMemoryContents)
         }
 
         if global_metadata.version_number != LOG_PROGRAM_VERSION_NUMBER {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_version_unsupported(global_metadata.version_number, LOG_PROGRAM_VERSION_NUMBER);
             return Err(LogErr::StartFailedDueToProgramVersionNumberUnsupported{
                 version_number: global_metadata.version_number,
                 max_supported: LOG_PROGRAM_VERSION_NUMBER,
@@ -165,6 +537,7 @@ verus! {
 
         if global_metadata.length_of_region_metadata != LENGTH_OF_REGION_METADATA {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_invalid_metadata(MetadataKind::Global, "unexpected region metadata length");
             return Err(LogErr::StartFailedDueToInvalidMemoryContents)
         }
 
@@ -175,8 +548,10 @@ verus! {
         let region_crc = pm_region.read_and_deserialize(ABSOLUTE_POS_OF_REGION_CRC);
         if !check_crc_deserialized(region_metadata, region_crc,
                       Ghost(mem), Ghost(pm_region.constants().impervious_to_corruption),
+                      pm_region.known_impervious_to_corruption(),
                       Ghost(ABSOLUTE_POS_OF_REGION_METADATA), Ghost(LENGTH_OF_REGION_METADATA),
                       Ghost(ABSOLUTE_POS_OF_REGION_CRC)) {
+            observer.report_crc_mismatch(MetadataKind::Region, ABSOLUTE_POS_OF_REGION_METADATA);
             return Err(LogErr::CRCMismatch);
         }
 
@@ -187,6 +562,7 @@ verus! {
 
         if region_metadata.region_size != region_size {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_region_size_mismatch(region_size, region_metadata.region_size);
             return Err(LogErr::StartFailedDueToRegionSizeMismatch{
                 region_size_expected: region_size,
                 region_size_read: region_metadata.region_size,
@@ -195,6 +571,7 @@ verus! {
 
         if region_metadata.log_id != log_id {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_log_id_mismatch(log_id, region_metadata.log_id);
             return Err(LogErr::StartFailedDueToLogIDMismatch{
                 log_id_expected: log_id,
                 log_id_read: region_metadata.log_id,
@@ -203,14 +580,17 @@ verus! {
 
         if region_metadata.log_area_len > region_size {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_invalid_metadata(MetadataKind::Region, "log area larger than region");
             return Err(LogErr::StartFailedDueToInvalidMemoryContents)
         }
         if region_size - region_metadata.log_area_len < ABSOLUTE_POS_OF_LOG_AREA {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_invalid_metadata(MetadataKind::Region, "log area overlaps metadata");
             return Err(LogErr::StartFailedDueToInvalidMemoryContents)
         }
         if region_metadata.log_area_len < MIN_LOG_AREA_SIZE {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_invalid_metadata(MetadataKind::Region, "log area too small");
             return Err(LogErr::StartFailedDueToInvalidMemoryContents)
         }
 
@@ -226,7 +606,9 @@ verus! {
         let log_crc = pm_region.read_and_deserialize::<u64>(log_crc_pos);
         if !check_crc_deserialized(log_metadata, log_crc, Ghost(mem),
                                    Ghost(pm_region.constants().impervious_to_corruption),
+                                   pm_region.known_impervious_to_corruption(),
                                     Ghost(log_metadata_pos), Ghost(LENGTH_OF_LOG_METADATA), Ghost(log_crc_pos)) {
+            observer.report_crc_mismatch(MetadataKind::Log { cdb }, log_metadata_pos);
             return Err(LogErr::CRCMismatch);
         }
 
@@ -239,10 +621,12 @@ verus! {
         let log_length = log_metadata.log_length;
         if log_length > region_metadata.log_area_len {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_invalid_metadata(MetadataKind::Log { cdb }, "log length exceeds log area");
             return Err(LogErr::StartFailedDueToInvalidMemoryContents)
         }
         if log_length as u128 > u128::MAX - head {
             assert(state.is_None()); // This can't happen if the persistent memory is recoverable
+            observer.report_invalid_metadata(MetadataKind::Log { cdb }, "tail position overflows");
             return Err(LogErr::StartFailedDueToInvalidMemoryContents)
         }
 
@@ -261,6 +645,7 @@ verus! {
         // upon recovery, there are no pending appends beyond the tail
         // of the log.
 
+        observer.report_recovered(cdb, head, log_length);
         Ok(LogInfo{
             log_area_len: region_metadata.log_area_len,
             head,
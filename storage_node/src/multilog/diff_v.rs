@@ -0,0 +1,281 @@
+//! This file contains support for incremental replication of a
+//! multilog. Given the committed tail of each log at some earlier
+//! point, `diff` computes which logs have grown since then and by
+//! how much, so that only the new bytes need to be shipped to a
+//! backup. The backup applies each log's new bytes by appending them,
+//! committing, and advancing its head to the primary's head.
+//! `lemma_apply_diff_yields_new_state` proves that doing so takes
+//! the backup's abstract state to the primary's.
+//!
+//! The code in this file is verified and untrusted (as indicated by
+//! the `_v.rs` suffix), so you don't have to read it to be confident
+//! of the system's correctness.
+
+use crate::multilog::multilogimpl_t::*;
+use crate::multilog::multilogspec_t::*;
+use crate::pmem::pmemspec_t::*;
+use builtin::*;
+use builtin_macros::*;
+use vstd::prelude::*;
+
+verus! {
+
+    // This spec function returns the virtual position just past the
+    // last committed byte of `s`.
+    pub open spec fn log_tail(s: AbstractLogState) -> int
+    {
+        s.head + s.log.len()
+    }
+
+    // This spec function says that `new` can be reached from `old`
+    // by committed appends and head advances, without the head
+    // passing `old`'s tail. That's the case for two snapshots of the
+    // same log, with pending appends dropped, as long as the head
+    // hasn't been advanced past bytes a backup never received.
+    pub open spec fn log_extends(old: AbstractLogState, new: AbstractLogState) -> bool
+    {
+        &&& old.pending.len() == 0
+        &&& new.pending.len() == 0
+        &&& old.capacity == new.capacity
+        &&& old.head <= new.head <= log_tail(old)
+        &&& log_tail(old) <= log_tail(new)
+        &&& forall |pos: int| new.head <= pos < log_tail(old) ==>
+               #[trigger] new.log[pos - new.head] == old.log[pos - old.head]
+    }
+
+    pub open spec fn multilog_extends(old: AbstractMultiLogState, new: AbstractMultiLogState) -> bool
+    {
+        &&& old.num_logs() == new.num_logs()
+        &&& forall |i: int| 0 <= i < old.num_logs() ==> log_extends(#[trigger] old[i], new[i])
+    }
+
+    // This spec function describes how a backup applies the bytes
+    // `bytes` shipped for one log whose new head is `new_head`.
+    pub open spec fn apply_log_diff(s: AbstractLogState, bytes: Seq<u8>, new_head: int) -> AbstractLogState
+    {
+        s.tentatively_append(bytes).commit().advance_head(new_head)
+    }
+
+    // This spec function says that `entries` is the diff between a
+    // multilog whose committed tails were `old_tails` and the
+    // multilog `new`. Each entry `(which_log, from_position,
+    // byte_count)` describes a log that grew, from its old tail to
+    // its new one. Entries are sorted by log, so each log appears at
+    // most once, and every log that grew appears.
+    pub open spec fn diff_matches_tails(
+        old_tails: Seq<u128>,
+        new: AbstractMultiLogState,
+        entries: Seq<(usize, u128, u64)>,
+    ) -> bool
+    {
+        &&& forall |j: int| 0 <= j < entries.len() ==> {
+               let (which_log, from_position, byte_count) = #[trigger] entries[j];
+               &&& which_log < new.num_logs()
+               &&& from_position == old_tails[which_log as int]
+               &&& byte_count > 0
+               &&& from_position + byte_count == log_tail(new[which_log as int])
+           }
+        &&& forall |j: int, k: int| 0 <= j < k < entries.len() ==> (#[trigger] entries[j]).0 < (#[trigger] entries[k]).0
+        &&& forall |i: int| 0 <= i < new.num_logs() && old_tails[i] < log_tail(#[trigger] new[i]) ==>
+               exists |j: int| 0 <= j < entries.len() && (#[trigger] entries[j]).0 == i
+    }
+
+    pub open spec fn diff_matches(
+        old: AbstractMultiLogState,
+        new: AbstractMultiLogState,
+        entries: Seq<(usize, u128, u64)>,
+    ) -> bool
+    {
+        &&& old.num_logs() == new.num_logs()
+        &&& forall |i: int| 0 <= i < old.num_logs() ==> log_tail(#[trigger] old[i]) <= u128::MAX
+        &&& diff_matches_tails(Seq::new(old.num_logs(), |i: int| log_tail(old[i]) as u128), new, entries)
+    }
+
+    // This spec function returns the bytes shipped for log
+    // `which_log`: the bytes of `new` described by its entry in
+    // `entries`, or nothing if it has no entry.
+    pub open spec fn diff_bytes(
+        new: AbstractMultiLogState,
+        entries: Seq<(usize, u128, u64)>,
+        which_log: int,
+    ) -> Seq<u8>
+    {
+        if exists |j: int| 0 <= j < entries.len() && (#[trigger] entries[j]).0 == which_log {
+            let j = choose |j: int| 0 <= j < entries.len() && (#[trigger] entries[j]).0 == which_log;
+            let (_, from_position, byte_count) = entries[j];
+            new.read(which_log, from_position as int, byte_count as int)
+        }
+        else {
+            Seq::<u8>::empty()
+        }
+    }
+
+    // This spec function describes a backup in state `old` applying
+    // the diff `entries`, reading the shipped bytes from `new`.
+    pub open spec fn apply_diff(
+        old: AbstractMultiLogState,
+        new: AbstractMultiLogState,
+        entries: Seq<(usize, u128, u64)>,
+    ) -> AbstractMultiLogState
+    {
+        AbstractMultiLogState {
+            states: Seq::new(old.num_logs(), |i: int| apply_log_diff(old[i], diff_bytes(new, entries, i), new[i].head))
+        }
+    }
+
+    // This lemma says that appending the bytes between `old`'s tail
+    // and `new`'s tail to `old`, committing, and advancing the head
+    // to `new`'s head yields `new`.
+    pub proof fn lemma_apply_log_diff_yields_new_log(old: AbstractLogState, new: AbstractLogState)
+        requires
+            log_extends(old, new),
+        ensures
+            apply_log_diff(old, new.read(log_tail(old), log_tail(new) - log_tail(old)), new.head) =~= new,
+    {
+        let bytes = new.read(log_tail(old), log_tail(new) - log_tail(old));
+        let applied = apply_log_diff(old, bytes, new.head);
+        assert(applied.log.len() == new.log.len());
+        assert forall |k: int| 0 <= k < new.log.len() implies applied.log[k] == new.log[k] by {
+            let pos = new.head + k;
+            if pos < log_tail(old) {
+                assert(new.log[pos - new.head] == old.log[pos - old.head]);
+            }
+        }
+        assert(applied.log =~= new.log);
+        assert(applied.pending =~= new.pending);
+    }
+
+    // This lemma says that if `new` extends `old`, a backup in state
+    // `old` that applies the diff between them ends up in state
+    // `new`.
+    pub proof fn lemma_apply_diff_yields_new_state(
+        old: AbstractMultiLogState,
+        new: AbstractMultiLogState,
+        entries: Seq<(usize, u128, u64)>,
+    )
+        requires
+            multilog_extends(old, new),
+            diff_matches(old, new, entries),
+        ensures
+            apply_diff(old, new, entries) =~= new,
+    {
+        let old_tails = Seq::new(old.num_logs(), |i: int| log_tail(old[i]) as u128);
+        let applied = apply_diff(old, new, entries);
+        assert forall |i: int| 0 <= i < old.num_logs() implies #[trigger] applied.states[i] == new.states[i] by {
+            assert(log_extends(old[i], new[i]));
+            if exists |j: int| 0 <= j < entries.len() && (#[trigger] entries[j]).0 == i {
+                let j = choose |j: int| 0 <= j < entries.len() && (#[trigger] entries[j]).0 == i;
+                assert(old_tails[i] == log_tail(old[i]));
+                assert(diff_bytes(new, entries, i) ==
+                       new[i].read(log_tail(old[i]), log_tail(new[i]) - log_tail(old[i])));
+            }
+            else {
+                assert(old_tails[i] == log_tail(old[i]));
+                assert(log_tail(new[i]) == log_tail(old[i]));
+                assert(new[i].read(log_tail(old[i]), 0) =~= Seq::<u8>::empty());
+            }
+            lemma_apply_log_diff_yields_new_log(old[i], new[i]);
+        }
+        assert(applied.states =~= new.states);
+    }
+
+    // This function computes the diff between the logs of `multilog`
+    // when their committed tails were `old_tails` and their current
+    // committed state. It fails with `CantReadPastTail` if a log's
+    // tail is now before its old tail, and with
+    // `CantReadBeforeHead` if a log's head has been advanced past
+    // its old tail, since then the bytes a backup is missing are
+    // gone and it has to be resynchronized from scratch.
+    pub fn diff<PMRegions: PersistentMemoryRegions>(
+        multilog: &MultiLogImpl<PMRegions>,
+        old_tails: &[u128],
+    ) -> (result: Result<Vec<(usize, u128, u64)>, MultiLogErr>)
+        requires
+            multilog.valid(),
+            old_tails@.len() == multilog@.num_logs(),
+            old_tails@.len() <= u32::MAX,
+        ensures
+            match result {
+                Ok(entries) => diff_matches_tails(old_tails@, multilog@, entries@),
+                Err(MultiLogErr::CantReadPastTail { tail: _ }) => true,
+                Err(MultiLogErr::CantReadBeforeHead { head: _ }) => true,
+                _ => false,
+            }
+    {
+        let mut entries: Vec<(usize, u128, u64)> = Vec::new();
+        let mut which_log: usize = 0;
+        while which_log < old_tails.len()
+            invariant
+                multilog.valid(),
+                old_tails@.len() == multilog@.num_logs(),
+                old_tails@.len() <= u32::MAX,
+                which_log <= old_tails@.len(),
+                forall |j: int| 0 <= j < entries@.len() ==> {
+                    let (w, from_position, byte_count) = #[trigger] entries@[j];
+                    &&& w < which_log
+                    &&& from_position == old_tails@[w as int]
+                    &&& byte_count > 0
+                    &&& from_position + byte_count == log_tail(multilog@[w as int])
+                },
+                forall |j: int, k: int| 0 <= j < k < entries@.len() ==>
+                    (#[trigger] entries@[j]).0 < (#[trigger] entries@[k]).0,
+                forall |i: int| 0 <= i < which_log && old_tails@[i] < log_tail(#[trigger] multilog@[i]) ==>
+                    exists |j: int| 0 <= j < entries@.len() && (#[trigger] entries@[j]).0 == i,
+        {
+            let (head, tail, _capacity) = match multilog.get_head_tail_and_capacity(which_log as u32) {
+                Ok(t) => t,
+                Err(_) => { assert(false); return Err(MultiLogErr::InvalidLogIndex { }); },
+            };
+            let old_tail = old_tails[which_log];
+            if tail < old_tail {
+                return Err(MultiLogErr::CantReadPastTail { tail });
+            }
+            if head > old_tail {
+                return Err(MultiLogErr::CantReadBeforeHead { head });
+            }
+            if tail > old_tail {
+                let ghost old_entries = entries@;
+                entries.push((which_log, old_tail, (tail - old_tail) as u64));
+                assert(entries@[entries@.len() - 1].0 == which_log);
+                assert forall |i: int| 0 <= i <= which_log && old_tails@[i] < log_tail(#[trigger] multilog@[i])
+                           implies exists |j: int| 0 <= j < entries@.len() && (#[trigger] entries@[j]).0 == i by {
+                    if i < which_log {
+                        let j = choose |j: int| 0 <= j < old_entries.len() && (#[trigger] old_entries[j]).0 == i;
+                        assert(entries@[j] == old_entries[j]);
+                    }
+                    else {
+                        assert(entries@[entries@.len() - 1].0 == i);
+                    }
+                }
+            }
+            which_log = which_log + 1;
+        }
+        Ok(entries)
+    }
+
+    // This function reads the bytes described by the diff entry
+    // `entry` from the live regions of `multilog`, for shipping to a
+    // backup.
+    pub fn read_diff_bytes<PMRegions: PersistentMemoryRegions>(
+        multilog: &MultiLogImpl<PMRegions>,
+        entry: (usize, u128, u64),
+    ) -> (result: Result<Vec<u8>, MultiLogErr>)
+        requires
+            multilog.valid(),
+            entry.0 <= u32::MAX,
+            entry.1 + entry.2 <= u128::MAX,
+        ensures
+            match result {
+                Ok(bytes) => read_correct_modulo_corruption(
+                    bytes@,
+                    multilog@.read(entry.0 as int, entry.1 as int, entry.2 as int),
+                    multilog.constants().impervious_to_corruption
+                ),
+                _ => true,
+            }
+    {
+        let (which_log, from_position, byte_count) = entry;
+        multilog.read(which_log as u32, from_position, byte_count)
+    }
+
+}
@@ -0,0 +1,98 @@
+//! This file contains `dump_layout`, a debugging aid that renders the
+//! metadata stored in a multilog region as human-readable text. It
+//! prints every field of the global, region, and log metadata, both
+//! CDB slots, and whether each CRC matches, even when they don't. So
+//! it's useful for working out why a region won't recover.
+//!
+//! The code in this file is trusted and unverified (as indicated by
+//! the `_t.rs` suffix), so it must be audited. It only reads from
+//! the region, so it can't affect the crash-safety guarantees of
+//! `MultiLogImpl`.
+
+use crate::multilog::layout_v::*;
+use crate::multilog::multilogimpl_t::decode_label;
+use crate::pmem::pmemspec_t::*;
+use crate::pmem::serialization_t::*;
+use std::fmt::Write;
+
+// This helper function returns "ok" or "MISMATCH" depending on
+// whether `crc` is the CRC of `val`.
+#[cfg(not(verus_keep_ghost))]
+fn crc_status<S: Serializable + Sized>(val: &S, crc: u64) -> &'static str
+{
+    if calculate_crc(val) == crc { "ok" } else { "MISMATCH" }
+}
+
+// This helper function describes a CDB value.
+#[cfg(not(verus_keep_ghost))]
+fn cdb_status(cdb: u64) -> &'static str
+{
+    if cdb == CDB_FALSE {
+        "false"
+    }
+    else if cdb == CDB_TRUE {
+        "true"
+    }
+    else {
+        "INVALID"
+    }
+}
+
+// This function returns a human-readable dump of the multilog
+// metadata stored in `pm_region`. It doesn't check that the metadata
+// is valid or that its CRCs match; it just reports what's there. It
+// flushes nothing, so outstanding writes may or may not be
+// reflected.
+//
+// `pm_region` -- the region to describe
+#[cfg(not(verus_keep_ghost))]
+pub fn dump_layout(pm_region: &impl PersistentMemoryRegion) -> String
+{
+    let mut out = String::new();
+    let region_size = pm_region.get_region_size();
+    writeln!(out, "region size: {}", region_size).unwrap();
+    if region_size < ABSOLUTE_POS_OF_LOG_AREA {
+        writeln!(out, "region is too small to hold multilog metadata ({} bytes needed)",
+                 ABSOLUTE_POS_OF_LOG_AREA).unwrap();
+        return out;
+    }
+
+    let global_metadata = pm_region.read_and_deserialize::<GlobalMetadata>(ABSOLUTE_POS_OF_GLOBAL_METADATA);
+    let global_crc = pm_region.read_and_deserialize::<u64>(ABSOLUTE_POS_OF_GLOBAL_CRC);
+    writeln!(out, "global metadata @ {} (crc {}):", ABSOLUTE_POS_OF_GLOBAL_METADATA,
+             crc_status(global_metadata, *global_crc)).unwrap();
+    writeln!(out, "  version_number: {}", global_metadata.version_number).unwrap();
+    writeln!(out, "  length_of_region_metadata: {}", global_metadata.length_of_region_metadata).unwrap();
+    writeln!(out, "  program_guid: {:#034x}{}", global_metadata.program_guid,
+             if global_metadata.program_guid == MULTILOG_PROGRAM_GUID { "" } else { " (not a multilog)" }).unwrap();
+
+    let region_metadata = pm_region.read_and_deserialize::<RegionMetadata>(ABSOLUTE_POS_OF_REGION_METADATA);
+    let region_crc = pm_region.read_and_deserialize::<u64>(ABSOLUTE_POS_OF_REGION_CRC);
+    writeln!(out, "region metadata @ {} (crc {}):", ABSOLUTE_POS_OF_REGION_METADATA,
+             crc_status(region_metadata, *region_crc)).unwrap();
+    writeln!(out, "  num_logs: {}", region_metadata.num_logs).unwrap();
+    writeln!(out, "  which_log: {}", region_metadata.which_log).unwrap();
+    writeln!(out, "  region_size: {}", region_metadata.region_size).unwrap();
+    writeln!(out, "  log_area_len: {}", region_metadata.log_area_len).unwrap();
+    writeln!(out, "  creation_timestamp: {}", region_metadata.creation_timestamp).unwrap();
+    writeln!(out, "  multilog_id: {:#034x}", region_metadata.multilog_id).unwrap();
+    writeln!(out, "  label: {:?}", decode_label(region_metadata.label_lo, region_metadata.label_hi)).unwrap();
+
+    let cdb = pm_region.read_and_deserialize::<u64>(ABSOLUTE_POS_OF_LOG_CDB);
+    writeln!(out, "cdb @ {}: {:#018x} ({}; only meaningful in region 0)", ABSOLUTE_POS_OF_LOG_CDB, *cdb,
+             cdb_status(*cdb)).unwrap();
+
+    for (which_cdb, metadata_pos, crc_pos) in [
+        (false, ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE),
+        (true, ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE),
+    ] {
+        let log_metadata = pm_region.read_and_deserialize::<LogMetadata>(metadata_pos);
+        let log_crc = pm_region.read_and_deserialize::<u64>(crc_pos);
+        writeln!(out, "log metadata for cdb {} @ {} (crc {}):", which_cdb, metadata_pos,
+                 crc_status(log_metadata, *log_crc)).unwrap();
+        writeln!(out, "  head: {}", log_metadata.head).unwrap();
+        writeln!(out, "  log_length: {}", log_metadata.log_length).unwrap();
+    }
+
+    out
+}
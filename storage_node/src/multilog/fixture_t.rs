@@ -0,0 +1,151 @@
+//! This file contains `MultiLogStateBuilder`, a test fixture for
+//! writing recovery tests. A test describes the abstract multilog
+//! state it expects, one log at a time, and the builder writes a
+//! persistent-memory image that recovers to exactly that state. This
+//! lets tests check recovery of states, like a head that has wrapped
+//! around the log area, without driving the multilog into them
+//! through a long sequence of operations.
+//!
+//! The code in this file is trusted and unverified (as indicated by
+//! the `_t.rs` suffix), so it must be audited. It writes metadata
+//! directly, bypassing the write restrictions that keep a multilog
+//! recoverable, so it's only compiled into test builds.
+
+use crate::multilog::layout_v::*;
+use crate::multilog::multilogimpl_t::*;
+use crate::pmem::pmemmock_t::*;
+use crate::pmem::pmemspec_t::*;
+use crate::pmem::serialization_t::*;
+
+// An `ExpectedLogState` is the executable counterpart of an
+// `AbstractLogState` with no pending appends: the virtual head
+// position, the committed bytes, and the capacity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpectedLogState {
+    pub head: u128,
+    pub log: Vec<u8>,
+    pub capacity: u64,
+}
+
+// A `MultiLogStateBuilder` accumulates the expected states of a
+// multilog's logs. Log #`n` is stored on region #`n`, and each
+// region's log area is `log_area_len` bytes long, which is also each
+// log's capacity.
+pub struct MultiLogStateBuilder {
+    log_area_len: u64,
+    logs: Vec<ExpectedLogState>,
+}
+
+impl MultiLogStateBuilder {
+    pub fn new(log_area_len: u64) -> Self
+    {
+        assert!(log_area_len >= MIN_LOG_AREA_SIZE);
+        Self { log_area_len, logs: Vec::new() }
+    }
+
+    // This function adds a log whose committed bytes are `bytes`,
+    // starting at virtual position `head`.
+    pub fn add_log(mut self, head: u128, bytes: &[u8]) -> Self
+    {
+        assert!(bytes.len() as u64 <= self.log_area_len, "log doesn't fit in its log area");
+        assert!(head.checked_add(bytes.len() as u128).is_some(), "log's tail overflows");
+        self.logs.push(ExpectedLogState { head, log: bytes.to_vec(), capacity: self.log_area_len });
+        self
+    }
+
+    // This function returns the state the multilog should recover to.
+    pub fn expected_state(&self) -> Vec<ExpectedLogState>
+    {
+        self.logs.clone()
+    }
+
+    // This function returns the size of each region the image needs.
+    pub fn region_sizes(&self) -> Vec<u64>
+    {
+        vec![ABSOLUTE_POS_OF_LOG_AREA + self.log_area_len; self.logs.len()]
+    }
+
+    // This function writes an image of the expected state to
+    // `pm_regions`, which must have the sizes `region_sizes` returns,
+    // and flushes it. The log metadata goes in the slot selected by
+    // `cdb`, and the other slot is left as it was.
+    pub fn write_image<PMRegions: PersistentMemoryRegions>(
+        &self,
+        pm_regions: &mut PMRegions,
+        multilog_id: u128,
+        cdb: bool,
+    ) -> Result<(), PmemError>
+    {
+        assert!(!self.logs.is_empty(), "a multilog needs at least one log");
+        let region_size = ABSOLUTE_POS_OF_LOG_AREA + self.log_area_len;
+        let (log_metadata_pos, log_crc_pos) =
+            if cdb { (ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE) }
+            else { (ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE) };
+        let cdb_value = if cdb { CDB_TRUE } else { CDB_FALSE };
+
+        for (which_log, log) in self.logs.iter().enumerate() {
+            assert_eq!(pm_regions.get_region_size(which_log), region_size);
+            let global_metadata = GlobalMetadata {
+                version_number: MULTILOG_PROGRAM_VERSION_NUMBER,
+                length_of_region_metadata: LENGTH_OF_REGION_METADATA,
+                program_guid: MULTILOG_PROGRAM_GUID,
+            };
+            let region_metadata = RegionMetadata {
+                num_logs: self.logs.len() as u32,
+                which_log: which_log as u32,
+                region_size,
+                log_area_len: self.log_area_len,
+                creation_timestamp: 0,
+                _padding0: 0,
+                _padding1: 0,
+                multilog_id,
+                label_lo: 0,
+                label_hi: 0,
+            };
+            let log_metadata = LogMetadata { log_length: log.log.len() as u64, _padding: 0, head: log.head };
+
+            // Byte #`n` of the log area holds the byte whose virtual
+            // position modulo the log area length is `n`.
+            let mut log_area = vec![0u8; self.log_area_len as usize];
+            for (i, byte) in log.log.iter().enumerate() {
+                let pos = (log.head + i as u128) % self.log_area_len as u128;
+                log_area[pos as usize] = *byte;
+            }
+
+            pm_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_GLOBAL_METADATA, &global_metadata);
+            pm_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_GLOBAL_CRC, &calculate_crc(&global_metadata));
+            pm_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_REGION_METADATA, &region_metadata);
+            pm_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_REGION_CRC, &calculate_crc(&region_metadata));
+            pm_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_LOG_CDB, &cdb_value);
+            pm_regions.serialize_and_write(which_log, log_metadata_pos, &log_metadata);
+            pm_regions.serialize_and_write(which_log, log_crc_pos, &calculate_crc(&log_metadata));
+            pm_regions.write(which_log, ABSOLUTE_POS_OF_LOG_AREA, log_area.as_slice());
+        }
+        pm_regions.flush()
+    }
+
+    // This function returns volatile mock regions holding an image of
+    // the expected state.
+    pub fn build_mock(&self, multilog_id: u128, cdb: bool) -> VolatileMemoryMockingPersistentMemoryRegions
+    {
+        let mut pm_regions = VolatileMemoryMockingPersistentMemoryRegions::new(self.region_sizes().as_slice());
+        self.write_image(&mut pm_regions, multilog_id, cdb).unwrap();
+        pm_regions
+    }
+}
+
+// This function returns the state a started multilog is in, in the
+// same form as `MultiLogStateBuilder::expected_state`, so tests can
+// compare the two.
+pub fn observed_state<PMRegions: PersistentMemoryRegions>(
+    multilog: &MultiLogImpl<PMRegions>,
+    num_logs: u32,
+) -> Vec<ExpectedLogState>
+{
+    (0..num_logs).map(|which_log| {
+        let (head, tail, capacity) = multilog.get_head_tail_and_capacity(which_log).unwrap();
+        let log = if tail > head { multilog.read(which_log, head, (tail - head) as u64).unwrap() }
+                  else { Vec::new() };
+        ExpectedLogState { head, log, capacity }
+    }).collect()
+}
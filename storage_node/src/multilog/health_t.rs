@@ -0,0 +1,211 @@
+//! This file contains a health check for a multilog. It reports, for
+//! each region, whether the CRCs of its metadata match and whether it
+//! recovers, so that a periodic check can flag corruption before a
+//! restart trips over it.
+//!
+//! The code in this file is trusted (as indicated by the `_t.rs`
+//! suffix), so it must be audited. It only reads from the regions,
+//! so it can't affect the crash-safety guarantees of `MultiLogImpl`.
+//! The helpers that call into `start_v` are verified, so they're
+//! checked to meet the preconditions of what they call, which,
+//! unlike `read_cdb`, don't require the regions to be recoverable.
+
+use crate::multilog::layout_v::*;
+use crate::multilog::start_v::{probe_cdb, read_log_variables};
+use crate::pmem::pmemspec_t::*;
+use builtin::*;
+use builtin_macros::*;
+use vstd::prelude::*;
+
+verus! {
+
+    // This structure describes the health of a single region. Its
+    // fields are:
+    //
+    // `global_crc_ok` -- whether the global metadata matches its CRC
+    //
+    // `region_crc_ok` -- whether the region metadata matches its CRC
+    //
+    // `log_crc_ok` -- whether the log metadata selected by the
+    // corruption-detecting boolean matches its CRC. This is `false`
+    // if the corruption-detecting boolean couldn't be read.
+    //
+    // `recovers` -- whether all the metadata of the region can be
+    // read and is valid for the given multilog ID
+    pub struct RegionHealth {
+        pub global_crc_ok: bool,
+        pub region_crc_ok: bool,
+        pub log_crc_ok: bool,
+        pub recovers: bool,
+    }
+
+    // This structure is the result of a health check. Its fields are:
+    //
+    // `cdb_ok` -- whether the corruption-detecting boolean, stored
+    // in region 0, is one of its two valid values
+    //
+    // `regions` -- the health of each region, indexed by log
+    //
+    // `healthy` -- whether everything checked out, i.e., `cdb_ok`
+    // holds and every field of every element of `regions` is `true`
+    pub struct HealthReport {
+        pub cdb_ok: bool,
+        pub regions: Vec<RegionHealth>,
+        pub healthy: bool,
+    }
+
+    // This helper function reads `data_len` bytes at `data_addr` and
+    // the CRC at `crc_addr` from region `index`, and returns whether
+    // the CRC matches. It returns `false` if the region is too small
+    // to hold them.
+    #[verifier::external_body]
+    fn crc_matches<PMRegions: PersistentMemoryRegions>(
+        pm_regions: &PMRegions,
+        index: usize,
+        data_addr: u64,
+        data_len: u64,
+        crc_addr: u64,
+    ) -> bool
+        requires
+            pm_regions.inv(),
+            index < pm_regions@.len(),
+            pm_regions@.no_outstanding_writes(),
+    {
+        let region_size = pm_regions.get_region_size(index);
+        if region_size < data_addr + data_len || region_size < crc_addr + CRC_SIZE {
+            return false;
+        }
+        let data = pm_regions.read(index, data_addr, data_len);
+        let crc = pm_regions.read(index, crc_addr, CRC_SIZE);
+        bytes_crc(data.as_slice()) == crc
+    }
+
+    // This helper function reads the corruption-detecting boolean
+    // from region 0, returning `None` if it can't be read or isn't
+    // valid. It uses `probe_cdb` rather than `read_cdb`, since the
+    // whole point is to check regions that may not recover.
+    fn read_cdb_if_present<PMRegions: PersistentMemoryRegions>(pm_regions: &PMRegions) -> Option<bool>
+        requires
+            pm_regions.inv(),
+            pm_regions@.no_outstanding_writes(),
+    {
+        if pm_regions.get_num_regions() == 0 {
+            return None;
+        }
+        match probe_cdb(pm_regions) {
+            Ok(Some(cdb)) => Some(cdb),
+            _ => None,
+        }
+    }
+
+    // This helper function checks the health of region `which_log`,
+    // given the corruption-detecting boolean `cdb` read from region
+    // 0. It only reads region `which_log`.
+    fn check_region_health<PMRegions: PersistentMemoryRegions>(
+        pm_regions: &PMRegions,
+        multilog_id: u128,
+        cdb: Option<bool>,
+        which_log: usize,
+    ) -> RegionHealth
+        requires
+            pm_regions.inv(),
+            which_log < pm_regions@.len(),
+            pm_regions@.no_outstanding_writes(),
+    {
+        let num_regions = pm_regions.get_num_regions();
+        let global_crc_ok = crc_matches(pm_regions, which_log, ABSOLUTE_POS_OF_GLOBAL_METADATA,
+                                        LENGTH_OF_GLOBAL_METADATA, ABSOLUTE_POS_OF_GLOBAL_CRC);
+        let region_crc_ok = crc_matches(pm_regions, which_log, ABSOLUTE_POS_OF_REGION_METADATA,
+                                        LENGTH_OF_REGION_METADATA, ABSOLUTE_POS_OF_REGION_CRC);
+        let (log_crc_ok, recovers) = match cdb {
+            None => (false, false),
+            Some(cdb) => {
+                let (log_metadata_pos, log_crc_pos) =
+                    if cdb { (ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE) }
+                    else { (ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE) };
+                let log_crc_ok = crc_matches(pm_regions, which_log, log_metadata_pos,
+                                             LENGTH_OF_LOG_METADATA, log_crc_pos);
+                let recovers = num_regions <= u32::MAX as usize &&
+                    read_log_variables(pm_regions, multilog_id, cdb, num_regions as u32,
+                                       which_log as u32).is_ok();
+                (log_crc_ok, recovers)
+            },
+        };
+        RegionHealth { global_crc_ok, region_crc_ok, log_crc_ok, recovers }
+    }
+
+    // This helper function assembles the health report from the
+    // corruption-detecting boolean and the health of each region.
+    #[verifier::external_body]
+    fn summarize(cdb: Option<bool>, regions: Vec<RegionHealth>) -> HealthReport
+    {
+        let healthy = cdb.is_some() && regions.iter().all(
+            |r| r.global_crc_ok && r.region_crc_ok && r.log_crc_ok && r.recovers
+        );
+        HealthReport { cdb_ok: cdb.is_some(), regions, healthy }
+    }
+
+    // This function checks the health of the multilog stored in
+    // `pm_regions`. It only reads metadata, never the log areas, so
+    // its cost doesn't depend on how much is in the logs. It doesn't
+    // write anything, so it's safe to call on regions in use by a
+    // `MultiLogImpl` as long as they have no outstanding writes.
+    // Its preconditions are the ones the helpers it calls require,
+    // and `which_log` is always less than the number of regions.
+    //
+    // `pm_regions` -- the persistent memory regions to check
+    //
+    // `multilog_id` -- the GUID of the multilog
+    #[verifier::external_body]
+    pub fn health_check<PMRegions: PersistentMemoryRegions>(
+        pm_regions: &PMRegions,
+        multilog_id: u128,
+    ) -> (report: HealthReport)
+        requires
+            pm_regions.inv(),
+            pm_regions@.no_outstanding_writes(),
+        ensures
+            report.regions@.len() == pm_regions@.len(),
+    {
+        let num_regions = pm_regions.get_num_regions();
+        let cdb = read_cdb_if_present(pm_regions);
+
+        let mut regions = Vec::<RegionHealth>::with_capacity(num_regions);
+        for which_log in 0..num_regions {
+            regions.push(check_region_health(pm_regions, multilog_id, cdb, which_log));
+        }
+
+        summarize(cdb, regions)
+    }
+
+    // This function is like `health_check`, except that it checks the
+    // regions in parallel, using one thread per region. This helps
+    // when the regions are on different devices. Each region's check
+    // only reads that region, so the report is the same as the one
+    // `health_check` would produce.
+    #[cfg(feature = "parallel-health-check")]
+    #[verifier::external_body]
+    pub fn health_check_parallel<PMRegions: PersistentMemoryRegions + Sync>(
+        pm_regions: &PMRegions,
+        multilog_id: u128,
+    ) -> (report: HealthReport)
+        requires
+            pm_regions.inv(),
+            pm_regions@.no_outstanding_writes(),
+        ensures
+            report.regions@.len() == pm_regions@.len(),
+    {
+        let num_regions = pm_regions.get_num_regions();
+        let cdb = read_cdb_if_present(pm_regions);
+
+        let regions = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..num_regions).map(|which_log| {
+                scope.spawn(move || check_region_health(pm_regions, multilog_id, cdb, which_log))
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<RegionHealth>>()
+        });
+
+        summarize(cdb, regions)
+    }
+
+}
@@ -630,4 +630,62 @@ verus! {
         }
     }
 
+    // This lemma establishes that, if one flushes some of the regions
+    // of persistent memory but not others, this will maintain various
+    // invariants. This is the state left behind if flushing regions
+    // one at a time fails partway through.
+    //
+    // `pm_regions_view` -- the persistent memory regions view
+    // `pm_regions_view2` -- the view after flushing some regions
+    // `multilog_id` -- the ID of the multilog
+    // `num_logs` -- the number of logs
+    // `cdb` -- the current value of the corruption-detecting boolean
+    // `infos` -- the log information
+    // `state` -- the abstract multilog state
+    pub proof fn lemma_partially_flushing_metadata_maintains_invariants(
+        pm_regions_view: PersistentMemoryRegionsView,
+        pm_regions_view2: PersistentMemoryRegionsView,
+        multilog_id: u128,
+        num_logs: u32,
+        cdb: bool,
+        infos: Seq<LogInfo>,
+        state: AbstractMultiLogState,
+    )
+        requires
+            memory_matches_deserialized_cdb(pm_regions_view, cdb),
+            each_metadata_consistent_with_info(pm_regions_view,  multilog_id, num_logs, cdb, infos),
+            each_info_consistent_with_log_area(pm_regions_view, num_logs, infos, state),
+            pm_regions_view2.len() == pm_regions_view.len(),
+            forall |i: int| 0 <= i < pm_regions_view.len() ==> {
+                ||| #[trigger] pm_regions_view2[i] == pm_regions_view[i]
+                ||| pm_regions_view2[i] == pm_regions_view[i].flush()
+            },
+       ensures
+            memory_matches_deserialized_cdb(pm_regions_view2, cdb),
+            each_metadata_consistent_with_info(pm_regions_view2, multilog_id, num_logs, cdb, infos),
+            each_info_consistent_with_log_area(pm_regions_view2, num_logs, infos, state),
+    {
+        // Each region is either unchanged or flushed, and flushing a
+        // single region maintains the invariants for it, just as
+        // flushing all of them does.
+
+        lemma_flushing_metadata_maintains_invariants(pm_regions_view, multilog_id, num_logs, cdb, infos, state);
+        let flushed = pm_regions_view.flush();
+        assert(pm_regions_view2.regions.len() == pm_regions_view.regions.len());
+
+        assert(memory_matches_deserialized_cdb(pm_regions_view2, cdb)) by {
+            assert(is_valid_log_index(0, num_logs)); // This triggers various `forall`s in invariants.
+            assert(pm_regions_view2[0] == pm_regions_view[0] || pm_regions_view2[0] == flushed[0]);
+        }
+
+        assert forall |which_log: u32| #[trigger] is_valid_log_index(which_log, num_logs) implies {
+            let w = which_log as int;
+            &&& metadata_consistent_with_info(pm_regions_view2[w], multilog_id, num_logs, which_log, cdb, infos[w])
+            &&& info_consistent_with_log_area(pm_regions_view2[w], infos[w], state[w])
+        } by {
+            let w = which_log as int;
+            assert(pm_regions_view2[w] == pm_regions_view[w] || pm_regions_view2[w] == flushed[w]);
+        }
+    }
+
 }
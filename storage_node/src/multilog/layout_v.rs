@@ -10,7 +10,7 @@
 //! Global metadata:   Metadata whose length is constant across all versions and
 //!                    the same for each region/log
 //! Region metadata:   Per-region metadata that does not change over the course
-//!                    of execution.
+//!                    of execution. It's written once, at setup.
 //! Log metadata:      Per-log metadata that changes as the data changes, so it
 //!                    has two versions and a corruption-detecting boolean
 //!                    distinguishing which of those two versions is active
@@ -32,8 +32,11 @@
 //!   bytes 48..56:   Unused padding bytes
 //!   bytes 56..64:   This region's size
 //!   bytes 64..72:   Length of log area (LoLA)
-//!   bytes 72..88:   Multilog ID
-//!   bytes 88..96:   CRC of the above 48 bytes
+//!   bytes 72..80:   Creation timestamp (seconds since the Unix epoch)
+//!   bytes 80..88:   Unused padding bytes
+//!   bytes 88..104:  Multilog ID
+//!   bytes 104..136: Label, as UTF-8 padded with zero bytes
+//!   bytes 136..144: CRC of the above 96 bytes
 //!
 //! Log metadata (relative offsets):
 //!   bytes 0..8:     Log length
@@ -82,22 +85,26 @@ verus! {
     pub const ABSOLUTE_POS_OF_REGION_METADATA: u64 = 40;
     pub const RELATIVE_POS_OF_REGION_NUM_LOGS: u64 = 0;
     pub const RELATIVE_POS_OF_REGION_WHICH_LOG: u64 = 4;
-    pub const RELATIVE_POS_OF_REGION_PADDING: u64 = 8;
+    pub const RELATIVE_POS_OF_REGION_PADDING0: u64 = 8;
     pub const RELATIVE_POS_OF_REGION_REGION_SIZE: u64 = 16;
     pub const RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA: u64 = 24;
-    pub const RELATIVE_POS_OF_REGION_MULTILOG_ID: u64 = 32;
-    pub const LENGTH_OF_REGION_METADATA: u64 = 48;
-    pub const ABSOLUTE_POS_OF_REGION_CRC: u64 = 88;
-
-    pub const ABSOLUTE_POS_OF_LOG_CDB: u64 = 96;
-    pub const ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE: u64 = 104;
-    pub const ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE: u64 = 144;
+    pub const RELATIVE_POS_OF_REGION_CREATION_TIMESTAMP: u64 = 32;
+    pub const RELATIVE_POS_OF_REGION_PADDING1: u64 = 40;
+    pub const RELATIVE_POS_OF_REGION_MULTILOG_ID: u64 = 48;
+    pub const RELATIVE_POS_OF_REGION_LABEL_LO: u64 = 64;
+    pub const RELATIVE_POS_OF_REGION_LABEL_HI: u64 = 80;
+    pub const LENGTH_OF_REGION_METADATA: u64 = 96;
+    pub const ABSOLUTE_POS_OF_REGION_CRC: u64 = 136;
+
+    pub const ABSOLUTE_POS_OF_LOG_CDB: u64 = 144;
+    pub const ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE: u64 = 152;
+    pub const ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE: u64 = 192;
     pub const RELATIVE_POS_OF_LOG_LOG_LENGTH: u64 = 0;
     pub const RELATIVE_POS_OF_LOG_PADDING: u64 = 8;
     pub const RELATIVE_POS_OF_LOG_HEAD: u64 = 16;
     pub const LENGTH_OF_LOG_METADATA: u64 = 32;
-    pub const ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE: u64 = 136;
-    pub const ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE: u64 = 176;
+    pub const ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE: u64 = 184;
+    pub const ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE: u64 = 224;
     pub const ABSOLUTE_POS_OF_LOG_AREA: u64 = 256;
     pub const MIN_LOG_AREA_SIZE: u64 = 1;
 
@@ -107,9 +114,12 @@ verus! {
     pub const MULTILOG_PROGRAM_GUID: u128 = 0x21b8b4b3c7d140a9abf7e80c07b7f01fu128;
 
     // The current version number, and the only one whose contents
-    // this program can read, is the following:
+    // this program can read, is the following. Version 2 added the
+    // creation timestamp and the label to the region metadata, which
+    // moved everything after them, so version-1 regions can't be
+    // read directly. `migrate_t.rs` converts them to this version.
 
-    pub const MULTILOG_PROGRAM_VERSION_NUMBER: u64 = 1;
+    pub const MULTILOG_PROGRAM_VERSION_NUMBER: u64 = 2;
 
     // These structs represent the different levels of metadata.
     // TODO: confirm with runtime checks that the sizes and offsets are as expected
@@ -173,8 +183,8 @@ verus! {
             lemma_auto_spec_u128_to_from_le_bytes();
         }
 
-        open spec fn spec_serialized_len() -> u64 {
-            LENGTH_OF_GLOBAL_METADATA
+        open spec fn spec_serialized_len() -> nat {
+            LENGTH_OF_GLOBAL_METADATA as nat
         }
 
         closed spec fn spec_crc(self) -> u64;
@@ -189,18 +199,30 @@ verus! {
     pub struct RegionMetadata {
         pub num_logs: u32,
         pub which_log: u32,
-        pub _padding: u64,
+        pub _padding0: u64,
         pub region_size: u64,
         pub log_area_len: u64,
+        pub creation_timestamp: u64,
+        pub _padding1: u64,
         pub multilog_id: u128,
+        pub label_lo: u128,
+        pub label_hi: u128,
     }
 
+    // The label is stored as two `u128`s so that `RegionMetadata`
+    // stays a struct of integers. Since they're serialized little
+    // endian, the serialization of `label_lo` followed by that of
+    // `label_hi` is the 32 bytes of the label in order.
+    pub const LABEL_SIZE: usize = 32;
+
     impl Serializable for RegionMetadata {
         open spec fn spec_serialize(self) -> Seq<u8>
         {
             spec_u32_to_le_bytes(self.num_logs) + spec_u32_to_le_bytes(self.which_log) +
-                spec_u64_to_le_bytes(self._padding) + spec_u64_to_le_bytes(self.region_size) +
-                spec_u64_to_le_bytes(self.log_area_len) + spec_u128_to_le_bytes(self.multilog_id)
+                spec_u64_to_le_bytes(self._padding0) + spec_u64_to_le_bytes(self.region_size) +
+                spec_u64_to_le_bytes(self.log_area_len) + spec_u64_to_le_bytes(self.creation_timestamp) +
+                spec_u64_to_le_bytes(self._padding1) + spec_u128_to_le_bytes(self.multilog_id) +
+                spec_u128_to_le_bytes(self.label_lo) + spec_u128_to_le_bytes(self.label_hi)
         }
 
         open spec fn spec_deserialize(bytes: Seq<u8>) -> Self
@@ -210,14 +232,22 @@ verus! {
                     bytes.subrange(RELATIVE_POS_OF_REGION_NUM_LOGS as int, RELATIVE_POS_OF_REGION_NUM_LOGS + 4)),
                 which_log: spec_u32_from_le_bytes(
                     bytes.subrange(RELATIVE_POS_OF_REGION_WHICH_LOG as int, RELATIVE_POS_OF_REGION_WHICH_LOG + 4)),
-                _padding: spec_u64_from_le_bytes(
-                    bytes.subrange(RELATIVE_POS_OF_REGION_PADDING as int, RELATIVE_POS_OF_REGION_PADDING + 8)),
+                _padding0: spec_u64_from_le_bytes(
+                    bytes.subrange(RELATIVE_POS_OF_REGION_PADDING0 as int, RELATIVE_POS_OF_REGION_PADDING0 + 8)),
                 region_size: spec_u64_from_le_bytes(
                     bytes.subrange(RELATIVE_POS_OF_REGION_REGION_SIZE as int, RELATIVE_POS_OF_REGION_REGION_SIZE + 8)),
                 log_area_len: spec_u64_from_le_bytes(
                     bytes.subrange(RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA as int, RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA + 8)),
+                creation_timestamp: spec_u64_from_le_bytes(
+                    bytes.subrange(RELATIVE_POS_OF_REGION_CREATION_TIMESTAMP as int, RELATIVE_POS_OF_REGION_CREATION_TIMESTAMP + 8)),
+                _padding1: spec_u64_from_le_bytes(
+                    bytes.subrange(RELATIVE_POS_OF_REGION_PADDING1 as int, RELATIVE_POS_OF_REGION_PADDING1 + 8)),
                 multilog_id: spec_u128_from_le_bytes(
                     bytes.subrange(RELATIVE_POS_OF_REGION_MULTILOG_ID as int, RELATIVE_POS_OF_REGION_MULTILOG_ID + 16)),
+                label_lo: spec_u128_from_le_bytes(
+                    bytes.subrange(RELATIVE_POS_OF_REGION_LABEL_LO as int, RELATIVE_POS_OF_REGION_LABEL_LO + 16)),
+                label_hi: spec_u128_from_le_bytes(
+                    bytes.subrange(RELATIVE_POS_OF_REGION_LABEL_HI as int, RELATIVE_POS_OF_REGION_LABEL_HI + 16)),
             }
         }
 
@@ -229,10 +259,14 @@ verus! {
             assert(forall |s: Self| {
                 let serialized_num_logs = #[trigger] spec_u32_to_le_bytes(s.num_logs);
                 let serialized_which_log = #[trigger] spec_u32_to_le_bytes(s.which_log);
-                let serialized_padding = #[trigger] spec_u64_to_le_bytes(s._padding);
+                let serialized_padding0 = #[trigger] spec_u64_to_le_bytes(s._padding0);
                 let serialized_region_size = #[trigger] spec_u64_to_le_bytes(s.region_size);
                 let serialized_len = #[trigger] spec_u64_to_le_bytes(s.log_area_len);
+                let serialized_timestamp = #[trigger] spec_u64_to_le_bytes(s.creation_timestamp);
+                let serialized_padding1 = #[trigger] spec_u64_to_le_bytes(s._padding1);
                 let serialized_id = #[trigger] spec_u128_to_le_bytes(s.multilog_id);
+                let serialized_label_lo = #[trigger] spec_u128_to_le_bytes(s.label_lo);
+                let serialized_label_hi = #[trigger] spec_u128_to_le_bytes(s.label_hi);
                 let serialized_metadata = #[trigger] s.spec_serialize();
                 &&& serialized_metadata.subrange(
                         RELATIVE_POS_OF_REGION_NUM_LOGS as int,
@@ -243,9 +277,9 @@ verus! {
                         RELATIVE_POS_OF_REGION_WHICH_LOG + 4
                     ) == serialized_which_log
                 &&& serialized_metadata.subrange(
-                        RELATIVE_POS_OF_REGION_PADDING as int,
-                        RELATIVE_POS_OF_REGION_PADDING + 8,
-                    ) == serialized_padding
+                        RELATIVE_POS_OF_REGION_PADDING0 as int,
+                        RELATIVE_POS_OF_REGION_PADDING0 + 8,
+                    ) == serialized_padding0
                 &&& serialized_metadata.subrange(
                         RELATIVE_POS_OF_REGION_REGION_SIZE as int,
                         RELATIVE_POS_OF_REGION_REGION_SIZE + 8
@@ -254,10 +288,26 @@ verus! {
                         RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA as int,
                         RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA + 8
                     ) == serialized_len
+                &&& serialized_metadata.subrange(
+                        RELATIVE_POS_OF_REGION_CREATION_TIMESTAMP as int,
+                        RELATIVE_POS_OF_REGION_CREATION_TIMESTAMP + 8
+                    ) == serialized_timestamp
+                &&& serialized_metadata.subrange(
+                        RELATIVE_POS_OF_REGION_PADDING1 as int,
+                        RELATIVE_POS_OF_REGION_PADDING1 + 8
+                    ) == serialized_padding1
                 &&& serialized_metadata.subrange(
                         RELATIVE_POS_OF_REGION_MULTILOG_ID as int,
                         RELATIVE_POS_OF_REGION_MULTILOG_ID + 16
                     ) == serialized_id
+                &&& serialized_metadata.subrange(
+                        RELATIVE_POS_OF_REGION_LABEL_LO as int,
+                        RELATIVE_POS_OF_REGION_LABEL_LO + 16
+                    ) == serialized_label_lo
+                &&& serialized_metadata.subrange(
+                        RELATIVE_POS_OF_REGION_LABEL_HI as int,
+                        RELATIVE_POS_OF_REGION_LABEL_HI + 16
+                    ) == serialized_label_hi
             });
         }
 
@@ -268,9 +318,9 @@ verus! {
             lemma_auto_spec_u128_to_from_le_bytes();
         }
 
-        open spec fn spec_serialized_len() -> u64
+        open spec fn spec_serialized_len() -> nat
         {
-            LENGTH_OF_REGION_METADATA
+            LENGTH_OF_REGION_METADATA as nat
         }
 
         closed spec fn spec_crc(self) -> u64;
@@ -281,6 +331,32 @@ verus! {
         }
     }
 
+    // This lemma says that comparing region metadata read from
+    // persistent memory against an expected template with
+    // `serializes_same` is equivalent to comparing it field by
+    // field. Note that this includes the padding, which `setup`
+    // always writes as 0, the creation timestamp, and the label.
+    pub proof fn lemma_region_metadata_serializes_same_iff_fields_match(
+        read: RegionMetadata,
+        template: RegionMetadata,
+    )
+        ensures
+            serializes_same(read, template) <==> {
+                &&& read.num_logs == template.num_logs
+                &&& read.which_log == template.which_log
+                &&& read._padding0 == template._padding0
+                &&& read.region_size == template.region_size
+                &&& read.log_area_len == template.log_area_len
+                &&& read.creation_timestamp == template.creation_timestamp
+                &&& read._padding1 == template._padding1
+                &&& read.multilog_id == template.multilog_id
+                &&& read.label_lo == template.label_lo
+                &&& read.label_hi == template.label_hi
+            }
+    {
+        lemma_serializes_same_iff_equal(read, template);
+    }
+
     #[repr(C)]
     pub struct LogMetadata {
         pub log_length: u64,
@@ -306,9 +382,9 @@ verus! {
             }
         }
 
-        open spec fn spec_serialized_len() -> u64
+        open spec fn spec_serialized_len() -> nat
         {
-            LENGTH_OF_LOG_METADATA
+            LENGTH_OF_LOG_METADATA as nat
         }
 
         closed spec fn spec_crc(self) -> u64;
@@ -319,7 +395,7 @@ verus! {
             lemma_auto_spec_u128_to_from_le_bytes();
             assert(forall |s: Self| {
                 let serialized_log_length = #[trigger] spec_u64_to_le_bytes(s.log_length);
-                let serialized_padding = #[trigger] spec_u64_to_le_bytes(s._padding);
+                let serialized_padding1 = #[trigger] spec_u64_to_le_bytes(s._padding1);
                 let serialized_head = #[trigger] spec_u128_to_le_bytes(s.head);
                 let serialized_metadata = #[trigger] s.spec_serialize();
                 &&& serialized_metadata.subrange(
@@ -551,7 +627,11 @@ verus! {
         let num_logs = parse_u32(bytes, RELATIVE_POS_OF_REGION_NUM_LOGS as int);
         let which_log = parse_u32(bytes, RELATIVE_POS_OF_REGION_WHICH_LOG as int);
         let log_area_len = parse_u64(bytes, RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA as int);
-        RegionMetadata { region_size, multilog_id, _padding: 0, num_logs, which_log, log_area_len }
+        let creation_timestamp = parse_u64(bytes, RELATIVE_POS_OF_REGION_CREATION_TIMESTAMP as int);
+        let label_lo = parse_u128(bytes, RELATIVE_POS_OF_REGION_LABEL_LO as int);
+        let label_hi = parse_u128(bytes, RELATIVE_POS_OF_REGION_LABEL_HI as int);
+        RegionMetadata { region_size, multilog_id, _padding0: 0, num_logs, which_log, log_area_len, creation_timestamp,
+                         _padding1: 0, label_lo, label_hi }
     }
 
     // This function returns the log metadata encoded as the given
@@ -713,8 +793,8 @@ verus! {
                     // Otherwise, it wasn't created by this program.
                     None
                 }
-                else if global_metadata.version_number == 1 {
-                    // If this metadata was written by version #1 of this code, then this is how to
+                else if global_metadata.version_number == 2 {
+                    // If this metadata was written by version #2 of this code, then this is how to
                     // interpret it:
 
                     if global_metadata.length_of_region_metadata != LENGTH_OF_REGION_METADATA {
@@ -767,9 +847,9 @@ verus! {
                 }
                 else {
                     // This version of the code doesn't know how to parse metadata for any other
-                    // versions of this code besides 1. If we reach this point, we're presumably
-                    // reading metadata written by a future version of this code, which we can't
-                    // interpret.
+                    // versions of this code besides 2. If we reach this point, we're reading metadata
+                    // written by version 1, whose region metadata has a different layout, or by a
+                    // future version of this code. Either way, we can't interpret it.
                     None
                 }
             }
@@ -854,8 +934,8 @@ verus! {
                     // Otherwise, it wasn't created by this program.
                     None
                 }
-                else if global_metadata.version_number == 1 {
-                    // If this metadata was written by version #1 of this code, then this is how to
+                else if global_metadata.version_number == 2 {
+                    // If this metadata was written by version #2 of this code, then this is how to
                     // interpret it:
 
                     if mem.len() < ABSOLUTE_POS_OF_LOG_CDB + CRC_SIZE {
@@ -870,9 +950,9 @@ verus! {
                 }
                 else {
                     // This version of the code doesn't know how to parse metadata for any other
-                    // versions of this code besides 1. If we reach this point, we're presumably
-                    // reading metadata written by a future version of this code, which we can't
-                    // interpret.
+                    // versions of this code besides 2. If we reach this point, we're reading metadata
+                    // written by version 1, whose region metadata has a different layout, or by a
+                    // future version of this code. Either way, we can't interpret it.
                     None
                 }
             }
@@ -963,6 +1043,23 @@ verus! {
         }
     }
 
+    // This lemma is the contrapositive of
+    // `lemma_recover_all_successful_implies_region_sizes_sufficient`:
+    // if region `i` is too small to hold a log area, recovery fails.
+    // It lets `regions_plausibly_sized` reject such regions without
+    // reading any metadata.
+    pub proof fn lemma_region_too_small_implies_recover_all_fails(mems: Seq<Seq<u8>>, multilog_id: u128, i: int)
+        requires
+            0 <= i < mems.len(),
+            mems[i].len() < ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE,
+        ensures
+            recover_all(mems, multilog_id).is_None()
+    {
+        if recover_all(mems, multilog_id).is_Some() {
+            lemma_recover_all_successful_implies_region_sizes_sufficient(mems, multilog_id);
+        }
+    }
+
     // This lemma establishes that for any `i` and `n`, if
     //
     // `forall |k| 0 <= k < n ==> mem1[i+k] == mem2[i+k]`
@@ -1005,6 +1102,128 @@ verus! {
                 S::spec_deserialize(#[trigger] extract_bytes(mem1, i, n)) == S::spec_deserialize(#[trigger] extract_bytes(mem2, i, n))
     {}
 
+    // This lemma establishes that two persistent memory regions views
+    // with the same committed contents recover to the same abstract
+    // state. It's the multi-region analogue of
+    // `lemma_same_bytes_same_deserialization`.
+    //
+    // This is an obvious fact, but Z3 only concludes that the two
+    // committed sequences are equal if prompted to reason about them
+    // extensionally, which this lemma does.
+    //
+    // `v1` -- the first persistent memory regions view
+    // `v2` -- the second persistent memory regions view
+    // `multilog_id` -- the GUID associated with the multilog
+    pub proof fn lemma_same_committed_implies_same_recovery(
+        v1: PersistentMemoryRegionsView,
+        v2: PersistentMemoryRegionsView,
+        multilog_id: u128,
+    )
+        requires
+            v1.len() == v2.len(),
+            forall |i: int| 0 <= i < v1.len() ==> #[trigger] v1[i].committed() == v2[i].committed(),
+        ensures
+            v1.committed() == v2.committed(),
+            recover_all(v1.committed(), multilog_id) == recover_all(v2.committed(), multilog_id),
+    {
+        assert(v1.committed() =~= v2.committed());
+    }
+
+    // This executable function checks whether two collections of
+    // persistent memory regions have the same contents, and thus
+    // recover to the same abstract multilog state. It's meant for use
+    // in crash-consistency tests. Since reads may be corrupted, it
+    // only guarantees anything if both collections are impervious to
+    // corruption.
+    //
+    // `pm_regions1` -- the first collection of regions
+    // `pm_regions2` -- the second collection of regions
+    // `multilog_id` (ghost) -- the GUID associated with the multilog
+    pub fn recovers_identically<PMRegions: PersistentMemoryRegions>(
+        pm_regions1: &PMRegions,
+        pm_regions2: &PMRegions,
+        Ghost(multilog_id): Ghost<u128>,
+    ) -> (result: bool)
+        requires
+            pm_regions1.inv(),
+            pm_regions2.inv(),
+            pm_regions1@.no_outstanding_writes(),
+            pm_regions2@.no_outstanding_writes(),
+        ensures
+            ({
+                &&& result
+                &&& pm_regions1.constants().impervious_to_corruption
+                &&& pm_regions2.constants().impervious_to_corruption
+            }) ==> recover_all(pm_regions1@.committed(), multilog_id) ==
+                  recover_all(pm_regions2@.committed(), multilog_id),
+    {
+        let ghost impervious = pm_regions1.constants().impervious_to_corruption &&
+                               pm_regions2.constants().impervious_to_corruption;
+        let num_regions = pm_regions1.get_num_regions();
+        if num_regions != pm_regions2.get_num_regions() {
+            return false;
+        }
+
+        let mut which_region: usize = 0;
+        while which_region < num_regions
+            invariant
+                pm_regions1.inv(),
+                pm_regions2.inv(),
+                pm_regions1@.no_outstanding_writes(),
+                pm_regions2@.no_outstanding_writes(),
+                impervious == (pm_regions1.constants().impervious_to_corruption &&
+                               pm_regions2.constants().impervious_to_corruption),
+                num_regions == pm_regions1@.len(),
+                num_regions == pm_regions2@.len(),
+                which_region <= num_regions,
+                impervious ==> forall |i: int| 0 <= i < which_region ==>
+                    #[trigger] pm_regions1@[i].committed() == pm_regions2@[i].committed(),
+        {
+            let region_size = pm_regions1.get_region_size(which_region);
+            if region_size != pm_regions2.get_region_size(which_region) {
+                return false;
+            }
+
+            let bytes1 = pm_regions1.read(which_region, 0, region_size);
+            let bytes2 = pm_regions2.read(which_region, 0, region_size);
+            if bytes1.len() != bytes2.len() {
+                return false;
+            }
+
+            // Compare the two regions' contents byte by byte.
+
+            let mut pos: usize = 0;
+            while pos < bytes1.len()
+                invariant
+                    bytes1@.len() == bytes2@.len(),
+                    pos <= bytes1@.len(),
+                    forall |k: int| 0 <= k < pos ==> bytes1@[k] == bytes2@[k],
+            {
+                if bytes1[pos] != bytes2[pos] {
+                    return false;
+                }
+                pos = pos + 1;
+            }
+
+            proof {
+                if impervious {
+                    assert(bytes1@ =~= bytes2@);
+                    assert(pm_regions1@[which_region as int].committed() =~= bytes1@);
+                    assert(pm_regions2@[which_region as int].committed() =~= bytes2@);
+                }
+            }
+
+            which_region = which_region + 1;
+        }
+
+        proof {
+            if impervious {
+                lemma_same_committed_implies_same_recovery(pm_regions1@, pm_regions2@, multilog_id);
+            }
+        }
+        true
+    }
+
     // This lemma establishes that if the given persistent memory
     // regions' contents can be recovered to a valid abstract state,
     // then that abstract state is unaffected by
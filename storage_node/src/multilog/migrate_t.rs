@@ -0,0 +1,254 @@
+//! This file contains `migrate_from_version_1`, which converts a
+//! multilog set up by version 1 of this code into the current
+//! layout. Version 2 added the creation timestamp and the label to
+//! the region metadata, which moved the region CRC, the CDB, and the
+//! log metadata, so `start` can't read a version-1 multilog
+//! directly. The log area starts at the same offset in both
+//! versions, so its bytes carry over unchanged.
+//!
+//! Migration is done out of place: it only reads the version-1
+//! regions and writes a complete image of the same abstract state to
+//! a second set of regions. So a crash during migration leaves the
+//! version-1 regions intact, and the caller can just migrate again.
+//! Version 1 didn't record when a multilog was set up, so the
+//! migrated multilog's creation timestamp is 0, and its label is
+//! empty.
+//!
+//! The code in this file is trusted and unverified (as indicated by
+//! the `_t.rs` suffix), so it must be audited. It writes metadata
+//! directly, bypassing the write restrictions that keep a multilog
+//! recoverable, but only to regions that don't yet hold a multilog.
+
+use crate::multilog::layout_v::*;
+use crate::multilog::multilogimpl_t::MultiLogErr;
+use crate::pmem::pmemspec_t::*;
+use crate::pmem::serialization_t::*;
+
+// These are the version-1 offsets that differ from the current
+// layout. The global metadata, its CRC, the first 32 bytes of the
+// region metadata, the layout of the log metadata, and the log area
+// are the same in both versions.
+//
+// Version-1 region metadata (absolute offsets):
+//   bytes 40..44:   Number of logs in the multilog
+//   bytes 44..48:   Index of this log in the multilog
+//   bytes 48..56:   Unused padding bytes
+//   bytes 56..64:   This region's size
+//   bytes 64..72:   Length of log area (LoLA)
+//   bytes 72..88:   Multilog ID
+//   bytes 88..96:   CRC of the above 48 bytes
+pub const V1_PROGRAM_VERSION_NUMBER: u64 = 1;
+pub const V1_RELATIVE_POS_OF_REGION_MULTILOG_ID: u64 = 32;
+pub const V1_LENGTH_OF_REGION_METADATA: u64 = 48;
+pub const V1_ABSOLUTE_POS_OF_REGION_CRC: u64 = 88;
+pub const V1_ABSOLUTE_POS_OF_LOG_CDB: u64 = 96;
+pub const V1_ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE: u64 = 104;
+pub const V1_ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE: u64 = 144;
+pub const V1_ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE: u64 = 136;
+pub const V1_ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE: u64 = 176;
+
+// A `V1Region` holds what migration needs from one version-1
+// region: its log area length and its active log metadata.
+#[cfg(not(verus_keep_ghost))]
+struct V1Region {
+    log_area_len: u64,
+    log_metadata: LogMetadata,
+}
+
+// This helper function parses a little-endian integer of `N` bytes
+// at `offset` in `bytes`.
+#[cfg(not(verus_keep_ghost))]
+fn le_bytes<const N: usize>(bytes: &[u8], offset: u64) -> [u8; N]
+{
+    bytes[offset as usize..offset as usize + N].try_into().unwrap()
+}
+
+// This helper function reads and checks region #`which_log` of a
+// version-1 multilog with ID `multilog_id` and `num_logs` logs,
+// whose CDB is `cdb`. It returns the same errors `start` would for
+// the corresponding problem in a current multilog.
+#[cfg(not(verus_keep_ghost))]
+fn read_v1_region<PMRegions: PersistentMemoryRegions>(
+    pm_regions: &PMRegions,
+    multilog_id: u128,
+    num_logs: u32,
+    which_log: u32,
+    cdb: bool,
+) -> Result<V1Region, MultiLogErr>
+{
+    let index = which_log as usize;
+    let region_size = pm_regions.get_region_size(index);
+    if region_size < ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE {
+        return Err(MultiLogErr::StartFailedDueToInvalidMemoryContents{ which_log });
+    }
+
+    let global_metadata = pm_regions.read_and_deserialize::<GlobalMetadata>(index, ABSOLUTE_POS_OF_GLOBAL_METADATA);
+    let global_crc = pm_regions.read_and_deserialize::<u64>(index, ABSOLUTE_POS_OF_GLOBAL_CRC);
+    if calculate_crc(global_metadata) != *global_crc {
+        return Err(MultiLogErr::CRCMismatch);
+    }
+    if global_metadata.program_guid != MULTILOG_PROGRAM_GUID {
+        return Err(MultiLogErr::StartFailedDueToInvalidMemoryContents{ which_log });
+    }
+    if global_metadata.version_number != V1_PROGRAM_VERSION_NUMBER {
+        return Err(MultiLogErr::StartFailedDueToProgramVersionNumberUnsupported{
+            which_log,
+            version_number: global_metadata.version_number,
+            max_supported: V1_PROGRAM_VERSION_NUMBER,
+        });
+    }
+    if global_metadata.length_of_region_metadata != V1_LENGTH_OF_REGION_METADATA {
+        return Err(MultiLogErr::StartFailedDueToInvalidMemoryContents{ which_log });
+    }
+
+    let region_metadata = pm_regions.read(index, ABSOLUTE_POS_OF_REGION_METADATA, V1_LENGTH_OF_REGION_METADATA);
+    let region_crc = pm_regions.read(index, V1_ABSOLUTE_POS_OF_REGION_CRC, CRC_SIZE);
+    if bytes_crc(region_metadata.as_slice()) != region_crc {
+        return Err(MultiLogErr::CRCMismatch);
+    }
+    let region_num_logs = u32::from_le_bytes(le_bytes(&region_metadata, RELATIVE_POS_OF_REGION_NUM_LOGS));
+    let region_which_log = u32::from_le_bytes(le_bytes(&region_metadata, RELATIVE_POS_OF_REGION_WHICH_LOG));
+    let region_region_size = u64::from_le_bytes(le_bytes(&region_metadata, RELATIVE_POS_OF_REGION_REGION_SIZE));
+    let log_area_len = u64::from_le_bytes(le_bytes(&region_metadata, RELATIVE_POS_OF_REGION_LENGTH_OF_LOG_AREA));
+    let region_multilog_id = u128::from_le_bytes(le_bytes(&region_metadata, V1_RELATIVE_POS_OF_REGION_MULTILOG_ID));
+    if region_region_size != region_size {
+        return Err(MultiLogErr::StartFailedDueToRegionSizeMismatch{
+            which_log,
+            region_size_expected: region_size,
+            region_size_read: region_region_size,
+        });
+    }
+    if region_multilog_id != multilog_id {
+        return Err(MultiLogErr::StartFailedDueToMultilogIDMismatch{
+            which_log,
+            multilog_id_expected: multilog_id,
+            multilog_id_read: region_multilog_id,
+        });
+    }
+    if region_num_logs != num_logs || region_which_log != which_log || log_area_len < MIN_LOG_AREA_SIZE ||
+       region_size - ABSOLUTE_POS_OF_LOG_AREA < log_area_len {
+        return Err(MultiLogErr::StartFailedDueToInvalidMemoryContents{ which_log });
+    }
+
+    let (log_metadata_pos, log_crc_pos) =
+        if cdb { (V1_ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_TRUE, V1_ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_TRUE) }
+        else { (V1_ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, V1_ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE) };
+    let log_metadata = pm_regions.read_and_deserialize::<LogMetadata>(index, log_metadata_pos);
+    let log_crc = pm_regions.read_and_deserialize::<u64>(index, log_crc_pos);
+    if calculate_crc(log_metadata) != *log_crc {
+        return Err(MultiLogErr::CRCMismatch);
+    }
+    if log_metadata.log_length > log_area_len || log_metadata.head.checked_add(log_metadata.log_length as u128).is_none() {
+        return Err(MultiLogErr::StartFailedDueToInvalidMemoryContents{ which_log });
+    }
+
+    Ok(V1Region {
+        log_area_len,
+        log_metadata: LogMetadata {
+            log_length: log_metadata.log_length,
+            _padding: 0,
+            head: log_metadata.head,
+        },
+    })
+}
+
+// This function migrates the version-1 multilog with ID
+// `multilog_id` stored in `old_regions` to the current layout,
+// writing it to `new_regions` and flushing them. Afterward,
+// `MultiLogImpl::start(new_regions, multilog_id)` starts a multilog
+// with the same logs, heads, and capacities, whose creation
+// timestamp is 0 and whose label is empty.
+//
+// `old_regions` -- the version-1 regions, which are only read
+//
+// `new_regions` -- regions of the same number and sizes as
+// `old_regions`, whose contents are overwritten
+//
+// `multilog_id` -- the multilog's ID, which is kept
+//
+// It checks the version-1 metadata the way `start` checks current
+// metadata and returns the same errors. If `old_regions` already
+// holds a current multilog, it returns
+// `MultiLogErr::StartFailedDueToProgramVersionNumberUnsupported`.
+#[cfg(not(verus_keep_ghost))]
+pub fn migrate_from_version_1<PMRegions: PersistentMemoryRegions>(
+    old_regions: &PMRegions,
+    new_regions: &mut PMRegions,
+    multilog_id: u128,
+) -> Result<(), MultiLogErr>
+{
+    let num_regions = old_regions.get_num_regions();
+    if num_regions < 1 {
+        return Err(MultiLogErr::CantSetupWithFewerThanOneRegion{ });
+    }
+    if num_regions > u32::MAX as usize {
+        return Err(MultiLogErr::CantSetupWithMoreThanU32MaxRegions{ });
+    }
+    let num_logs = num_regions as u32;
+    if new_regions.get_num_regions() != num_regions {
+        return Err(MultiLogErr::StartFailedDueToInvalidMemoryContents{ which_log: 0 });
+    }
+    for which_log in 0..num_logs {
+        let region_size = old_regions.get_region_size(which_log as usize);
+        let new_region_size = new_regions.get_region_size(which_log as usize);
+        if new_region_size != region_size {
+            return Err(MultiLogErr::StartFailedDueToRegionSizeMismatch{
+                which_log,
+                region_size_expected: region_size,
+                region_size_read: new_region_size,
+            });
+        }
+    }
+
+    // Version 1, like the current version, uses only region 0's CDB.
+
+    if old_regions.get_region_size(0) < ABSOLUTE_POS_OF_LOG_AREA {
+        return Err(MultiLogErr::StartFailedDueToInvalidMemoryContents{ which_log: 0 });
+    }
+    let cdb = match *old_regions.read_and_deserialize::<u64>(0, V1_ABSOLUTE_POS_OF_LOG_CDB) {
+        CDB_FALSE => false,
+        CDB_TRUE => true,
+        _ => { return Err(MultiLogErr::CRCMismatch); },
+    };
+
+    // Read and check every region before writing anything, so a
+    // problem with any of them leaves `new_regions` untouched.
+
+    let mut v1_regions = Vec::new();
+    for which_log in 0..num_logs {
+        v1_regions.push(read_v1_region(old_regions, multilog_id, num_logs, which_log, cdb)?);
+    }
+
+    for (which_log, v1_region) in v1_regions.iter().enumerate() {
+        let region_size = old_regions.get_region_size(which_log);
+        let global_metadata = GlobalMetadata {
+            version_number: MULTILOG_PROGRAM_VERSION_NUMBER,
+            length_of_region_metadata: LENGTH_OF_REGION_METADATA,
+            program_guid: MULTILOG_PROGRAM_GUID,
+        };
+        let region_metadata = RegionMetadata {
+            num_logs,
+            which_log: which_log as u32,
+            region_size,
+            log_area_len: v1_region.log_area_len,
+            creation_timestamp: 0,
+            _padding0: 0,
+            _padding1: 0,
+            multilog_id,
+            label_lo: 0,
+            label_hi: 0,
+        };
+        let log_area = old_regions.read(which_log, ABSOLUTE_POS_OF_LOG_AREA, v1_region.log_area_len);
+
+        new_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_GLOBAL_METADATA, &global_metadata);
+        new_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_GLOBAL_CRC, &calculate_crc(&global_metadata));
+        new_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_REGION_METADATA, &region_metadata);
+        new_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_REGION_CRC, &calculate_crc(&region_metadata));
+        new_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_LOG_CDB, &CDB_FALSE);
+        new_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_LOG_METADATA_FOR_CDB_FALSE, &v1_region.log_metadata);
+        new_regions.serialize_and_write(which_log, ABSOLUTE_POS_OF_LOG_CRC_FOR_CDB_FALSE,
+                                        &calculate_crc(&v1_region.log_metadata));
+        new_regions.write(which_log, ABSOLUTE_POS_OF_LOG_AREA, log_area.as_slice());
+    }
+    new_regions.flush().map_err(|_| MultiLogErr::FlushFailed)
+}
@@ -1,6 +1,12 @@
 pub mod append_v;
+pub mod diff_v;
+pub mod dump_t;
+#[cfg(test)]
+pub mod fixture_t;
+pub mod health_t;
 pub mod inv_v;
 pub mod layout_v;
+pub mod migrate_t;
 pub mod multilogimpl_t;
 pub mod multilogimpl_v;
 pub mod multilogspec_t;
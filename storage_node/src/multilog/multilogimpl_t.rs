@@ -40,6 +40,7 @@
 
 use std::fmt::Write;
 
+use crate::multilog::layout_v::LABEL_SIZE;
 use crate::multilog::multilogimpl_v::UntrustedMultiLogImpl;
 use crate::multilog::multilogspec_t::AbstractMultiLogState;
 use crate::pmem::pmemspec_t::*;
@@ -174,6 +175,7 @@ verus! {
         CantReadPastTail { tail: u128 },
         CantAdvanceHeadPositionBeforeHead { head: u128 },
         CantAdvanceHeadPositionBeyondTail { tail: u128 },
+        FlushFailed,
         PmemErr { err: PmemError } // janky workaround so that callers can handle PmemErrors as MultiLogErrors
     }
 
@@ -185,6 +187,45 @@ verus! {
         deps_hack::rand::thread_rng().gen::<u128>()
     }
 
+    // This executable method returns the current time in seconds
+    // since the Unix epoch, or 0 if the system clock is set earlier
+    // than that. `setup` records it as the creation timestamp.
+    #[verifier::external_body]
+    pub exec fn current_time_in_seconds() -> (out: u64)
+    {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+    }
+
+    // This function encodes `label` as the two `u128`s stored in
+    // region metadata. Its UTF-8 bytes are truncated or padded with
+    // zero bytes to `LABEL_SIZE` bytes. Truncation is done at a
+    // character boundary, so the stored label is always valid UTF-8.
+    #[verifier::external_body]
+    pub exec fn encode_label(label: &str) -> (out: (u128, u128))
+    {
+        let mut end = label.len().min(LABEL_SIZE);
+        while !label.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut bytes = [0u8; LABEL_SIZE];
+        bytes[..end].copy_from_slice(&label.as_bytes()[..end]);
+        (u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+         u128::from_le_bytes(bytes[16..].try_into().unwrap()))
+    }
+
+    // This function is the inverse of `encode_label`. The padding
+    // bytes are dropped. Since the region metadata is CRC-checked,
+    // the bytes should be valid UTF-8, but if they aren't, invalid
+    // sequences are replaced rather than failing.
+    #[verifier::external_body]
+    pub exec fn decode_label(label_lo: u128, label_hi: u128) -> (out: String)
+    {
+        let mut bytes = label_lo.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&label_hi.to_le_bytes());
+        let len = bytes.iter().position(|b| *b == 0).unwrap_or(LABEL_SIZE);
+        String::from_utf8_lossy(&bytes[..len]).into_owned()
+    }
+
     /// A `MultiLogImpl` wraps one `UntrustedMultiLogImpl` and a
     /// collection of persistent memory regions to provide the
     /// executable interface that turns the persistent memory regions
@@ -241,6 +282,20 @@ verus! {
             &&& can_only_crash_as_state(self.wrpm_regions@, self.multilog_id@, self@.drop_pending_appends())
         }
 
+        // This predicate describes what we guarantee about crashes
+        // after an operation fails with `MultiLogErr::FlushFailed`. In
+        // that case, `self` is no longer valid, so the caller has to
+        // drop it and restart the multilog with `start`. But the
+        // persistent memory, if it crashes and recovers, must
+        // represent either `s1` or `s2`.
+        pub closed spec fn can_only_crash_as_one_of(self, s1: AbstractMultiLogState,
+                                                    s2: AbstractMultiLogState) -> bool {
+            forall |s| #[trigger] self.wrpm_regions@.can_crash_as(s) ==> {
+                ||| UntrustedMultiLogImpl::recover(s, self.multilog_id@) == Some(s1)
+                ||| UntrustedMultiLogImpl::recover(s, self.multilog_id@) == Some(s2)
+            }
+        }
+
         proof fn lemma_valid_implies_wrpm_inv(self)
             requires
                 self.valid()
@@ -269,10 +324,10 @@ verus! {
                 old(pm_regions).inv(),
             ensures
                 pm_regions.inv(),
-                pm_regions@.no_outstanding_writes(),
                 match result {
                     Ok((log_capacities, multilog_id)) => {
                         let state = AbstractMultiLogState::initialize(log_capacities@);
+                        &&& pm_regions@.no_outstanding_writes()
                         &&& pm_regions@.len() == old(pm_regions)@.len()
                         &&& pm_regions@.len() >= 1
                         &&& pm_regions@.len() <= u32::MAX
@@ -303,11 +358,69 @@ verus! {
                         &&& pm_regions@ == flushed_regions
                         &&& pm_regions@.len() > u32::MAX
                     },
+                    Err(MultiLogErr::FlushFailed) => true,
+                    _ => false
+                }
+        {
+            Self::setup_with_label(pm_regions, "")
+        }
+
+        // The `setup_with_label` method is like `setup`, except that
+        // it also records `label`, a short human-readable name for
+        // the multilog, in every region's metadata. Only the first
+        // `LABEL_SIZE` bytes of `label` are kept. `get_label` returns
+        // it once the multilog is started.
+        pub exec fn setup_with_label(
+            pm_regions: &mut PMRegions,
+            label: &str,
+        ) -> (result: Result<(Vec<u64>, u128), MultiLogErr>)
+            requires
+                old(pm_regions).inv(),
+            ensures
+                pm_regions.inv(),
+                match result {
+                    Ok((log_capacities, multilog_id)) => {
+                        let state = AbstractMultiLogState::initialize(log_capacities@);
+                        &&& pm_regions@.no_outstanding_writes()
+                        &&& pm_regions@.len() == old(pm_regions)@.len()
+                        &&& pm_regions@.len() >= 1
+                        &&& pm_regions@.len() <= u32::MAX
+                        &&& log_capacities@.len() == pm_regions@.len()
+                        &&& forall |i: int| 0 <= i < pm_regions@.len() ==>
+                               #[trigger] log_capacities@[i] <= pm_regions@[i].len()
+                        &&& forall |i: int| 0 <= i < pm_regions@.len() ==>
+                               #[trigger] pm_regions@[i].len() == old(pm_regions)@[i].len()
+                        &&& can_only_crash_as_state(pm_regions@, multilog_id, state)
+                        &&& UntrustedMultiLogImpl::recover(pm_regions@.committed(), multilog_id) == Some(state)
+                        // Required by the `start` function's precondition. Putting this in the
+                        // postcond of `setup` ensures that the trusted caller doesn't have to prove it
+                        &&& UntrustedMultiLogImpl::recover(pm_regions@.flush().committed(), multilog_id) == Some(state)
+                        &&& state == state.drop_pending_appends()
+                    },
+                    Err(MultiLogErr::InsufficientSpaceForSetup { which_log, required_space }) => {
+                        let flushed_regions = old(pm_regions)@.flush();
+                        &&& pm_regions@ == flushed_regions
+                        &&& pm_regions@[which_log as int].len() < required_space
+                    },
+                    Err(MultiLogErr::CantSetupWithFewerThanOneRegion { }) => {
+                        let flushed_regions = old(pm_regions)@.flush();
+                        &&& pm_regions@ == flushed_regions
+                        &&& pm_regions@.len() < 1
+                    },
+                    Err(MultiLogErr::CantSetupWithMoreThanU32MaxRegions { }) => {
+                        let flushed_regions = old(pm_regions)@.flush();
+                        &&& pm_regions@ == flushed_regions
+                        &&& pm_regions@.len() > u32::MAX
+                    },
+                    Err(MultiLogErr::FlushFailed) => true,
                     _ => false
                 }
         {
             let multilog_id = generate_fresh_multilog_id();
-            let capacities = UntrustedMultiLogImpl::setup(pm_regions, multilog_id)?;
+            let creation_timestamp = current_time_in_seconds();
+            let (label_lo, label_hi) = encode_label(label);
+            let capacities = UntrustedMultiLogImpl::setup(pm_regions, multilog_id, creation_timestamp,
+                                                          label_lo, label_hi)?;
             Ok((capacities, multilog_id))
         }
 
@@ -330,6 +443,7 @@ verus! {
                                                                                      multilog_id)
                     },
                     Err(MultiLogErr::CRCMismatch) => !pm_regions.constants().impervious_to_corruption,
+                    Err(MultiLogErr::FlushFailed) => true,
                     _ => false
                 }
         {
@@ -404,18 +518,28 @@ verus! {
 
         // The `commit` method atomically commits all tentative
         // appends that have been done to `self` since the last
-        // commit. The commit is atomic in that even if there's a
-        // crash in the middle, the recovered-to state either reflects
-        // all those tentative appends or none of them. See `README.md`
-        // for more documentation and examples of use.
+        // commit, to every log at once. The commit is atomic in that
+        // even if there's a crash in the middle, the recovered-to
+        // state either reflects all those tentative appends or none
+        // of them. It writes each region's new log metadata to its
+        // inactive slot, flushes all regions, and then flips the
+        // shared CDB in region 0 and flushes that region. The CDB
+        // flip is a single write within the persistence granularity,
+        // which is what makes the commit atomic across logs. See
+        // `README.md` for more documentation and examples of use.
         pub exec fn commit(&mut self) -> (result: Result<(), MultiLogErr>)
             requires
                 old(self).valid(),
             ensures
-                self.valid(),
                 self.constants() == old(self).constants(),
                 match result {
-                    Ok(()) => self@ == old(self)@.commit(),
+                    Ok(()) => {
+                        &&& self.valid()
+                        &&& self@ == old(self)@.commit()
+                    },
+                    Err(MultiLogErr::FlushFailed) =>
+                        self.can_only_crash_as_one_of(old(self)@.drop_pending_appends(),
+                                                      old(self)@.commit().drop_pending_appends()),
                     _ => false,
                 }
         {
@@ -441,31 +565,39 @@ verus! {
             requires
                 old(self).valid(),
             ensures
-                self.valid(),
                 self.constants() == old(self).constants(),
                 match result {
                     Ok(()) => {
                         let w = which_log as int;
+                        &&& self.valid()
                         &&& which_log < self@.num_logs()
                         &&& old(self)@[w].head <= new_head <= old(self)@[w].head + old(self)@[w].log.len()
                         &&& self@ == old(self)@.advance_head(w, new_head as int)
                     },
                     Err(MultiLogErr::InvalidLogIndex{ }) => {
+                        &&& self.valid()
                         &&& which_log >= self@.num_logs()
                         &&& self@ == old(self)@
                     },
                     Err(MultiLogErr::CantAdvanceHeadPositionBeforeHead { head }) => {
+                        &&& self.valid()
                         &&& self@ == old(self)@
                         &&& which_log < self@.num_logs()
                         &&& head == self@[which_log as int].head
                         &&& new_head < head
                     },
                     Err(MultiLogErr::CantAdvanceHeadPositionBeyondTail { tail }) => {
+                        &&& self.valid()
                         &&& self@ == old(self)@
                         &&& which_log < self@.num_logs()
                         &&& tail == self@[which_log as int].head + self@[which_log as int].log.len()
                         &&& new_head > tail
                     },
+                    Err(MultiLogErr::FlushFailed) =>
+                        self.can_only_crash_as_one_of(
+                            old(self)@.drop_pending_appends(),
+                            old(self)@.advance_head(which_log as int, new_head as int).drop_pending_appends()
+                        ),
                     _ => false,
                 }
         {
@@ -553,6 +685,30 @@ verus! {
         {
             self.untrusted_log_impl.get_head_tail_and_capacity(&self.wrpm_regions, which_log, self.multilog_id)
         }
+
+        // The `get_creation_timestamp` method returns when the
+        // multilog was set up, in seconds since the Unix epoch. It's
+        // recorded once by `setup` and read back by `start`, so it's
+        // the same across crashes and restarts. It's 0 for a
+        // multilog migrated from version 1, which didn't record it.
+        pub exec fn get_creation_timestamp(&self) -> (result: u64)
+            requires
+                self.valid()
+        {
+            self.untrusted_log_impl.get_creation_timestamp()
+        }
+
+        // The `get_label` method returns the label the multilog was
+        // set up with, or the empty string if it was set up without
+        // one. Like the creation timestamp, it's read back by
+        // `start`.
+        pub exec fn get_label(&self) -> (result: String)
+            requires
+                self.valid()
+        {
+            let (label_lo, label_hi) = self.untrusted_log_impl.get_label();
+            decode_label(label_lo, label_hi)
+        }
     }
 
 }
@@ -13,7 +13,9 @@ use crate::multilog::multilogspec_t::AbstractMultiLogState;
 use crate::multilog::setup_v::{
     check_for_required_space, compute_log_capacities, write_setup_metadata_to_all_regions,
 };
-use crate::multilog::start_v::{read_cdb, read_logs_variables};
+use crate::multilog::start_v::{
+    check_program_version, read_cdb, read_creation_timestamp, read_label, read_logs_variables,
+};
 use crate::pmem::pmemspec_t::*;
 use crate::pmem::pmemutil_v::*;
 use crate::pmem::serialization_t::*;
@@ -58,11 +60,16 @@ verus! {
     //
     // `num_logs` -- the number of logs in the multilog
     // `cdb` -- the current value of the corruption-detecting boolean
+    // `creation_timestamp` -- when the multilog was set up
+    // `label_lo`, `label_hi` -- the multilog's label, as two `u128`s
     // `infos` -- a vector of `LogInfo`s, one per log
     // `state` -- the abstract view of the multilog
     pub struct UntrustedMultiLogImpl {
         num_logs: u32,
         cdb: bool,
+        creation_timestamp: u64,
+        label_lo: u128,
+        label_hi: u128,
         infos: Vec<LogInfo>,
         state: Ghost<AbstractMultiLogState>
     }
@@ -139,10 +146,15 @@ verus! {
         // The `setup` method sets up persistent memory objects `pm_regions`
         // to store an initial empty multilog. It returns a vector
         // listing the capacities of the logs. See `README.md` for more
-        // documentation.
+        // documentation. `creation_timestamp` and the label, given as
+        // `label_lo` and `label_hi`, are recorded in every region's
+        // metadata, and `start` reads them back.
         pub exec fn setup<PMRegions>(
             pm_regions: &mut PMRegions,
             multilog_id: u128,
+            creation_timestamp: u64,
+            label_lo: u128,
+            label_hi: u128,
         ) -> (result: Result<Vec<u64>, MultiLogErr>)
             where
                 PMRegions: PersistentMemoryRegions
@@ -151,10 +163,10 @@ verus! {
             ensures
                 pm_regions.inv(),
                 pm_regions.constants() == old(pm_regions).constants(),
-                pm_regions@.no_outstanding_writes(),
                 match result {
                     Ok(log_capacities) => {
                         let state = AbstractMultiLogState::initialize(log_capacities@);
+                        &&& pm_regions@.no_outstanding_writes()
                         &&& pm_regions@.len() == old(pm_regions)@.len()
                         &&& pm_regions@.len() >= 1
                         &&& pm_regions@.len() <= u32::MAX
@@ -182,6 +194,7 @@ verus! {
                         &&& pm_regions@ == flushed_regions
                         &&& pm_regions@.len() > u32::MAX
                     },
+                    Err(MultiLogErr::FlushFailed) => true,
                     _ => false
                 }
         {
@@ -207,7 +220,10 @@ verus! {
             // might be invalid. So we need to flush before writing
             // anything anyway.
 
-            pm_regions.flush();
+            match pm_regions.flush() {
+                Ok(()) => {},
+                Err(_) => { return Err(MultiLogErr::FlushFailed); },
+            }
 
             // Get the list of region sizes and make sure they support
             // storing a multilog. If not, return an appropriate
@@ -231,7 +247,12 @@ verus! {
 
             // Write setup metadata to all regions.
 
-            write_setup_metadata_to_all_regions(pm_regions, &region_sizes, Ghost(log_capacities@), multilog_id);
+            match write_setup_metadata_to_all_regions(pm_regions, &region_sizes, Ghost(log_capacities@),
+                                                      multilog_id, creation_timestamp,
+                                                      label_lo, label_hi) {
+                Ok(()) => {},
+                Err(_) => { return Err(MultiLogErr::FlushFailed); },
+            }
 
             proof {
                 // Prove various postconditions about how we can
@@ -283,6 +304,7 @@ verus! {
                         &&& can_only_crash_as_state(wrpm_regions@, multilog_id, state.drop_pending_appends())
                     },
                     Err(MultiLogErr::CRCMismatch) => !wrpm_regions.constants().impervious_to_corruption,
+                    Err(MultiLogErr::FlushFailed) => true,
                     _ => false
                 }
         {
@@ -290,7 +312,10 @@ verus! {
             // writes to various location. To make sure of this, we
             // flush all memory regions.
 
-            wrpm_regions.flush();
+            match wrpm_regions.flush() {
+                Ok(()) => {},
+                Err(_) => { return Err(MultiLogErr::FlushFailed); },
+            }
 
             // Out of paranoia, we check to make sure that the number
             // of regions is sensible. Both cases are technically
@@ -312,12 +337,29 @@ verus! {
             }
             let num_logs = num_regions as u32;
 
-            // First, we read the corruption-detecting boolean and
+            // First, we check that region 0 was written by this
+            // version of the code, since other versions lay out the
+            // metadata differently.
+
+            check_program_version(pm_regions)?;
+
+            // Next, we read the creation timestamp, which isn't part
+            // of the abstract state but is reported by
+            // `get_creation_timestamp`.
+
+            let creation_timestamp = read_creation_timestamp(pm_regions)?;
+
+            // Likewise, we read the label, which is reported by
+            // `get_label`.
+
+            let (label_lo, label_hi) = read_label(pm_regions)?;
+
+            // Next, we read the corruption-detecting boolean and
             // return an error if that fails.
 
             let cdb = read_cdb(pm_regions)?;
 
-            // Second, we read the logs variables to store in
+            // Then, we read the logs variables to store in
             // `infos`. If that fails, we return an error.
 
             let infos = read_logs_variables(pm_regions, multilog_id, cdb, num_logs, Ghost(state))?;
@@ -333,7 +375,7 @@ verus! {
                                                             infos@, state);
                 lemma_recovered_state_is_crash_idempotent(wrpm_regions@.committed(), multilog_id);
             }
-            Ok(Self{ num_logs, cdb, infos, state: Ghost(state) })
+            Ok(Self{ num_logs, cdb, creation_timestamp, label_lo, label_hi, infos, state: Ghost(state) })
         }
 
         // The `tentatively_append` method tentatively appends
@@ -561,11 +603,58 @@ verus! {
             Ok(old_pending_tail)
         }
 
+        // This local helper method flushes the regions in
+        // `wrpm_regions` one at a time with `flush_region`, so that
+        // the outstanding writes to each are durable before the
+        // caller writes the CDB in region #0. If a flush fails, it
+        // returns that error, leaving each region either flushed or
+        // unchanged.
+        exec fn flush_each_region<PMRegions>(
+            wrpm_regions: &mut WriteRestrictedPersistentMemoryRegions<TrustedPermission, PMRegions>,
+            num_regions: u32,
+        ) -> (result: Result<(), PmemError>)
+            where
+                PMRegions: PersistentMemoryRegions
+            requires
+                old(wrpm_regions).inv(),
+                old(wrpm_regions)@.len() == num_regions,
+            ensures
+                wrpm_regions.inv(),
+                wrpm_regions.constants() == old(wrpm_regions).constants(),
+                wrpm_regions@.len() == old(wrpm_regions)@.len(),
+                match result {
+                    Ok(()) => wrpm_regions@ == old(wrpm_regions)@.flush(),
+                    Err(_) => forall |i: int| 0 <= i < num_regions ==> {
+                        ||| #[trigger] wrpm_regions@[i] == old(wrpm_regions)@[i]
+                        ||| wrpm_regions@[i] == old(wrpm_regions)@[i].flush()
+                    },
+                },
+        {
+            for which_region in 0..num_regions
+                invariant
+                    wrpm_regions.inv(),
+                    wrpm_regions.constants() == old(wrpm_regions).constants(),
+                    wrpm_regions@.len() == old(wrpm_regions)@.len() == num_regions,
+                    forall |i: int| 0 <= i < which_region ==>
+                        #[trigger] wrpm_regions@[i] == old(wrpm_regions)@[i].flush(),
+                    forall |i: int| which_region <= i < num_regions ==>
+                        #[trigger] wrpm_regions@[i] == old(wrpm_regions)@[i],
+            {
+                match wrpm_regions.flush_region(which_region as usize) {
+                    Ok(()) => {},
+                    Err(e) => { return Err(e); },
+                }
+            }
+            assert(wrpm_regions@ =~= old(wrpm_regions)@.flush());
+            Ok(())
+        }
+
         // This local helper method updates the log metadata on
         // persistent memory to be consistent with `self.infos` and
         // `self.state`. It does so in the following steps: (1) update, in
         // each region, the log metadata corresponding to the inactive
-        // CDB; (2) flush; (3) swap the CDB in region #0; (4) flush again.
+        // CDB; (2) flush each region; (3) swap the CDB in region #0;
+        // (4) flush region #0 again.
         //
         // The first of these steps only writes to inactive metadata, i.e.,
         // metadata that's ignored during recovery. So even if a crash
@@ -589,6 +678,11 @@ verus! {
         // after the next flush, since we're going to be doing a flush.
         // This weaker requirement allows a performance optimization: the
         // caller doesn't have to flush before calling this function.
+        //
+        // If either flush fails, this returns
+        // `Err(MultiLogErr::FlushFailed)` without flipping `self.cdb`.
+        // The invariant no longer holds in that case, but any crash is
+        // still permitted by `perm`.
         exec fn update_log_metadata<PMRegions>(
             &mut self,
             wrpm_regions: &mut WriteRestrictedPersistentMemoryRegions<TrustedPermission, PMRegions>,
@@ -596,7 +690,7 @@ verus! {
             Ghost(prev_infos): Ghost<Seq<LogInfo>>,
             Ghost(prev_state): Ghost<AbstractMultiLogState>,
             Tracked(perm): Tracked<&TrustedPermission>,
-        )
+        ) -> (result: Result<(), MultiLogErr>)
             where
                 PMRegions: PersistentMemoryRegions
             requires
@@ -615,9 +709,15 @@ verus! {
                           ||| Self::recover(s, multilog_id) == Some(old(self).state@.drop_pending_appends())
                       } ==> #[trigger] perm.check_permission(s),
             ensures
-                self.inv(wrpm_regions, multilog_id),
+                wrpm_regions.inv(),
                 wrpm_regions.constants() == old(wrpm_regions).constants(),
                 self.state == old(self).state,
+                match result {
+                    Ok(()) => self.inv(wrpm_regions, multilog_id),
+                    Err(MultiLogErr::FlushFailed) =>
+                        forall |s| #[trigger] wrpm_regions@.can_crash_as(s) ==> perm.check_permission(s),
+                    _ => false
+                },
         {
             // Set the `unused_metadata_pos` to be the position corresponding to !self.cdb
             // since we're writing in the inactive part of the metadata.
@@ -763,10 +863,27 @@ verus! {
                                                              prev_infos, prev_state);
             }
 
-            // Next, flush all outstanding writes to memory. This is
+            // Next, flush each region's outstanding writes. This is
             // necessary so that those writes are ordered before the update
-            // to the CDB.
-            wrpm_regions.flush();
+            // to the CDB. We flush the regions one at a time, since
+            // that's all the CDB write depends on. If a flush fails, each
+            // region's metadata writes may or may not have reached memory,
+            // but the invariants we just established hold in every such
+            // case, so crashing is permitted.
+            let ghost pm_regions_before_flush = wrpm_regions@;
+            match Self::flush_each_region(wrpm_regions, self.num_logs) {
+                Ok(()) => {},
+                Err(_) => {
+                    proof {
+                        lemma_partially_flushing_metadata_maintains_invariants(
+                            pm_regions_before_flush, wrpm_regions@, multilog_id, self.num_logs, self.cdb,
+                            prev_infos, prev_state);
+                        lemma_invariants_imply_crash_recover_forall(wrpm_regions@, multilog_id, self.num_logs,
+                                                                    self.cdb, prev_infos, prev_state);
+                    }
+                    return Err(MultiLogErr::FlushFailed);
+                },
+            }
 
             // Next, compute the new encoded CDB to write.
 
@@ -856,14 +973,33 @@ verus! {
                                                                    ABSOLUTE_POS_OF_LOG_CDB as int, new_cdb_bytes);
             }
 
+            // The CDB is the only outstanding write, and it's in region
+            // 0, so flushing just region 0 is as good as flushing
+            // everything.
+
+            proof {
+                assert forall |i: int| 0 <= i < pm_regions_after_write.len() && i != 0 implies
+                       #[trigger] pm_regions_after_write[i].no_outstanding_writes() by {
+                    assert(pm_regions_after_write[i] == pm_regions_before_flush[i].flush());
+                }
+                lemma_flush_region_is_flush_if_no_other_outstanding_writes(pm_regions_after_write, 0);
+            }
+
             // Finally, update the CDB, then flush, then flip `self.cdb`.
             // There's no need to flip `self.cdb` atomically with the write
             // since the flip of `self.cdb` is happening in local
             // non-persistent memory so if we crash it'll be lost anyway.
             // wrpm_regions.write(0, ABSOLUTE_POS_OF_LOG_CDB, new_cdb.as_slice(), Tracked(perm));
             wrpm_regions.serialize_and_write(0, ABSOLUTE_POS_OF_LOG_CDB, &new_cdb, Tracked(perm));
-            wrpm_regions.flush();
+            match wrpm_regions.flush_region(0) {
+                Ok(()) => {},
+                Err(_) => {
+                    assert(wrpm_regions@ == pm_regions_after_write);
+                    return Err(MultiLogErr::FlushFailed);
+                },
+            }
             self.cdb = !self.cdb;
+            Ok(())
         }
 
         // The `commit` method commits all tentative appends that have been
@@ -894,11 +1030,20 @@ verus! {
                     ||| Self::recover(s, multilog_id) == Some(old(self)@.commit().drop_pending_appends())
                 },
             ensures
-                self.inv(wrpm_regions, multilog_id),
+                wrpm_regions.inv(),
                 wrpm_regions.constants() == old(wrpm_regions).constants(),
-                can_only_crash_as_state(wrpm_regions@, multilog_id, self@.drop_pending_appends()),
-                result is Ok,
-                self@ == old(self)@.commit(),
+                match result {
+                    Ok(()) => {
+                        &&& self.inv(wrpm_regions, multilog_id)
+                        &&& can_only_crash_as_state(wrpm_regions@, multilog_id, self@.drop_pending_appends())
+                        &&& self@ == old(self)@.commit()
+                    },
+                    Err(MultiLogErr::FlushFailed) => forall |s| #[trigger] wrpm_regions@.can_crash_as(s) ==> {
+                        ||| Self::recover(s, multilog_id) == Some(old(self)@.drop_pending_appends())
+                        ||| Self::recover(s, multilog_id) == Some(old(self)@.commit().drop_pending_appends())
+                    },
+                    _ => false
+                }
         {
             let ghost prev_infos = self.infos@;
             let ghost prev_state = self.state@;
@@ -957,9 +1102,7 @@ verus! {
             // swap the CDB to its opposite.
 
             self.update_log_metadata(wrpm_regions, Ghost(multilog_id), Ghost(prev_infos),
-                                        Ghost(prev_state), Tracked(perm));
-
-            Ok(())
+                                        Ghost(prev_state), Tracked(perm))
         }
 
         // The `advance_head` method advances the head of one of the logs,
@@ -995,32 +1138,44 @@ verus! {
                         Some(old(self)@.advance_head(which_log as int, new_head as int).drop_pending_appends())
                 },
             ensures
-                self.inv(wrpm_regions, multilog_id),
+                wrpm_regions.inv(),
                 wrpm_regions.constants() == old(wrpm_regions).constants(),
-                can_only_crash_as_state(wrpm_regions@, multilog_id, self@.drop_pending_appends()),
                 match result {
                     Ok(()) => {
                         let w = which_log as int;
+                        &&& self.inv(wrpm_regions, multilog_id)
+                        &&& can_only_crash_as_state(wrpm_regions@, multilog_id, self@.drop_pending_appends())
                         &&& which_log < self@.num_logs()
                         &&& old(self)@[w].head <= new_head <= old(self)@[w].head + old(self)@[w].log.len()
                         &&& self@ == old(self)@.advance_head(w, new_head as int)
                     },
                     Err(MultiLogErr::InvalidLogIndex{ }) => {
+                        &&& self.inv(wrpm_regions, multilog_id)
+                        &&& can_only_crash_as_state(wrpm_regions@, multilog_id, self@.drop_pending_appends())
                         &&& self@ == old(self)@
                         &&& which_log >= self@.num_logs()
                     },
                     Err(MultiLogErr::CantAdvanceHeadPositionBeforeHead { head }) => {
+                        &&& self.inv(wrpm_regions, multilog_id)
+                        &&& can_only_crash_as_state(wrpm_regions@, multilog_id, self@.drop_pending_appends())
                         &&& self@ == old(self)@
                         &&& which_log < self@.num_logs()
                         &&& head == self@[which_log as int].head
                         &&& new_head < head
                     },
                     Err(MultiLogErr::CantAdvanceHeadPositionBeyondTail { tail }) => {
+                        &&& self.inv(wrpm_regions, multilog_id)
+                        &&& can_only_crash_as_state(wrpm_regions@, multilog_id, self@.drop_pending_appends())
                         &&& self@ == old(self)@
                         &&& which_log < self@.num_logs()
                         &&& tail == self@[which_log as int].head + self@[which_log as int].log.len()
                         &&& new_head > tail
                     },
+                    Err(MultiLogErr::FlushFailed) => forall |s| #[trigger] wrpm_regions@.can_crash_as(s) ==> {
+                        ||| Self::recover(s, multilog_id) == Some(old(self)@.drop_pending_appends())
+                        ||| Self::recover(s, multilog_id) ==
+                            Some(old(self)@.advance_head(which_log as int, new_head as int).drop_pending_appends())
+                    },
                     _ => false
                 }
         {
@@ -1167,9 +1322,7 @@ verus! {
             // to update the inactive metadata on all regions.
 
             self.update_log_metadata(wrpm_regions, Ghost(multilog_id), Ghost(prev_infos), Ghost(prev_state),
-                                        Tracked(perm));
-
-            Ok(())
+                                        Tracked(perm))
         }
 
         // This local helper method proves that we can read a portion of
@@ -1465,6 +1618,20 @@ verus! {
             let info = &self.infos[which_log as usize];
             Ok((info.head, info.head + info.log_length as u128, info.log_area_len))
         }
+
+        // The `get_creation_timestamp` method returns the creation
+        // timestamp read from region 0's metadata during `start`.
+        pub exec fn get_creation_timestamp(&self) -> (result: u64)
+        {
+            self.creation_timestamp
+        }
+
+        // The `get_label` method returns the label read from region
+        // 0's metadata during `start`, as two `u128`s.
+        pub exec fn get_label(&self) -> (result: (u128, u128))
+        {
+            (self.label_lo, self.label_hi)
+        }
     }
 
 }
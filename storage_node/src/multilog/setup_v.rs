@@ -99,12 +99,17 @@ verus! {
     // `multilog_id` -- the GUID of the multilog it's being used for
     // `num_logs` -- the number of logs in the multilog
     // `which_log` -- which among those logs this region is for
+    // `creation_timestamp` -- when the multilog was set up
+    // `label_lo`, `label_hi` -- the multilog's label, as two `u128`s
     spec fn memory_correctly_set_up_on_single_region(
         mem: Seq<u8>,
         region_size: u64,
         multilog_id: u128,
         num_logs: u32,
         which_log: u32,
+        creation_timestamp: u64,
+        label_lo: u128,
+        label_hi: u128,
     ) -> bool
     {
         let global_crc = deserialize_global_crc(mem);
@@ -126,6 +131,9 @@ verus! {
         &&& region_metadata.multilog_id == multilog_id
         &&& region_metadata.num_logs == num_logs
         &&& region_metadata.which_log == which_log
+        &&& region_metadata.creation_timestamp == creation_timestamp
+        &&& region_metadata.label_lo == label_lo
+        &&& region_metadata.label_hi == label_hi
         &&& region_metadata.log_area_len == region_size - ABSOLUTE_POS_OF_LOG_AREA
         &&& log_cdb == Some(false)
         &&& log_metadata.head == 0
@@ -140,6 +148,9 @@ verus! {
     // `multilog_id`: the GUID of the multilog it's being used for
     // `num_logs`: the number of logs in the multilog
     // `which_log`: which among those logs this region is for
+    // `creation_timestamp`: when the multilog was set up, in seconds
+    // since the Unix epoch
+    // `label_lo`, `label_hi`: the multilog's label, as two `u128`s
     //
     // It also needs the parameter `pm_regions` that gives the
     // persistent memory regions for us to write to. It'll only write
@@ -148,7 +159,8 @@ verus! {
     // The main postcondition is:
     //
     // `memory_correctly_set_up_on_single_region(pm_regions@[which_log as int].flush().committed(),
-    //                                           region_size, multilog_id, num_logs, which_log)`
+    //                                           region_size, multilog_id, num_logs, which_log,
+    //                                           creation_timestamp, label_lo, label_hi)`
     //
     // This means that, after the next flush, the memory in this
     // region will have been set up correctly. (This function doesn't
@@ -160,6 +172,9 @@ verus! {
         multilog_id: u128,
         num_logs: u32,
         which_log: u32,
+        creation_timestamp: u64,
+        label_lo: u128,
+        label_hi: u128,
     )
         requires
             old(pm_regions).inv(),
@@ -175,7 +190,8 @@ verus! {
             forall |i: int| 0 <= i < pm_regions@.len() && i != which_log ==> pm_regions@[i] == old(pm_regions)@[i],
             memory_correctly_set_up_on_single_region(
                 pm_regions@[which_log as int].flush().committed(), // it'll be correct after the next flush
-                region_size, multilog_id, num_logs, which_log),
+                region_size, multilog_id, num_logs, which_log, creation_timestamp,
+                label_lo, label_hi),
     {
 
         // Initialize global metadata and compute its CRC
@@ -196,7 +212,11 @@ verus! {
             num_logs,
             which_log,
             log_area_len: region_size - ABSOLUTE_POS_OF_LOG_AREA,
-            _padding: 0,
+            creation_timestamp,
+            _padding0: 0,
+            _padding1: 0,
+            label_lo,
+            label_hi,
         };
         let region_crc = calculate_crc(&region_metadata);
 
@@ -206,7 +226,8 @@ verus! {
         // Initialize log metadata and compute its CRC
         let log_metadata = LogMetadata {
             head: 0,
-            _padding: 0,
+            _padding0: 0,
+            _padding1: 0,
             log_length: 0
         };
         let log_crc = calculate_crc(&log_metadata);
@@ -282,6 +303,12 @@ verus! {
     //
     // `multilog_id`: the GUID of the multilog it's being used for
     //
+    // `creation_timestamp`: when the multilog was set up, in seconds
+    // since the Unix epoch. It's recorded in every region's metadata.
+    //
+    // `label_lo`, `label_hi`: the multilog's label, as two `u128`s.
+    // It's also recorded in every region's metadata.
+    //
     // It also needs the parameter `pm_regions` that gives the
     // persistent memory regions for us to write to.
     //
@@ -296,12 +323,18 @@ verus! {
     // the resulting recovered abstract state will be the valid
     // initial value
     // `AbstractMultiLogState::initialize(log_capacities)`.
+    //
+    // This postcondition only holds if the final flush succeeds. If
+    // it fails, this returns the error from that flush.
     pub fn write_setup_metadata_to_all_regions<PMRegions: PersistentMemoryRegions>(
         pm_regions: &mut PMRegions,
         region_sizes: &Vec<u64>,
         Ghost(log_capacities): Ghost<Seq<u64>>,
         multilog_id: u128,
-    )
+        creation_timestamp: u64,
+        label_lo: u128,
+        label_hi: u128,
+    ) -> (result: Result<(), PmemError>)
         requires
             old(pm_regions).inv(),
             old(pm_regions)@.len() == region_sizes@.len() == log_capacities.len(),
@@ -318,8 +351,14 @@ verus! {
             pm_regions.constants() == old(pm_regions).constants(),
             pm_regions@.len() == old(pm_regions)@.len(),
             forall |i: int| 0 <= i < pm_regions@.len() ==> #[trigger] pm_regions@[i].len() == old(pm_regions)@[i].len(),
-            pm_regions@.no_outstanding_writes(),
-            recover_all(pm_regions@.committed(), multilog_id) == Some(AbstractMultiLogState::initialize(log_capacities)),
+            match result {
+                Ok(()) => {
+                    &&& pm_regions@.no_outstanding_writes()
+                    &&& recover_all(pm_regions@.committed(), multilog_id) ==
+                           Some(AbstractMultiLogState::initialize(log_capacities))
+                },
+                Err(_) => true,
+            },
     {
         // Loop `which_log` from 0 to `region_sizes.len() - 1`, each time
         // setting up the metadata for region `which_log`.
@@ -344,11 +383,13 @@ verus! {
                 // The key invariant is that every region less than `which_log` has been set up correctly.
                 forall |i: u32| i < which_log ==>
                     memory_correctly_set_up_on_single_region(#[trigger] pm_regions@[i as int].flush().committed(),
-                                                             region_sizes@[i as int], multilog_id, num_logs, i),
+                                                             region_sizes@[i as int], multilog_id, num_logs, i,
+                                                             creation_timestamp, label_lo, label_hi),
         {
             let region_size: u64 = region_sizes[which_log as usize];
             assert (region_size == pm_regions@[which_log as int].len());
-            write_setup_metadata_to_single_region(pm_regions, region_size, multilog_id, num_logs, which_log);
+            write_setup_metadata_to_single_region(pm_regions, region_size, multilog_id, num_logs, which_log,
+                                                  creation_timestamp, label_lo, label_hi);
         }
 
         proof {
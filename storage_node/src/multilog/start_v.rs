@@ -11,7 +11,7 @@ use crate::multilog::layout_v::*;
 use crate::multilog::multilogimpl_t::MultiLogErr;
 use crate::multilog::multilogimpl_v::LogInfo;
 use crate::multilog::multilogspec_t::AbstractMultiLogState;
-use crate::pmem::pmemspec_t::{PersistentMemoryRegions, CRC_SIZE};
+use crate::pmem::pmemspec_t::{PersistentMemoryRegions, CDB_FALSE, CDB_TRUE, CRC_SIZE};
 use crate::pmem::pmemutil_v::{check_cdb, check_crc, check_crc_deserialized};
 use crate::pmem::serialization_t::*;
 use builtin::*;
@@ -63,6 +63,252 @@ verus! {
         }
     }
 
+    // This exported function reads the corruption-detecting boolean
+    // from region 0's metadata, like `read_cdb`, but without requiring
+    // that region 0 hold a multilog. It's the safe first step when
+    // opening regions of unknown provenance, since it checks the
+    // region's size and global metadata before reading the CDB.
+    //
+    // `pm_regions` -- the persistent-memory regions to read from
+    //
+    // The result is a `Result<Option<bool>, MultiLogErr>` with the following meanings:
+    //
+    // `Ok(None)` -- Region 0 is too short to hold a multilog's
+    // metadata, or its metadata shows it isn't a multilog region
+    // this code can interpret.
+    //
+    // `Err(MultiLogErr::CRCMismatch)` -- The metadata couldn't be
+    // read due to a CRC error, or an invalid CDB was read. If the
+    // regions are known to be impervious to corruption, this is
+    // reported as `Ok(None)` instead, since it can only mean region
+    // 0 isn't a multilog region.
+    //
+    // `Ok(Some(b))` -- The CDB could be read and represents the boolean `b`.
+    pub fn probe_cdb<PMRegions: PersistentMemoryRegions>(pm_regions: &PMRegions)
+                                                         -> (result: Result<Option<bool>, MultiLogErr>)
+        requires
+            pm_regions.inv(),
+            pm_regions@.len() > 0,
+            pm_regions@.no_outstanding_writes(),
+        ensures
+            ({
+                let cdb = recover_cdb(pm_regions@[0].committed());
+                match result {
+                    Ok(Some(b)) => cdb.is_Some() ==> cdb == Some(b),
+                    Ok(None) => cdb.is_None(),
+                    Err(MultiLogErr::CRCMismatch) => cdb.is_Some() ==> !pm_regions.constants().impervious_to_corruption,
+                    _ => false,
+                }
+            })
+    {
+        let ghost mem = pm_regions@[0].committed();
+        let ghost impervious = pm_regions.constants().impervious_to_corruption;
+
+        // Every way of recovering a CDB reads it from past the global
+        // metadata and its CRC, so a region too short to hold the CDB
+        // can't hold one.
+
+        let region_size = pm_regions.get_region_size(0);
+        if region_size < ABSOLUTE_POS_OF_LOG_CDB + CRC_SIZE {
+            return Ok(None);
+        }
+
+        // Read the global metadata and its CRC, and check that the
+        // CRC matches.
+
+        let global_metadata = pm_regions.read_and_deserialize::<GlobalMetadata>(0, ABSOLUTE_POS_OF_GLOBAL_METADATA);
+        let global_crc = pm_regions.read_and_deserialize(0, ABSOLUTE_POS_OF_GLOBAL_CRC);
+        if !check_crc_deserialized(global_metadata, global_crc,
+                      Ghost(mem), Ghost(impervious),
+                      pm_regions.known_impervious_to_corruption(),
+                      Ghost(ABSOLUTE_POS_OF_GLOBAL_METADATA), Ghost(LENGTH_OF_GLOBAL_METADATA),
+                      Ghost(ABSOLUTE_POS_OF_GLOBAL_CRC)) {
+            if pm_regions.known_impervious_to_corruption() {
+                return Ok(None);
+            }
+            return Err(MultiLogErr::CRCMismatch);
+        }
+
+        // If the CRC matches, the global metadata read is what recovery
+        // would read, so it decides whether this is a multilog region.
+
+        if global_metadata.program_guid != MULTILOG_PROGRAM_GUID {
+            return Ok(None);
+        }
+
+        if global_metadata.version_number != MULTILOG_PROGRAM_VERSION_NUMBER {
+            return Ok(None);
+        }
+
+        // Read the CDB. If it's neither `CDB_FALSE` nor `CDB_TRUE`,
+        // either it's corrupted or this isn't a multilog region.
+
+        let log_cdb = pm_regions.read_and_deserialize::<u64>(0, ABSOLUTE_POS_OF_LOG_CDB);
+        let log_cdb_val = *log_cdb;
+        if log_cdb_val != CDB_FALSE && log_cdb_val != CDB_TRUE {
+            if pm_regions.known_impervious_to_corruption() {
+                return Ok(None);
+            }
+            return Err(MultiLogErr::CRCMismatch);
+        }
+
+        proof {
+            // If the CDB recovery would read is valid, then
+            // `axiom_corruption_detecting_boolean` justifies concluding
+            // that the one read, being valid too, isn't corrupted.
+            let true_cdb = deserialize_log_cdb(mem);
+            if !impervious && (true_cdb == CDB_FALSE || true_cdb == CDB_TRUE) {
+                axiom_corruption_detecting_boolean_serialized(log_cdb_val, true_cdb, ABSOLUTE_POS_OF_LOG_CDB as int);
+            }
+        }
+
+        Ok(Some(log_cdb_val == CDB_TRUE))
+    }
+
+    // This exported function checks that region 0's global metadata
+    // was written by this version of the code. It's checked before
+    // anything else is read, since other versions lay out the rest
+    // of the metadata differently. E.g., reading a version-1 region
+    // at this version's offsets would report a spurious CRC
+    // mismatch.
+    //
+    // `pm_regions` -- the persistent-memory regions to read from
+    //
+    // The result is a `Result<(), MultiLogErr>` with the following meanings:
+    //
+    // `Err(MultiLogErr::CRCMismatch)` -- The global metadata couldn't
+    // be read due to a CRC error.
+    //
+    // `Err(MultiLogErr::StartFailedDueToProgramVersionNumberUnsupported)`
+    // -- The global metadata was written by another version. This
+    // can't happen if region 0 is recoverable.
+    //
+    // `Ok(())` -- The global metadata has this version's number.
+    pub fn check_program_version<PMRegions: PersistentMemoryRegions>(pm_regions: &PMRegions)
+                                                                     -> (result: Result<(), MultiLogErr>)
+        requires
+            pm_regions.inv(),
+            pm_regions@.len() > 0,
+            recover_cdb(pm_regions@[0].committed()).is_Some(),
+            pm_regions@.no_outstanding_writes(),
+        ensures
+            match result {
+                Ok(()) => true,
+                Err(MultiLogErr::CRCMismatch) => !pm_regions.constants().impervious_to_corruption,
+                Err(MultiLogErr::StartFailedDueToProgramVersionNumberUnsupported{ .. }) =>
+                    recover_cdb(pm_regions@[0].committed()).is_None(),
+                _ => false,
+            }
+    {
+        let ghost mem = pm_regions@[0].committed();
+
+        let global_metadata = pm_regions.read_and_deserialize::<GlobalMetadata>(0, ABSOLUTE_POS_OF_GLOBAL_METADATA);
+        let global_crc = pm_regions.read_and_deserialize(0, ABSOLUTE_POS_OF_GLOBAL_CRC);
+        if !check_crc_deserialized(global_metadata, global_crc,
+                      Ghost(mem), Ghost(pm_regions.constants().impervious_to_corruption),
+                      pm_regions.known_impervious_to_corruption(),
+                      Ghost(ABSOLUTE_POS_OF_GLOBAL_METADATA), Ghost(LENGTH_OF_GLOBAL_METADATA),
+                      Ghost(ABSOLUTE_POS_OF_GLOBAL_CRC)) {
+            return Err(MultiLogErr::CRCMismatch);
+        }
+
+        if global_metadata.version_number != MULTILOG_PROGRAM_VERSION_NUMBER {
+            return Err(MultiLogErr::StartFailedDueToProgramVersionNumberUnsupported{
+                which_log: 0,
+                version_number: global_metadata.version_number,
+                max_supported: MULTILOG_PROGRAM_VERSION_NUMBER,
+            })
+        }
+        Ok(())
+    }
+
+    // This exported function reads the creation timestamp that
+    // `setup` recorded in region 0's metadata and returns it.
+    //
+    // `pm_regions` -- the persistent-memory regions to read from
+    //
+    // The result is a `Result<u64, MultiLogErr>` with the following meanings:
+    //
+    // `Err(MultiLogErr::CRCMismatch)` -- The region metadata couldn't
+    // be read due to a CRC error.
+    //
+    // `Ok(creation_timestamp)` -- The region metadata could be read
+    // and records the creation timestamp `creation_timestamp`.
+    pub fn read_creation_timestamp<PMRegions: PersistentMemoryRegions>(pm_regions: &PMRegions)
+                                                                       -> (result: Result<u64, MultiLogErr>)
+        requires
+            pm_regions.inv(),
+            pm_regions@.len() > 0,
+            recover_cdb(pm_regions@[0].committed()).is_Some(),
+            pm_regions@.no_outstanding_writes(),
+        ensures
+            match result {
+                Ok(creation_timestamp) =>
+                    creation_timestamp == deserialize_region_metadata(pm_regions@[0].committed()).creation_timestamp,
+                Err(MultiLogErr::CRCMismatch) => !pm_regions.constants().impervious_to_corruption,
+                _ => false,
+            }
+    {
+        let ghost mem = pm_regions@[0].committed();
+
+        let region_metadata = pm_regions.read_and_deserialize::<RegionMetadata>(0, ABSOLUTE_POS_OF_REGION_METADATA);
+        let region_crc = pm_regions.read_and_deserialize(0, ABSOLUTE_POS_OF_REGION_CRC);
+        if !check_crc_deserialized(region_metadata, region_crc,
+                      Ghost(mem), Ghost(pm_regions.constants().impervious_to_corruption),
+                      pm_regions.known_impervious_to_corruption(),
+                      Ghost(ABSOLUTE_POS_OF_REGION_METADATA), Ghost(LENGTH_OF_REGION_METADATA),
+                      Ghost(ABSOLUTE_POS_OF_REGION_CRC)) {
+            return Err(MultiLogErr::CRCMismatch);
+        }
+        Ok(region_metadata.creation_timestamp)
+    }
+
+    // This exported function reads the label that `setup` recorded in
+    // region 0's metadata and returns it as the pair
+    // `(label_lo, label_hi)`.
+    //
+    // `pm_regions` -- the persistent-memory regions to read from
+    //
+    // The result is a `Result<(u128, u128), MultiLogErr>` with the
+    // following meanings:
+    //
+    // `Err(MultiLogErr::CRCMismatch)` -- The region metadata couldn't
+    // be read due to a CRC error.
+    //
+    // `Ok((label_lo, label_hi))` -- The region metadata could be read
+    // and records that label.
+    pub fn read_label<PMRegions: PersistentMemoryRegions>(pm_regions: &PMRegions)
+                                                          -> (result: Result<(u128, u128), MultiLogErr>)
+        requires
+            pm_regions.inv(),
+            pm_regions@.len() > 0,
+            recover_cdb(pm_regions@[0].committed()).is_Some(),
+            pm_regions@.no_outstanding_writes(),
+        ensures
+            match result {
+                Ok((label_lo, label_hi)) => {
+                    let region_metadata = deserialize_region_metadata(pm_regions@[0].committed());
+                    &&& label_lo == region_metadata.label_lo
+                    &&& label_hi == region_metadata.label_hi
+                },
+                Err(MultiLogErr::CRCMismatch) => !pm_regions.constants().impervious_to_corruption,
+                _ => false,
+            }
+    {
+        let ghost mem = pm_regions@[0].committed();
+
+        let region_metadata = pm_regions.read_and_deserialize::<RegionMetadata>(0, ABSOLUTE_POS_OF_REGION_METADATA);
+        let region_crc = pm_regions.read_and_deserialize(0, ABSOLUTE_POS_OF_REGION_CRC);
+        if !check_crc_deserialized(region_metadata, region_crc,
+                      Ghost(mem), Ghost(pm_regions.constants().impervious_to_corruption),
+                      pm_regions.known_impervious_to_corruption(),
+                      Ghost(ABSOLUTE_POS_OF_REGION_METADATA), Ghost(LENGTH_OF_REGION_METADATA),
+                      Ghost(ABSOLUTE_POS_OF_REGION_CRC)) {
+            return Err(MultiLogErr::CRCMismatch);
+        }
+        Ok((region_metadata.label_lo, region_metadata.label_hi))
+    }
+
     // This function reads the log information for a single log from
     // persistent memory.
     //
@@ -87,7 +333,9 @@ verus! {
     // `Err(MultiLogErr::StartFailedDueToProgramVersionNumberUnsupported)`
     // -- The program version number stored in persistent memory is
     // one that this code doesn't know how to recover from. It was
-    // presumably created by a later version of this code.
+    // created by version 1, which laid out region metadata
+    // differently (see `migrate_from_version_1`), or presumably by a
+    // later version of this code.
     //
     // `Err(MultiLogErr::StartFailedDueToMultilogIDMismatch)` -- The
     // multilog ID stored in persistent memory doesn't match the one
@@ -104,7 +352,7 @@ verus! {
     // The region's contents aren't valid, i.e., they're not
     // recoverable to a valid log. The user must have requested to
     // start using the wrong region of persistent memory.
-    fn read_log_variables<PMRegions: PersistentMemoryRegions>(
+    pub fn read_log_variables<PMRegions: PersistentMemoryRegions>(
         pm_regions: &PMRegions,
         multilog_id: u128,
         cdb: bool,
@@ -153,6 +401,7 @@ verus! {
         let global_crc = pm_regions.read_and_deserialize(which_log as usize, ABSOLUTE_POS_OF_GLOBAL_CRC);
         if !check_crc_deserialized(global_metadata, global_crc,
                       Ghost(mem), Ghost(pm_regions.constants().impervious_to_corruption),
+                      pm_regions.known_impervious_to_corruption(),
                       Ghost(ABSOLUTE_POS_OF_GLOBAL_METADATA), Ghost(LENGTH_OF_GLOBAL_METADATA),
                       Ghost(ABSOLUTE_POS_OF_GLOBAL_CRC)) {
             proof {
@@ -194,6 +443,7 @@ verus! {
         let region_crc = pm_regions.read_and_deserialize(which_log as usize, ABSOLUTE_POS_OF_REGION_CRC);
         if !check_crc_deserialized(region_metadata, region_crc,
                       Ghost(mem), Ghost(pm_regions.constants().impervious_to_corruption),
+                      pm_regions.known_impervious_to_corruption(),
                       Ghost(ABSOLUTE_POS_OF_REGION_METADATA), Ghost(LENGTH_OF_REGION_METADATA),
                       Ghost(ABSOLUTE_POS_OF_REGION_CRC)) {
             return Err(MultiLogErr::CRCMismatch);
@@ -256,6 +506,7 @@ verus! {
         let log_metadata = pm_regions.read_and_deserialize::<LogMetadata>(which_log as usize, log_metadata_pos);
         let log_crc = pm_regions.read_and_deserialize::<u64>(which_log as usize, log_crc_pos);
         if !check_crc_deserialized(log_metadata, log_crc, Ghost(mem), Ghost(pm_regions.constants().impervious_to_corruption),
+                                    pm_regions.known_impervious_to_corruption(),
                                     Ghost(log_metadata_pos), Ghost(LENGTH_OF_LOG_METADATA), Ghost(log_crc_pos)) {
             return Err(MultiLogErr::CRCMismatch);
         }
@@ -383,4 +634,39 @@ verus! {
         }
         Ok(infos)
     }
+
+    // This function is a cheap check that every region is big enough
+    // to hold a log, i.e., has at least `ABSOLUTE_POS_OF_LOG_AREA +
+    // MIN_LOG_AREA_SIZE` bytes. It doesn't read the regions, only
+    // their sizes. If it returns `false`, recovery is bound to fail,
+    // so there's no point reading any metadata.
+    //
+    // `pm_regions` -- the persistent-memory regions to check
+    pub fn regions_plausibly_sized<PMRegions: PersistentMemoryRegions>(pm_regions: &PMRegions) -> (result: bool)
+        requires
+            pm_regions.inv(),
+        ensures
+            result <==> forall |i| 0 <= i < pm_regions@.len() ==>
+                            #[trigger] pm_regions@[i].len() >= ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE,
+            !result ==> forall |multilog_id| #[trigger] recover_all(pm_regions@.committed(), multilog_id).is_None(),
+    {
+        let num_regions = pm_regions.get_num_regions();
+        for which_region in 0..num_regions
+            invariant
+                pm_regions.inv(),
+                num_regions == pm_regions@.len(),
+                forall |j| 0 <= j < which_region ==>
+                    #[trigger] pm_regions@[j].len() >= ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE,
+        {
+            if pm_regions.get_region_size(which_region) < ABSOLUTE_POS_OF_LOG_AREA + MIN_LOG_AREA_SIZE {
+                assert forall |multilog_id| #[trigger] recover_all(pm_regions@.committed(), multilog_id).is_None() by {
+                    let mems = pm_regions@.committed();
+                    assert(mems[which_region as int] == pm_regions@[which_region as int].committed());
+                    lemma_region_too_small_implies_recover_all_fails(mems, multilog_id, which_region as int);
+                }
+                return false;
+            }
+        }
+        true
+    }
 }
@@ -0,0 +1,254 @@
+//! This file contains the trusted implementation for
+//! `CowPersistentMemoryRegion`, a copy-on-write overlay on top of
+//! another persistent memory region. It implements trait
+//! `PersistentMemoryRegion`.
+//!
+//! The base region is never written. Writes go to a sparse,
+//! volatile overlay of overridden bytes, and reads consult that
+//! overlay before falling back to the base region. This makes it
+//! possible to try out a recovery or repair procedure against a
+//! production region without modifying it.
+//!
+//! THIS IS ONLY INTENDED FOR TESTING AND EXPERIMENTATION! Nothing
+//! written through the overlay is persistent, so `flush` is a no-op.
+
+use crate::pmem::pmemspec_t::{
+    PersistentMemoryConstants, PersistentMemoryRegion, PersistentMemoryRegionView, PmemError,
+};
+use crate::pmem::serialization_t::*;
+use builtin::*;
+use builtin_macros::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::*;
+use vstd::prelude::*;
+
+verus! {
+
+    // The `CowOverlay` struct holds the overridden bytes, keyed by
+    // address. It also holds scratch buffers used to hand out
+    // references from `read_and_deserialize` when the requested
+    // range overlaps overridden bytes. Every method taking `&mut
+    // self` frees them, since no reference into them can still be
+    // live then, so they don't pile up across reads.
+    #[verifier::external_body]
+    pub struct CowOverlay {
+        bytes: HashMap<u64, u8>,
+        scratch: RefCell<Vec<Vec<u64>>>,
+    }
+
+    // The `CowPersistentMemoryRegion` struct contains the base
+    // region and the overlay, as well as a ghost view that, like
+    // the volatile mock, pretends that outstanding writes remain
+    // outstanding even though the overlay is updated immediately.
+    pub struct CowPersistentMemoryRegion<PMRegion: PersistentMemoryRegion>
+    {
+        base: PMRegion,
+        overlay: CowOverlay,
+    }
+
+    impl<PMRegion: PersistentMemoryRegion> CowPersistentMemoryRegion<PMRegion>
+    {
+        // This spec function gives the overridden bytes in the
+        // overlay, keyed by address.
+        #[verifier::external_body]
+        pub closed spec fn overlay_view(&self) -> Map<int, u8>;
+
+        // This function wraps `base` in an empty overlay. It flushes
+        // `base` first so that every byte of it can be read.
+        //
+        // `base` -- the region to overlay; it's never written to
+        //
+        // Returns `Err(PmemError::FlushFailed)` if flushing `base`
+        // fails.
+        #[verifier::external_body]
+        pub fn new(base: PMRegion) -> (result: Result<Self, PmemError>)
+            requires
+                base.inv(),
+            ensures
+                match result {
+                    Ok(region) => {
+                        &&& region.inv()
+                        &&& region@ == base@.flush()
+                        &&& region.constants() == base.constants()
+                    },
+                    Err(_) => true,
+                }
+        {
+            let mut base = base;
+            base.flush()?;
+            Ok(Self {
+                base,
+                overlay: CowOverlay { bytes: HashMap::new(), scratch: RefCell::new(Vec::new()) },
+            })
+        }
+
+        // This function returns the number of bytes currently
+        // overridden by the overlay.
+        #[verifier::external_body]
+        pub fn num_overridden_bytes(&self) -> usize
+        {
+            self.overlay.bytes.len()
+        }
+
+        // This function discards every overridden byte, so that the
+        // region once again reads as the base region.
+        #[verifier::external_body]
+        pub fn discard_overlay(&mut self)
+            requires
+                old(self).inv(),
+            ensures
+                self.inv(),
+                self.constants() == old(self).constants(),
+                self@.len() == old(self)@.len(),
+                self@.no_outstanding_writes(),
+                self@.committed() == self.base@.committed(),
+        {
+            self.overlay.bytes.clear();
+            self.overlay.scratch.get_mut().clear();
+        }
+    }
+
+    // This helper function reads `num_bytes` bytes at `addr`,
+    // taking each byte from the overlay if it's overridden there and
+    // from `base` otherwise.
+    #[verifier::external_body]
+    fn read_through_overlay<PMRegion: PersistentMemoryRegion>(
+        base: &PMRegion,
+        overlay: &CowOverlay,
+        addr: u64,
+        num_bytes: u64,
+    ) -> Vec<u8>
+    {
+        let mut bytes = base.read(addr, num_bytes);
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            if let Some(b) = overlay.bytes.get(&(addr + i as u64)) {
+                *byte = *b;
+            }
+        }
+        bytes
+    }
+
+    impl<PMRegion: PersistentMemoryRegion> PersistentMemoryRegion for CowPersistentMemoryRegion<PMRegion>
+    {
+        #[verifier::external_body]
+        closed spec fn view(&self) -> PersistentMemoryRegionView;
+
+        closed spec fn inv(&self) -> bool
+        {
+            &&& self.base.inv()
+            &&& self@.len() == self.base@.len()
+            &&& self.base@.no_outstanding_writes()
+
+            // The view, once flushed, is the base region with the
+            // overlay applied.
+            &&& forall |i: int| 0 <= i < self@.len() ==> {
+                   let b = #[trigger] self@.flush().committed()[i];
+                   if self.overlay_view().contains_key(i) {
+                       b == self.overlay_view()[i]
+                   }
+                   else {
+                       b == self.base@.committed()[i]
+                   }
+               }
+        }
+
+        closed spec fn constants(&self) -> PersistentMemoryConstants
+        {
+            self.base.constants()
+        }
+
+        fn get_region_size(&self) -> (result: u64)
+        {
+            self.base.get_region_size()
+        }
+
+        fn known_impervious_to_corruption(&self) -> (result: bool)
+        {
+            self.base.known_impervious_to_corruption()
+        }
+
+        #[verifier::external_body]
+        fn read(&self, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
+        {
+            read_through_overlay(&self.base, &self.overlay, addr, num_bytes)
+        }
+
+        #[verifier::external_body]
+        fn read_and_deserialize<S>(&self, addr: u64) -> &S
+            where
+                S: Serializable + Sized
+        {
+            debug_check_serialized_layout::<S>();
+            let num_bytes = S::serialized_len();
+            let overridden = (addr..addr + num_bytes).any(|a| self.overlay.bytes.contains_key(&a));
+            if !overridden {
+                return self.base.read_and_deserialize(addr);
+            }
+
+            // Copy the bytes into a scratch buffer of `u64`s, so that
+            // it's suitably aligned, and hand out a reference into it.
+            let bytes = read_through_overlay(&self.base, &self.overlay, addr, num_bytes);
+            let mut buffer: Vec<u64> = vec![0; (bytes.len() + 7) / 8];
+            // SAFETY: `buffer` has room for at least `bytes.len()` bytes,
+            // and the two don't overlap.
+            unsafe {
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), buffer.as_mut_ptr() as *mut u8, bytes.len());
+            }
+            let s_pointer = buffer.as_ptr() as *const S;
+            self.overlay.scratch.borrow_mut().push(buffer);
+            // SAFETY: `s_pointer` points to `S::serialized_len()`
+            // initialized bytes on the heap. Moving `buffer` into
+            // `scratch` doesn't move its heap allocation, and scratch
+            // buffers are only freed by methods that take `&mut self`,
+            // so the allocation outlives the returned reference.
+            unsafe { &(*s_pointer) }
+        }
+
+        #[verifier::external_body]
+        fn write(&mut self, addr: u64, bytes: &[u8])
+        {
+            self.overlay.scratch.get_mut().clear();
+            for (i, b) in bytes.iter().enumerate() {
+                self.overlay.bytes.insert(addr + i as u64, *b);
+            }
+        }
+
+        #[verifier::external_body]
+        fn serialize_and_write<S>(&mut self, addr: u64, to_write: &S)
+            where
+                S: Serializable + Sized
+        {
+            let num_bytes: usize = S::serialized_len().try_into().unwrap();
+            let s_pointer = to_write as *const S;
+            let bytes_pointer = s_pointer as *const u8;
+            // SAFETY: `bytes_pointer` always points to `num_bytes` consecutive, initialized
+            // bytes because it was obtained by casting a regular Rust object reference
+            // to a raw pointer.
+            let bytes = unsafe {
+                std::slice::from_raw_parts(bytes_pointer, num_bytes)
+            };
+            self.write(addr, bytes);
+        }
+
+        // Nothing written to the overlay is persistent, so there's
+        // nothing to flush.
+        #[verifier::external_body]
+        fn flush(&mut self) -> (result: Result<(), PmemError>)
+        {
+            self.overlay.scratch.get_mut().clear();
+            Ok(())
+        }
+
+        // Growing would extend the base region, which is never
+        // modified, so the overlay can't grow.
+        #[verifier::external_body]
+        #[allow(unused_variables)]
+        fn grow_region(&mut self, new_size: u64) -> (result: Result<(), PmemError>)
+        {
+            self.overlay.scratch.get_mut().clear();
+            Err(PmemError::AccessOutOfRange)
+        }
+    }
+
+}
@@ -0,0 +1,472 @@
+//! This file contains the trusted implementation for
+//! `CrashSimulatingPersistentMemoryRegion`, a volatile memory region
+//! that remembers enough about its past to simulate crashes. It
+//! implements trait `PersistentMemoryRegion`.
+//!
+//! Every write and flush records a crash point. A crash point holds
+//! the contents as of the last flush and the contents after all
+//! writes so far. Crashing at a crash point produces a fresh region
+//! in which each 8-byte chunk comes from one or the other, chosen at
+//! random. That's exactly the freedom the model in `pmemspec_t.rs`
+//! gives a crash, so a client that's correct under that model must
+//! recover from every such region.
+//!
+//! It also contains `CrashSimulatingPersistentMemoryRegions`, which
+//! does the same for a collection of regions, as used by a multilog.
+//! Its crash points cover all the regions at once, and instead of
+//! choosing chunks at random, a test picks exactly which outstanding
+//! chunks persist. So a test can enumerate every crash state.
+//!
+//! THIS IS ONLY INTENDED FOR USE IN TESTING! Every crash point holds
+//! a copy of the region, so it's only practical for small regions
+//! and short runs.
+
+use crate::pmem::pmemspec_t::{
+    PersistentMemoryConstants, PersistentMemoryRegion, PersistentMemoryRegionView,
+    PersistentMemoryRegions, PersistentMemoryRegionsView, PmemError,
+};
+use crate::pmem::serialization_t::*;
+use builtin::*;
+use builtin_macros::*;
+use deps_hack::rand::Rng;
+use std::cell::RefCell;
+use std::convert::*;
+use std::rc::Rc;
+use vstd::prelude::*;
+
+// The number of bytes in each chunk that persists atomically. This
+// matches `const_persistence_chunk_size` in `pmemspec_t.rs`.
+const PERSISTENCE_CHUNK_SIZE: usize = 8;
+
+// A crash point is a moment at which a crash can be simulated. It
+// records the contents as of the last flush and the contents after
+// all writes up to that moment.
+struct CrashPoint {
+    flushed: Rc<Vec<u8>>,
+    current: Vec<u8>,
+}
+
+verus! {
+
+    // The `CrashHistory` struct holds the crash points recorded by a
+    // `CrashSimulatingPersistentMemoryRegion`. It's shared with the
+    // region, so a test can keep a handle to it even after handing
+    // the region to a log.
+    #[verifier::external_body]
+    #[derive(Clone)]
+    pub struct CrashHistory {
+        points: Rc<RefCell<Vec<CrashPoint>>>,
+    }
+
+    // The `FlushedContents` struct holds the contents as of the last
+    // flush. It's reference-counted since every crash point recorded
+    // before the next flush shares it.
+    #[verifier::external_body]
+    pub struct FlushedContents {
+        bytes: Rc<Vec<u8>>,
+    }
+
+    // The `RegionsCrashHistory` struct holds the crash points
+    // recorded by a `CrashSimulatingPersistentMemoryRegions`. Each
+    // crash point has one `CrashPoint` per region.
+    #[verifier::external_body]
+    #[derive(Clone)]
+    pub struct RegionsCrashHistory {
+        points: Rc<RefCell<Vec<Vec<CrashPoint>>>>,
+    }
+
+    // The `CrashSimulatingPersistentMemoryRegion` struct contains
+    // the current contents, the contents as of the last flush, and
+    // the crash history. Like the volatile mock, its ghost view
+    // pretends that outstanding writes remain outstanding even
+    // though `contents` is updated immediately.
+    pub struct CrashSimulatingPersistentMemoryRegion
+    {
+        contents: Vec<u8>,
+        flushed: FlushedContents,
+        history: CrashHistory,
+    }
+
+    impl CrashSimulatingPersistentMemoryRegion
+    {
+        // This function creates a zeroed region with an empty crash
+        // history.
+        #[verifier::external_body]
+        pub fn new(region_size: u64) -> (result: Self)
+            ensures
+                result.inv(),
+                result@.len() == region_size,
+                result@.no_outstanding_writes(),
+        {
+            let contents: Vec<u8> = vec![0; region_size as usize];
+            Self {
+                flushed: FlushedContents { bytes: Rc::new(contents.clone()) },
+                contents,
+                history: CrashHistory { points: Rc::new(RefCell::new(Vec::new())) },
+            }
+        }
+
+        // This function returns a handle to the region's crash
+        // history.
+        #[verifier::external_body]
+        pub fn history(&self) -> CrashHistory
+        {
+            self.history.clone()
+        }
+
+        #[verifier::external_body]
+        fn record_crash_point(&self)
+        {
+            self.history.points.borrow_mut().push(
+                CrashPoint { flushed: self.flushed.bytes.clone(), current: self.contents.clone() }
+            );
+        }
+    }
+
+    impl PersistentMemoryRegion for CrashSimulatingPersistentMemoryRegion
+    {
+        #[verifier::external_body]
+        closed spec fn view(&self) -> PersistentMemoryRegionView;
+
+        closed spec fn inv(&self) -> bool
+        {
+            // We maintain the invariant that our size fits in a `u64`.
+            &&& self.contents.len() <= u64::MAX
+            &&& self.contents.len() == self@.len()
+
+            // We also maintain the invariant that the contents of our
+            // volatile buffer matches the result of flushing the
+            // abstract state.
+            &&& self.contents@ == self@.flush().committed()
+        }
+
+        closed spec fn constants(&self) -> PersistentMemoryConstants;
+
+        fn get_region_size(&self) -> (result: u64)
+        {
+            self.contents.len() as u64
+        }
+
+        // The mock is never actually corrupted, but it doesn't claim
+        // to be impervious, so that tests still exercise CRC checks.
+        #[verifier::external_body]
+        fn known_impervious_to_corruption(&self) -> (result: bool)
+        {
+            false
+        }
+
+        #[verifier::external_body]
+        fn read(&self, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
+        {
+            let addr_usize: usize = addr.try_into().unwrap();
+            let num_bytes_usize: usize = num_bytes.try_into().unwrap();
+            self.contents[addr_usize..addr_usize+num_bytes_usize].to_vec()
+        }
+
+        #[verifier::external_body]
+        fn read_and_deserialize<S>(&self, addr: u64) -> &S
+            where
+                S: Serializable + Sized
+        {
+            debug_check_serialized_layout::<S>();
+            let addr_usize: usize = addr.try_into().unwrap();
+            let num_bytes: usize = S::serialized_len().try_into().unwrap();
+            let bytes = &self.contents[addr_usize..addr_usize+num_bytes];
+            // SAFETY: The precondition of the method ensures that we do not
+            // attempt to read out of bounds. The user of the mock is responsible
+            // for ensuring that there is a valid S at this address and checking
+            // for corruption.
+            unsafe {
+                let bytes_pointer = bytes.as_ptr();
+                let s_pointer = bytes_pointer as *const S;
+                &(*s_pointer)
+            }
+        }
+
+        #[verifier::external_body]
+        fn write(&mut self, addr: u64, bytes: &[u8])
+        {
+            let addr_usize: usize = addr.try_into().unwrap();
+            self.contents.splice(addr_usize..addr_usize+bytes.len(), bytes.iter().cloned());
+            self.record_crash_point();
+        }
+
+        #[verifier::external_body]
+        fn serialize_and_write<S>(&mut self, addr: u64, to_write: &S)
+            where
+                S: Serializable + Sized
+        {
+            let num_bytes: usize = S::serialized_len().try_into().unwrap();
+            let s_pointer = to_write as *const S;
+            let bytes_pointer = s_pointer as *const u8;
+            // SAFETY: `bytes_pointer` always points to `num_bytes` consecutive, initialized
+            // bytes because it was obtained by casting a regular Rust object reference
+            // to a raw pointer.
+            let bytes = unsafe {
+                std::slice::from_raw_parts(bytes_pointer, num_bytes)
+            };
+            self.write(addr, bytes);
+        }
+
+        #[verifier::external_body]
+        fn flush(&mut self) -> Result<(), PmemError>
+        {
+            self.flushed = FlushedContents { bytes: Rc::new(self.contents.clone()) };
+            self.record_crash_point();
+            Ok(())
+        }
+
+        // There are no outstanding writes, so the flushed contents
+        // grow along with the current ones. Crash points recorded
+        // before growing still produce regions of the old size.
+        #[verifier::external_body]
+        fn grow_region(&mut self, new_size: u64) -> (result: Result<(), PmemError>)
+        {
+            self.contents.resize(new_size as usize, 0);
+            self.flushed = FlushedContents { bytes: Rc::new(self.contents.clone()) };
+            Ok(())
+        }
+    }
+
+    // The `CrashSimulatingPersistentMemoryRegions` struct is like
+    // `CrashSimulatingPersistentMemoryRegion`, but for a collection
+    // of regions. Each write or flush records one crash point
+    // covering every region.
+    pub struct CrashSimulatingPersistentMemoryRegions
+    {
+        contents: Vec<Vec<u8>>,
+        flushed: Vec<FlushedContents>,
+        history: RegionsCrashHistory,
+    }
+
+    impl CrashSimulatingPersistentMemoryRegions
+    {
+        // This function creates zeroed regions of the given sizes
+        // with an empty crash history.
+        #[verifier::external_body]
+        pub fn new(region_sizes: &[u64]) -> (result: Self)
+            ensures
+                result.inv(),
+                result@.len() == region_sizes@.len(),
+                forall |i| 0 <= i < region_sizes@.len() ==> #[trigger] result@[i].len() == region_sizes[i],
+                result@.no_outstanding_writes(),
+        {
+            let contents: Vec<Vec<u8>> = region_sizes.iter().map(|size| vec![0; *size as usize]).collect();
+            Self {
+                flushed: contents.iter().map(|c| FlushedContents { bytes: Rc::new(c.clone()) }).collect(),
+                contents,
+                history: RegionsCrashHistory { points: Rc::new(RefCell::new(Vec::new())) },
+            }
+        }
+
+        // This function returns a handle to the regions' crash
+        // history.
+        #[verifier::external_body]
+        pub fn history(&self) -> RegionsCrashHistory
+        {
+            self.history.clone()
+        }
+
+        #[verifier::external_body]
+        fn record_crash_point(&self)
+        {
+            let point = self.contents.iter().zip(self.flushed.iter()).map(
+                |(current, flushed)| CrashPoint { flushed: flushed.bytes.clone(), current: current.clone() }
+            ).collect();
+            self.history.points.borrow_mut().push(point);
+        }
+    }
+
+    impl PersistentMemoryRegions for CrashSimulatingPersistentMemoryRegions
+    {
+        #[verifier::external_body]
+        closed spec fn view(&self) -> PersistentMemoryRegionsView;
+
+        #[verifier::external_body]
+        closed spec fn inv(&self) -> bool;
+
+        #[verifier::external_body]
+        closed spec fn constants(&self) -> PersistentMemoryConstants;
+
+        #[verifier::external_body]
+        fn get_num_regions(&self) -> usize
+        {
+            self.contents.len()
+        }
+
+        #[verifier::external_body]
+        fn get_region_size(&self, index: usize) -> u64
+        {
+            self.contents[index].len() as u64
+        }
+
+        // The mock is never actually corrupted, but it doesn't claim
+        // to be impervious, so that tests still exercise CRC checks.
+        #[verifier::external_body]
+        fn known_impervious_to_corruption(&self) -> (result: bool)
+        {
+            false
+        }
+
+        // `flush_region` only snapshots the one region, so it
+        // leaves the other regions' writes outstanding.
+        fn capabilities(&self) -> (result: PmemCapabilities)
+        {
+            PmemCapabilities { targeted_flush: true, persistent: false }
+        }
+
+        #[verifier::external_body]
+        fn read(&self, index: usize, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
+        {
+            let addr_usize: usize = addr.try_into().unwrap();
+            let num_bytes_usize: usize = num_bytes.try_into().unwrap();
+            self.contents[index][addr_usize..addr_usize+num_bytes_usize].to_vec()
+        }
+
+        #[verifier::external_body]
+        fn read_and_deserialize<S>(&self, index: usize, addr: u64) -> &S
+            where
+                S: Serializable + Sized
+        {
+            debug_check_serialized_layout::<S>();
+            let addr_usize: usize = addr.try_into().unwrap();
+            let num_bytes: usize = S::serialized_len().try_into().unwrap();
+            let bytes = &self.contents[index][addr_usize..addr_usize+num_bytes];
+            // SAFETY: The precondition of the method ensures that we do not
+            // attempt to read out of bounds. The user of the mock is responsible
+            // for ensuring that there is a valid S at this address and checking
+            // for corruption.
+            unsafe {
+                let bytes_pointer = bytes.as_ptr();
+                let s_pointer = bytes_pointer as *const S;
+                &(*s_pointer)
+            }
+        }
+
+        #[verifier::external_body]
+        fn write(&mut self, index: usize, addr: u64, bytes: &[u8])
+        {
+            let addr_usize: usize = addr.try_into().unwrap();
+            self.contents[index].splice(addr_usize..addr_usize+bytes.len(), bytes.iter().cloned());
+            self.record_crash_point();
+        }
+
+        #[verifier::external_body]
+        fn serialize_and_write<S>(&mut self, index: usize, addr: u64, to_write: &S)
+            where
+                S: Serializable + Sized
+        {
+            let num_bytes: usize = S::serialized_len().try_into().unwrap();
+            let s_pointer = to_write as *const S;
+            let bytes_pointer = s_pointer as *const u8;
+            // SAFETY: `bytes_pointer` always points to `num_bytes` consecutive, initialized
+            // bytes because it was obtained by casting a regular Rust object reference
+            // to a raw pointer.
+            let bytes = unsafe {
+                std::slice::from_raw_parts(bytes_pointer, num_bytes)
+            };
+            self.write(index, addr, bytes);
+        }
+
+        #[verifier::external_body]
+        fn flush(&mut self) -> Result<(), PmemError>
+        {
+            for (flushed, current) in self.flushed.iter_mut().zip(self.contents.iter()) {
+                *flushed = FlushedContents { bytes: Rc::new(current.clone()) };
+            }
+            self.record_crash_point();
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn flush_region(&mut self, index: usize) -> Result<(), PmemError>
+        {
+            self.flushed[index] = FlushedContents { bytes: Rc::new(self.contents[index].clone()) };
+            self.record_crash_point();
+            Ok(())
+        }
+    }
+
+}
+
+impl CrashHistory {
+    // This function returns the number of crash points recorded so
+    // far. Crash points are numbered in the order they occurred.
+    pub fn num_crash_points(&self) -> usize
+    {
+        self.points.borrow().len()
+    }
+
+    // This function simulates a crash at crash point `point`. It
+    // returns a fresh region, with no outstanding writes and an empty
+    // crash history, whose contents are what persistent memory could
+    // hold after such a crash. Each chunk with outstanding writes at
+    // that point is persisted or not at random, using `rng`.
+    pub fn crash<R: Rng>(&self, point: usize, rng: &mut R) -> CrashSimulatingPersistentMemoryRegion
+    {
+        let points = self.points.borrow();
+        let crash_point = &points[point];
+        let mut contents = crash_point.flushed.as_ref().clone();
+        for (start, chunk) in crash_point.current.chunks(PERSISTENCE_CHUNK_SIZE).enumerate() {
+            let start = start * PERSISTENCE_CHUNK_SIZE;
+            if rng.gen::<bool>() {
+                contents[start..start + chunk.len()].copy_from_slice(chunk);
+            }
+        }
+        CrashSimulatingPersistentMemoryRegion {
+            flushed: FlushedContents { bytes: Rc::new(contents.clone()) },
+            contents,
+            history: CrashHistory { points: Rc::new(RefCell::new(Vec::new())) },
+        }
+    }
+}
+
+impl RegionsCrashHistory {
+    // This function returns the number of crash points recorded so
+    // far. Crash points are numbered in the order they occurred.
+    pub fn num_crash_points(&self) -> usize
+    {
+        self.points.borrow().len()
+    }
+
+    // This function returns the chunks with outstanding writes at
+    // crash point `point`, as `(region index, chunk index)` pairs.
+    // A chunk only counts if the outstanding writes change it, since
+    // otherwise it makes no difference whether it persists.
+    pub fn outstanding_chunks(&self, point: usize) -> Vec<(usize, usize)>
+    {
+        let points = self.points.borrow();
+        let mut chunks = Vec::new();
+        for (region, crash_point) in points[point].iter().enumerate() {
+            let flushed_chunks = crash_point.flushed.chunks(PERSISTENCE_CHUNK_SIZE);
+            let current_chunks = crash_point.current.chunks(PERSISTENCE_CHUNK_SIZE);
+            for (chunk, (flushed, current)) in flushed_chunks.zip(current_chunks).enumerate() {
+                if flushed != current {
+                    chunks.push((region, chunk));
+                }
+            }
+        }
+        chunks
+    }
+
+    // This function simulates a crash at crash point `point` in
+    // which exactly the chunks `persisted` of its outstanding chunks
+    // persist. It returns fresh regions, with no outstanding writes
+    // and an empty crash history, holding what persistent memory
+    // would hold after such a crash.
+    pub fn simulate_crash(&self, point: usize, persisted: &[(usize, usize)]) -> CrashSimulatingPersistentMemoryRegions
+    {
+        let points = self.points.borrow();
+        let mut contents: Vec<Vec<u8>> = points[point].iter().map(|p| p.flushed.as_ref().clone()).collect();
+        for &(region, chunk) in persisted {
+            let start = chunk * PERSISTENCE_CHUNK_SIZE;
+            let end = (start + PERSISTENCE_CHUNK_SIZE).min(contents[region].len());
+            contents[region][start..end].copy_from_slice(&points[point][region].current[start..end]);
+        }
+        CrashSimulatingPersistentMemoryRegions {
+            flushed: contents.iter().map(|c| FlushedContents { bytes: Rc::new(c.clone()) }).collect(),
+            contents,
+            history: RegionsCrashHistory { points: Rc::new(RefCell::new(Vec::new())) },
+        }
+    }
+}
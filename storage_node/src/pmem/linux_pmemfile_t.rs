@@ -1,7 +1,7 @@
 use crate::pmem::pmemspec_t::*;
 use crate::pmem::serialization_t::*;
 use core::ffi::c_void;
-use std::{cell::RefCell, convert::TryInto, ffi::CString, rc::Rc};
+use std::{cell::RefCell, convert::TryInto, ffi::CString, rc::Rc, sync::Arc};
 
 use builtin::*;
 use builtin_macros::*;
@@ -13,9 +13,11 @@ use deps_hack::{
 };
 
 pub struct MemoryMappedFile {
+    path: CString,
     virt_addr: *mut u8,
     size: usize,
     num_bytes_sectioned: usize,
+    is_device_dax: bool,
 }
 
 impl Drop for MemoryMappedFile
@@ -58,12 +60,19 @@ impl MemoryMappedFile
         };
 
         if addr.is_null() {
+            // Check `errno` before printing, which may change it.
+            // Creating a file that already exists is reported
+            // separately, so the caller can restore it instead.
+            let already_exists = std::io::Error::last_os_error().kind() == std::io::ErrorKind::AlreadyExists;
             eprintln!("{}", unsafe {
                 CString::from_raw(pmem_errormsg() as *mut i8)
                     .into_string()
                     .unwrap()
             });
-            Err(PmemError::CannotOpenPmFile)
+            match (file_open_behavior, already_exists) {
+                (FileOpenBehavior::CreateNew, true) => Err(PmemError::AlreadyExists),
+                _ => Err(PmemError::CannotOpenPmFile),
+            }
         } else if is_pm == 0 && require_pm {
             eprintln!("{}", unsafe {
                 CString::from_raw(pmem_errormsg() as *mut i8)
@@ -73,12 +82,123 @@ impl MemoryMappedFile
             Err(PmemError::NotPm)
         } else {
             Ok(Self {
+                path: file.to_owned(),
                 virt_addr: addr as *mut u8,
                 size: mapped_len.try_into().unwrap(),
                 num_bytes_sectioned: 0,
+                is_device_dax: false,
             })
         }
     }
+
+    // The function `from_dax_device` maps a whole device-DAX character
+    // device, e.g., `/dev/dax0.0`. A device has a fixed size and can't
+    // be created or truncated, so libpmem requires mapping it with
+    // length 0 and no flags, and reports the device's size as the
+    // mapped length. It fails with `PmemError::CannotOpenPmFile` if
+    // `device` isn't a character device, and with `PmemError::NotPm`
+    // if libpmem doesn't report the mapping as persistent memory,
+    // since flushing assumes it is.
+    fn from_dax_device(device: &str) -> Result<Self, PmemError>
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        match std::fs::metadata(device) {
+            Ok(metadata) if metadata.file_type().is_char_device() => {},
+            Ok(_) => {
+                eprintln!("{} isn't a character device", device);
+                return Err(PmemError::CannotOpenPmFile);
+            },
+            Err(e) => {
+                eprintln!("Can't open {}. err={}", device, e);
+                return Err(PmemError::CannotOpenPmFile);
+            },
+        }
+
+        let mut mapped_len = 0;
+        let mut is_pm = 0;
+        let device = CString::new(device).map_err(|_| PmemError::InvalidFileName)?;
+
+        let addr = unsafe {
+            pmem_map_file(device.as_ptr(), 0, 0, 0, &mut mapped_len, &mut is_pm)
+        };
+        if addr.is_null() {
+            eprintln!("{}", unsafe { std::ffi::CStr::from_ptr(pmem_errormsg()).to_string_lossy() });
+            return Err(PmemError::CannotOpenPmFile);
+        }
+        if is_pm == 0 {
+            eprintln!("{:?} isn't persistent memory", device);
+            unsafe { pmem_unmap(addr, mapped_len) };
+            return Err(PmemError::NotPm);
+        }
+
+        Ok(Self {
+            path: device,
+            virt_addr: addr as *mut u8,
+            size: mapped_len.try_into().unwrap(),
+            num_bytes_sectioned: 0,
+            is_device_dax: true,
+        })
+    }
+
+    // The function `size` returns the number of bytes mapped, which
+    // is the size of the file.
+    pub fn size(&self) -> usize
+    {
+        self.size
+    }
+
+    // The function `grow` extends the file to `new_size` bytes and
+    // maps it again at that size. The new bytes are zero. It fails
+    // with `PmemError::AccessOutOfRange` if `new_size` is smaller than
+    // the current size.
+    //
+    // The new mapping is generally at a different address, and the
+    // old one is unmapped. So every `MemoryMappedFileSection` carved
+    // out of this file before the call holds a dangling pointer and
+    // must never be used again. The caller must drop all of them
+    // before calling this, and carve new sections afterward. To make
+    // that possible, this resets the count of sectioned bytes, so the
+    // next section starts at offset 0. If this fails, the old mapping
+    // and sections remain valid.
+    pub fn grow(&mut self, new_size: usize) -> Result<(), PmemError>
+    {
+        if self.is_device_dax {
+            eprintln!("Can't resize a device-DAX device");
+            return Err(PmemError::AccessOutOfRange);
+        }
+        if new_size < self.size {
+            eprintln!("Can't grow a {}-byte file to {} bytes", self.size, new_size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+
+        // Mapping an existing file with `PMEM_FILE_CREATE` extends it
+        // to the given length, like `ftruncate`. We map the new
+        // length before unmapping the old so a failure leaves the old
+        // mapping intact.
+        let mut mapped_len = 0;
+        let mut is_pm = 0;
+        let addr = unsafe {
+            pmem_map_file(
+                self.path.as_ptr(),
+                new_size,
+                PMEM_FILE_CREATE.try_into().unwrap(),
+                0666,
+                &mut mapped_len,
+                &mut is_pm,
+            )
+        };
+        if addr.is_null() {
+            eprintln!("{}", unsafe { std::ffi::CStr::from_ptr(pmem_errormsg()).to_string_lossy() });
+            return Err(PmemError::CannotOpenPmFile);
+        }
+
+        unsafe { pmem_unmap(self.virt_addr as *mut c_void, self.size) };
+        self.virt_addr = addr as *mut u8;
+        self.size = mapped_len.try_into().unwrap();
+        self.num_bytes_sectioned = 0;
+        Ok(())
+    }
 }
 
 #[verifier::external_body]
@@ -102,11 +222,15 @@ impl MemoryMappedFileSection
             },
         };
 
-        if offset + len > mmf_borrowed.size {
-            eprintln!("Can't allocate {} bytes because only {} remain", len, mmf_borrowed.size - offset);
-            return Err(PmemError::AccessOutOfRange);
-        }
-
+        // A section may end exactly at the end of the file.
+        match offset.checked_add(len) {
+            Some(end) if end <= mmf_borrowed.size => {},
+            _ => {
+                eprintln!("Can't allocate {} bytes because only {} remain", len, mmf_borrowed.size - offset);
+                return Err(PmemError::AccessOutOfRange);
+            },
+        }
+
         mmf_borrowed.num_bytes_sectioned += len;
         let new_virt_addr = unsafe { mmf_borrowed.virt_addr.offset(offset_as_isize) };
 
@@ -119,7 +243,191 @@ impl MemoryMappedFileSection
         };
         Ok(section)
     }
-}
+
+    // The function `subsection` carves out the bytes in `[offset,
+    // offset + len)` of this section as a new section of the same
+    // memory-mapped file. Unlike `new`, it doesn't allocate fresh
+    // bytes from the file, so it must only be used in a way that
+    // prevents the two sections from being used at the same time.
+    fn subsection(self, offset: usize, len: usize) -> Result<Self, PmemError>
+    {
+        let offset_as_isize: isize = match offset.try_into() {
+            Ok(off) => off,
+            Err(_) => {
+                eprintln!("Can't express offset {} as isize", offset);
+                return Err(PmemError::AccessOutOfRange)
+            },
+        };
+
+        if offset > self.size || len > self.size - offset {
+            eprintln!("Can't carve {} bytes at offset {} out of a section of {} bytes", len, offset, self.size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+
+        let new_virt_addr = unsafe { self.virt_addr.offset(offset_as_isize) };
+        Ok(Self {
+            mmf: self.mmf,
+            virt_addr: new_virt_addr,
+            size: len,
+        })
+    }
+
+    // The function `new_at` creates a section for the bytes in
+    // `[offset, offset + len)` of the memory-mapped file. Unlike
+    // `new`, it doesn't allocate the bytes from the file, so the
+    // caller must make sure no other section covers them.
+    fn new_at(mmf: Rc<RefCell<MemoryMappedFile>>, offset: usize, len: usize) -> Result<Self, PmemError>
+    {
+        let mmf_borrowed = mmf.borrow();
+        let offset_as_isize: isize = match offset.try_into() {
+            Ok(off) => off,
+            Err(_) => {
+                eprintln!("Can't express offset {} as isize", offset);
+                return Err(PmemError::AccessOutOfRange)
+            },
+        };
+
+        if offset > mmf_borrowed.size || len > mmf_borrowed.size - offset {
+            eprintln!("Can't map {} bytes at offset {} of a file of {} bytes", len, offset, mmf_borrowed.size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+
+        let new_virt_addr = unsafe { mmf_borrowed.virt_addr.offset(offset_as_isize) };
+        std::mem::drop(mmf_borrowed);
+
+        Ok(Self {
+            mmf,
+            virt_addr: new_virt_addr,
+            size: len,
+        })
+    }
+
+    // This function returns the offset of this section within its
+    // memory-mapped file.
+    fn offset_in_file(&self) -> usize
+    {
+        self.virt_addr as usize - self.mmf.borrow().virt_addr as usize
+    }
+
+    // The function `grow` grows this section, and its file, to
+    // `new_size` bytes. Growing the file remaps it, which would leave
+    // any other section of it dangling, so this only works if this
+    // section is the file's only one and covers all of it. Otherwise,
+    // it returns `Err(PmemError::AccessOutOfRange)`.
+    fn grow(&mut self, new_size: usize) -> Result<(), PmemError>
+    {
+        if Rc::strong_count(&self.mmf) != 1 || self.offset_in_file() != 0 || self.size != self.mmf.borrow().size {
+            eprintln!("Can't grow a section that isn't the only one covering its file");
+            return Err(PmemError::AccessOutOfRange);
+        }
+
+        let mut mmf_borrowed = self.mmf.borrow_mut();
+        mmf_borrowed.grow(new_size)?;
+        mmf_borrowed.num_bytes_sectioned = new_size;
+        self.virt_addr = mmf_borrowed.virt_addr;
+        self.size = new_size;
+        Ok(())
+    }
+}
+
+// A `SharedMemoryMappedFileSection` is like a `MemoryMappedFileSection`
+// except that it holds its file with an `Arc` rather than an `Rc`, so
+// it can be sent to and shared with other threads. It's only used by
+// `ReadOnlyFileBackedPersistentMemoryRegion`, which never writes
+// through it.
+#[verifier::external_body]
+#[derive(Clone)]
+pub struct SharedMemoryMappedFileSection {
+    mmf: Arc<MemoryMappedFile>,
+    virt_addr: *mut u8,
+    size: usize,
+}
+
+// SAFETY: The mapping stays valid as long as `mmf` is alive, and the
+// `Arc` keeps it alive until the last clone of the section is dropped,
+// so unmapping the file on whichever thread drops it last is fine.
+// Other handles to the same file, in this process or another, may
+// write the mapped bytes at any time, so the section never hands out
+// a reference into the mapping. Its only user copies bytes out with a
+// raw-pointer copy, so no reference ever aliases memory that's being
+// written.
+unsafe impl Send for SharedMemoryMappedFileSection {}
+unsafe impl Sync for SharedMemoryMappedFileSection {}
+
+impl SharedMemoryMappedFileSection
+{
+    fn new(mmf: MemoryMappedFile, len: usize) -> Result<Self, PmemError>
+    {
+        if len > mmf.size {
+            eprintln!("Can't allocate {} bytes because only {} exist", len, mmf.size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+        let virt_addr = mmf.virt_addr;
+        Ok(Self { mmf: Arc::new(mmf), virt_addr, size: len })
+    }
+}
+
+// A `ReadOnlyFileBackedPersistentMemoryRegion` is a handle to an
+// existing file-backed region that can be cloned and shared across
+// threads, so several reader threads can read it concurrently. It
+// doesn't implement `PersistentMemoryRegion`: `read` copies the
+// requested bytes into a fresh `Vec`, and there's no way to write.
+// Writes go through `FileBackedPersistentMemoryRegion` on a single
+// thread.
+//
+// A writer may be updating the file while a reader copies from it,
+// so the bytes read can be torn. Callers must check them, e.g.,
+// against their CRCs, just as they would check possibly-corrupted
+// bytes. To run recovery code such as `read_log_variables`, copy the
+// bytes into a region that implements `PersistentMemoryRegion`.
+#[derive(Clone)]
+pub struct ReadOnlyFileBackedPersistentMemoryRegion
+{
+    section: SharedMemoryMappedFileSection,
+}
+
+impl ReadOnlyFileBackedPersistentMemoryRegion
+{
+    pub fn open(path: &str, region_size: u64) -> Result<Self, PmemError>
+    {
+        let mmf = MemoryMappedFile::from_file(
+            path,
+            region_size as usize,
+            FileOpenBehavior::OpenExisting,
+            PersistentMemoryCheck::DontCheckForPersistentMemory,
+        )?;
+        let section = SharedMemoryMappedFileSection::new(mmf, region_size as usize)?;
+        Ok(Self { section })
+    }
+
+    pub fn get_region_size(&self) -> u64
+    {
+        self.section.size as u64
+    }
+
+    // The function `read` copies `num_bytes` bytes starting at `addr`
+    // out of the region. It fails with `PmemError::AccessOutOfRange`
+    // if that range doesn't lie within the region.
+    pub fn read(&self, addr: u64, num_bytes: u64) -> Result<Vec<u8>, PmemError>
+    {
+        match addr.checked_add(num_bytes) {
+            Some(end) if end <= self.section.size as u64 => {},
+            _ => { return Err(PmemError::AccessOutOfRange); },
+        }
+        let mut bytes: Vec<u8> = Vec::with_capacity(num_bytes as usize);
+        // SAFETY: The range was checked to lie within the mapping,
+        // which stays mapped while `self.section` is alive. We copy
+        // through raw pointers without forming a reference to the
+        // mapped bytes, and `bytes` has capacity for `num_bytes`
+        // bytes, which are all initialized by the copy.
+        unsafe {
+            let addr_on_pm: *const u8 = self.section.virt_addr.offset(addr.try_into().unwrap());
+            std::ptr::copy_nonoverlapping(addr_on_pm, bytes.as_mut_ptr(), num_bytes as usize);
+            bytes.set_len(num_bytes as usize);
+        }
+        Ok(bytes)
+    }
+}
 
 verus! {
 
@@ -190,6 +498,32 @@ impl FileBackedPersistentMemoryRegion
     {
         Self{ section }
     }
+
+    // The function `subregion` turns this region into a region
+    // consisting of just the bytes in `[offset, offset + len)`, with
+    // addresses relative to `offset`. It consumes `self` so that the
+    // parent and the subregion can't both be used to access the same
+    // bytes. The offset must be aligned to the persistence chunk size
+    // so that the subregion's chunks line up with the parent's.
+    #[verifier::external_body]
+    pub fn subregion(self, offset: u64, len: u64) -> (result: Result<Self, PmemError>)
+        requires
+            self.inv(),
+            offset % (const_persistence_chunk_size() as u64) == 0,
+            offset + len <= self@.len(),
+        ensures
+            match result {
+                Ok(region) => {
+                    &&& region.inv()
+                    &&& region.constants() == self.constants()
+                    &&& region@ == self@.subrange(offset as int, offset + len)
+                },
+                Err(_) => true,
+            }
+    {
+        let section = self.section.subsection(offset as usize, len as usize)?;
+        Ok(Self { section })
+    }
 }
 
 impl PersistentMemoryRegion for FileBackedPersistentMemoryRegion
@@ -204,6 +538,12 @@ impl PersistentMemoryRegion for FileBackedPersistentMemoryRegion
         self.section.size as u64
     }
 
+    // Persistent memory can be corrupted, so we never skip CRC checks.
+    fn known_impervious_to_corruption(&self) -> bool
+    {
+        false
+    }
+
     #[verifier::external_body]
     fn read(&self, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
     {
@@ -237,6 +577,7 @@ impl PersistentMemoryRegion for FileBackedPersistentMemoryRegion
         where
             S: Serializable + Sized
     {
+        debug_check_serialized_layout::<S>();
         // SAFETY: The `offset` method is safe as long as both the start
         // and resulting pointer are in bounds and the computed offset does
         // not overflow `isize`. `addr` and `num_bytes` are unsigned and
@@ -331,14 +672,23 @@ impl PersistentMemoryRegion for FileBackedPersistentMemoryRegion
     }
 
     #[verifier::external_body]
-    fn flush(&mut self)
+    fn flush(&mut self) -> (result: Result<(), PmemError>)
     {
         // `pmem_drain()` invokes an ordering primitive to drain store buffers and
         // ensure that all cache lines that were flushed since the previous ordering
         // primitive are durable. This guarantees that all updates made with `write`/
         // `serialize_and_write` since the last `flush` call will be durable before
-        // any new updates become durable.
+        // any new updates become durable. It can't fail.
         unsafe { pmem_drain(); }
+        Ok(())
+    }
+
+    // Only a region that covers its whole file, and isn't a
+    // subregion, can grow. Extending the file zeroes the new bytes.
+    #[verifier::external_body]
+    fn grow_region(&mut self, new_size: u64) -> (result: Result<(), PmemError>)
+    {
+        self.section.grow(new_size as usize)
     }
 }
 
@@ -346,6 +696,16 @@ pub struct FileBackedPersistentMemoryRegions {
     regions: Vec<FileBackedPersistentMemoryRegion>,
 }
 
+// This function gives the offset in the file at which region `index`
+// starts when regions of sizes `region_sizes` are laid out back to
+// back, as `FileBackedPersistentMemoryRegions::new` lays them out. It's
+// the sum of the sizes of the regions before it.
+pub open spec fn region_start_in_file(region_sizes: Seq<u64>, index: int) -> int
+    decreases index
+{
+    if index <= 0 { 0 } else { region_start_in_file(region_sizes, index - 1) + region_sizes[index - 1] }
+}
+
 impl FileBackedPersistentMemoryRegions {
     // TODO: detailed information for error returns
     #[verifier::external_body]
@@ -419,6 +779,237 @@ impl FileBackedPersistentMemoryRegions {
     {
         Self::new_internal(file_to_map, region_sizes, FileOpenBehavior::OpenExisting, persistent_memory_check)
     }
+
+    // The function `restore_at_offsets` is like `restore`, except
+    // that region `i` is at `region_offsets[i]` in the file rather
+    // than right after region `i - 1`. It's for reopening a file in
+    // which regions have been moved with `relocate_region`. The
+    // regions mustn't overlap, and the file must already be large
+    // enough to hold all of them.
+    #[verifier::external_body]
+    pub fn restore_at_offsets<'a>(file_to_map: &StrSlice<'a>, region_offsets: &[u64], region_sizes: &[u64],
+                                  persistent_memory_check: PersistentMemoryCheck)
+                                  -> (result: Result<Self, PmemError>)
+        requires
+            region_offsets@.len() == region_sizes@.len(),
+        ensures
+            match result {
+                Ok(regions) => {
+                    &&& regions.inv()
+                    &&& regions@.no_outstanding_writes()
+                    &&& regions@.len() == region_sizes@.len()
+                    &&& forall |i| 0 <= i < regions@.len() ==> #[trigger] regions@[i].len() == region_sizes@[i]
+                },
+                Err(_) => true,
+            }
+    {
+        let mut file_size: usize = 0;
+        for (&region_offset, &region_size) in region_offsets.iter().zip(region_sizes.iter()) {
+            let region_end = match region_offset.checked_add(region_size) {
+                Some(end) => end as usize,
+                None => { return Err(PmemError::AccessOutOfRange); },
+            };
+            file_size = file_size.max(region_end);
+        }
+        for i in 0..region_offsets.len() {
+            for j in i + 1..region_offsets.len() {
+                if region_offsets[i] < region_offsets[j] + region_sizes[j] &&
+                   region_offsets[j] < region_offsets[i] + region_sizes[i] {
+                    eprintln!("Regions {} and {} overlap", i, j);
+                    return Err(PmemError::AccessOutOfRange);
+                }
+            }
+        }
+        let mmf = MemoryMappedFile::from_file(
+            file_to_map.into_rust_str(),
+            file_size,
+            FileOpenBehavior::OpenExisting,
+            persistent_memory_check,
+        )?;
+        let mmf = Rc::<RefCell<MemoryMappedFile>>::new(RefCell::<MemoryMappedFile>::new(mmf));
+        let mut regions = Vec::<FileBackedPersistentMemoryRegion>::new();
+        for (&region_offset, &region_size) in region_offsets.iter().zip(region_sizes.iter()) {
+            let section = MemoryMappedFileSection::new_at(mmf.clone(), region_offset as usize,
+                                                          region_size as usize)?;
+            regions.push(FileBackedPersistentMemoryRegion::new_from_section(section));
+        }
+        Ok(Self { regions })
+    }
+
+    // The function `from_dax_device` creates a
+    // `FileBackedPersistentMemoryRegions` object by mapping the
+    // device-DAX character device `device`, e.g., `/dev/dax0.0`, and
+    // carving it into regions of sizes `region_sizes`, laid out back
+    // to back from the start of the device. Unlike `new` and
+    // `restore`, it never creates or resizes anything; it returns
+    // `Err(PmemError::AccessOutOfRange)` if the regions don't fit in
+    // the device. Any bytes past the last region are left unused.
+    // It returns `Err(PmemError::NotPm)` if the device isn't
+    // persistent memory, so flushing works as for a file on a DAX
+    // file system.
+    #[verifier::external_body]
+    pub fn from_dax_device<'a>(device: &StrSlice<'a>, region_sizes: &[u64]) -> (result: Result<Self, PmemError>)
+        ensures
+            match result {
+                Ok(regions) => {
+                    &&& regions.inv()
+                    &&& regions@.no_outstanding_writes()
+                    &&& regions@.len() == region_sizes@.len()
+                    &&& forall |i| 0 <= i < regions@.len() ==> #[trigger] regions@[i].len() == region_sizes@[i]
+                },
+                Err(_) => true,
+            }
+    {
+        let mut total_size: u64 = 0;
+        for &region_size in region_sizes {
+            total_size = match total_size.checked_add(region_size) {
+                Some(total) => total,
+                None => { return Err(PmemError::AccessOutOfRange); },
+            };
+        }
+        let mmf = MemoryMappedFile::from_dax_device(device.into_rust_str())?;
+        if total_size > mmf.size as u64 {
+            eprintln!("Can't carve {} bytes of regions out of a {}-byte device", total_size, mmf.size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+        let mmf = Rc::<RefCell<MemoryMappedFile>>::new(RefCell::<MemoryMappedFile>::new(mmf));
+        let mut regions = Vec::<FileBackedPersistentMemoryRegion>::new();
+        for &region_size in region_sizes {
+            let section = MemoryMappedFileSection::new(mmf.clone(), region_size as usize)?;
+            regions.push(FileBackedPersistentMemoryRegion::new_from_section(section));
+        }
+        Ok(Self { regions })
+    }
+
+    // The function `split_existing` re-carves a collection holding a
+    // single region that covers its whole file into regions of sizes
+    // `region_sizes`, laid out back to back. It's for when the layout
+    // is only discovered after opening the file, e.g., from metadata
+    // showing it holds a multilog with several logs. It reuses the
+    // existing mapping rather than mapping the file again, so each
+    // new region views the same bytes the single region did. The
+    // sizes must add up to the file's size, and each region must start
+    // at a multiple of the persistence chunk size, so that its chunks
+    // line up with the single region's.
+    #[verifier::external_body]
+    pub fn split_existing(self, region_sizes: &[u64]) -> (result: Result<Self, PmemError>)
+        requires
+            self.inv(),
+            self@.len() == 1,
+            self@.no_outstanding_writes(),
+            forall |i: int| 0 <= i < region_sizes@.len() ==>
+                #[trigger] region_start_in_file(region_sizes@, i) % const_persistence_chunk_size() == 0,
+        ensures
+            match result {
+                Ok(regions) => {
+                    &&& regions.inv()
+                    &&& regions.constants() == self.constants()
+                    &&& regions@.no_outstanding_writes()
+                    &&& regions@.len() == region_sizes@.len()
+                    &&& region_start_in_file(region_sizes@, region_sizes@.len() as int) == self@[0].len()
+                    &&& forall |i: int| 0 <= i < regions@.len() ==> {
+                            let start = region_start_in_file(region_sizes@, i);
+                            #[trigger] regions@[i] == self@[0].subrange(start, start + region_sizes@[i])
+                        }
+                },
+                Err(_) => true,
+            }
+    {
+        let mut regions = self.regions;
+        if regions.len() != 1 {
+            eprintln!("Can only split a collection of exactly one region, not {}", regions.len());
+            return Err(PmemError::AccessOutOfRange);
+        }
+        let section = regions.pop().unwrap().section;
+        let file_size = section.mmf.borrow().size;
+        if section.offset_in_file() != 0 || section.size != file_size {
+            eprintln!("Can only split a region that covers its whole file");
+            return Err(PmemError::AccessOutOfRange);
+        }
+
+        let mut total_size: usize = 0;
+        for &region_size in region_sizes {
+            total_size = match total_size.checked_add(region_size as usize) {
+                Some(total) => total,
+                None => { return Err(PmemError::AccessOutOfRange); },
+            };
+        }
+        if total_size != file_size {
+            eprintln!("Region sizes add up to {} bytes, but the file has {}", total_size, file_size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+
+        // Each new section holds its own reference to the mapping, so
+        // dropping the old section doesn't unmap it.
+        let mmf = section.mmf.clone();
+        std::mem::drop(section);
+        let mut offset: usize = 0;
+        for &region_size in region_sizes {
+            let section = MemoryMappedFileSection::new_at(mmf.clone(), offset, region_size as usize)?;
+            regions.push(FileBackedPersistentMemoryRegion::new_from_section(section));
+            offset += region_size as usize;
+        }
+        Ok(Self { regions })
+    }
+
+    // The function `relocate_region` moves region `index` to
+    // `dest_offset` in the same file, e.g., after the file has been
+    // grown. It copies the region's bytes to the new location and
+    // makes them durable before switching the region over to it, so
+    // the region's contents, and thus whatever state would be
+    // recovered from them, don't change.
+    //
+    // The new location mustn't overlap any region, including the one
+    // being moved. So a crash at any point leaves the old location
+    // intact, and once this returns `Ok` the new location is a
+    // durable copy of it. Nothing in the file itself records where
+    // each region is, so the caller must durably record
+    // `dest_offset`, for use with `restore_at_offsets`, only after
+    // this returns `Ok`. Until then, the old location remains the
+    // one to recover from.
+    #[verifier::external_body]
+    pub fn relocate_region(&mut self, index: usize, dest_offset: u64) -> (result: Result<(), PmemError>)
+        requires
+            old(self).inv(),
+            old(self)@.no_outstanding_writes(),
+            index < old(self)@.len(),
+            dest_offset % (const_persistence_chunk_size() as u64) == 0,
+        ensures
+            self.inv(),
+            self.constants() == old(self).constants(),
+            self@ == old(self)@,
+    {
+        let src = &self.regions[index].section;
+        let len = src.size;
+        let dest_offset = dest_offset as usize;
+        let dest_end = match dest_offset.checked_add(len) {
+            Some(end) => end,
+            None => { return Err(PmemError::AccessOutOfRange); },
+        };
+        for region in self.regions.iter() {
+            let start = region.section.offset_in_file();
+            if dest_offset < start + region.section.size && start < dest_end {
+                eprintln!("Can't relocate a region to offset {} since it overlaps another region", dest_offset);
+                return Err(PmemError::AccessOutOfRange);
+            }
+        }
+        let dest = MemoryMappedFileSection::new_at(src.mmf.clone(), dest_offset, len)?;
+
+        // Copy the bytes and make them durable before switching to the
+        // new location. The two locations don't overlap, so the copy
+        // doesn't disturb the old location.
+        unsafe {
+            pmem_memcpy_nodrain_helper(
+                dest.virt_addr as *mut c_void,
+                src.virt_addr as *const c_void,
+                len
+            );
+            pmem_drain();
+        }
+
+        self.regions[index] = FileBackedPersistentMemoryRegion::new_from_section(dest);
+        Ok(())
+    }
 }
 
 impl PersistentMemoryRegions for FileBackedPersistentMemoryRegions {
@@ -438,6 +1029,18 @@ impl PersistentMemoryRegions for FileBackedPersistentMemoryRegions {
         self.regions[index].get_region_size()
     }
 
+    // Persistent memory can be corrupted, so we never skip CRC checks.
+    fn known_impervious_to_corruption(&self) -> bool
+    {
+        false
+    }
+
+    // `flush_region` uses the same global fence as `flush`.
+    fn capabilities(&self) -> (result: PmemCapabilities)
+    {
+        PmemCapabilities { targeted_flush: false, persistent: true }
+    }
+
     #[verifier::external_body]
     fn read(&self, index: usize, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
     {
@@ -449,6 +1052,7 @@ impl PersistentMemoryRegions for FileBackedPersistentMemoryRegions {
         where
             S: Serializable + Sized
     {
+        debug_check_serialized_layout::<S>();
         self.regions[index].read_and_deserialize(addr)
     }
 
@@ -467,10 +1071,49 @@ impl PersistentMemoryRegions for FileBackedPersistentMemoryRegions {
     }
 
     #[verifier::external_body]
-    fn flush(&mut self)
+    fn flush(&mut self) -> (result: Result<(), PmemError>)
     {
         unsafe { pmem_drain(); }
+        Ok(())
     }
+
+    // `pmem_drain` is a global fence, so it can't be limited to one
+    // region. It's no more expensive than flushing them all, though.
+    #[verifier::external_body]
+    fn flush_region(&mut self, index: usize) -> (result: Result<(), PmemError>)
+    {
+        unsafe { pmem_drain(); }
+        Ok(())
+    }
+}
+
 }
 
+// The function `truncate_region_file` shrinks the file at `path` to
+// `new_size` bytes with `ftruncate`, returning the space past that
+// point to the file system, and makes the new size durable. It never
+// grows the file. Accessing a mapping past the new end of a file
+// raises `SIGBUS`, so every region backed by the file must be dropped
+// first. It's up to the caller to make sure nothing live lies past
+// `new_size`; `LogImpl::truncate_region_file` does that for a log.
+pub fn truncate_region_file(path: &str, new_size: u64) -> Result<(), PmemError>
+{
+    let file = std::fs::OpenOptions::new().write(true).open(path).map_err(|e| {
+        eprintln!("Could not open file {} to truncate it: {}", path, e);
+        PmemError::CannotOpenPmFile
+    })?;
+    let current_size = file.metadata().map_err(|e| {
+        eprintln!("Could not get the size of file {}: {}", path, e);
+        PmemError::CannotOpenPmFile
+    })?.len();
+    if new_size > current_size {
+        eprintln!("Can't truncate file {} to {} bytes since it only has {}", path, new_size, current_size);
+        return Err(PmemError::AccessOutOfRange);
+    }
+    // `File::set_len` calls `ftruncate`, and `sync_all` makes the new
+    // size durable.
+    file.set_len(new_size).and_then(|()| file.sync_all()).map_err(|e| {
+        eprintln!("Could not truncate file {}: {}", path, e);
+        PmemError::FlushFailed
+    })
 }
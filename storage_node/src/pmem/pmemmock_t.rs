@@ -60,13 +60,24 @@ verus! {
             &&& self.contents@ == self@.flush().committed()
         }
 
-        closed spec fn constants(&self) -> PersistentMemoryConstants;
+        // Volatile memory is never corrupted, so the mock is
+        // impervious to corruption. Tests that need CRC checks to
+        // run should use `CrashSimulatingPersistentMemoryRegion`.
+        closed spec fn constants(&self) -> PersistentMemoryConstants
+        {
+            PersistentMemoryConstants { impervious_to_corruption: true }
+        }
 
         fn get_region_size(&self) -> (result: u64)
         {
             self.contents.len() as u64
         }
 
+        fn known_impervious_to_corruption(&self) -> (result: bool)
+        {
+            true
+        }
+
         #[verifier::external_body]
         fn read(&self, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
         {
@@ -80,6 +91,7 @@ verus! {
             where
                 S: Serializable + Sized
         {
+            debug_check_serialized_layout::<S>();
             let addr_usize: usize = addr.try_into().unwrap();
             let num_bytes: usize = S::serialized_len().try_into().unwrap();
             let bytes = &self.contents[addr_usize..addr_usize+num_bytes];
@@ -122,8 +134,16 @@ verus! {
         }
 
         #[verifier::external_body]
-        fn flush(&mut self)
+        fn flush(&mut self) -> Result<(), PmemError>
         {
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn grow_region(&mut self, new_size: u64) -> (result: Result<(), PmemError>)
+        {
+            self.contents.resize(new_size as usize, 0);
+            Ok(())
         }
     }
 
@@ -174,8 +194,12 @@ verus! {
             forall |i| 0 <= i < self.regions.len() ==> #[trigger] self.regions[i].inv()
         }
 
-        #[verifier::external_body]
-        closed spec fn constants(&self) -> PersistentMemoryConstants;
+        // Like each region, the collection is impervious to
+        // corruption.
+        closed spec fn constants(&self) -> PersistentMemoryConstants
+        {
+            PersistentMemoryConstants { impervious_to_corruption: true }
+        }
 
         #[verifier::external_body]
         fn get_num_regions(&self) -> usize
@@ -189,6 +213,18 @@ verus! {
             self.regions[index].get_region_size()
         }
 
+        fn known_impervious_to_corruption(&self) -> (result: bool)
+        {
+            true
+        }
+
+        // Flushing the mock is a no-op, so there's nothing for
+        // `flush_region` to save.
+        fn capabilities(&self) -> (result: PmemCapabilities)
+        {
+            PmemCapabilities { targeted_flush: false, persistent: false }
+        }
+
         #[verifier::external_body]
         fn read(&self, index: usize, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
         {
@@ -200,6 +236,7 @@ verus! {
             where
                 S: Serializable + Sized
         {
+            debug_check_serialized_layout::<S>();
             self.regions[index].read_and_deserialize(addr)
         }
 
@@ -218,8 +255,15 @@ verus! {
         }
 
         #[verifier::external_body]
-        fn flush(&mut self)
+        fn flush(&mut self) -> Result<(), PmemError>
+        {
+            Ok(())
+        }
+
+        #[verifier::external_body]
+        fn flush_region(&mut self, index: usize) -> Result<(), PmemError>
         {
+            Ok(())
         }
     }
 }
@@ -50,6 +50,9 @@ verus! {
         NotPm,
         PmdkError,
         AccessOutOfRange,
+        FlushFailed,
+        MediaTypeMismatch,
+        AlreadyExists,
     }
 
     /// This is our model of bit corruption. It models corruption of a
@@ -98,6 +101,49 @@ verus! {
         u64_to_le_bytes(digest.sum64())
     }
 
+    // A `CrcDigest` computes the CRC of a sequence of bytes supplied
+    // in pieces, so the caller doesn't have to assemble them into one
+    // buffer first, e.g., for a log range that wraps around the end of
+    // the log area. Its view is the concatenation of the pieces
+    // supplied so far. We trust that the `crc` crate's digest is
+    // incremental, i.e., that feeding it `a` and then `b` gives the
+    // CRC of `a + b`, which is what the specifications of `update`
+    // and `finalize` say together.
+    #[verifier::external_body]
+    pub struct CrcDigest {
+        digest: Digest,
+    }
+
+    impl CrcDigest {
+        #[verifier::external_body]
+        pub closed spec fn view(&self) -> Seq<u8>;
+
+        #[verifier::external_body]
+        pub exec fn new() -> (result: Self)
+            ensures
+                result@ == Seq::<u8>::empty(),
+        {
+            Self { digest: Digest::new() }
+        }
+
+        #[verifier::external_body]
+        pub exec fn update(&mut self, bytes: &[u8])
+            ensures
+                self@ == old(self)@ + bytes@,
+        {
+            self.digest.write(bytes);
+        }
+
+        #[verifier::external_body]
+        pub exec fn finalize(self) -> (out: Vec<u8>)
+            ensures
+                spec_crc_bytes(self@) == out@,
+                out@.len() == CRC_SIZE,
+        {
+            u64_to_le_bytes(self.digest.sum64())
+        }
+    }
+
     /// We make two assumptions about how CRCs can be used to detect
     /// corruption.
 
@@ -246,6 +292,30 @@ verus! {
             self.state.map(|_addr, b: PersistentMemoryByte| b.state_at_last_flush)
         }
 
+        // This specification function describes the view of the
+        // bytes in `[start, end)` of `self` as a region of their own,
+        // with addresses relative to `start`.
+        pub open spec fn subrange(self, start: int, end: int) -> Self
+        {
+            Self {
+                state: self.state.subrange(start, end),
+            }
+        }
+
+        // This specification function describes the view of `self`
+        // after it grows to `new_len` bytes. The new bytes are zero
+        // and have no outstanding writes.
+        pub open spec fn grow(self, new_len: int) -> Self
+        {
+            Self {
+                state: self.state + Seq::new((new_len - self.len()) as nat,
+                                             |_i: int| PersistentMemoryByte {
+                                                 state_at_last_flush: 0,
+                                                 outstanding_write: None,
+                                             }),
+            }
+        }
+
         // This specification function describes what it means for
         // chunk number `chunk` in `self` to match the corresponding
         // bytes in `bytes` if outstanding writes to those bytes in
@@ -329,6 +399,17 @@ verus! {
             }
         }
 
+        // This describes flushing only region `index`, leaving the
+        // outstanding writes to other regions outstanding.
+        pub open spec fn flush_region(self, index: int) -> Self
+        {
+            Self {
+                regions: self.regions.map(|pos: int, pm: PersistentMemoryRegionView|
+                    if pos == index { pm.flush() } else { pm }
+                ),
+            }
+        }
+
         pub open spec fn no_outstanding_writes(self) -> bool {
             forall |i: int| #![auto] 0 <= i < self.len() ==> self[i].no_outstanding_writes()
         }
@@ -357,6 +438,24 @@ verus! {
         pub impervious_to_corruption: bool
     }
 
+    // A `PmemCapabilities` describes optional properties of a
+    // `PersistentMemoryRegions` implementation, so that code that
+    // works across implementations can pick the cheapest way to do
+    // something at run time. Each implementation reports the same
+    // capabilities for its whole lifetime. They're advisory: the
+    // specifications of the trait methods hold whatever they are.
+    //
+    // `targeted_flush` -- `flush_region` does less work than `flush`,
+    // rather than being a global fence that costs the same
+    //
+    // `persistent` -- the contents outlive the process, so they can
+    // be recovered by opening the same regions again
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct PmemCapabilities {
+        pub targeted_flush: bool,
+        pub persistent: bool,
+    }
+
     pub trait PersistentMemoryRegion : Sized
     {
         spec fn view(&self) -> PersistentMemoryRegionView;
@@ -372,6 +471,17 @@ verus! {
                 result == self@.len()
         ;
 
+        // Returns `true` only if the region is known to be impervious
+        // to corruption, in which case reads are always faithful and
+        // CRC checks can be skipped. It may conservatively return
+        // `false`.
+        fn known_impervious_to_corruption(&self) -> (result: bool)
+            requires
+                self.inv()
+            ensures
+                result ==> self.constants().impervious_to_corruption
+        ;
+
         fn read(&self, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
             requires
                 self.inv(),
@@ -442,13 +552,41 @@ verus! {
         ;
 
 
-        fn flush(&mut self)
+        // Flushing can fail, e.g., due to an I/O error on the
+        // underlying device. If it does, outstanding writes remain
+        // outstanding, i.e., each may or may not have reached the
+        // media, which is exactly what our crash model already
+        // permits.
+        fn flush(&mut self) -> (result: Result<(), PmemError>)
             requires
                 old(self).inv()
             ensures
                 self.inv(),
                 self.constants() == old(self).constants(),
-                self@ == old(self)@.flush(),
+                match result {
+                    Ok(()) => self@ == old(self)@.flush(),
+                    Err(_) => self@ == old(self)@,
+                },
+        ;
+
+        // Grows the region to `new_size` bytes, preserving its bytes
+        // and making the new ones read as zero, so they can't be
+        // mistaken for data. Growing may remap the region, so there
+        // mustn't be outstanding writes. A region that can't grow
+        // returns `Err(PmemError::AccessOutOfRange)` and is left
+        // unchanged.
+        fn grow_region(&mut self, new_size: u64) -> (result: Result<(), PmemError>)
+            requires
+                old(self).inv(),
+                old(self)@.len() <= new_size,
+                old(self)@.no_outstanding_writes(),
+            ensures
+                self.inv(),
+                self.constants() == old(self).constants(),
+                match result {
+                    Ok(()) => self@ == old(self)@.grow(new_size as int),
+                    Err(_) => self@ == old(self)@,
+                },
         ;
     }
 
@@ -478,6 +616,24 @@ verus! {
                 result == self@[index as int].len(),
         ;
 
+        // Returns `true` only if the regions are known to be
+        // impervious to corruption, in which case reads are always
+        // faithful and CRC checks can be skipped. It may
+        // conservatively return `false`.
+        fn known_impervious_to_corruption(&self) -> (result: bool)
+            requires
+                self.inv()
+            ensures
+                result ==> self.constants().impervious_to_corruption,
+        ;
+
+        // Returns the optional capabilities of this implementation.
+        // See `PmemCapabilities`.
+        fn capabilities(&self) -> (result: PmemCapabilities)
+            requires
+                self.inv()
+        ;
+
         fn read(&self, index: usize, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
             requires
                 self.inv(),
@@ -561,13 +717,129 @@ verus! {
                 self@ == old(self)@.write(index as int, addr as int, to_write.spec_serialize()),
         ;
 
-        fn flush(&mut self)
+        // See the comment on `PersistentMemoryRegion::flush` about
+        // what happens if flushing fails.
+        fn flush(&mut self) -> (result: Result<(), PmemError>)
+            requires
+                old(self).inv(),
+            ensures
+                self.inv(),
+                self.constants() == old(self).constants(),
+                match result {
+                    Ok(()) => self@ == old(self)@.flush(),
+                    Err(_) => self@ == old(self)@,
+                },
+        ;
+
+        // This flushes only region `index`, which is cheaper than
+        // `flush` when only that region has outstanding writes. An
+        // implementation may make more writes durable than that,
+        // e.g., if its flush primitive is a global fence, but the
+        // specification conservatively treats the outstanding writes
+        // to other regions as still outstanding.
+        fn flush_region(&mut self, index: usize) -> (result: Result<(), PmemError>)
             requires
                 old(self).inv(),
+                index < old(self)@.len(),
             ensures
                 self.inv(),
                 self.constants() == old(self).constants(),
-                self@ == old(self)@.flush(),
+                match result {
+                    Ok(()) => self@ == old(self)@.flush_region(index as int),
+                    Err(_) => self@ == old(self)@,
+                },
         ;
     }
 }
+
+// So that applications using `std::io::Result` can propagate a
+// `PmemError` with `?`, we make it a standard error and convert it
+// to a `std::io::Error`. `PmemError` doesn't record the OS error code
+// behind a failure, so the conversion picks the closest
+// `std::io::ErrorKind` and uses the `Display` text as the message.
+// In particular, `CannotOpenPmFile` covers many causes, from a
+// missing file to insufficient permissions, so it maps to `Other`.
+// Only a file that already exists when creating one gets its own
+// variant, `AlreadyExists`, so callers can fall back to restoring it.
+
+impl std::fmt::Display for PmemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            PmemError::InvalidFileName => write!(f, "invalid persistent memory file name"),
+            PmemError::CannotOpenPmFile => write!(f, "cannot open persistent memory file"),
+            PmemError::NotPm => write!(f, "file is not backed by persistent memory"),
+            PmemError::PmdkError => write!(f, "PMDK operation failed"),
+            PmemError::AccessOutOfRange => write!(f, "access out of range of persistent memory region"),
+            PmemError::FlushFailed => write!(f, "flushing persistent memory failed"),
+            PmemError::MediaTypeMismatch => write!(f, "media type doesn't match the one the file was created with"),
+            PmemError::AlreadyExists => write!(f, "persistent memory file already exists"),
+        }
+    }
+}
+
+impl std::error::Error for PmemError {}
+
+impl From<PmemError> for std::io::Error {
+    fn from(e: PmemError) -> std::io::Error
+    {
+        let kind = match e {
+            PmemError::InvalidFileName => std::io::ErrorKind::InvalidInput,
+            PmemError::AccessOutOfRange => std::io::ErrorKind::InvalidInput,
+            PmemError::MediaTypeMismatch => std::io::ErrorKind::InvalidInput,
+            PmemError::NotPm => std::io::ErrorKind::Unsupported,
+            PmemError::AlreadyExists => std::io::ErrorKind::AlreadyExists,
+            PmemError::CannotOpenPmFile | PmemError::PmdkError | PmemError::FlushFailed =>
+                std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
+// A `FlushPolicy` says how persistent-memory implementations retry a
+// flush that fails with an error the OS reports as transient, e.g.,
+// because another process briefly has the file locked. Up to
+// `max_retries` retries are made, sleeping `backoff` before the first
+// and doubling the sleep before each subsequent one. If every attempt
+// fails, the flush fails with `PmemError::FlushFailed`. Errors that
+// aren't transient are never retried. The default policy doesn't
+// retry at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FlushPolicy {
+    pub max_retries: u32,
+    pub backoff: std::time::Duration,
+}
+
+impl FlushPolicy {
+    pub fn new(max_retries: u32, backoff: std::time::Duration) -> Self
+    {
+        Self { max_retries, backoff }
+    }
+
+    // This function calls `try_flush` until it succeeds, it fails
+    // with an error for which `is_transient` is false, or the retries
+    // run out. `try_flush` returns the OS error code on failure.
+    pub fn run<F, T>(&self, mut try_flush: F, is_transient: T) -> Result<(), PmemError>
+        where
+            F: FnMut() -> Result<(), u32>,
+            T: Fn(u32) -> bool,
+    {
+        let mut backoff = self.backoff;
+        let mut retries = 0;
+        loop {
+            match try_flush() {
+                Ok(()) => return Ok(()),
+                Err(code) if is_transient(code) && retries < self.max_retries => {
+                    eprintln!("Flush failed with transient error {}; retrying in {:?}", code, backoff);
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    retries += 1;
+                },
+                Err(code) => {
+                    eprintln!("Flush failed. err={}", code);
+                    return Err(PmemError::FlushFailed);
+                },
+            }
+        }
+    }
+}
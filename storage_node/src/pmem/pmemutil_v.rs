@@ -132,6 +132,28 @@ verus! {
         assert(regions_view.flush() =~= regions_view);
     }
 
+    // This lemma establishes that if no region other than `index`
+    // has outstanding writes, then flushing just region `index` is
+    // the same as flushing all of them.
+    pub proof fn lemma_flush_region_is_flush_if_no_other_outstanding_writes(
+        regions_view: PersistentMemoryRegionsView,
+        index: int,
+    )
+        requires
+            forall |i: int| 0 <= i < regions_view.len() && i != index ==>
+                #[trigger] regions_view[i].no_outstanding_writes(),
+        ensures
+            regions_view.flush_region(index) == regions_view.flush(),
+    {
+        assert forall |i| 0 <= i < regions_view.len() implies
+               #[trigger] regions_view.flush_region(index).regions[i] == regions_view.flush().regions[i] by {
+            if i != index {
+                lemma_if_no_outstanding_writes_to_region_then_flush_is_idempotent(regions_view.regions[i]);
+            }
+        }
+        assert(regions_view.flush_region(index) =~= regions_view.flush());
+    }
+
     // This is an auto lemma for lemma_if_no_outstanding_writes_then_flush_is_idempotent.
     pub proof fn lemma_auto_if_no_outstanding_writes_then_flush_is_idempotent()
         ensures
@@ -142,6 +164,96 @@ verus! {
         };
     }
 
+    // This lemma establishes that viewing a subrange of a persistent
+    // memory region as its own region preserves committed bytes and
+    // outstanding writes. That is, the subregion's committed contents
+    // are the corresponding subrange of the parent's committed
+    // contents, and the subregion has no outstanding writes if the
+    // parent has none in that range.
+    pub proof fn lemma_subrange_of_region_view_matches_parent(
+        region_view: PersistentMemoryRegionView,
+        start: int,
+        end: int,
+    )
+        requires
+            0 <= start <= end <= region_view.len(),
+        ensures
+            region_view.subrange(start, end).len() == end - start,
+            region_view.subrange(start, end).committed() == region_view.committed().subrange(start, end),
+            region_view.no_outstanding_writes_in_range(start, end) ==>
+                region_view.subrange(start, end).no_outstanding_writes(),
+    {
+        assert(region_view.subrange(start, end).committed() =~= region_view.committed().subrange(start, end));
+    }
+
+    // This lemma establishes that, on memory impervious to
+    // corruption, bytes satisfying `PersistentMemoryRegion::read`'s
+    // postcondition are exactly the committed bytes read. The
+    // postcondition is restated in the `requires` so that callers can
+    // pass it along directly.
+    pub proof fn lemma_impervious_reads_are_faithful(
+        region_view: PersistentMemoryRegionView,
+        addr: int,
+        len: int,
+        bytes: Seq<u8>,
+        impervious_to_corruption: bool,
+    )
+        requires
+            0 <= addr,
+            0 <= len,
+            addr + len <= region_view.len(),
+            impervious_to_corruption,
+            if impervious_to_corruption {
+                bytes == region_view.committed().subrange(addr, addr + len)
+            }
+            else {
+                maybe_corrupted(bytes, region_view.committed().subrange(addr, addr + len),
+                                Seq::<int>::new(len as nat, |i: int| i + addr))
+            },
+        ensures
+            bytes == region_view.committed().subrange(addr, addr + len),
+            bytes.len() == len,
+    {
+    }
+
+    // This lemma establishes that two reads of the same range of
+    // memory impervious to corruption, in the same committed state,
+    // return equal bytes. This doesn't hold on memory that may be
+    // corrupted, since the two reads may be corrupted differently.
+    pub proof fn lemma_impervious_reads_of_same_range_are_equal(
+        region_view: PersistentMemoryRegionView,
+        addr: int,
+        len: int,
+        bytes1: Seq<u8>,
+        bytes2: Seq<u8>,
+        impervious_to_corruption: bool,
+    )
+        requires
+            0 <= addr,
+            0 <= len,
+            addr + len <= region_view.len(),
+            impervious_to_corruption,
+            if impervious_to_corruption {
+                bytes1 == region_view.committed().subrange(addr, addr + len)
+            }
+            else {
+                maybe_corrupted(bytes1, region_view.committed().subrange(addr, addr + len),
+                                Seq::<int>::new(len as nat, |i: int| i + addr))
+            },
+            if impervious_to_corruption {
+                bytes2 == region_view.committed().subrange(addr, addr + len)
+            }
+            else {
+                maybe_corrupted(bytes2, region_view.committed().subrange(addr, addr + len),
+                                Seq::<int>::new(len as nat, |i: int| i + addr))
+            },
+        ensures
+            bytes1 == bytes2,
+    {
+        lemma_impervious_reads_are_faithful(region_view, addr, len, bytes1, impervious_to_corruption);
+        lemma_impervious_reads_are_faithful(region_view, addr, len, bytes2, impervious_to_corruption);
+    }
+
     // This executable function returns a vector containing the sizes
     // of the regions in the given collection of persistent memory
     // regions.
@@ -165,6 +277,203 @@ verus! {
         result
     }
 
+    // This executable function writes `value` at `addr` in
+    // `pm_region`, immediately followed by its CRC, and returns the
+    // address just past the CRC. Laying out several CRC-protected
+    // records back to back is then a matter of passing each call's
+    // result to the next, rather than adding up lengths by hand.
+    pub fn append_serialized<PMRegion, S>(pm_region: &mut PMRegion, addr: u64, value: &S) -> (next_addr: u64)
+        where
+            PMRegion: PersistentMemoryRegion,
+            S: Serializable + Sized,
+        requires
+            old(pm_region).inv(),
+            addr + S::spec_serialized_len() + CRC_SIZE <= old(pm_region)@.len(),
+            old(pm_region)@.no_outstanding_writes_in_range(addr as int, addr + S::spec_serialized_len() + CRC_SIZE),
+        ensures
+            pm_region.inv(),
+            pm_region.constants() == old(pm_region).constants(),
+            next_addr == addr + S::spec_serialized_len() + CRC_SIZE,
+            pm_region@ == old(pm_region)@.write(addr as int, value.spec_serialize())
+                                         .write(addr + S::spec_serialized_len(), value.spec_crc().spec_serialize()),
+    {
+        proof {
+            u64::lemma_auto_serialized_len();
+        }
+        let crc = calculate_crc(value);
+        let crc_addr = addr + S::serialized_len();
+        pm_region.serialize_and_write(addr, value);
+        assert(pm_region@.no_outstanding_writes_in_range(crc_addr as int, crc_addr + CRC_SIZE));
+        pm_region.serialize_and_write(crc_addr, &crc);
+        crc_addr + CRC_SIZE
+    }
+
+    // This executable function reads `num_bytes` bytes at `addr` in
+    // `pm_region`, like `PersistentMemoryRegion::read`, and also
+    // returns the ghost sequence of addresses they were read from.
+    // That's the sequence `maybe_corrupted` relates the bytes to, so
+    // a caller can pass it straight to `axiom_bytes_uncorrupted` to
+    // do its own corruption reasoning rather than using `check_crc`.
+    pub fn read_with_addrs<PMRegion: PersistentMemoryRegion>(
+        pm_region: &PMRegion,
+        addr: u64,
+        num_bytes: u64,
+    ) -> (result: (Vec<u8>, Ghost<Seq<int>>))
+        requires
+            pm_region.inv(),
+            addr + num_bytes <= pm_region@.len(),
+            pm_region@.no_outstanding_writes_in_range(addr as int, addr + num_bytes),
+        ensures
+            ({
+                let (bytes, addrs) = result;
+                let true_bytes = pm_region@.committed().subrange(addr as int, addr + num_bytes);
+                &&& addrs@ == Seq::<int>::new(num_bytes as nat, |i: int| i + addr)
+                &&& if pm_region.constants().impervious_to_corruption {
+                        bytes@ == true_bytes
+                    }
+                    else {
+                        maybe_corrupted(bytes@, true_bytes, addrs@)
+                    }
+            })
+    {
+        let bytes = pm_region.read(addr, num_bytes);
+        (bytes, Ghost(Seq::<int>::new(num_bytes as nat, |i: int| i + addr)))
+    }
+
+    // This executable function is like `read_with_addrs`, but reads
+    // from region `index` of a collection of regions. The addresses
+    // are relative to the start of that region.
+    pub fn read_region_with_addrs<PMRegions: PersistentMemoryRegions>(
+        pm_regions: &PMRegions,
+        index: usize,
+        addr: u64,
+        num_bytes: u64,
+    ) -> (result: (Vec<u8>, Ghost<Seq<int>>))
+        requires
+            pm_regions.inv(),
+            index < pm_regions@.len(),
+            addr + num_bytes <= pm_regions@[index as int].len(),
+            pm_regions@.no_outstanding_writes_in_range(index as int, addr as int, addr + num_bytes),
+        ensures
+            ({
+                let (bytes, addrs) = result;
+                let true_bytes = pm_regions@[index as int].committed().subrange(addr as int, addr + num_bytes);
+                &&& addrs@ == Seq::<int>::new(num_bytes as nat, |i: int| i + addr)
+                &&& if pm_regions.constants().impervious_to_corruption {
+                        bytes@ == true_bytes
+                    }
+                    else {
+                        maybe_corrupted(bytes@, true_bytes, addrs@)
+                    }
+            })
+    {
+        let bytes = pm_regions.read(index, addr, num_bytes);
+        (bytes, Ghost(Seq::<int>::new(num_bytes as nat, |i: int| i + addr)))
+    }
+
+    // This executable function is like `PersistentMemoryRegion::read`,
+    // but it checks at run time that the range to read lies within
+    // the region, rather than requiring the caller to prove it. It's
+    // meant for reading offsets that come from outside the verified
+    // code, e.g., from a user of a tool. If the range doesn't fit, it
+    // returns `Err(PmemError::AccessOutOfRange)` without reading.
+    pub fn checked_read<PMRegion: PersistentMemoryRegion>(
+        pm_region: &PMRegion,
+        addr: u64,
+        num_bytes: u64,
+    ) -> (result: Result<Vec<u8>, PmemError>)
+        requires
+            pm_region.inv(),
+            pm_region@.no_outstanding_writes(),
+        ensures
+            match result {
+                Ok(bytes) => {
+                    let true_bytes = pm_region@.committed().subrange(addr as int, addr + num_bytes);
+                    let addrs = Seq::<int>::new(num_bytes as nat, |i: int| i + addr);
+                    &&& addr + num_bytes <= pm_region@.len()
+                    &&& if pm_region.constants().impervious_to_corruption {
+                            bytes@ == true_bytes
+                        }
+                        else {
+                            maybe_corrupted(bytes@, true_bytes, addrs)
+                        }
+                },
+                Err(PmemError::AccessOutOfRange) => addr + num_bytes > pm_region@.len(),
+                Err(_) => false,
+            }
+    {
+        // Compare without computing `addr + num_bytes`, which could
+        // overflow.
+        let region_size = pm_region.get_region_size();
+        if num_bytes > region_size || addr > region_size - num_bytes {
+            return Err(PmemError::AccessOutOfRange);
+        }
+        Ok(pm_region.read(addr, num_bytes))
+    }
+
+    // This executable function reads `count` records of type `S`
+    // stored back to back starting at `addr` in `pm_region`, e.g., an
+    // array of list entries. Record #`i` is read from
+    // `addr + i * S::serialized_len()`, with the same guarantee
+    // `read_and_deserialize` gives for a single record.
+    pub fn read_array<PMRegion, S>(pm_region: &PMRegion, addr: u64, count: u64) -> (result: Vec<S>)
+        where
+            PMRegion: PersistentMemoryRegion,
+            S: Serializable + Sized + Copy,
+        requires
+            pm_region.inv(),
+            addr + count * S::spec_serialized_len() <= pm_region@.len(),
+            addr + count * S::spec_serialized_len() <= u64::MAX,
+            pm_region@.no_outstanding_writes_in_range(addr as int, addr + count * S::spec_serialized_len()),
+        ensures
+            result@.len() == count,
+            forall |i: int| 0 <= i < count ==> {
+                let record_addr = addr + i * S::spec_serialized_len();
+                let true_val = S::spec_deserialize(
+                    pm_region@.committed().subrange(record_addr, record_addr + S::spec_serialized_len()));
+                if pm_region.constants().impervious_to_corruption {
+                    #[trigger] result@[i] == true_val
+                } else {
+                    maybe_corrupted_serialized(result@[i], true_val, record_addr)
+                }
+            },
+    {
+        let len = S::serialized_len();
+        let mut result: Vec<S> = Vec::new();
+        let mut record_addr = addr;
+        for i in 0..count
+            invariant
+                pm_region.inv(),
+                len == S::spec_serialized_len(),
+                result@.len() == i,
+                record_addr == addr + i * len,
+                addr + count * len <= pm_region@.len(),
+                addr + count * len <= u64::MAX,
+                pm_region@.no_outstanding_writes_in_range(addr as int, addr + count * len),
+                forall |j: int| 0 <= j < i ==> {
+                    let record_addr = addr + j * len;
+                    let true_val = S::spec_deserialize(
+                        pm_region@.committed().subrange(record_addr, record_addr + len));
+                    if pm_region.constants().impervious_to_corruption {
+                        #[trigger] result@[j] == true_val
+                    } else {
+                        maybe_corrupted_serialized(result@[j], true_val, record_addr)
+                    }
+                },
+        {
+            assert(addr + (i + 1) * len <= addr + count * len) by (nonlinear_arith)
+                requires
+                    i < count;
+            assert(record_addr + len == addr + (i + 1) * len) by (nonlinear_arith)
+                requires
+                    record_addr == addr + i * len;
+            let record = pm_region.read_and_deserialize::<S>(record_addr);
+            result.push(*record);
+            record_addr = record_addr + len;
+        }
+        result
+    }
+
     // This executable function checks whether the given CRC read from
     // persistent memory is the actual CRC of the given bytes read
     // from persistent memory. It returns a boolean indicating whether
@@ -192,6 +501,9 @@ verus! {
     // `impervious_to_corruption` (ghost) -- whether that memory is
     // impervious to corruption
     //
+    // `known_impervious` -- whether the caller knows that memory is
+    // impervious to corruption, in which case the CRC isn't computed
+    //
     // `data_addr` (ghost) -- where the data were read from in memory
     //
     // `data_length` (ghost) -- the length of the data read from memory
@@ -202,6 +514,7 @@ verus! {
         crc_c: &[u8],
         Ghost(mem): Ghost<Seq<u8>>,
         Ghost(impervious_to_corruption): Ghost<bool>,
+        known_impervious: bool,
         Ghost(data_addr): Ghost<u64>,
         Ghost(data_length): Ghost<u64>,
         Ghost(crc_addr): Ghost<u64>,
@@ -210,6 +523,7 @@ verus! {
             data_addr + data_length <= mem.len(),
             crc_addr + CRC_SIZE <= mem.len(),
             crc_c@.len() == CRC_SIZE,
+            known_impervious ==> impervious_to_corruption,
             ({
                 let true_data = mem.subrange(data_addr as int, data_addr + data_length);
                 let true_crc = mem.subrange(crc_addr as int, crc_addr + CRC_SIZE);
@@ -236,6 +550,13 @@ verus! {
                 }
             })
     {
+        // If the memory is impervious to corruption, the data and CRC
+        // read are the ones last written, so there's no need to
+        // compute the CRC.
+        if known_impervious {
+            return true;
+        }
+
         // Compute the CRC of the possibly-corrupted data.
         let computed_crc = bytes_crc(data_c);
 
@@ -286,6 +607,7 @@ verus! {
         crc_c: &u64,
         Ghost(mem): Ghost<Seq<u8>>,
         Ghost(impervious_to_corruption): Ghost<bool>,
+        known_impervious: bool,
         Ghost(data_addr): Ghost<u64>,
         Ghost(data_length): Ghost<u64>,
         Ghost(crc_addr): Ghost<u64>,
@@ -297,6 +619,7 @@ verus! {
             crc_addr + CRC_SIZE <= mem.len(),
             data_length == S::spec_serialized_len(),
             crc_addr < crc_addr + CRC_SIZE <= data_addr || crc_addr >= data_addr + S::spec_serialized_len(),
+            known_impervious ==> impervious_to_corruption,
             ({
                 let true_data = S::spec_deserialize(mem.subrange(data_addr as int, data_addr + data_length));
                 let true_crc = u64::spec_deserialize(mem.subrange(crc_addr as int, crc_addr + CRC_SIZE));
@@ -323,6 +646,13 @@ verus! {
                     }
             })
     {
+        // If the memory is impervious to corruption, the data and CRC
+        // read are the ones last written, so there's no need to
+        // compute the CRC.
+        if known_impervious {
+            return true;
+        }
+
         // Compute the CRC of the possibly-corrupted data.
         let computed_crc = calculate_crc(data_c);
 
@@ -584,4 +914,37 @@ verus! {
                bytes);
     }
 
+    // This lemma establishes that writing the first `mid` bytes of
+    // `bytes[0..end)` and then the rest, right after them, has the
+    // same effect as writing all of `bytes[0..end)` at once. It's
+    // what lets a long write be split into shorter ones.
+    pub proof fn lemma_adjacent_writes_compose(
+        regions_view: PersistentMemoryRegionsView,
+        index: int,
+        addr: int,
+        bytes: Seq<u8>,
+        mid: int,
+        end: int,
+    )
+        requires
+            0 <= index < regions_view.len(),
+            0 <= addr,
+            0 <= mid <= end <= bytes.len(),
+        ensures
+            regions_view.write(index, addr, bytes.subrange(0, mid)).write(index, addr + mid, bytes.subrange(mid, end))
+                == regions_view.write(index, addr, bytes.subrange(0, end)),
+    {
+        let split = regions_view.write(index, addr, bytes.subrange(0, mid))
+                                .write(index, addr + mid, bytes.subrange(mid, end));
+        let whole = regions_view.write(index, addr, bytes.subrange(0, end));
+        assert forall |i| 0 <= i < regions_view.len() implies
+               #[trigger] split.regions[i] == whole.regions[i] by {
+            if i == index {
+                assert(split.regions[i].state =~= whole.regions[i].state);
+                assert(split.regions[i] =~= whole.regions[i]);
+            }
+        }
+        assert(split =~= whole);
+    }
+
 }
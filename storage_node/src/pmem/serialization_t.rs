@@ -85,8 +85,10 @@ verus! {
         ;
 
         // TODO: this should really be a constant, but verus doesn't
-        // support associated constants right now
-        spec fn spec_serialized_len() -> u64;
+        // support associated constants right now. It's a `nat` so
+        // that the lengths of composite types, like pairs, are exact
+        // sums that can't overflow.
+        spec fn spec_serialized_len() -> nat;
 
         spec fn spec_crc(self) -> u64;
 
@@ -120,7 +122,7 @@ verus! {
             assert(Self::spec_serialized_len() == 8);
         }
 
-        open spec fn spec_serialized_len() -> u64
+        open spec fn spec_serialized_len() -> nat
         {
             8
         }
@@ -133,6 +135,372 @@ verus! {
         }
     }
 
+    // `vstd::bytes` has no little-endian conversions for `u16`, so
+    // we define them here, following its `u32` and `u64` ones.
+    pub open spec fn spec_u16_to_le_bytes(x: u16) -> Seq<u8>
+    {
+        seq![(x & 0xff) as u8, ((x >> 8) & 0xff) as u8]
+    }
+
+    pub open spec fn spec_u16_from_le_bytes(s: Seq<u8>) -> u16
+        recommends
+            s.len() == 2
+    {
+        (s[0] as u16) | ((s[1] as u16) << 8)
+    }
+
+    pub proof fn lemma_auto_spec_u16_to_from_le_bytes()
+        ensures
+            forall |x: u16| #![trigger spec_u16_to_le_bytes(x)]
+            {
+                &&& spec_u16_to_le_bytes(x).len() == 2
+                &&& spec_u16_from_le_bytes(spec_u16_to_le_bytes(x)) == x
+            },
+            forall |s: Seq<u8>| s.len() == 2 ==> #[trigger] spec_u16_to_le_bytes(spec_u16_from_le_bytes(s)) == s,
+    {
+        assert forall |x: u16| #![trigger spec_u16_to_le_bytes(x)]
+            spec_u16_from_le_bytes(spec_u16_to_le_bytes(x)) == x by {
+            let b0 = (x & 0xff) as u8;
+            let b1 = ((x >> 8) & 0xff) as u8;
+            assert(((b0 as u16) | ((b1 as u16) << 8)) == x) by (bit_vector)
+                requires
+                    b0 == (x & 0xff) as u8,
+                    b1 == ((x >> 8) & 0xff) as u8;
+        }
+        assert forall |s: Seq<u8>| s.len() == 2 implies
+            #[trigger] spec_u16_to_le_bytes(spec_u16_from_le_bytes(s)) == s by {
+            let b0 = s[0];
+            let b1 = s[1];
+            let x = spec_u16_from_le_bytes(s);
+            assert(((x & 0xff) as u8) == b0 && (((x >> 8) & 0xff) as u8) == b1) by (bit_vector)
+                requires
+                    x == (b0 as u16) | ((b1 as u16) << 8);
+            assert(spec_u16_to_le_bytes(x) =~= s);
+        }
+    }
+
+    impl Serializable for u16 {
+        closed spec fn spec_serialize(self) -> Seq<u8>
+        {
+            spec_u16_to_le_bytes(self)
+        }
+
+        closed spec fn spec_deserialize(bytes: Seq<u8>) -> Self
+        {
+            spec_u16_from_le_bytes(bytes)
+        }
+
+        proof fn lemma_auto_serialize_deserialize()
+        {
+            lemma_auto_spec_u16_to_from_le_bytes();
+            assert(forall |s: Self| #![auto] s == Self::spec_deserialize(s.spec_serialize()));
+        }
+
+        proof fn lemma_auto_serialized_len()
+        {
+            lemma_auto_spec_u16_to_from_le_bytes();
+            assert(forall |s: Self| #![auto] s.spec_serialize().len() == 2);
+            assert(Self::spec_serialized_len() == 2);
+        }
+
+        open spec fn spec_serialized_len() -> nat
+        {
+            2
+        }
+
+        closed spec fn spec_crc(self) -> u64;
+
+        fn serialized_len() -> u64
+        {
+            2
+        }
+    }
+
+    // A pair is serialized as its first element's serialization
+    // followed by its second element's. Since `read_and_deserialize`
+    // and `serialize_and_write` reinterpret raw bytes as the in-memory
+    // representation, this is only correct if `(A, B)` is laid out
+    // with `A` at offset 0 and `B` immediately after it, with no
+    // padding. Rust doesn't guarantee this for tuples, and it doesn't
+    // hold whenever `B`'s alignment exceeds `A`'s size (e.g., for
+    // `(u64, u128)` on x86-64). So `serialized_len` checks the layout
+    // at runtime and panics if it doesn't match.
+    impl<A: Serializable, B: Serializable> Serializable for (A, B) {
+        open spec fn spec_serialize(self) -> Seq<u8>
+        {
+            self.0.spec_serialize() + self.1.spec_serialize()
+        }
+
+        open spec fn spec_deserialize(bytes: Seq<u8>) -> Self
+        {
+            let a_len = A::spec_serialized_len() as int;
+            let b_len = B::spec_serialized_len() as int;
+            (
+                A::spec_deserialize(bytes.subrange(0, a_len)),
+                B::spec_deserialize(bytes.subrange(a_len, a_len + b_len)),
+            )
+        }
+
+        proof fn lemma_auto_serialize_deserialize()
+        {
+            A::lemma_auto_serialize_deserialize();
+            B::lemma_auto_serialize_deserialize();
+            A::lemma_auto_serialized_len();
+            B::lemma_auto_serialized_len();
+            assert forall |s: Self| #![auto] s == Self::spec_deserialize(s.spec_serialize()) by {
+                let a_bytes = s.0.spec_serialize();
+                let b_bytes = s.1.spec_serialize();
+                assert((a_bytes + b_bytes).subrange(0, a_bytes.len() as int) =~= a_bytes);
+                assert((a_bytes + b_bytes).subrange(a_bytes.len() as int, a_bytes.len() + b_bytes.len())
+                       =~= b_bytes);
+            }
+        }
+
+        proof fn lemma_auto_serialized_len()
+        {
+            A::lemma_auto_serialized_len();
+            B::lemma_auto_serialized_len();
+        }
+
+        open spec fn spec_serialized_len() -> nat
+        {
+            A::spec_serialized_len() + B::spec_serialized_len()
+        }
+
+        closed spec fn spec_crc(self) -> u64;
+
+        #[verifier::external_body]
+        fn serialized_len() -> u64
+        {
+            let a_len = A::serialized_len();
+            let b_len = B::serialized_len();
+
+            // Check that `(A, B)` is laid out as `A`'s bytes followed
+            // immediately by `B`'s bytes. We compute field offsets
+            // from an uninitialized value; `addr_of!` doesn't read
+            // the fields, so this is safe.
+            let pair = core::mem::MaybeUninit::<(A, B)>::uninit();
+            let base = pair.as_ptr() as usize;
+            // SAFETY: `addr_of!` only computes the address of the
+            // fields; it doesn't create references or read them.
+            let (first, second) = unsafe {
+                (core::ptr::addr_of!((*pair.as_ptr()).0) as usize,
+                 core::ptr::addr_of!((*pair.as_ptr()).1) as usize)
+            };
+            assert!(
+                first == base
+                    && second - base == a_len as usize
+                    && core::mem::size_of::<(A, B)>() == (a_len + b_len) as usize,
+                "pair type {} isn't laid out as its two elements back to back, so it can't be serialized",
+                core::any::type_name::<(A, B)>()
+            );
+            a_len + b_len
+        }
+    }
+
+    // This trait is implemented by single-field newtypes, like
+    // `struct Timestamp(u64)`, that should be serialized exactly like
+    // the type they wrap. Every such type gets a `Serializable`
+    // implementation from the blanket implementation below, whose
+    // round-trip lemma follows from the wrapped type's. Since
+    // serialization reinterprets the in-memory representation, the
+    // newtype must be `#[repr(transparent)]`; `serialized_len`
+    // checks at runtime that its size matches the wrapped type's.
+    pub trait TransparentSerializable : Sized {
+        type Inner: Serializable;
+
+        spec fn spec_inner(self) -> Self::Inner;
+
+        spec fn spec_from_inner(inner: Self::Inner) -> Self;
+
+        proof fn lemma_auto_from_inner_inverts_inner()
+            ensures
+                forall |s: Self| #![auto] Self::spec_from_inner(s.spec_inner()) == s
+        ;
+    }
+
+    impl<T: TransparentSerializable> Serializable for T {
+        open spec fn spec_serialize(self) -> Seq<u8>
+        {
+            self.spec_inner().spec_serialize()
+        }
+
+        open spec fn spec_deserialize(bytes: Seq<u8>) -> Self
+        {
+            T::spec_from_inner(<T as TransparentSerializable>::Inner::spec_deserialize(bytes))
+        }
+
+        proof fn lemma_auto_serialize_deserialize()
+        {
+            <T as TransparentSerializable>::Inner::lemma_auto_serialize_deserialize();
+            T::lemma_auto_from_inner_inverts_inner();
+            assert forall |s: Self| #![auto] s == Self::spec_deserialize(s.spec_serialize()) by {
+                assert(s.spec_inner() ==
+                       <T as TransparentSerializable>::Inner::spec_deserialize(s.spec_inner().spec_serialize()));
+            }
+        }
+
+        proof fn lemma_auto_serialized_len()
+        {
+            <T as TransparentSerializable>::Inner::lemma_auto_serialized_len();
+        }
+
+        open spec fn spec_serialized_len() -> nat
+        {
+            <T as TransparentSerializable>::Inner::spec_serialized_len()
+        }
+
+        closed spec fn spec_crc(self) -> u64;
+
+        #[verifier::external_body]
+        fn serialized_len() -> u64
+        {
+            let len = <T as TransparentSerializable>::Inner::serialized_len();
+            assert!(
+                core::mem::size_of::<T>() == len as usize,
+                "newtype {} isn't the same size as the type it wraps, so it can't be serialized transparently",
+                core::any::type_name::<T>()
+            );
+            len
+        }
+    }
+
+    // A `Tagged<P>` is the persistent-memory form of a
+    // `SerializableEnum` whose payload type is `P`: a `u32`
+    // discriminant, four bytes of padding so that the payload is
+    // 8-byte aligned, and then the payload. Every bit pattern is a
+    // valid `Tagged<P>` if every bit pattern is a valid `P`, so it
+    // can be read back from arbitrary persistent-memory contents.
+    #[repr(C)]
+    pub struct Tagged<P> {
+        pub discriminant: u32,
+        pub _padding: u32,
+        pub payload: P,
+    }
+
+    impl<P: Serializable> Serializable for Tagged<P> {
+        open spec fn spec_serialize(self) -> Seq<u8>
+        {
+            spec_u32_to_le_bytes(self.discriminant) + spec_u32_to_le_bytes(self._padding)
+                + self.payload.spec_serialize()
+        }
+
+        open spec fn spec_deserialize(bytes: Seq<u8>) -> Self
+        {
+            Self {
+                discriminant: spec_u32_from_le_bytes(bytes.subrange(0, 4)),
+                _padding: spec_u32_from_le_bytes(bytes.subrange(4, 8)),
+                payload: P::spec_deserialize(bytes.subrange(8, 8 + P::spec_serialized_len())),
+            }
+        }
+
+        proof fn lemma_auto_serialize_deserialize()
+        {
+            lemma_auto_spec_u32_to_from_le_bytes();
+            P::lemma_auto_serialize_deserialize();
+            P::lemma_auto_serialized_len();
+            assert forall |s: Self| #![auto] s == Self::spec_deserialize(s.spec_serialize()) by {
+                let bytes = s.spec_serialize();
+                let payload_bytes = s.payload.spec_serialize();
+                assert(bytes.subrange(0, 4) =~= spec_u32_to_le_bytes(s.discriminant));
+                assert(bytes.subrange(4, 8) =~= spec_u32_to_le_bytes(s._padding));
+                assert(bytes.subrange(8, 8 + payload_bytes.len()) =~= payload_bytes);
+            }
+        }
+
+        // The discriminant and the padding are four bytes each, so
+        // the payload's bytes start at offset 8.
+        proof fn lemma_auto_serialized_len()
+        {
+            lemma_auto_spec_u32_to_from_le_bytes();
+            P::lemma_auto_serialized_len();
+        }
+
+        open spec fn spec_serialized_len() -> nat
+        {
+            8 + P::spec_serialized_len()
+        }
+
+        closed spec fn spec_crc(self) -> u64;
+
+        #[verifier::external_body]
+        fn serialized_len() -> u64
+        {
+            let payload_len = P::serialized_len();
+
+            // Check that the payload immediately follows the
+            // discriminant and padding. It won't if `P` needs more
+            // than 8-byte alignment.
+            let tagged = core::mem::MaybeUninit::<Tagged<P>>::uninit();
+            let base = tagged.as_ptr() as usize;
+            // SAFETY: `addr_of!` only computes the address of the
+            // field; it doesn't create a reference or read it.
+            let payload = unsafe { core::ptr::addr_of!((*tagged.as_ptr()).payload) as usize };
+            assert!(
+                payload - base == 8 && core::mem::size_of::<Tagged<P>>() == 8 + payload_len as usize,
+                "tagged type {} isn't laid out as a discriminant, padding, and payload back to back, \
+                 so it can't be serialized",
+                core::any::type_name::<Tagged<P>>()
+            );
+            8 + payload_len
+        }
+    }
+
+    // This trait is implemented by enums that should be stored on
+    // persistent memory. An enum can't be `Serializable` itself,
+    // since reinterpreting arbitrary bytes as an enum is undefined
+    // behavior whenever they don't hold a valid discriminant. So it's
+    // stored as a `Tagged<Self::Payload>` instead, converting with
+    // `to_tagged` before writing and `from_tagged` after reading.
+    //
+    // `Payload` must be able to hold any variant's fields, e.g., it
+    // can be the largest variant's fields as a tuple. A variant with
+    // fewer fields leaves the rest of the payload zero.
+    //
+    // `spec_from_parts` must be total, so that recovery can make
+    // sense of any bytes it reads: it maps every discriminant that
+    // isn't any variant's to one chosen default variant.
+    pub trait SerializableEnum : Sized {
+        type Payload: Serializable;
+
+        spec fn spec_discriminant(self) -> u32;
+
+        spec fn spec_payload(self) -> Self::Payload;
+
+        spec fn spec_from_parts(discriminant: u32, payload: Self::Payload) -> Self;
+
+        proof fn lemma_auto_from_parts_inverts_parts()
+            ensures
+                forall |s: Self| #![auto] Self::spec_from_parts(s.spec_discriminant(), s.spec_payload()) == s
+        ;
+
+        fn to_tagged(&self) -> (result: Tagged<Self::Payload>)
+            ensures
+                result.discriminant == self.spec_discriminant(),
+                result._padding == 0,
+                result.payload == self.spec_payload(),
+        ;
+
+        fn from_tagged(tagged: &Tagged<Self::Payload>) -> (result: Self)
+            ensures
+                result == Self::spec_from_parts(tagged.discriminant, tagged.payload),
+        ;
+    }
+
+    // This lemma says that an enum value survives being converted to
+    // its tagged form, serialized, deserialized, and converted back.
+    pub proof fn lemma_serializable_enum_round_trip<E: SerializableEnum>(e: E)
+        ensures
+            ({
+                let tagged = Tagged { discriminant: e.spec_discriminant(), _padding: 0u32, payload: e.spec_payload() };
+                let read_back = Tagged::<E::Payload>::spec_deserialize(tagged.spec_serialize());
+                E::spec_from_parts(read_back.discriminant, read_back.payload) == e
+            }),
+    {
+        Tagged::<E::Payload>::lemma_auto_serialize_deserialize();
+        E::lemma_auto_from_parts_inverts_parts();
+    }
+
     #[verifier::external_body]
     pub fn calculate_crc<S>(val: &S) -> (out: u64)
         where
@@ -154,4 +522,178 @@ verus! {
         digest.write(bytes);
         digest.sum64()
     }
+
+    // This spec function says whether `a` and `b` have the same
+    // serialization. It lets callers compare a whole expected value,
+    // e.g., a template of the metadata they expect to find on
+    // persistent memory, in one step rather than field by field.
+    pub open spec fn serializes_same<S: Serializable>(a: S, b: S) -> bool
+    {
+        a.spec_serialize() == b.spec_serialize()
+    }
+
+    // This lemma says that two values serialize the same exactly when
+    // they're equal, since deserialization undoes serialization. So
+    // for a struct, `serializes_same` is equivalent to comparing every
+    // field, including any padding fields.
+    pub proof fn lemma_serializes_same_iff_equal<S: Serializable>(a: S, b: S)
+        ensures
+            serializes_same(a, b) <==> a == b
+    {
+        S::lemma_auto_serialize_deserialize();
+        if serializes_same(a, b) {
+            assert(a == S::spec_deserialize(a.spec_serialize()));
+            assert(b == S::spec_deserialize(b.spec_serialize()));
+        }
+    }
+
+    // This function compares the raw bytes of `a` and `b`, as laid out
+    // in memory, and so decides `serializes_same(*a, *b)` without
+    // comparing individual fields.
+    #[verifier::external_body]
+    pub fn serialized_bytes_equal<S>(a: &S, b: &S) -> (result: bool)
+        where
+            S: Serializable + Sized
+        ensures
+            result == serializes_same(*a, *b)
+    {
+        let num_bytes: usize = S::serialized_len().try_into().unwrap();
+        // SAFETY: Both pointers always point to `num_bytes`
+        // consecutive, initialized bytes because they were obtained
+        // by casting regular Rust object references to raw pointers.
+        let (a_bytes, b_bytes) = unsafe {
+            (std::slice::from_raw_parts(a as *const S as *const u8, num_bytes),
+             std::slice::from_raw_parts(b as *const S as *const u8, num_bytes))
+        };
+        a_bytes == b_bytes
+    }
+
+    // This function decides whether `bytes` is exactly the
+    // serialization of `val`, e.g., whether bytes just read from
+    // persistent memory already hold `val`, so that writing `val`
+    // there would change nothing. `serialized_bytes_equal` compares
+    // two values; this compares a value with raw bytes, so it works
+    // even for bytes that don't deserialize to a valid value.
+    #[verifier::external_body]
+    pub fn serialization_matches_bytes<S>(val: &S, bytes: &[u8]) -> (result: bool)
+        where
+            S: Serializable + Sized
+        ensures
+            result == (bytes@ == val.spec_serialize())
+    {
+        let num_bytes: usize = S::serialized_len().try_into().unwrap();
+        // SAFETY: `val` always points to `num_bytes` consecutive,
+        // initialized bytes because it's a regular Rust object
+        // reference.
+        let val_bytes = unsafe {
+            std::slice::from_raw_parts(val as *const S as *const u8, num_bytes)
+        };
+        val_bytes == bytes
+    }
+
+    // This function returns the serialization of `val` as a vector of
+    // bytes, e.g., for sending it somewhere other than persistent
+    // memory.
+    #[verifier::external_body]
+    pub fn serialize_to_vec<S>(val: &S) -> (bytes: Vec<u8>)
+        where
+            S: Serializable + Sized
+        ensures
+            bytes@ == val.spec_serialize()
+    {
+        let num_bytes: usize = S::serialized_len().try_into().unwrap();
+        let s_pointer = val as *const S;
+        let bytes_pointer = s_pointer as *const u8;
+        // SAFETY: `bytes_pointer` always points to `num_bytes` consecutive, initialized
+        // bytes because it was obtained by casting a regular Rust object reference
+        // to a raw pointer.
+        let bytes = unsafe {
+            std::slice::from_raw_parts(bytes_pointer, num_bytes)
+        };
+        bytes.to_vec()
+    }
+
+    // This function is the inverse of `serialize_to_vec`. Like
+    // `read_and_deserialize`, it assumes every sequence of
+    // `S::serialized_len()` bytes is a valid `S`.
+    #[verifier::external_body]
+    pub fn deserialize_from_slice<S>(bytes: &[u8]) -> (val: S)
+        where
+            S: Serializable + Sized
+        requires
+            bytes@.len() == S::spec_serialized_len(),
+        ensures
+            val == S::spec_deserialize(bytes@)
+    {
+        let num_bytes: usize = S::serialized_len().try_into().unwrap();
+        assert!(bytes.len() == num_bytes);
+        // SAFETY: `bytes` holds `size_of::<S>()` initialized bytes,
+        // and `read_unaligned` doesn't require them to be aligned.
+        unsafe {
+            std::ptr::read_unaligned(bytes.as_ptr() as *const S)
+        }
+    }
+
+    // This function writes the serialization of `val` into `buf` at
+    // `offset`, leaving the rest of `buf` alone. Serializing several
+    // values into one buffer this way lets the caller write a
+    // multi-field record to persistent memory with a single write.
+    #[verifier::external_body]
+    pub fn serialize_into<S>(val: &S, buf: &mut [u8], offset: usize)
+        where
+            S: Serializable + Sized
+        requires
+            offset + S::spec_serialized_len() <= old(buf)@.len(),
+        ensures
+            buf@ == old(buf)@.subrange(0, offset as int) + val.spec_serialize() +
+                    old(buf)@.subrange(offset + S::spec_serialized_len(), old(buf)@.len() as int),
+    {
+        let num_bytes: usize = S::serialized_len().try_into().unwrap();
+        // SAFETY: `val` always points to `num_bytes` consecutive,
+        // initialized bytes because it's a regular Rust object
+        // reference.
+        let bytes = unsafe {
+            std::slice::from_raw_parts(val as *const S as *const u8, num_bytes)
+        };
+        buf[offset..offset + num_bytes].copy_from_slice(bytes);
+    }
+
+    // This function is the inverse of `serialize_into`: it
+    // deserializes the `S` whose serialization starts at `offset` in
+    // `buf`. Like `read_and_deserialize`, it assumes every sequence of
+    // `S::serialized_len()` bytes is a valid `S`.
+    #[verifier::external_body]
+    pub fn deserialize_from<S>(buf: &[u8], offset: usize) -> (val: S)
+        where
+            S: Serializable + Sized
+        requires
+            offset + S::spec_serialized_len() <= buf@.len(),
+        ensures
+            val == S::spec_deserialize(buf@.subrange(offset as int, offset + S::spec_serialized_len())),
+    {
+        let num_bytes: usize = S::serialized_len().try_into().unwrap();
+        deserialize_from_slice(&buf[offset..offset + num_bytes])
+    }
+
+    // This function is called at the start of every
+    // `read_and_deserialize`, which reinterprets `S::serialized_len()`
+    // bytes of persistent memory as an `S`. That's only sound if `S`
+    // occupies exactly that many bytes in memory; a `#[repr(C)]`
+    // struct whose padding isn't accounted for in `serialized_len`
+    // would otherwise be silently misread. The check is only done in
+    // debug builds, so tests catch such layout bugs without slowing
+    // down release builds.
+    #[verifier::external_body]
+    pub fn debug_check_serialized_layout<S>()
+        where
+            S: Serializable + Sized
+    {
+        debug_assert!(
+            core::mem::size_of::<S>() == S::serialized_len() as usize,
+            "type {} occupies {} bytes in memory but serializes to {}, so it can't be read by reinterpreting bytes",
+            core::any::type_name::<S>(),
+            core::mem::size_of::<S>(),
+            S::serialized_len()
+        );
+    }
 }
@@ -6,22 +6,25 @@
 use builtin::*;
 use builtin_macros::*;
 use crate::pmem::pmemspec_t::{
-    PersistentMemoryByte, PersistentMemoryConstants, PersistentMemoryRegion,
+    const_persistence_chunk_size, FlushPolicy, PersistentMemoryByte, PersistentMemoryConstants, PersistentMemoryRegion,
     PersistentMemoryRegionView, PersistentMemoryRegions, PersistentMemoryRegionsView,
     PmemError,
 };
 use crate::pmem::serialization_t::*;
 use deps_hack::rand::Rng;
 use deps_hack::winapi::ctypes::c_void;
-use deps_hack::winapi::shared::winerror::SUCCEEDED;
+use deps_hack::winapi::shared::winerror::{ERROR_BUSY, ERROR_FILE_EXISTS, ERROR_LOCK_VIOLATION};
 use deps_hack::winapi::um::errhandlingapi::GetLastError;
-use deps_hack::winapi::um::fileapi::{CreateFileA, CREATE_NEW, DeleteFileA, OPEN_EXISTING};
+use deps_hack::winapi::um::fileapi::{
+    CreateFileA, CREATE_NEW, DeleteFileA, FlushFileBuffers, GetFileSizeEx, OPEN_EXISTING, SetEndOfFile,
+    SetFilePointerEx,
+};
 use deps_hack::winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
 use deps_hack::winapi::um::memoryapi::{FILE_MAP_ALL_ACCESS, FlushViewOfFile, MapViewOfFile, UnmapViewOfFile};
-use deps_hack::winapi::um::winbase::CreateFileMappingA;
+use deps_hack::winapi::um::winbase::{CreateFileMappingA, FILE_BEGIN};
 use deps_hack::winapi::um::winnt::{
     FILE_ATTRIBUTE_NORMAL, FILE_ATTRIBUTE_TEMPORARY, FILE_SHARE_DELETE, FILE_SHARE_READ,
-    FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE, HANDLE, PAGE_READWRITE, ULARGE_INTEGER,
+    FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE, HANDLE, LARGE_INTEGER, PAGE_READWRITE, ULARGE_INTEGER,
 };
 use std::cell::RefCell;
 use std::convert::*;
@@ -102,6 +105,12 @@ impl MemoryMappedFile {
                     FileOpenBehavior::OpenExisting =>
                         eprintln!("Could not open existing file {}. err={}", path, error_code),
                 };
+                // `CREATE_NEW` fails with `ERROR_FILE_EXISTS` if the
+                // file exists. Report that separately, so the caller
+                // can restore the file instead.
+                if let (FileOpenBehavior::CreateNew, ERROR_FILE_EXISTS) = (open_behavior, error_code) {
+                    return Err(PmemError::AlreadyExists);
+                }
                 return Err(PmemError::CannotOpenPmFile);
             }
 
@@ -155,6 +164,105 @@ impl MemoryMappedFile {
             Ok(mmf)
         }
     }
+
+    // The function `size` returns the number of bytes mapped, which
+    // is the size of the file.
+    pub fn size(&self) -> usize
+    {
+        self.size
+    }
+
+    // The function `grow` extends the file to `new_size` bytes and
+    // maps it again at that size. The new bytes are zero. It fails
+    // with `PmemError::AccessOutOfRange` if `new_size` is smaller than
+    // the current size.
+    //
+    // The new view is generally at a different address, and the old
+    // one is unmapped. So every `MemoryMappedFileSection` carved out
+    // of this file before the call holds a dangling pointer and must
+    // never be used again. The caller must drop all of them before
+    // calling this, and carve new sections afterward. To make that
+    // possible, this resets the count of sectioned bytes, so the next
+    // section starts at offset 0. If this fails, the old view and
+    // sections remain valid.
+    pub fn grow(&mut self, new_size: usize) -> Result<(), PmemError>
+    {
+        if new_size < self.size {
+            eprintln!("Can't grow a {}-byte file to {} bytes", self.size, new_size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+        let new_size_as_u64: u64 =
+            match new_size.try_into() {
+                Ok(sz) => sz,
+                Err(_) => {
+                    eprintln!("Could not convert size {} into u64", new_size);
+                    return Err(PmemError::AccessOutOfRange);
+                }
+            };
+
+        unsafe {
+            let mut li: ULARGE_INTEGER = std::mem::zeroed();
+            *li.QuadPart_mut() = new_size_as_u64;
+
+            // A file mapping object bigger than its file extends the
+            // file, so there's no need to call `SetEndOfFile`, which
+            // would fail anyway while the old view is mapped. We
+            // create the new mapping and view before closing the old
+            // ones so a failure leaves the old view intact.
+            let h_map_file = CreateFileMappingA(
+                self.h_file,
+                core::ptr::null_mut(),
+                PAGE_READWRITE,
+                li.u().HighPart,
+                li.u().LowPart,
+                core::ptr::null_mut()
+            );
+
+            if h_map_file.is_null() {
+                eprintln!("Could not create file mapping object to grow file, got error {}", GetLastError());
+                return Err(PmemError::CannotOpenPmFile);
+            }
+
+            let h_map_addr = MapViewOfFile(
+                h_map_file,
+                FILE_MAP_ALL_ACCESS,
+                0,
+                0,
+                new_size,
+            );
+
+            if h_map_addr.is_null() {
+                eprintln!("Could not map view of grown file, got error {}", GetLastError());
+                CloseHandle(h_map_file);
+                return Err(PmemError::CannotOpenPmFile);
+            }
+
+            // Extending the file zero-fills it, but the file may
+            // already be longer than the old view, e.g., if it was
+            // restored with a smaller size than it was created with.
+            // Then the bytes past the old view hold stale data. So we
+            // zero them explicitly, and flush the zeroes, before
+            // switching views.
+            if new_size > self.size {
+                let new_bytes = (h_map_addr as *mut u8).add(self.size);
+                std::ptr::write_bytes(new_bytes, 0, new_size - self.size);
+                if FlushViewOfFile(new_bytes as *const c_void, new_size - self.size) == 0 {
+                    eprintln!("Could not flush zeroed bytes of grown file, got error {}", GetLastError());
+                    UnmapViewOfFile(h_map_addr);
+                    CloseHandle(h_map_file);
+                    return Err(PmemError::FlushFailed);
+                }
+            }
+
+            UnmapViewOfFile(self.h_map_addr);
+            CloseHandle(self.h_map_file);
+            self.h_map_file = h_map_file;
+            self.h_map_addr = h_map_addr;
+        }
+        self.size = new_size;
+        self.num_bytes_sectioned = 0;
+        Ok(())
+    }
 }
 
 impl Drop for MemoryMappedFile {
@@ -178,6 +286,7 @@ pub struct MemoryMappedFileSection {
     media_type: MemoryMappedFileMediaType,  // type of media on which the file is stored
     size: usize,                            // number of bytes in the section
     h_map_addr: HANDLE,                     // address of the first byte of the section
+    flush_policy: FlushPolicy,              // how to retry transient flush failures
 }
 
 impl MemoryMappedFileSection {
@@ -193,9 +302,13 @@ impl MemoryMappedFileSection {
             },
         };
 
-        if offset + len > mmf_borrowed.size {
-            eprintln!("Can't allocate {} bytes because only {} remain", len, mmf_borrowed.size - offset);
-            return Err(PmemError::AccessOutOfRange);
+        // A section may end exactly at the end of the file.
+        match offset.checked_add(len) {
+            Some(end) if end <= mmf_borrowed.size => {},
+            _ => {
+                eprintln!("Can't allocate {} bytes because only {} remain", len, mmf_borrowed.size - offset);
+                return Err(PmemError::AccessOutOfRange);
+            },
         }
         
         let h_map_addr = unsafe { (mmf_borrowed.h_map_addr as *mut u8).offset(offset_as_isize) };
@@ -210,48 +323,326 @@ impl MemoryMappedFileSection {
             media_type,
             size: len,
             h_map_addr: h_map_addr as HANDLE,
+            flush_policy: FlushPolicy::default(),
         };
         Ok(section)
     }
 
+    // The function `subsection` carves out the bytes in `[offset,
+    // offset + len)` of this section as a new section of the same
+    // memory-mapped file. Unlike `new`, it doesn't allocate fresh
+    // bytes from the file, so it must only be used in a way that
+    // prevents the two sections from being used at the same time.
+
+    fn subsection(self, offset: usize, len: usize) -> Result<Self, PmemError>
+    {
+        let offset_as_isize: isize = match offset.try_into() {
+            Ok(off) => off,
+            Err(_) => {
+                eprintln!("Can't express offset {} as isize", offset);
+                return Err(PmemError::AccessOutOfRange)
+            },
+        };
+
+        if offset > self.size || len > self.size - offset {
+            eprintln!("Can't carve {} bytes at offset {} out of a section of {} bytes", len, offset, self.size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+
+        let h_map_addr = unsafe { (self.h_map_addr as *mut u8).offset(offset_as_isize) };
+        Ok(Self {
+            mmf: self.mmf,
+            media_type: self.media_type,
+            size: len,
+            h_map_addr: h_map_addr as HANDLE,
+            flush_policy: self.flush_policy,
+        })
+    }
+
+    // The function `grow` grows this section, and its file, to
+    // `new_size` bytes. Growing the file remaps its view, which would
+    // leave any other section of it dangling, so this only works if
+    // this section is the file's only one and covers all of it.
+    // Otherwise, it returns `Err(PmemError::AccessOutOfRange)`.
+
+    fn grow(&mut self, new_size: usize) -> Result<(), PmemError>
+    {
+        let mut mmf_borrowed = self.mmf.borrow_mut();
+        if Rc::strong_count(&self.mmf) != 1 || self.h_map_addr != mmf_borrowed.h_map_addr
+           || self.size != mmf_borrowed.size {
+            eprintln!("Can't grow a section that isn't the only one covering its file");
+            return Err(PmemError::AccessOutOfRange);
+        }
+
+        mmf_borrowed.grow(new_size)?;
+        mmf_borrowed.num_bytes_sectioned = new_size;
+        self.h_map_addr = mmf_borrowed.h_map_addr;
+        self.size = new_size;
+        Ok(())
+    }
+
     // The function `flush` flushes updated parts of the
-    // memory-mapped file back to the media.
+    // memory-mapped file back to the media, in the steps the media
+    // type's `MediaFlushBehavior` calls for. It returns
+    // `Err(PmemError::FlushFailed)` if the OS reports that it
+    // couldn't do so, after retrying transient failures as
+    // `flush_policy` says.
 
-    fn flush(&mut self) {
+    fn flush(&mut self) -> Result<(), PmemError> {
+        let behavior = self.media_type.flush_behavior();
         unsafe {
-            match self.media_type {
-                MemoryMappedFileMediaType::BatteryBackedDRAM => {
-                    // If using battery-backed DRAM, there's no need
-                    // to flush cache lines, since those will be
-                    // flushed during the battery-enabled graceful
-                    // shutdown after power loss.
-                    _mm_sfence();
-                },
-                _ => {
-                    let hr = FlushViewOfFile(
-                        self.h_map_addr as *const c_void,
-                        self.size
-                    );
-
-                    if !SUCCEEDED(hr) {
-                        panic!("Failed to flush view of file. err={}", hr);
-                    }
-                },
+            if behavior.flush_cache_lines {
+                // `clflush` flushes the whole line containing its
+                // address, so start at the line containing the
+                // section's first byte.
+                let start = self.h_map_addr as usize;
+                let mut line = start & !(CACHE_LINE_SIZE - 1);
+                while line < start + self.size {
+                    _mm_clflush(line as *const u8);
+                    line += CACHE_LINE_SIZE;
+                }
+            }
+            if behavior.fence {
+                _mm_sfence();
+            }
+            if behavior.flush_view {
+                let h_map_addr = self.h_map_addr;
+                let size = self.size;
+                self.flush_policy.run(
+                    || {
+                        // `FlushViewOfFile` returns a `BOOL`, not an
+                        // `HRESULT`, so it fails when it returns 0.
+                        if FlushViewOfFile(h_map_addr as *const c_void, size) != 0 { Ok(()) }
+                        else { Err(GetLastError()) }
+                    },
+                    is_transient_flush_error,
+                )?;
             }
         }
+        Ok(())
+    }
+}
+
+const CACHE_LINE_SIZE: usize = 64;
+
+impl MemoryMappedFileMediaType {
+    // The function `flush_behavior` returns the steps a flush takes
+    // on this media type. On an HDD or SSD, the OS has to write the
+    // file view back. On battery-backed DRAM, there's no need to
+    // flush cache lines, since those will be flushed during the
+    // battery-enabled graceful shutdown after power loss, so a fence
+    // suffices.
+
+    pub fn flush_behavior(&self) -> MediaFlushBehavior
+    {
+        match self {
+            MemoryMappedFileMediaType::HDD | MemoryMappedFileMediaType::SSD =>
+                MediaFlushBehavior { flush_cache_lines: false, fence: false, flush_view: true },
+            MemoryMappedFileMediaType::BatteryBackedDRAM =>
+                MediaFlushBehavior { flush_cache_lines: false, fence: true, flush_view: false },
+            MemoryMappedFileMediaType::Custom(behavior) => *behavior,
+        }
+    }
+}
+
+impl MediaFlushBehavior {
+    // The function `new` returns the flush behavior with the given
+    // steps, or `None` if none is set, since such a flush wouldn't
+    // persist anything.
+
+    pub fn new(flush_cache_lines: bool, fence: bool, flush_view: bool) -> Option<Self>
+    {
+        if flush_cache_lines || fence || flush_view {
+            Some(MediaFlushBehavior { flush_cache_lines, fence, flush_view })
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn flush_cache_lines(&self) -> bool
+    {
+        self.flush_cache_lines
+    }
+
+    pub fn fence(&self) -> bool
+    {
+        self.fence
+    }
+
+    pub fn flush_view(&self) -> bool
+    {
+        self.flush_view
+    }
+
+    // A flush that's only a fence applies to all of memory at once,
+    // so flushing several regions needs just one.
+
+    pub fn is_fence_only(&self) -> bool
+    {
+        self.fence && !self.flush_cache_lines && !self.flush_view
+    }
+}
+
+// `FlushViewOfFile` can fail transiently while another process has
+// part of the file locked, e.g., an antivirus scanner or backup agent.
+// Such failures are worth retrying; others aren't.
+
+fn is_transient_flush_error(error_code: u32) -> bool
+{
+    error_code == ERROR_LOCK_VIOLATION || error_code == ERROR_BUSY
+}
+
+// Media types can be given as strings, e.g., in configuration files
+// or environment variables, so we support parsing them from and
+// formatting them as strings. Parsing is case-insensitive and
+// returns a descriptive error message for unknown values. A custom
+// media type is written `custom:` followed by its flush steps joined
+// with `+`, e.g., `custom:cache-lines+fence`. At least one step is
+// required.
+
+impl std::str::FromStr for MemoryMappedFileMediaType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let lower = s.to_ascii_lowercase();
+        if let Some(steps) = lower.strip_prefix("custom:") {
+            let (mut flush_cache_lines, mut fence, mut flush_view) = (false, false, false);
+            for step in steps.split('+').filter(|step| !step.is_empty()) {
+                match step {
+                    "cache-lines" => flush_cache_lines = true,
+                    "fence" => fence = true,
+                    "view" => flush_view = true,
+                    _ => return Err(format!("unknown flush step {:?} in media type {:?}; expected \
+                                             \"cache-lines\", \"fence\", or \"view\"", step, s)),
+                }
+            }
+            return MediaFlushBehavior::new(flush_cache_lines, fence, flush_view)
+                .map(MemoryMappedFileMediaType::Custom)
+                .ok_or_else(|| format!("media type {:?} has no flush steps, so it wouldn't persist anything", s));
+        }
+        match lower.as_str() {
+            "hdd" => Ok(MemoryMappedFileMediaType::HDD),
+            "ssd" => Ok(MemoryMappedFileMediaType::SSD),
+            "bbdram" | "battery-backed-dram" => Ok(MemoryMappedFileMediaType::BatteryBackedDRAM),
+            _ => Err(format!("unknown media type {:?}; expected one of \"hdd\", \"ssd\", \
+                              \"bbdram\", \"battery-backed-dram\", or \"custom:<steps>\"", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for MemoryMappedFileMediaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            MemoryMappedFileMediaType::HDD => write!(f, "hdd"),
+            MemoryMappedFileMediaType::SSD => write!(f, "ssd"),
+            MemoryMappedFileMediaType::BatteryBackedDRAM => write!(f, "battery-backed-dram"),
+            MemoryMappedFileMediaType::Custom(behavior) => {
+                let steps = [
+                    (behavior.flush_cache_lines, "cache-lines"),
+                    (behavior.fence, "fence"),
+                    (behavior.flush_view, "view"),
+                ];
+                let steps: Vec<&str> = steps.iter().filter(|(on, _)| *on).map(|(_, name)| *name).collect();
+                write!(f, "custom:{}", steps.join("+"))
+            },
+        }
+    }
+}
+
+// Flush semantics depend on the media type, so a file must be
+// restored with the same media type it was created with. To catch
+// mismatches, `new` records the media type in an alternate data
+// stream of the memory-mapped file, and `restore` checks it. The
+// stream is part of the file, so it's renamed, copied, and deleted
+// along with it, and the region bytes, which belong to the client's
+// layout, aren't touched.
+//
+// File systems without alternate data streams, e.g., FAT, can't hold
+// the record. Recording is best-effort, so such files can still be
+// created; they're just restored without the check.
+
+fn media_type_path(path: &str) -> String
+{
+    format!("{}:media", path)
+}
+
+fn record_media_type(path: &str, media_type: &MemoryMappedFileMediaType)
+{
+    if let Err(e) = std::fs::write(media_type_path(path), media_type.to_string()) {
+        eprintln!("Could not record media type of {}, so restoring it won't be checked. err={}", path, e);
+    }
+}
+
+// The function `check_media_type` checks that `requested` is the
+// media type recorded when the file at `path` was created. If none
+// was recorded, e.g., for files created before media types were
+// recorded, any media type is accepted.
+
+fn check_media_type(path: &str, requested: &MemoryMappedFileMediaType) -> Result<(), PmemError>
+{
+    let recorded = match std::fs::read_to_string(media_type_path(path)) {
+        Ok(contents) => match contents.trim().parse::<MemoryMappedFileMediaType>() {
+            Ok(media_type) => Some(media_type),
+            Err(e) => {
+                eprintln!("Could not parse recorded media type of {}: {}", path, e);
+                return Err(PmemError::CannotOpenPmFile);
+            },
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("Could not read recorded media type of {}, so it won't be checked. err={}", path, e);
+            None
+        },
+    };
+
+    match recorded {
+        Some(recorded) if recorded != *requested => {
+            eprintln!("File {} was created with media type {} but is being restored as {}",
+                      path, recorded, requested);
+            Err(PmemError::MediaTypeMismatch)
+        },
+        _ => Ok(()),
     }
 }
 
 verus! {
 
 // The `MemoryMappedFileMediaType` enum represents a type of media
-// from which a file can be memory-mapped.
+// from which a file can be memory-mapped. Each type flushes as its
+// `flush_behavior` says. `Custom` lets tests, or media without a
+// preset, pick the flush steps directly.
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum MemoryMappedFileMediaType {
     HDD,
     SSD,
     BatteryBackedDRAM,
+    Custom(MediaFlushBehavior),
+}
+
+// A `MediaFlushBehavior` describes the steps a flush of a
+// memory-mapped section takes, in this order:
+//
+// `flush_cache_lines` -- flush each cache line of the section with
+// `clflush`
+//
+// `fence` -- issue an `sfence`
+//
+// `flush_view` -- have the OS write the section back to the file
+// with `FlushViewOfFile`, retrying as the section's `FlushPolicy`
+// says
+//
+// At least one step must be set; `MediaFlushBehavior::new` enforces
+// this, which is why the fields are private.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MediaFlushBehavior {
+    flush_cache_lines: bool,
+    fence: bool,
+    flush_view: bool,
 }
 
 #[derive(Clone, Copy)]
@@ -290,10 +681,13 @@ impl FileBackedPersistentMemoryRegion
         let mmf = MemoryMappedFile::from_file(
             path.into_rust_str(),
             region_size as usize,
-            media_type,
+            media_type.clone(),
             open_behavior,
             close_behavior
         )?;
+        if let (FileOpenBehavior::CreateNew, FileCloseBehavior::Persistent) = (open_behavior, close_behavior) {
+            record_media_type(path.into_rust_str(), &media_type);
+        }
         let mmf =
             Rc::<RefCell<MemoryMappedFile>>::new(RefCell::<MemoryMappedFile>::new(mmf));
         let section = MemoryMappedFileSection::new(mmf, region_size as usize)?;
@@ -311,6 +705,11 @@ impl FileBackedPersistentMemoryRegion
         Self::new_internal(path, media_type, region_size, FileOpenBehavior::CreateNew, close_behavior)
     }
 
+    // The static function `restore` opens an existing file created
+    // by `new`. It returns `Err(PmemError::MediaTypeMismatch)` if
+    // `media_type` differs from the media type the file was created
+    // with.
+    #[verifier::external_body]
     pub fn restore(path: &StrSlice, media_type: MemoryMappedFileMediaType, region_size: u64)
                -> (result: Result<Self, PmemError>)
         ensures
@@ -319,6 +718,7 @@ impl FileBackedPersistentMemoryRegion
                 Err(_) => true,
             }
     {
+        check_media_type(path.into_rust_str(), &media_type)?;
         Self::new_internal(path, media_type, region_size, FileOpenBehavior::OpenExisting, FileCloseBehavior::Persistent)
     }
 
@@ -327,6 +727,32 @@ impl FileBackedPersistentMemoryRegion
     {
         Self{ section }
     }
+
+    // The function `subregion` turns this region into a region
+    // consisting of just the bytes in `[offset, offset + len)`, with
+    // addresses relative to `offset`. It consumes `self` so that the
+    // parent and the subregion can't both be used to access the same
+    // bytes. The offset must be aligned to the persistence chunk size
+    // so that the subregion's chunks line up with the parent's.
+    #[verifier::external_body]
+    pub fn subregion(self, offset: u64, len: u64) -> (result: Result<Self, PmemError>)
+        requires
+            self.inv(),
+            offset % (const_persistence_chunk_size() as u64) == 0,
+            offset + len <= self@.len(),
+        ensures
+            match result {
+                Ok(region) => {
+                    &&& region.inv()
+                    &&& region.constants() == self.constants()
+                    &&& region@ == self@.subrange(offset as int, offset + len)
+                },
+                Err(_) => true,
+            }
+    {
+        let section = self.section.subsection(offset as usize, len as usize)?;
+        Ok(Self { section })
+    }
 }
 
 impl PersistentMemoryRegion for FileBackedPersistentMemoryRegion
@@ -341,6 +767,12 @@ impl PersistentMemoryRegion for FileBackedPersistentMemoryRegion
         self.section.size as u64
     }
 
+    // Persistent memory can be corrupted, so we never skip CRC checks.
+    fn known_impervious_to_corruption(&self) -> bool
+    {
+        false
+    }
+
     #[verifier::external_body]
     fn read(&self, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
     {
@@ -356,6 +788,7 @@ impl PersistentMemoryRegion for FileBackedPersistentMemoryRegion
         where
             S: Serializable + Sized
     {
+        debug_check_serialized_layout::<S>();
         // SAFETY: The `offset` method is safe as long as both the start
         // and resulting pointer are in bounds and the computed offset does
         // not overflow `isize`. `addr` and `num_bytes` are unsigned and
@@ -419,9 +852,19 @@ impl PersistentMemoryRegion for FileBackedPersistentMemoryRegion
     }
 
     #[verifier::external_body]
-    fn flush(&mut self)
+    fn flush(&mut self) -> (result: Result<(), PmemError>)
+    {
+        self.section.flush()
+    }
+
+    // Only a region that covers its whole file, and isn't a
+    // subregion, can grow. `MemoryMappedFile::grow` zeroes and
+    // flushes the new bytes itself, since they may not be past the
+    // end of the file.
+    #[verifier::external_body]
+    fn grow_region(&mut self, new_size: u64) -> (result: Result<(), PmemError>)
     {
-        self.section.flush();
+        self.section.grow(new_size as usize)
     }
 }
 
@@ -467,6 +910,9 @@ impl FileBackedPersistentMemoryRegions {
             open_behavior,
             close_behavior
         )?;
+        if let (FileOpenBehavior::CreateNew, FileCloseBehavior::Persistent) = (open_behavior, close_behavior) {
+            record_media_type(path.into_rust_str(), &media_type);
+        }
         let mmf =
             Rc::<RefCell<MemoryMappedFile>>::new(RefCell::<MemoryMappedFile>::new(mmf));
         let mut regions = Vec::<FileBackedPersistentMemoryRegion>::new();
@@ -514,10 +960,13 @@ impl FileBackedPersistentMemoryRegions {
     //
     // `path` -- the path to use for the file
     //
-    // `media_type` -- the type of media the path refers to
+    // `media_type` -- the type of media the path refers to. It must
+    // match the one the file was created with, or this returns
+    // `Err(PmemError::MediaTypeMismatch)`.
     //
     // `region_sizes` -- a vector of region sizes, where
     // `region_sizes[i]` is the length of file `log<i>`
+    #[verifier::external_body]
     pub fn restore(path: &StrSlice, media_type: MemoryMappedFileMediaType, region_sizes: &[u64])
                    -> (result: Result<Self, PmemError>)
         ensures
@@ -531,6 +980,7 @@ impl FileBackedPersistentMemoryRegions {
                 Err(_) => true
             }
     {
+        check_media_type(path.into_rust_str(), &media_type)?;
         Self::new_internal(
             path, media_type, region_sizes, FileOpenBehavior::OpenExisting, FileCloseBehavior::Persistent
         )
@@ -554,6 +1004,22 @@ impl PersistentMemoryRegions for FileBackedPersistentMemoryRegions {
         self.regions[index].get_region_size()
     }
 
+    // Persistent memory can be corrupted, so we never skip CRC checks.
+    fn known_impervious_to_corruption(&self) -> bool
+    {
+        false
+    }
+
+    // If a flush is just a fence, as on battery-backed DRAM,
+    // `flush_region` is the same single sfence as `flush`. On other
+    // media, it flushes only the one region's section.
+    #[verifier::external_body]
+    fn capabilities(&self) -> (result: PmemCapabilities)
+    {
+        let targeted_flush = !self.media_type.flush_behavior().is_fence_only();
+        PmemCapabilities { targeted_flush, persistent: true }
+    }
+
     #[verifier::external_body]
     fn read(&self, index: usize, addr: u64, num_bytes: u64) -> (bytes: Vec<u8>)
     {
@@ -565,6 +1031,7 @@ impl PersistentMemoryRegions for FileBackedPersistentMemoryRegions {
         where
             S: Serializable + Sized
     {
+        debug_check_serialized_layout::<S>();
         self.regions[index].read_and_deserialize(addr)
     }
 
@@ -583,28 +1050,134 @@ impl PersistentMemoryRegions for FileBackedPersistentMemoryRegions {
     }
 
     #[verifier::external_body]
-    fn flush(&mut self)
-    {
-        match self.media_type {
-            MemoryMappedFileMediaType::BatteryBackedDRAM => {
-                // If using battery-backed DRAM, a single sfence
-                // instruction will fence all of memory, so
-                // there's no need to iterate through all the
-                // regions. Also, there's no need to flush cache
-                // lines, since those will be flushed during the
-                // battery-enabled graceful shutdown after power
-                // loss.
-                unsafe {
-                    core::arch::x86_64::_mm_sfence();
-                }
-            },
-            _ => {
-                for region in &mut self.regions {
-                    region.flush();
-                }
-            },
+    fn flush(&mut self) -> (result: Result<(), PmemError>)
+    {
+        if self.media_type.flush_behavior().is_fence_only() {
+            // If a flush is just a fence, e.g., on battery-backed
+            // DRAM, a single sfence instruction will fence all of
+            // memory, so there's no need to iterate through all
+            // the regions.
+            unsafe {
+                core::arch::x86_64::_mm_sfence();
+            }
+        }
+        else {
+            for region in &mut self.regions {
+                region.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    #[verifier::external_body]
+    fn flush_region(&mut self, index: usize) -> (result: Result<(), PmemError>)
+    {
+        if self.media_type.flush_behavior().is_fence_only() {
+            // As in `flush`, a single sfence suffices.
+            unsafe {
+                core::arch::x86_64::_mm_sfence();
+            }
+        }
+        else {
+            self.regions[index].flush()?;
         }
+        Ok(())
     }
 }
 
 }
+
+// These functions set how transient flush failures are retried. Call
+// them right after `new` or `restore`, e.g.,
+// `FileBackedPersistentMemoryRegions::restore(...)?.with_flush_policy(policy)`.
+// Changing the policy doesn't change any region's contents, so they
+// don't need to be visible to the verifier.
+
+impl FileBackedPersistentMemoryRegion {
+    pub fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self
+    {
+        self.section.flush_policy = flush_policy;
+        self
+    }
+}
+
+impl FileBackedPersistentMemoryRegions {
+    pub fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self
+    {
+        for region in &mut self.regions {
+            region.section.flush_policy = flush_policy;
+        }
+        self
+    }
+}
+
+// The function `truncate_region_file` shrinks the file at `path` to
+// `new_size` bytes with `SetEndOfFile`, returning the space past that
+// point to the file system, and makes the new size durable. It never
+// grows the file. Windows won't truncate a file that has a mapped
+// view, so every region backed by the file must be dropped first.
+// It's up to the caller to make sure nothing live lies past
+// `new_size`; `LogImpl::truncate_region_file` does that for a log.
+pub fn truncate_region_file(path: &str, new_size: u64) -> Result<(), PmemError>
+{
+    let path_cstr = match CString::new(path) {
+        Ok(p) => p,
+        Err(_) => {
+            eprintln!("Could not convert path {} to string", path);
+            return Err(PmemError::InvalidFileName);
+        }
+    };
+    let new_size_as_i64: i64 = match new_size.try_into() {
+        Ok(sz) => sz,
+        Err(_) => {
+            eprintln!("Could not convert size {} into i64", new_size);
+            return Err(PmemError::AccessOutOfRange);
+        }
+    };
+
+    unsafe {
+        let h_file = CreateFileA(
+            path_cstr.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_WRITE | FILE_SHARE_READ | FILE_SHARE_DELETE,
+            core::ptr::null_mut(),
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            core::ptr::null_mut()
+        );
+        if h_file.is_null() || h_file == INVALID_HANDLE_VALUE {
+            eprintln!("Could not open existing file {}. err={}", path, GetLastError());
+            return Err(PmemError::CannotOpenPmFile);
+        }
+        let result = truncate_open_file(h_file, path, new_size_as_i64);
+        CloseHandle(h_file);
+        result
+    }
+}
+
+// This helper function does the work of `truncate_region_file` once
+// the file is open, so that the caller can close the handle on every
+// path.
+unsafe fn truncate_open_file(h_file: HANDLE, path: &str, new_size: i64) -> Result<(), PmemError>
+{
+    let mut current_size: LARGE_INTEGER = std::mem::zeroed();
+    if GetFileSizeEx(h_file, &mut current_size) == 0 {
+        eprintln!("Could not get the size of file {}. err={}", path, GetLastError());
+        return Err(PmemError::CannotOpenPmFile);
+    }
+    if new_size > *current_size.QuadPart() {
+        eprintln!("Can't truncate file {} to {} bytes since it only has {}", path, new_size,
+                  *current_size.QuadPart());
+        return Err(PmemError::AccessOutOfRange);
+    }
+
+    let mut distance: LARGE_INTEGER = std::mem::zeroed();
+    *distance.QuadPart_mut() = new_size;
+    if SetFilePointerEx(h_file, distance, core::ptr::null_mut(), FILE_BEGIN) == 0
+       || SetEndOfFile(h_file) == 0
+       || FlushFileBuffers(h_file) == 0 {
+        eprintln!("Could not truncate file {}. err={}", path, GetLastError());
+        return Err(PmemError::FlushFailed);
+    }
+    Ok(())
+}
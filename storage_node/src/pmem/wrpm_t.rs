@@ -1,10 +1,38 @@
 use crate::pmem::pmemspec_t::*;
+use crate::pmem::pmemutil_v::lemma_adjacent_writes_compose;
 use crate::pmem::serialization_t::*;
 use builtin::*;
 use builtin_macros::*;
+use vstd::arithmetic::div_mod::*;
 use vstd::prelude::*;
+use vstd::slice::*;
 
 verus! {
+
+// This function returns whether `a` and `b` hold the same bytes.
+fn bytes_equal(a: &[u8], b: &[u8]) -> (result: bool)
+    ensures
+        result == (a@ == b@),
+{
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i: usize = 0;
+    while i < a.len()
+        invariant
+            a@.len() == b@.len(),
+            i <= a@.len(),
+            forall |j: int| 0 <= j < i ==> a@[j] == b@[j],
+    {
+        if a[i] != b[i] {
+            return false;
+        }
+        i = i + 1;
+    }
+    assert(a@ =~= b@);
+    true
+}
+
 /// A `WriteRestrictedPersistentMemoryRegions` is a wrapper around a
 /// collection of persistent memory regions that restricts how it can
 /// be written. Specifically, it only permits a write if it's
@@ -100,6 +128,80 @@ impl<Perm, PMRegions> WriteRestrictedPersistentMemoryRegions<Perm, PMRegions>
         self.pm_regions.write(index, addr, bytes)
     }
 
+    // This executable function is like `write`, but it splits the
+    // write into pieces of at most `max_write_len` bytes and writes
+    // them in order, for backends that cap how much one write can
+    // cover. The permission obligation is stated per piece: for each
+    // prefix of `bytes` ending where a piece ends, i.e., at a multiple
+    // of `max_write_len` or at the end of `bytes`, the caller must
+    // prove that `perm` authorizes every state the memory can crash
+    // into once that prefix is written. Writing the pieces in order
+    // has the same effect as writing `bytes` all at once.
+    #[allow(unused_variables)]
+    pub exec fn write_chunked(&mut self, index: usize, addr: u64, bytes: &[u8], max_write_len: u64,
+                              perm: Tracked<&Perm>)
+        requires
+            old(self).inv(),
+            index < old(self)@.len(),
+            addr + bytes@.len() <= old(self)@[index as int].len(),
+            addr + bytes@.len() <= u64::MAX,
+            0 < max_write_len,
+            old(self)@.no_outstanding_writes_in_range(index as int, addr as int, addr + bytes@.len()),
+            // The key thing the caller must prove is that all crash
+            // states after each piece are authorized by `perm`
+            forall |end: int, s| 0 < end <= bytes@.len() && (end % max_write_len as int == 0 || end == bytes@.len()) &&
+                   #[trigger] old(self)@.write(index as int, addr as int, bytes@.subrange(0, end)).can_crash_as(s)
+                  ==> perm@.check_permission(s),
+        ensures
+            self.inv(),
+            self.constants() == old(self).constants(),
+            self@ == old(self)@.write(index as int, addr as int, bytes@),
+    {
+        let ghost old_view = self@;
+        let len = bytes.len() as u64;
+        let mut pos: u64 = 0;
+        assert(old_view.write(index as int, addr as int, bytes@.subrange(0, 0)) =~~= old_view);
+
+        while pos < len
+            invariant
+                self.inv(),
+                self.constants() == old(self).constants(),
+                index < old_view.len(),
+                addr + bytes@.len() <= old_view[index as int].len(),
+                addr + bytes@.len() <= u64::MAX,
+                len == bytes@.len(),
+                0 < max_write_len,
+                pos <= len,
+                pos == len || pos % max_write_len == 0,
+                self@ == old_view.write(index as int, addr as int, bytes@.subrange(0, pos as int)),
+                old_view.no_outstanding_writes_in_range(index as int, addr as int, addr + bytes@.len()),
+                forall |end: int, s| 0 < end <= bytes@.len() && (end % max_write_len as int == 0 || end == bytes@.len()) &&
+                       #[trigger] old_view.write(index as int, addr as int, bytes@.subrange(0, end)).can_crash_as(s)
+                      ==> perm@.check_permission(s),
+        {
+            let chunk_len = if len - pos < max_write_len { len - pos } else { max_write_len };
+            let end = pos + chunk_len;
+            let chunk = slice_subrange(bytes, pos as usize, end as usize);
+            proof {
+                // Writing this piece after the earlier ones amounts to
+                // writing the prefix of `bytes` that ends with it, so
+                // the caller's permission for that prefix covers it.
+                lemma_adjacent_writes_compose(old_view, index as int, addr as int, bytes@, pos as int, end as int);
+                if end < len {
+                    lemma_mod_add_multiples_vanish(pos as int, max_write_len as int);
+                }
+                assert forall |k| addr + pos <= k < addr + end implies
+                       (#[trigger] self@[index as int].state[k].outstanding_write).is_none() by {
+                    assert(old_view[index as int].state[k].outstanding_write.is_none());
+                }
+            }
+            self.write(index, addr + pos, chunk, perm);
+            pos = end;
+        }
+
+        assert(bytes@.subrange(0, len as int) =~= bytes@);
+    }
+
     #[allow(unused_variables)]
     pub exec fn serialize_and_write<S>(&mut self, index: usize, addr: u64, to_write: &S, perm: Tracked<&Perm>)
         where
@@ -125,16 +227,35 @@ impl<Perm, PMRegions> WriteRestrictedPersistentMemoryRegions<Perm, PMRegions>
     // the possible states the memory can crash into. So if the memory
     // is already restricted to only crash into good states, `flush`
     // automatically maintains that restriction.
-    pub exec fn flush(&mut self)
+    pub exec fn flush(&mut self) -> (result: Result<(), PmemError>)
         requires
             old(self).inv(),
         ensures
             self.inv(),
-            self@ == old(self)@.flush(),
             self.constants() == old(self).constants(),
+            match result {
+                Ok(()) => self@ == old(self)@.flush(),
+                Err(_) => self@ == old(self)@,
+            },
     {
         self.pm_regions.flush()
     }
+
+    // Like `flush`, `flush_region` needs no permission.
+    pub exec fn flush_region(&mut self, index: usize) -> (result: Result<(), PmemError>)
+        requires
+            old(self).inv(),
+            index < old(self)@.len(),
+        ensures
+            self.inv(),
+            self.constants() == old(self).constants(),
+            match result {
+                Ok(()) => self@ == old(self)@.flush_region(index as int),
+                Err(_) => self@ == old(self)@,
+            },
+    {
+        self.pm_regions.flush_region(index)
+    }
 }
 
 #[allow(dead_code)]
@@ -196,6 +317,23 @@ impl<Perm, PMRegion> WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>
         &self.pm_region
     }
 
+    // This executable function consumes the write-restricted memory
+    // and returns the persistent memory region it wraps, without any
+    // write restriction. It's for trusted code that has to make an
+    // update no permission can authorize, e.g., changing the region
+    // size recorded in a log's metadata, and so must be audited for
+    // crash safety on its own.
+    pub exec fn into_pm_region(self) -> (pm_region: PMRegion)
+        requires
+            self.inv(),
+        ensures
+            pm_region.inv(),
+            pm_region@ == self@,
+            pm_region.constants() == self.constants(),
+    {
+        self.pm_region
+    }
+
     // This executable function is the only way to perform a write, and
     // it requires the caller to supply permission authorizing the
     // write. The caller must prove that for every state this memory
@@ -220,6 +358,58 @@ impl<Perm, PMRegion> WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>
         self.pm_region.write(addr, bytes);
     }
 
+    // This executable function writes `new_bytes` at `addr` only if
+    // the bytes currently there equal `expected_bytes`, and returns
+    // whether it wrote them. It needs the same permission as writing
+    // `new_bytes` unconditionally. It isn't atomic with respect to
+    // other threads or processes writing the same memory, so it's
+    // only a building block for optimistic updates by a single
+    // writer, e.g., swapping metadata only if nobody has changed it
+    // since it was read. Unless the memory is impervious to
+    // corruption, the comparison is against possibly corrupted bytes.
+    pub exec fn compare_and_write(
+        &mut self,
+        addr: u64,
+        expected_bytes: &[u8],
+        new_bytes: &[u8],
+        perm: Tracked<&Perm>,
+    ) -> (result: Result<bool, PmemError>)
+        requires
+            old(self).inv(),
+            addr + new_bytes@.len() <= old(self)@.len(),
+            addr + new_bytes@.len() <= u64::MAX,
+            old(self)@.no_outstanding_writes_in_range(addr as int, addr + new_bytes@.len()),
+            // The key thing the caller must prove is that all crash states are authorized by `perm`
+            forall |s| old(self)@.write(addr as int, new_bytes@).can_crash_as(s)
+                  ==> #[trigger] perm@.check_permission(s),
+        ensures
+            self.inv(),
+            self.constants() == old(self).constants(),
+            match result {
+                Ok(true) => {
+                    &&& self@ == old(self)@.write(addr as int, new_bytes@)
+                    &&& old(self).constants().impervious_to_corruption ==>
+                           old(self)@.committed().subrange(addr as int, addr + new_bytes@.len()) == expected_bytes@
+                },
+                Ok(false) => self@ == old(self)@,
+                Err(PmemError::AccessOutOfRange) => {
+                    &&& self@ == old(self)@
+                    &&& expected_bytes@.len() != new_bytes@.len()
+                },
+                Err(_) => false,
+            }
+    {
+        if expected_bytes.len() != new_bytes.len() {
+            return Err(PmemError::AccessOutOfRange);
+        }
+        let current_bytes = self.pm_region.read(addr, new_bytes.len() as u64);
+        if !bytes_equal(current_bytes.as_slice(), expected_bytes) {
+            return Ok(false);
+        }
+        self.write(addr, new_bytes, perm);
+        Ok(true)
+    }
+
     #[allow(unused_variables)]
     pub exec fn serialize_and_write<S>(&mut self, addr: u64, to_write: &S, perm: Tracked<&Perm>)
         where
@@ -239,18 +429,84 @@ impl<Perm, PMRegion> WriteRestrictedPersistentMemoryRegion<Perm, PMRegion>
         self.pm_region.serialize_and_write(addr, to_write);
     }
 
+    // This executable function is like `serialize_and_write`, but it
+    // first reads the bytes at `addr` and skips the write if they
+    // already hold the serialization of `to_write`. It returns
+    // whether it wrote. This saves a write when, e.g., recovery
+    // rewrites metadata that's already correct. If it skips the
+    // write, the memory is unchanged, and if the memory is
+    // impervious to corruption, it already holds `to_write` there.
+    pub exec fn serialize_and_write_if_changed<S>(&mut self, addr: u64, to_write: &S, perm: Tracked<&Perm>)
+                                                   -> (wrote: bool)
+        where
+            S: Serializable + Sized
+        requires
+            old(self).inv(),
+            addr + S::spec_serialized_len() <= old(self)@.len(),
+            old(self)@.no_outstanding_writes_in_range(addr as int, addr + S::spec_serialized_len()),
+            // The caller only needs to authorize the crash states of
+            // an actual write, since skipping it changes nothing.
+            forall |s| old(self)@.write(addr as int, to_write.spec_serialize()).can_crash_as(s)
+                  ==> #[trigger] perm@.check_permission(s),
+        ensures
+            self.inv(),
+            self.constants() == old(self).constants(),
+            if wrote {
+                self@ == old(self)@.write(addr as int, to_write.spec_serialize())
+            }
+            else {
+                &&& self@ == old(self)@
+                &&& self.constants().impervious_to_corruption ==>
+                       self@.committed().subrange(addr as int, addr + S::spec_serialized_len()) ==
+                       to_write.spec_serialize()
+            },
+    {
+        let current_bytes = self.pm_region.read(addr, S::serialized_len());
+        if serialization_matches_bytes(to_write, current_bytes.as_slice()) {
+            return false;
+        }
+        self.serialize_and_write(addr, to_write, perm);
+        true
+    }
+
+    // This executable function grows the memory to `new_size` bytes,
+    // with the new bytes zero. Growing changes what the memory can
+    // crash into, so it needs permission for the grown memory's
+    // crash states, just like a write.
+    #[allow(unused_variables)]
+    pub exec fn grow_region(&mut self, new_size: u64, perm: Tracked<&Perm>) -> (result: Result<(), PmemError>)
+        requires
+            old(self).inv(),
+            old(self)@.len() <= new_size,
+            old(self)@.no_outstanding_writes(),
+            forall |s| old(self)@.grow(new_size as int).can_crash_as(s)
+                  ==> #[trigger] perm@.check_permission(s),
+        ensures
+            self.inv(),
+            self.constants() == old(self).constants(),
+            match result {
+                Ok(()) => self@ == old(self)@.grow(new_size as int),
+                Err(_) => self@ == old(self)@,
+            },
+    {
+        self.pm_region.grow_region(new_size)
+    }
+
     // Even though the memory is write-restricted, no restrictions are
     // placed on calling `flush`. After all, `flush` can only narrow
     // the possible states the memory can crash into. So if the memory
     // is already restricted to only crash into good states, `flush`
     // automatically maintains that restriction.
-    pub exec fn flush(&mut self)
+    pub exec fn flush(&mut self) -> (result: Result<(), PmemError>)
         requires
             old(self).inv(),
         ensures
             self.inv(),
-            self@ == old(self)@.flush(),
             self.constants() == old(self).constants(),
+            match result {
+                Ok(()) => self@ == old(self)@.flush(),
+                Err(_) => self@ == old(self)@,
+            },
     {
         self.pm_region.flush()
     }